@@ -152,6 +152,59 @@ mod ops {
     // }
 }
 
+/// Compares finding the endpoints with a token by scanning every endpoint, as
+/// the token router used to, with the cluster's token index.
+#[divan::bench_group(sample_count = 10)]
+mod tokens {
+    use super::*;
+    use quilkin::net::{cluster::Token, endpoint::EndpointAddress};
+    use rand::{seq::SliceRandom as _, SeedableRng as _};
+    use shared::gen_cluster_map;
+
+    const TOKEN_KINDS: &[&str] = &["single:unique", "multi:2..128:duplicates"];
+
+    fn tokens(cm: &ClusterMap) -> Vec<Vec<u8>> {
+        cm.iter()
+            .flat_map(|set| {
+                set.endpoints
+                    .iter()
+                    .flat_map(|ep| ep.metadata.known.tokens.iter().cloned().collect::<Vec<_>>())
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    #[divan::bench(args = TOKEN_KINDS)]
+    fn linear_scan(b: Bencher, token_kind: &str) {
+        let gc = gen_cluster_map::<42>(token_kind.parse().unwrap());
+        let tokens = tokens(&gc.cm);
+        let mut rng = rand::rngs::SmallRng::seed_from_u64(42);
+
+        b.with_inputs(|| tokens.choose(&mut rng).unwrap().clone())
+            .bench_local_values(|token| {
+                divan::black_box(
+                    gc.cm
+                        .filter_endpoints(|ep| ep.metadata.known.tokens.contains(&token)),
+                )
+            });
+    }
+
+    #[divan::bench(args = TOKEN_KINDS)]
+    fn index(b: Bencher, token_kind: &str) {
+        let gc = gen_cluster_map::<42>(token_kind.parse().unwrap());
+        let tokens = tokens(&gc.cm);
+        let mut rng = rand::rngs::SmallRng::seed_from_u64(42);
+
+        b.with_inputs(|| tokens.choose(&mut rng).unwrap().clone())
+            .bench_local_values(|token| {
+                let mut addresses = Vec::<EndpointAddress>::new();
+                gc.cm
+                    .addresses_for_token(Token::new(&token), &mut addresses);
+                divan::black_box(addresses)
+            });
+    }
+}
+
 fn main() {
     divan::main();
 }
@@ -166,9 +166,11 @@ impl GenResource for Listener {
                             size: 3,
                             remove: true,
                         }),
+                        captures: vec![],
                     })
                     .unwrap(),
                 ),
+                when: None,
             },
             quilkin::config::Filter {
                 name: filters::compress::Compress::NAME.into(),
@@ -181,6 +183,7 @@ impl GenResource for Listener {
                     })
                     .unwrap(),
                 ),
+                when: None,
             },
         ];
 
@@ -192,8 +195,10 @@ impl GenResource for Listener {
                         name: f.name,
                         label: f.label,
                         config: f.config.map(|c| c.to_string()),
+                        when: None,
                     })
                     .collect(),
+                ..Default::default()
             })
         } else {
             ResourceType::Listener.encode_to_any(&quilkin::net::xds::listener::Listener {
@@ -247,15 +252,8 @@ impl GenResource for Cluster {
 // From Config::apply
 fn deserialize(a: prost_types::Any) {
     match Resource::try_from(a).unwrap() {
-        Resource::Listener(mut listener) => {
-            let chain: quilkin::filters::FilterChain = if listener.filter_chains.is_empty() {
-                Default::default()
-            } else {
-                quilkin::filters::FilterChain::try_create_fallible(
-                    listener.filter_chains.swap_remove(0).filters,
-                )
-                .unwrap()
-            };
+        Resource::Listener(listener) => {
+            let chain = quilkin::filters::FilterChain::try_from(*listener).unwrap();
 
             drop(chain);
         }
@@ -530,6 +530,7 @@ pub fn gen_endpoints(
                 ep_addr,
                 quilkin::net::endpoint::EndpointMetadata::new(quilkin::net::endpoint::Metadata {
                     tokens: set,
+                    labels: <_>::default(),
                 }),
             )
         } else {
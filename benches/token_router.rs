@@ -15,7 +15,6 @@ where
     let mut tokens = Vec::new();
 
     let cm = std::sync::Arc::new(gc.cm);
-    cm.build_token_maps();
 
     // Calculate the amount of bytes for all the tokens
     for eps in cm.iter() {
@@ -185,18 +185,30 @@ fn execute(which: &str) {
             &[
                 "relay/v1alpha1/relay",
                 "config/v1alpha1/config",
+                "filters/a2s/v1alpha1/a2s",
+                "filters/broadcast/v1alpha1/broadcast",
                 "filters/capture/v1alpha1/capture",
+                "filters/chaos/v1alpha1/chaos",
                 "filters/compress/v1alpha1/compress",
                 "filters/concatenate/v1alpha1/concatenate",
                 "filters/debug/v1alpha1/debug",
                 "filters/drop/v1alpha1/drop",
+                "filters/fan_out/v1alpha1/fan_out",
+                "filters/fec/v1alpha1/fec",
                 "filters/firewall/v1alpha1/firewall",
+                "filters/fragment/v1alpha1/fragment",
+                "filters/label_router/v1alpha1/label_router",
                 "filters/load_balancer/v1alpha1/load_balancer",
                 "filters/local_rate_limit/v1alpha1/local_rate_limit",
                 "filters/match/v1alpha1/match",
+                "filters/metadata/v1alpha1/metadata",
+                "filters/mirror/v1alpha1/mirror",
+                "filters/parse_header/v1alpha1/parse_header",
                 "filters/pass/v1alpha1/pass",
+                "filters/signed_token_router/v1alpha1/signed_token_router",
                 "filters/token_router/v1alpha1/token_router",
                 "filters/timestamp/v1alpha1/timestamp",
+                "filters/traffic_split/v1alpha1/traffic_split",
             ],
         ),
     ];
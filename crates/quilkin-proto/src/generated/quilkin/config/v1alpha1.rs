@@ -60,6 +60,16 @@ pub struct Endpoint {
     pub metadata: ::core::option::Option<::prost_types::Struct>,
     #[prost(message, optional, tag = "4")]
     pub host2: ::core::option::Option<Host>,
+    #[prost(message, repeated, tag = "5")]
+    pub token_expiry: ::prost::alloc::vec::Vec<TokenExpiry>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TokenExpiry {
+    #[prost(bytes = "vec", tag = "1")]
+    pub token: ::prost::alloc::vec::Vec<u8>,
+    #[prost(int64, tag = "2")]
+    pub expires_at: i64,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -80,10 +90,24 @@ pub struct Filter {
     pub label: ::core::option::Option<::prost::alloc::string::String>,
     #[prost(string, optional, tag = "3")]
     pub config: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "4")]
+    pub when: ::core::option::Option<::prost::alloc::string::String>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FilterList {
+    #[prost(message, repeated, tag = "1")]
+    pub filters: ::prost::alloc::vec::Vec<Filter>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct FilterChain {
     #[prost(message, repeated, tag = "1")]
     pub filters: ::prost::alloc::vec::Vec<Filter>,
+    /// Replaces `filters` for packets from clients, when set.
+    #[prost(message, optional, tag = "2")]
+    pub read_filters: ::core::option::Option<FilterList>,
+    /// Replaces `filters` for packets from endpoints, when set.
+    #[prost(message, optional, tag = "3")]
+    pub write_filters: ::core::option::Option<FilterList>,
 }
@@ -1,12 +1,24 @@
+pub mod a2s;
+pub mod broadcast;
 pub mod capture;
+pub mod chaos;
 pub mod compress;
 pub mod concatenate;
 pub mod debug;
 pub mod drop;
+pub mod fan_out;
+pub mod fec;
 pub mod firewall;
+pub mod fragment;
+pub mod label_router;
 pub mod load_balancer;
 pub mod local_rate_limit;
 pub mod matches;
+pub mod metadata;
+pub mod mirror;
+pub mod parse_header;
 pub mod pass;
+pub mod signed_token_router;
 pub mod timestamp;
 pub mod token_router;
+pub mod traffic_split;
@@ -0,0 +1,8 @@
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct A2s {
+    #[prost(message, optional, tag = "1")]
+    pub refresh_interval: ::core::option::Option<u32>,
+    #[prost(message, optional, tag = "2")]
+    pub info_challenge: ::core::option::Option<bool>,
+}
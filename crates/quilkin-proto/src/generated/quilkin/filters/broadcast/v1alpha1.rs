@@ -0,0 +1,11 @@
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Broadcast {
+    #[prost(message, optional, tag = "1")]
+    pub locality: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(map = "string, string", tag = "2")]
+    pub labels:
+        ::std::collections::HashMap<::prost::alloc::string::String, ::prost::alloc::string::String>,
+    #[prost(message, optional, tag = "3")]
+    pub max_endpoints: ::core::option::Option<u32>,
+}
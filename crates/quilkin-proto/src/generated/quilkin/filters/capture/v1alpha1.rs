@@ -3,7 +3,9 @@
 pub struct Capture {
     #[prost(message, optional, tag = "1")]
     pub metadata_key: ::core::option::Option<::prost::alloc::string::String>,
-    #[prost(oneof = "capture::Strategy", tags = "2, 3, 4")]
+    #[prost(message, repeated, tag = "6")]
+    pub captures: ::prost::alloc::vec::Vec<Capture>,
+    #[prost(oneof = "capture::Strategy", tags = "2, 3, 4, 5, 7")]
     pub strategy: ::core::option::Option<capture::Strategy>,
 }
 /// Nested message and enum types in `Capture`.
@@ -31,6 +33,24 @@ pub mod capture {
         pub regex: ::core::option::Option<::prost::alloc::string::String>,
     }
     #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Offset {
+        #[prost(uint32, tag = "1")]
+        pub offset: u32,
+        #[prost(uint32, tag = "2")]
+        pub size: u32,
+        #[prost(message, optional, tag = "3")]
+        pub remove: ::core::option::Option<bool>,
+    }
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Quic {
+        #[prost(uint32, tag = "1")]
+        pub connection_id_length: u32,
+        #[prost(message, optional, tag = "2")]
+        pub size: ::core::option::Option<u32>,
+    }
+    #[allow(clippy::derive_partial_eq_without_eq)]
     #[derive(Clone, PartialEq, ::prost::Oneof)]
     pub enum Strategy {
         #[prost(message, tag = "2")]
@@ -39,5 +59,9 @@ pub mod capture {
         Suffix(Suffix),
         #[prost(message, tag = "4")]
         Regex(Regex),
+        #[prost(message, tag = "5")]
+        Offset(Offset),
+        #[prost(message, tag = "7")]
+        Quic(Quic),
     }
 }
@@ -0,0 +1,111 @@
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Chaos {
+    #[prost(string, repeated, tag = "1")]
+    pub sources: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(message, optional, tag = "2")]
+    pub metadata_key: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(message, optional, tag = "3")]
+    pub on_read: ::core::option::Option<chaos::Impairment>,
+    #[prost(message, optional, tag = "4")]
+    pub on_write: ::core::option::Option<chaos::Impairment>,
+}
+/// Nested message and enum types in `Chaos`.
+pub mod chaos {
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Delay {
+        #[prost(message, optional, tag = "1")]
+        pub latency: ::core::option::Option<u32>,
+        #[prost(message, optional, tag = "2")]
+        pub jitter: ::core::option::Option<u32>,
+        #[prost(message, optional, tag = "3")]
+        pub distribution: ::core::option::Option<delay::DistributionValue>,
+    }
+    /// Nested message and enum types in `Delay`.
+    pub mod delay {
+        #[allow(clippy::derive_partial_eq_without_eq)]
+        #[derive(Clone, PartialEq, ::prost::Message)]
+        pub struct DistributionValue {
+            #[prost(enumeration = "Distribution", tag = "1")]
+            pub value: i32,
+        }
+        #[derive(
+            Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration,
+        )]
+        #[repr(i32)]
+        pub enum Distribution {
+            Uniform = 0,
+            Normal = 1,
+        }
+        impl Distribution {
+            /// String value of the enum field names used in the ProtoBuf definition.
+            ///
+            /// The values are not transformed in any way and thus are considered stable
+            /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+            pub fn as_str_name(&self) -> &'static str {
+                match self {
+                    Distribution::Uniform => "Uniform",
+                    Distribution::Normal => "Normal",
+                }
+            }
+            /// Creates an enum from field names used in the ProtoBuf definition.
+            pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+                match value {
+                    "Uniform" => Some(Self::Uniform),
+                    "Normal" => Some(Self::Normal),
+                    _ => None,
+                }
+            }
+        }
+    }
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct RandomLoss {
+        #[prost(double, tag = "1")]
+        pub probability: f64,
+    }
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct GilbertElliottLoss {
+        #[prost(double, tag = "1")]
+        pub p: f64,
+        #[prost(double, tag = "2")]
+        pub r: f64,
+        #[prost(message, optional, tag = "3")]
+        pub good_loss: ::core::option::Option<f64>,
+        #[prost(message, optional, tag = "4")]
+        pub bad_loss: ::core::option::Option<f64>,
+    }
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Reorder {
+        #[prost(double, tag = "1")]
+        pub probability: f64,
+        #[prost(message, optional, tag = "2")]
+        pub delay: ::core::option::Option<u32>,
+    }
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Impairment {
+        #[prost(message, optional, tag = "1")]
+        pub delay: ::core::option::Option<Delay>,
+        #[prost(message, optional, tag = "4")]
+        pub duplicate: ::core::option::Option<f64>,
+        #[prost(message, optional, tag = "5")]
+        pub reorder: ::core::option::Option<Reorder>,
+        #[prost(oneof = "impairment::Loss", tags = "2, 3")]
+        pub loss: ::core::option::Option<impairment::Loss>,
+    }
+    /// Nested message and enum types in `Impairment`.
+    pub mod impairment {
+        #[allow(clippy::derive_partial_eq_without_eq)]
+        #[derive(Clone, PartialEq, ::prost::Oneof)]
+        pub enum Loss {
+            #[prost(message, tag = "2")]
+            Random(super::RandomLoss),
+            #[prost(message, tag = "3")]
+            GilbertElliott(super::GilbertElliottLoss),
+        }
+    }
+}
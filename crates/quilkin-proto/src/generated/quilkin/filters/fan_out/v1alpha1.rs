@@ -0,0 +1,12 @@
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FanOut {
+    #[prost(message, optional, tag = "1")]
+    pub metadata_key: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub join: ::prost::alloc::vec::Vec<u8>,
+    #[prost(message, optional, tag = "3")]
+    pub ttl: ::core::option::Option<u32>,
+    #[prost(message, optional, tag = "4")]
+    pub max_spectators: ::core::option::Option<u32>,
+}
@@ -0,0 +1,56 @@
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Fec {
+    #[prost(message, optional, tag = "1")]
+    pub on_read: ::core::option::Option<fec::Coding>,
+    #[prost(message, optional, tag = "2")]
+    pub on_write: ::core::option::Option<fec::Coding>,
+}
+/// Nested message and enum types in `Fec`.
+pub mod fec {
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct ActionValue {
+        #[prost(enumeration = "Action", tag = "1")]
+        pub value: i32,
+    }
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Coding {
+        #[prost(message, optional, tag = "1")]
+        pub action: ::core::option::Option<ActionValue>,
+        #[prost(message, optional, tag = "2")]
+        pub group_size: ::core::option::Option<u32>,
+        #[prost(message, optional, tag = "3")]
+        pub parity: ::core::option::Option<u32>,
+    }
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum Action {
+        DoNothing = 0,
+        Encode = 1,
+        Decode = 2,
+    }
+    impl Action {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Action::DoNothing => "DoNothing",
+                Action::Encode => "Encode",
+                Action::Decode => "Decode",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "DoNothing" => Some(Self::DoNothing),
+                "Encode" => Some(Self::Encode),
+                "Decode" => Some(Self::Decode),
+                _ => None,
+            }
+        }
+    }
+}
@@ -0,0 +1,60 @@
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Fragment {
+    #[prost(message, optional, tag = "1")]
+    pub on_read: ::core::option::Option<fragment::Fragmentation>,
+    #[prost(message, optional, tag = "2")]
+    pub on_write: ::core::option::Option<fragment::Fragmentation>,
+}
+/// Nested message and enum types in `Fragment`.
+pub mod fragment {
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct ActionValue {
+        #[prost(enumeration = "Action", tag = "1")]
+        pub value: i32,
+    }
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Fragmentation {
+        #[prost(message, optional, tag = "1")]
+        pub action: ::core::option::Option<ActionValue>,
+        #[prost(message, optional, tag = "2")]
+        pub max_size: ::core::option::Option<u32>,
+        #[prost(message, optional, tag = "3")]
+        pub max_message_size: ::core::option::Option<u32>,
+        #[prost(message, optional, tag = "4")]
+        pub max_messages: ::core::option::Option<u32>,
+        #[prost(message, optional, tag = "5")]
+        pub timeout: ::core::option::Option<u32>,
+    }
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum Action {
+        DoNothing = 0,
+        Fragment = 1,
+        Reassemble = 2,
+    }
+    impl Action {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Action::DoNothing => "DoNothing",
+                Action::Fragment => "Fragment",
+                Action::Reassemble => "Reassemble",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "DoNothing" => Some(Self::DoNothing),
+                "Fragment" => Some(Self::Fragment),
+                "Reassemble" => Some(Self::Reassemble),
+                _ => None,
+            }
+        }
+    }
+}
@@ -0,0 +1,48 @@
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct LabelRouter {
+    #[prost(message, optional, tag = "1")]
+    pub metadata_key: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, tag = "2")]
+    pub label: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "3")]
+    pub match_type: ::core::option::Option<label_router::MatchTypeValue>,
+}
+/// Nested message and enum types in `LabelRouter`.
+pub mod label_router {
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct MatchTypeValue {
+        #[prost(enumeration = "MatchType", tag = "1")]
+        pub value: i32,
+    }
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum MatchType {
+        Exact = 0,
+        Prefix = 1,
+        OneOf = 2,
+    }
+    impl MatchType {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                MatchType::Exact => "Exact",
+                MatchType::Prefix => "Prefix",
+                MatchType::OneOf => "OneOf",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "Exact" => Some(Self::Exact),
+                "Prefix" => Some(Self::Prefix),
+                "OneOf" => Some(Self::OneOf),
+                _ => None,
+            }
+        }
+    }
+}
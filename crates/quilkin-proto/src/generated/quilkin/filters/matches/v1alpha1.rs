@@ -8,6 +8,47 @@ pub struct Match {
 }
 /// Nested message and enum types in `Match`.
 pub mod r#match {
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Comparison {
+        #[prost(oneof = "comparison::Kind", tags = "1, 2, 3, 4, 5, 6")]
+        pub kind: ::core::option::Option<comparison::Kind>,
+    }
+    /// Nested message and enum types in `Comparison`.
+    pub mod comparison {
+        #[allow(clippy::derive_partial_eq_without_eq)]
+        #[derive(Clone, PartialEq, ::prost::Message)]
+        pub struct Range {
+            #[prost(uint64, optional, tag = "1")]
+            pub min: ::core::option::Option<u64>,
+            #[prost(uint64, optional, tag = "2")]
+            pub max: ::core::option::Option<u64>,
+        }
+        #[allow(clippy::derive_partial_eq_without_eq)]
+        #[derive(Clone, PartialEq, ::prost::Oneof)]
+        pub enum Kind {
+            #[prost(message, tag = "1")]
+            Equals(::prost_types::Value),
+            #[prost(message, tag = "2")]
+            Range(Range),
+            #[prost(bytes, tag = "3")]
+            Prefix(::prost::alloc::vec::Vec<u8>),
+            #[prost(bytes, tag = "4")]
+            Suffix(::prost::alloc::vec::Vec<u8>),
+            #[prost(string, tag = "5")]
+            Regex(::prost::alloc::string::String),
+            #[prost(message, tag = "6")]
+            Contains(::prost_types::Value),
+        }
+    }
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct KeyComparison {
+        #[prost(string, tag = "1")]
+        pub key: ::prost::alloc::string::String,
+        #[prost(message, optional, tag = "2")]
+        pub comparison: ::core::option::Option<Comparison>,
+    }
     #[allow(clippy::derive_partial_eq_without_eq)]
     #[derive(Clone, PartialEq, ::prost::Message)]
     pub struct Branch {
@@ -17,6 +58,12 @@ pub mod r#match {
         pub filter: ::core::option::Option<
             super::super::super::super::super::envoy::config::listener::v3::Filter,
         >,
+        #[prost(message, optional, tag = "3")]
+        pub comparison: ::core::option::Option<Comparison>,
+        #[prost(message, repeated, tag = "4")]
+        pub all: ::prost::alloc::vec::Vec<KeyComparison>,
+        #[prost(message, repeated, tag = "5")]
+        pub any: ::prost::alloc::vec::Vec<KeyComparison>,
     }
     #[allow(clippy::derive_partial_eq_without_eq)]
     #[derive(Clone, PartialEq, ::prost::Message)]
@@ -0,0 +1,134 @@
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Metadata {
+    #[prost(message, repeated, tag = "1")]
+    pub on_read: ::prost::alloc::vec::Vec<metadata::Operation>,
+    #[prost(message, repeated, tag = "2")]
+    pub on_write: ::prost::alloc::vec::Vec<metadata::Operation>,
+}
+/// Nested message and enum types in `Metadata`.
+pub mod metadata {
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct EncodingValue {
+        #[prost(enumeration = "Encoding", tag = "1")]
+        pub value: i32,
+    }
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Set {
+        #[prost(string, tag = "1")]
+        pub key: ::prost::alloc::string::String,
+        #[prost(message, optional, tag = "2")]
+        pub value: ::core::option::Option<::prost_types::Value>,
+    }
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Move {
+        #[prost(string, tag = "1")]
+        pub from: ::prost::alloc::string::String,
+        #[prost(string, tag = "2")]
+        pub to: ::prost::alloc::string::String,
+    }
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Delete {
+        #[prost(string, tag = "1")]
+        pub key: ::prost::alloc::string::String,
+    }
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Hash {
+        #[prost(string, tag = "1")]
+        pub from: ::prost::alloc::string::String,
+        #[prost(string, optional, tag = "2")]
+        pub to: ::core::option::Option<::prost::alloc::string::String>,
+    }
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Transcode {
+        #[prost(string, tag = "1")]
+        pub from: ::prost::alloc::string::String,
+        #[prost(string, optional, tag = "2")]
+        pub to: ::core::option::Option<::prost::alloc::string::String>,
+        #[prost(message, optional, tag = "3")]
+        pub encoding: ::core::option::Option<EncodingValue>,
+    }
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Integer {
+        #[prost(string, tag = "1")]
+        pub from: ::prost::alloc::string::String,
+        #[prost(string, optional, tag = "2")]
+        pub to: ::core::option::Option<::prost::alloc::string::String>,
+        #[prost(uint32, tag = "3")]
+        pub offset: u32,
+        #[prost(uint32, tag = "4")]
+        pub size: u32,
+    }
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Concatenate {
+        #[prost(string, repeated, tag = "1")]
+        pub from: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+        #[prost(string, tag = "2")]
+        pub to: ::prost::alloc::string::String,
+    }
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Operation {
+        #[prost(oneof = "operation::Operation", tags = "1, 2, 3, 4, 5, 6, 7, 8, 9")]
+        pub operation: ::core::option::Option<operation::Operation>,
+    }
+    /// Nested message and enum types in `Operation`.
+    pub mod operation {
+        #[allow(clippy::derive_partial_eq_without_eq)]
+        #[derive(Clone, PartialEq, ::prost::Oneof)]
+        pub enum Operation {
+            #[prost(message, tag = "1")]
+            Set(super::Set),
+            #[prost(message, tag = "2")]
+            Copy(super::Move),
+            #[prost(message, tag = "3")]
+            Rename(super::Move),
+            #[prost(message, tag = "4")]
+            Delete(super::Delete),
+            #[prost(message, tag = "5")]
+            Hash(super::Hash),
+            #[prost(message, tag = "6")]
+            Encode(super::Transcode),
+            #[prost(message, tag = "7")]
+            Decode(super::Transcode),
+            #[prost(message, tag = "8")]
+            Integer(super::Integer),
+            #[prost(message, tag = "9")]
+            Concatenate(super::Concatenate),
+        }
+    }
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum Encoding {
+        Base64 = 0,
+        Hex = 1,
+    }
+    impl Encoding {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Encoding::Base64 => "Base64",
+                Encoding::Hex => "Hex",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "Base64" => Some(Self::Base64),
+                "Hex" => Some(Self::Hex),
+                _ => None,
+            }
+        }
+    }
+}
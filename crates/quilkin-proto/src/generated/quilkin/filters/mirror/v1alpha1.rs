@@ -0,0 +1,66 @@
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Mirror {
+    #[prost(message, optional, tag = "3")]
+    pub rate: ::core::option::Option<f64>,
+    #[prost(message, optional, tag = "4")]
+    pub stickiness: ::core::option::Option<mirror::StickinessValue>,
+    #[prost(message, optional, tag = "5")]
+    pub metadata_key: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(oneof = "mirror::Target", tags = "1, 2")]
+    pub target: ::core::option::Option<mirror::Target>,
+}
+/// Nested message and enum types in `Mirror`.
+pub mod mirror {
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct StickinessValue {
+        #[prost(enumeration = "Stickiness", tag = "1")]
+        pub value: i32,
+    }
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Label {
+        #[prost(string, tag = "1")]
+        pub key: ::prost::alloc::string::String,
+        #[prost(string, tag = "2")]
+        pub value: ::prost::alloc::string::String,
+    }
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum Stickiness {
+        None = 0,
+        Source = 1,
+        Token = 2,
+    }
+    impl Stickiness {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Stickiness::None => "None",
+                Stickiness::Source => "Source",
+                Stickiness::Token => "Token",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "None" => Some(Self::None),
+                "Source" => Some(Self::Source),
+                "Token" => Some(Self::Token),
+                _ => None,
+            }
+        }
+    }
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Target {
+        #[prost(string, tag = "1")]
+        Address(::prost::alloc::string::String),
+        #[prost(message, tag = "2")]
+        Label(Label),
+    }
+}
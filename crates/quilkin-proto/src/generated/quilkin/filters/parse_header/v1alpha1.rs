@@ -0,0 +1,133 @@
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ParseHeader {
+    #[prost(message, repeated, tag = "1")]
+    pub fields: ::prost::alloc::vec::Vec<parse_header::Field>,
+    #[prost(message, optional, tag = "2")]
+    pub on_read: ::core::option::Option<parse_header::ModeValue>,
+    #[prost(message, optional, tag = "3")]
+    pub on_write: ::core::option::Option<parse_header::ModeValue>,
+}
+/// Nested message and enum types in `ParseHeader`.
+pub mod parse_header {
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct ModeValue {
+        #[prost(enumeration = "Mode", tag = "1")]
+        pub value: i32,
+    }
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Integer {
+        #[prost(enumeration = "IntegerEncoding", tag = "1")]
+        pub encoding: i32,
+        #[prost(message, optional, tag = "2")]
+        pub size: ::core::option::Option<u32>,
+    }
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Bytes {
+        #[prost(uint32, tag = "1")]
+        pub size: u32,
+    }
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct LengthPrefixed {
+        #[prost(message, optional, tag = "1")]
+        pub length: ::core::option::Option<Integer>,
+    }
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Tlv {
+        #[prost(message, optional, tag = "1")]
+        pub block_length: ::core::option::Option<Integer>,
+        #[prost(message, optional, tag = "2")]
+        pub r#type: ::core::option::Option<Integer>,
+        #[prost(message, optional, tag = "3")]
+        pub length: ::core::option::Option<Integer>,
+    }
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Field {
+        #[prost(string, tag = "1")]
+        pub key: ::prost::alloc::string::String,
+        #[prost(oneof = "field::Kind", tags = "2, 3, 4, 5")]
+        pub kind: ::core::option::Option<field::Kind>,
+    }
+    /// Nested message and enum types in `Field`.
+    pub mod field {
+        #[allow(clippy::derive_partial_eq_without_eq)]
+        #[derive(Clone, PartialEq, ::prost::Oneof)]
+        pub enum Kind {
+            #[prost(message, tag = "2")]
+            Integer(super::Integer),
+            #[prost(message, tag = "3")]
+            Bytes(super::Bytes),
+            #[prost(message, tag = "4")]
+            LengthPrefixed(super::LengthPrefixed),
+            #[prost(message, tag = "5")]
+            Tlv(super::Tlv),
+        }
+    }
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum Mode {
+        DoNothing = 0,
+        Parse = 1,
+        Strip = 2,
+        Prepend = 3,
+    }
+    impl Mode {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Mode::DoNothing => "DoNothing",
+                Mode::Parse => "Parse",
+                Mode::Strip => "Strip",
+                Mode::Prepend => "Prepend",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "DoNothing" => Some(Self::DoNothing),
+                "Parse" => Some(Self::Parse),
+                "Strip" => Some(Self::Strip),
+                "Prepend" => Some(Self::Prepend),
+                _ => None,
+            }
+        }
+    }
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum IntegerEncoding {
+        BigEndian = 0,
+        LittleEndian = 1,
+        Varint = 2,
+    }
+    impl IntegerEncoding {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                IntegerEncoding::BigEndian => "BigEndian",
+                IntegerEncoding::LittleEndian => "LittleEndian",
+                IntegerEncoding::Varint => "Varint",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "BigEndian" => Some(Self::BigEndian),
+                "LittleEndian" => Some(Self::LittleEndian),
+                "Varint" => Some(Self::Varint),
+                _ => None,
+            }
+        }
+    }
+}
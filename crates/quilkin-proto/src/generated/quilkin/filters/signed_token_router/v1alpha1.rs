@@ -0,0 +1,21 @@
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SignedTokenRouter {
+    #[prost(message, optional, tag = "1")]
+    pub metadata_key: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(message, optional, tag = "2")]
+    pub player_id_key: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(oneof = "signed_token_router::Key", tags = "3, 4")]
+    pub key: ::core::option::Option<signed_token_router::Key>,
+}
+/// Nested message and enum types in `SignedTokenRouter`.
+pub mod signed_token_router {
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Key {
+        #[prost(bytes, tag = "3")]
+        HmacSha256(::prost::alloc::vec::Vec<u8>),
+        #[prost(bytes, tag = "4")]
+        Ed25519(::prost::alloc::vec::Vec<u8>),
+    }
+}
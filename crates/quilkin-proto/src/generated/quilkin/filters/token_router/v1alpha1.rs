@@ -3,4 +3,6 @@
 pub struct TokenRouter {
     #[prost(message, optional, tag = "1")]
     pub metadata_key: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(message, optional, tag = "2")]
+    pub session_routing: ::core::option::Option<bool>,
 }
@@ -0,0 +1,74 @@
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TrafficSplit {
+    #[prost(message, repeated, tag = "1")]
+    pub targets: ::prost::alloc::vec::Vec<traffic_split::Target>,
+    #[prost(message, optional, tag = "2")]
+    pub stickiness: ::core::option::Option<traffic_split::StickinessValue>,
+    #[prost(message, optional, tag = "3")]
+    pub metadata_key: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(message, optional, tag = "4")]
+    pub session_key: ::core::option::Option<::prost::alloc::string::String>,
+}
+/// Nested message and enum types in `TrafficSplit`.
+pub mod traffic_split {
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct StickinessValue {
+        #[prost(enumeration = "Stickiness", tag = "1")]
+        pub value: i32,
+    }
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Label {
+        #[prost(string, tag = "1")]
+        pub key: ::prost::alloc::string::String,
+        #[prost(string, tag = "2")]
+        pub value: ::prost::alloc::string::String,
+    }
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Target {
+        #[prost(uint32, tag = "1")]
+        pub weight: u32,
+        #[prost(oneof = "target::Selector", tags = "2, 3")]
+        pub selector: ::core::option::Option<target::Selector>,
+    }
+    /// Nested message and enum types in `Target`.
+    pub mod target {
+        #[allow(clippy::derive_partial_eq_without_eq)]
+        #[derive(Clone, PartialEq, ::prost::Oneof)]
+        pub enum Selector {
+            #[prost(string, tag = "2")]
+            Locality(::prost::alloc::string::String),
+            #[prost(message, tag = "3")]
+            Label(super::Label),
+        }
+    }
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum Stickiness {
+        Source = 0,
+        Token = 1,
+    }
+    impl Stickiness {
+        /// String value of the enum field names used in the ProtoBuf definition.
+        ///
+        /// The values are not transformed in any way and thus are considered stable
+        /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Stickiness::Source => "Source",
+                Stickiness::Token => "Token",
+            }
+        }
+        /// Creates an enum from field names used in the ProtoBuf definition.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "Source" => Some(Self::Source),
+                "Token" => Some(Self::Token),
+                _ => None,
+            }
+        }
+    }
+}
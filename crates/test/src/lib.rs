@@ -355,6 +355,7 @@ impl Pail {
                         (std::net::Ipv4Addr::UNSPECIFIED, server.port).into(),
                         quilkin::net::endpoint::Metadata {
                             tokens: tokens.into_iter().map(|t| Vec::from(*t)).collect(),
+                            labels: <_>::default(),
                         },
                     ));
                 }
@@ -492,6 +493,7 @@ impl Pail {
                         socket,
                         qcmp,
                         phoenix,
+                        canary: None,
                     }
                     .run(
                         RunArgs {
@@ -50,6 +50,7 @@ trace_test!(relay_routing, {
                             size: 3,
                             remove: true,
                         }),
+                        captures: vec![],
                     })
                     .unwrap(),
                     HashedTokenRouter::as_filter_config(None).unwrap(),
@@ -109,7 +110,10 @@ trace_test!(relay_routing, {
             config.clusters.insert_default(
                 [Endpoint::with_metadata(
                     (std::net::Ipv6Addr::LOCALHOST, server_port).into(),
-                    quilkin::net::endpoint::Metadata { tokens },
+                    quilkin::net::endpoint::Metadata {
+                        tokens,
+                        labels: <_>::default(),
+                    },
                 )]
                 .into(),
             );
@@ -123,6 +123,7 @@ trace_test!(uring_receiver, {
             tx,
             BUFFER_POOL.clone(),
             shutdown_rx,
+            <_>::default(),
         ),
     }
     .spawn()
@@ -165,6 +166,7 @@ trace_test!(
             tx,
             BUFFER_POOL.clone(),
             shutdown_rx,
+            <_>::default(),
         );
 
         const WORKER_COUNT: usize = 3;
@@ -283,6 +283,7 @@ mod tests {
                         remove: true,
                     }
                     .into(),
+                    captures: vec![],
                 })
                 .unwrap(),
                 Match::as_filter_config(r#match::Config {
@@ -290,7 +291,7 @@ mod tests {
                     on_read: Some(r#match::DirectionalConfig {
                         metadata_key: VERSION_KEY.into(),
                         branches: vec![r#match::Branch {
-                            value: 1.into(),
+                            condition: r#match::Condition::Value(1.into()),
                             filter: Capture::as_filter_config(capture::Config {
                                 metadata_key: TOKEN_KEY.into(),
                                 strategy: capture::Suffix {
@@ -298,6 +299,7 @@ mod tests {
                                     remove: true,
                                 }
                                 .into(),
+                                captures: vec![],
                             })
                             .unwrap(),
                         }],
@@ -310,7 +312,7 @@ mod tests {
                     on_read: Some(r#match::DirectionalConfig {
                         metadata_key: VERSION_KEY.into(),
                         branches: vec![r#match::Branch {
-                            value: 1.into(),
+                            condition: r#match::Condition::Value(1.into()),
                             filter: TokenRouter::as_filter_config(token_router::Config {
                                 metadata_key: TOKEN_KEY.into(),
                             })
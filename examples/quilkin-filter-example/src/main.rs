@@ -98,6 +98,7 @@ async fn main() -> quilkin::Result<()> {
             name: Greet::NAME.into(),
             label: None,
             config: None,
+            when: None,
         }])?,
     ));
     config.clusters.modify(|map| {
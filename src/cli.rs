@@ -155,6 +155,8 @@ impl Cli {
                         .idle_request_interval_secs
                         .map(std::time::Duration::from_secs)
                         .unwrap_or(admin_server::IDLE_REQUEST_INTERVAL),
+                    session_events: components::proxy::SessionEvents::default()
+                        .with_token_key(proxy.session_token_key),
                     ..Default::default()
                 };
                 Admin::Proxy(ready)
@@ -20,7 +20,9 @@ use tonic::transport::Endpoint;
 #[cfg(doc)]
 use crate::filters::FilterFactory;
 
-use crate::ShutdownRx;
+use crate::{
+    config::canary, filters::capture::CAPTURED_BYTES, net::endpoint::metadata, ShutdownRx,
+};
 
 pub use crate::components::proxy::Ready;
 
@@ -28,6 +30,9 @@ define_port!(7777);
 
 const QCMP_PORT: u16 = 7600;
 
+const DEFAULT_CANARY_DURATION_SECS: u64 = canary::DEFAULT_DURATION.as_secs();
+const DEFAULT_CANARY_MAX_DURATION_SECS: u64 = canary::DEFAULT_MAX_DURATION.as_secs();
+
 /// Run Quilkin as a UDP reverse proxy.
 #[derive(clap::Args, Clone, Debug)]
 pub struct Proxy {
@@ -54,6 +59,32 @@ pub struct Proxy {
     /// to number of cpus.
     #[clap(short, long, env = "QUILKIN_WORKERS")]
     pub workers: Option<std::num::NonZeroUsize>,
+    /// The fraction of sessions, greater than 0 and at most 1, that new filter
+    /// chains from a management server are trialled on before being applied
+    /// to every session. New filter chains are applied immediately if unset.
+    #[clap(long, env = "QUILKIN_CANARY_FRACTION")]
+    pub canary_fraction: Option<f64>,
+    /// The number of seconds a new filter chain is trialled for before it's
+    /// promoted or rolled back.
+    #[clap(long, env = "QUILKIN_CANARY_DURATION_SECS", default_value_t = DEFAULT_CANARY_DURATION_SECS)]
+    pub canary_duration_secs: u64,
+    /// The minimum number of packets a new filter chain has to process before
+    /// it's promoted or rolled back.
+    #[clap(long, env = "QUILKIN_CANARY_MIN_PACKETS", default_value_t = canary::DEFAULT_MIN_PACKETS)]
+    pub canary_min_packets: u64,
+    /// The maximum number of seconds a new filter chain is trialled for, after
+    /// which it's promoted or rolled back even if it hasn't processed the
+    /// minimum number of packets.
+    #[clap(long, env = "QUILKIN_CANARY_MAX_DURATION_SECS", default_value_t = DEFAULT_CANARY_MAX_DURATION_SECS)]
+    pub canary_max_duration_secs: u64,
+    /// How much higher a new filter chain's error or drop rate can be than the
+    /// previous chain's before it's rolled back, e.g. 0.01 for one percent.
+    #[clap(long, env = "QUILKIN_CANARY_MAX_RATE_INCREASE", default_value_t = canary::DEFAULT_MAX_RATE_INCREASE)]
+    pub canary_max_rate_increase: f64,
+    /// The dynamic metadata key the routing token reported in session events
+    /// is read from, e.g. the `metadataKey` of the `TokenRouter` filter.
+    #[clap(long, env = "QUILKIN_SESSION_TOKEN_KEY", default_value = CAPTURED_BYTES)]
+    pub session_token_key: metadata::Key,
 }
 
 impl Default for Proxy {
@@ -66,6 +97,12 @@ impl Default for Proxy {
             to: <_>::default(),
             idle_request_interval_secs: None,
             workers: None,
+            canary_fraction: None,
+            canary_duration_secs: DEFAULT_CANARY_DURATION_SECS,
+            canary_min_packets: canary::DEFAULT_MIN_PACKETS,
+            canary_max_duration_secs: DEFAULT_CANARY_MAX_DURATION_SECS,
+            canary_max_rate_increase: canary::DEFAULT_MAX_RATE_INCREASE,
+            session_token_key: metadata::Key::from_static(CAPTURED_BYTES),
         }
     }
 }
@@ -93,6 +130,20 @@ impl Proxy {
                 .expect("num_cpus returned 0, which should be impossible")
         });
 
+        let canary = self
+            .canary_fraction
+            .map(|fraction| {
+                let settings = canary::CanarySettings {
+                    fraction,
+                    duration: std::time::Duration::from_secs(self.canary_duration_secs),
+                    min_packets: self.canary_min_packets,
+                    max_duration: std::time::Duration::from_secs(self.canary_max_duration_secs),
+                    max_rate_increase: self.canary_max_rate_increase,
+                };
+                settings.validate().map(|_| settings)
+            })
+            .transpose()?;
+
         let socket = crate::net::raw_socket_with_reuse(self.port)?;
         let qcmp = crate::net::raw_socket_with_reuse(self.qcmp_port)?;
         let phoenix = crate::net::TcpListener::bind(Some(self.qcmp_port))?;
@@ -105,6 +156,7 @@ impl Proxy {
             socket,
             qcmp,
             phoenix,
+            canary,
         }
         .run(
             crate::components::RunArgs {
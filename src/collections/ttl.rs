@@ -217,6 +217,13 @@ where
         !self.is_empty()
     }
 
+    /// Calls `f` with every value in the map, without resetting their TTL.
+    pub fn for_each_mut(&self, mut f: impl FnMut(&mut V)) {
+        for mut entry in self.0.inner.iter_mut() {
+            f(&mut entry.value_mut().value);
+        }
+    }
+
     /// Returns true if the map contains a value for the specified key.
     pub fn contains_key(&self, key: &K) -> bool {
         self.0.inner.contains_key(key)
@@ -237,6 +244,11 @@ where
         self.0.inner.remove(&key).is_some()
     }
 
+    /// Removes a key-value pair from the map, returning the value if present.
+    pub fn take(&self, key: &K) -> Option<V> {
+        self.0.inner.remove(key).map(|(_, value)| value.value)
+    }
+
     /// Returns an entry for in-place updates of the specified key-value pair.
     /// Note: This acquires a write lock on the map's shard that corresponds
     /// to the entry.
@@ -560,6 +572,21 @@ mod tests {
         assert!(map.contains_key(&two));
     }
 
+    #[tokio::test]
+    async fn take() {
+        let (one, two) = address_pair();
+
+        let map = TtlMap::<EndpointAddress, usize>::new(
+            Duration::from_secs(10),
+            Duration::from_millis(10),
+        );
+        map.insert(one.clone(), 1);
+
+        assert_eq!(map.take(&one), Some(1));
+        assert_eq!(map.take(&two), None);
+        assert!(map.is_empty());
+    }
+
     #[tokio::test]
     async fn entry_occupied_insert_and_get() {
         let (one, _) = address_pair();
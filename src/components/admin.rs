@@ -139,6 +139,25 @@ impl Admin {
                 }
             }
             (&Method::GET, "/ready" | "/readyz") => check_readiness(|| self.is_ready(&config)),
+            (&Method::GET, "/sessions/events") => match self {
+                Self::Proxy(proxy) => Response::builder()
+                    .status(StatusCode::OK)
+                    .header(
+                        "Content-Type",
+                        hyper::header::HeaderValue::from_static("text/event-stream"),
+                    )
+                    .header(
+                        "Cache-Control",
+                        hyper::header::HeaderValue::from_static("no-cache"),
+                    )
+                    .body(Body::wrap_stream(proxy.session_events.server_sent_events()))
+                    .unwrap(),
+                _ => {
+                    let mut response = Response::new(Body::empty());
+                    *response.status_mut() = StatusCode::NOT_FOUND;
+                    response
+                }
+            },
             (&Method::GET, "/config") => match serde_json::to_string(&config) {
                 Ok(body) => Response::builder()
                     .status(StatusCode::OK)
@@ -54,6 +54,11 @@ impl Agent {
             icao_code.store(self.icao_code.unwrap_or_default().into());
         }
 
+        tokio::spawn(crate::net::cluster::prune_expired_tokens(
+            config.clusters.clone(),
+            shutdown_rx.clone(),
+        ));
+
         let _mds_task = if !self.relay_servers.is_empty() {
             let _provider_task = match self.provider {
                 Some(provider) => Some(provider.spawn(
@@ -68,6 +68,11 @@ impl Manage {
             None
         };
 
+        tokio::spawn(crate::net::cluster::prune_expired_tokens(
+            config.clusters.clone(),
+            shutdown_rx.clone(),
+        ));
+
         use futures::TryFutureExt as _;
         let server_task = tokio::spawn(crate::net::xds::server::spawn(
             self.listener,
@@ -6,7 +6,7 @@ use crate::{
     net::{maxmind_db::IpNetEntry, xds::ResourceType},
     pool::PoolBuffer,
 };
-pub use sessions::SessionPool;
+pub use sessions::{SessionEvent, SessionEventKind, SessionEvents, SessionPool, SESSION_ID};
 use std::{
     net::SocketAddr,
     sync::{
@@ -37,6 +37,8 @@ pub struct Ready {
     pub idle_request_interval: std::time::Duration,
     // RwLock as this check is conditional on the proxy using xDS.
     pub xds_is_healthy: Arc<parking_lot::RwLock<Option<Arc<AtomicBool>>>>,
+    /// Publishes the creation and expiry of sessions.
+    pub session_events: SessionEvents,
 }
 
 impl Ready {
@@ -57,6 +59,9 @@ pub struct Proxy {
     pub socket: socket2::Socket,
     pub qcmp: socket2::Socket,
     pub phoenix: crate::net::TcpListener,
+    /// Trials filter chains received from management servers on a fraction
+    /// of sessions before applying them to every session, when set.
+    pub canary: Option<crate::config::canary::CanarySettings>,
 }
 
 impl Default for Proxy {
@@ -72,6 +77,7 @@ impl Default for Proxy {
             socket: crate::net::raw_socket_with_reuse(0).unwrap(),
             qcmp,
             phoenix,
+            canary: None,
         }
     }
 }
@@ -118,6 +124,19 @@ impl Proxy {
              ));
         }
 
+        tokio::spawn(crate::net::cluster::prune_expired_tokens(
+            config.clusters.clone(),
+            shutdown_rx.clone(),
+        ));
+
+        if let Some(settings) = self.canary {
+            config.filter_rollout.enable(settings);
+            tokio::spawn(crate::config::canary::evaluate_canaries(
+                config.clone(),
+                shutdown_rx.clone(),
+            ));
+        }
+
         let id = config.id.load();
         let num_workers = self.num_workers.get();
 
@@ -129,6 +148,7 @@ impl Proxy {
             upstream_sender,
             buffer_pool.clone(),
             shutdown_rx.clone(),
+            ready.session_events.clone(),
         );
 
         if !self.management_servers.is_empty() {
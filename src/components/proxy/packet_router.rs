@@ -1,13 +1,16 @@
 use super::{
     sessions::{DownstreamReceiver, SessionKey},
-    PipelineError, PipelineErrorDiscriminants, SessionPool,
+    PipelineError, PipelineErrorDiscriminants, SessionPool, SESSION_ID,
 };
 use crate::{
+    config::canary::Outcome,
     filters::{Filter as _, ReadContext},
+    net::{endpoint::metadata, maxmind_db::IpNetEntry, DualStackLocalSocketRc},
     pool::PoolBuffer,
     time::UtcTimestamp,
     Config,
 };
+use bytes::Bytes;
 use std::{net::SocketAddr, sync::Arc};
 use tokio::sync::mpsc;
 
@@ -79,30 +82,7 @@ impl DownstreamReceiveWorkerConfig {
                                 }
                                 Ok((data, asn_info, send_addr)) => {
                                     let (result, _) = send_socket.send_to(data, send_addr).await;
-                                    let asn_info = asn_info.as_ref();
-                                    match result {
-                                        Ok(size) => {
-                                            crate::metrics::packets_total(crate::metrics::WRITE, asn_info)
-                                                .inc();
-                                            crate::metrics::bytes_total(crate::metrics::WRITE, asn_info)
-                                                .inc_by(size as u64);
-                                        }
-                                        Err(error) => {
-                                            let source = error.to_string();
-                                            crate::metrics::errors_total(
-                                                crate::metrics::WRITE,
-                                                &source,
-                                                asn_info,
-                                                )
-                                                .inc();
-                                            crate::metrics::packets_dropped_total(
-                                                crate::metrics::WRITE,
-                                                &source,
-                                                asn_info,
-                                                )
-                                                .inc();
-                                        }
-                                    }
+                                    record_sent(result, asn_info.as_ref());
                                 }
                             }
                         }
@@ -148,6 +128,7 @@ impl DownstreamReceiveWorkerConfig {
                             packet,
                             source,
                             worker_id,
+                            &socket,
                             &config,
                             &sessions,
                             &error_sender,
@@ -172,6 +153,7 @@ impl DownstreamReceiveWorkerConfig {
         packet: DownstreamPacket,
         source: std::net::SocketAddr,
         worker_id: usize,
+        socket: &DualStackLocalSocketRc,
         config: &Arc<Config>,
         sessions: &Arc<SessionPool>,
         error_sender: &mpsc::UnboundedSender<PipelineError>,
@@ -186,70 +168,191 @@ impl DownstreamReceiveWorkerConfig {
         let timer = crate::metrics::processing_time(crate::metrics::READ).start_timer();
         let asn_info = packet.asn_info.clone();
         let asn_info = asn_info.as_ref();
-        match Self::process_downstream_received_packet(packet, config, sessions).await {
-            Ok(()) => {}
-            Err(error) => {
-                let discriminant = PipelineErrorDiscriminants::from(&error).to_string();
-                crate::metrics::errors_total(crate::metrics::READ, &discriminant, asn_info).inc();
-                crate::metrics::packets_dropped_total(
-                    crate::metrics::READ,
-                    &discriminant,
-                    asn_info,
-                )
-                .inc();
-                let _ = error_sender.send(error);
+        let mut replies = Vec::new();
+        let result = Self::process_downstream_received_packet(
+            packet,
+            config,
+            sessions,
+            error_sender,
+            &mut replies,
+        )
+        .await;
+
+        for reply in replies {
+            tracing::trace!(%source, length = reply.len(), "sending reply downstream");
+            let (result, _) = socket.send_to(reply, source).await;
+            if result.is_ok() {
+                crate::metrics::packets_replied_total(crate::metrics::READ, asn_info).inc();
             }
+            record_sent(result, asn_info);
+        }
+
+        if let Err(error) = result {
+            record_error(error, asn_info, error_sender);
         }
 
         timer.stop_and_record();
     }
 
-    /// Processes a packet by running it through the filter chain.
+    /// Processes a packet by running it through the filter chain, adding the
+    /// replies of the filters to send back to the packet's source to `replies`.
     #[inline]
     async fn process_downstream_received_packet(
         packet: DownstreamPacket,
         config: &Arc<Config>,
         sessions: &Arc<SessionPool>,
+        error_sender: &mpsc::UnboundedSender<PipelineError>,
+        replies: &mut Vec<Bytes>,
     ) -> Result<(), PipelineError> {
         if !config.clusters.read().has_endpoints() {
             tracing::trace!("no upstream endpoints");
             return Err(PipelineError::NoUpstreamEndpoints);
         }
 
-        let filters = config.filters.load();
+        let filters = config.filters_for(packet.source);
         let mut context = ReadContext::new(
             config.clusters.clone_value(),
             packet.source.into(),
             packet.contents,
         );
-        filters.read(&mut context).await?;
+        context.session = sessions.metadata(packet.source);
+        if let Err(error) = filters.read(&mut context).await {
+            filters.record(Outcome::Errored);
+            return Err(error.into());
+        }
+
+        *replies = std::mem::take(&mut context.replies);
+        if context.is_handled() {
+            filters.record(Outcome::Forwarded);
+            return Ok(());
+        }
 
         let ReadContext {
             destinations,
             contents,
+            metadata: dynamic_metadata,
+            delay,
+            forwarded,
             ..
         } = context;
 
+        // The routing token is only reported in session events, so there's no
+        // need to look it up when no one is listening.
+        let token = sessions
+            .events()
+            .has_subscribers()
+            .then(
+                || match dynamic_metadata.get(&sessions.events().token_key()) {
+                    Some(metadata::Value::Bytes(token)) => Some(token.clone()),
+                    _ => None,
+                },
+            )
+            .flatten();
+
         // Similar to bytes::BytesMut::freeze, we turn the mutable pool buffer
         // into an immutable one with its own internal arc so it can be cloned
         // cheaply and returned to the pool once all references are dropped
         let contents = contents.freeze();
+        let forwarded = forwarded
+            .into_iter()
+            .map(PoolBuffer::freeze)
+            .collect::<Vec<_>>();
+
+        if let Some(metadata::Value::Bytes(id)) =
+            dynamic_metadata.get(&metadata::Key::from_static(SESSION_ID))
+        {
+            sessions.identify(id.clone(), packet.source).await;
+        }
 
-        for epa in destinations {
-            let session_key = SessionKey {
-                source: packet.source,
-                dest: epa.to_socket_addr().await?,
-            };
+        let DownstreamPacket {
+            source, asn_info, ..
+        } = packet;
+        // The ASN is only needed again to report errors forwarding a delayed
+        // packet.
+        let delayed_asn_info = (!delay.is_zero()).then(|| asn_info.clone()).flatten();
+        let sessions = sessions.clone();
+        let forward = async move {
+            for epa in destinations {
+                let session_key = SessionKey {
+                    source,
+                    dest: epa.to_socket_addr().await?,
+                };
+
+                // Any further packets a filter forwarded follow the packet.
+                for contents in std::iter::once(&contents).chain(&forwarded) {
+                    sessions
+                        .send(
+                            session_key,
+                            asn_info.clone(),
+                            token.clone(),
+                            contents.clone(),
+                        )
+                        .await?;
+                }
+            }
+
+            Ok::<_, PipelineError>(())
+        };
 
-            sessions
-                .send(session_key, packet.asn_info.clone(), contents.clone())
-                .await?;
+        if delay.is_zero() {
+            let forwarded = forward.await;
+            filters.record(if forwarded.is_ok() {
+                Outcome::Forwarded
+            } else {
+                Outcome::Dropped
+            });
+            return forwarded;
         }
 
+        // Delayed packets are forwarded by their own task, so they don't hold
+        // up the packets received after them.
+        tracing::trace!(%source, ?delay, "delaying packet");
+        let error_sender = error_sender.clone();
+        uring_inner_spawn!(async move {
+            tokio::time::sleep(delay).await;
+            let forwarded = forward.await;
+            filters.record(if forwarded.is_ok() {
+                Outcome::Forwarded
+            } else {
+                Outcome::Dropped
+            });
+            if let Err(error) = forwarded {
+                record_error(error, delayed_asn_info.as_ref(), &error_sender);
+            }
+        });
+
         Ok(())
     }
 }
 
+/// Records the metrics of a packet that couldn't be processed, and reports
+/// the error.
+fn record_error(
+    error: PipelineError,
+    asn_info: Option<&IpNetEntry>,
+    error_sender: &mpsc::UnboundedSender<PipelineError>,
+) {
+    let discriminant = PipelineErrorDiscriminants::from(&error).to_string();
+    crate::metrics::errors_total(crate::metrics::READ, &discriminant, asn_info).inc();
+    crate::metrics::packets_dropped_total(crate::metrics::READ, &discriminant, asn_info).inc();
+    let _ = error_sender.send(error);
+}
+
+/// Records the metrics of a packet sent downstream.
+fn record_sent(result: std::io::Result<usize>, asn_info: Option<&IpNetEntry>) {
+    match result {
+        Ok(size) => {
+            crate::metrics::packets_total(crate::metrics::WRITE, asn_info).inc();
+            crate::metrics::bytes_total(crate::metrics::WRITE, asn_info).inc_by(size as u64);
+        }
+        Err(error) => {
+            let source = error.to_string();
+            crate::metrics::errors_total(crate::metrics::WRITE, &source, asn_info).inc();
+            crate::metrics::packets_dropped_total(crate::metrics::WRITE, &source, asn_info).inc();
+        }
+    }
+}
+
 /// Spawns a background task that sits in a loop, receiving packets from the passed in socket.
 /// Each received packet is placed on a queue to be processed by a worker task.
 /// This function also spawns the set of worker tasks responsible for consuming packets
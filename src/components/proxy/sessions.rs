@@ -27,18 +27,29 @@ use tokio::{
 };
 
 use crate::{
-    config::Config,
+    config::{canary::Outcome, Config},
     filters::Filter,
+    net::endpoint::{EndpointAddress, Locality, SessionMetadata},
     net::maxmind_db::IpNetEntry,
-    net::DualStackLocalSocket,
+    net::{DualStackLocalSocket, DualStackLocalSocketRc},
     pool::{BufferPool, FrozenPoolBuffer, PoolBuffer},
     time::UtcTimestamp,
     Loggable, ShutdownRx,
 };
 
+mod events;
 pub(crate) mod metrics;
 
+pub use self::events::{SessionEvent, SessionEventKind, SessionEvents};
+
+/// The well known dynamic metadata key for the ID of a client's session. When
+/// set by a filter, packets with the same ID from a new client address
+/// migrate the client's existing sessions to that address.
+pub const SESSION_ID: &str = "quilkin.dev/session_id";
+
 pub type SessionMap = crate::collections::ttl::TtlMap<SessionKey, Session>;
+type SessionMetadataMap = crate::collections::ttl::TtlMap<SocketAddr, SessionMetadata>;
+type SessionIdMap = crate::collections::ttl::TtlMap<bytes::Bytes, SocketAddr>;
 type ChannelData = (PoolBuffer, Option<IpNetEntry>, SocketAddr);
 type UpstreamChannelData = (FrozenPoolBuffer, Option<IpNetEntry>, SocketAddr);
 type UpstreamSender = mpsc::Sender<UpstreamChannelData>;
@@ -57,10 +68,15 @@ pub struct SessionPool {
     ports_to_sockets: RwLock<HashMap<u16, UpstreamSender>>,
     storage: Arc<RwLock<SocketStorage>>,
     session_map: SessionMap,
+    /// Metadata shared by all of the sessions of a downstream address.
+    session_metadata: SessionMetadataMap,
+    /// The last downstream address seen for each session ID.
+    session_ids: SessionIdMap,
     downstream_sender: DownstreamSender,
     buffer_pool: Arc<BufferPool>,
     shutdown_rx: ShutdownRx,
     config: Arc<Config>,
+    events: SessionEvents,
 }
 
 /// The wrapper struct responsible for holding all of the socket related mappings.
@@ -81,6 +97,7 @@ impl SessionPool {
         downstream_sender: DownstreamSender,
         buffer_pool: Arc<BufferPool>,
         shutdown_rx: ShutdownRx,
+        events: SessionEvents,
     ) -> Arc<Self> {
         const SESSION_TIMEOUT_SECONDS: Duration = Duration::from_secs(60);
         const SESSION_EXPIRY_POLL_INTERVAL: Duration = Duration::from_secs(60);
@@ -92,7 +109,13 @@ impl SessionPool {
             ports_to_sockets: <_>::default(),
             storage: <_>::default(),
             session_map: SessionMap::new(SESSION_TIMEOUT_SECONDS, SESSION_EXPIRY_POLL_INTERVAL),
+            session_metadata: SessionMetadataMap::new(
+                SESSION_TIMEOUT_SECONDS,
+                SESSION_EXPIRY_POLL_INTERVAL,
+            ),
+            session_ids: SessionIdMap::new(SESSION_TIMEOUT_SECONDS, SESSION_EXPIRY_POLL_INTERVAL),
             buffer_pool,
+            events,
         })
     }
 
@@ -101,6 +124,7 @@ impl SessionPool {
         self: &'pool Arc<Self>,
         key: SessionKey,
         asn_info: Option<IpNetEntry>,
+        token: Option<bytes::Bytes>,
     ) -> Result<UpstreamSender, super::PipelineError> {
         tracing::trace!(source=%key.source, dest=%key.dest, "creating new socket for session");
         let raw_socket = crate::net::raw_socket_with_reuse(0)?;
@@ -145,34 +169,7 @@ impl SessionPool {
                             Some((data, asn_info, send_addr)) => {
                                 tracing::trace!(%send_addr, length = data.len(), "sending packet upstream");
                                 let (result, _) = socket2.send_to(data, send_addr).await;
-                                let asn_info = asn_info.as_ref();
-                                match result {
-                                    Ok(size) => {
-                                        crate::metrics::packets_total(
-                                            crate::metrics::READ,
-                                            asn_info,
-                                        )
-                                        .inc();
-                                        crate::metrics::bytes_total(crate::metrics::READ, asn_info)
-                                            .inc_by(size as u64);
-                                    }
-                                    Err(error) => {
-                                        tracing::trace!(%error, "sending packet upstream failed");
-                                        let source = error.to_string();
-                                        crate::metrics::errors_total(
-                                            crate::metrics::READ,
-                                            &source,
-                                            asn_info,
-                                        )
-                                        .inc();
-                                        crate::metrics::packets_dropped_total(
-                                            crate::metrics::READ,
-                                            &source,
-                                            asn_info,
-                                        )
-                                        .inc();
-                                    }
-                                }
+                                record_sent(result, asn_info.as_ref());
                             }
                         }
                     }
@@ -190,7 +187,7 @@ impl SessionPool {
                                     tracing::trace!(%error, "error receiving packet");
                                     crate::metrics::errors_total(crate::metrics::WRITE, &error.to_string(), None).inc();
                                 },
-                                Ok((_size, recv_addr)) => pool.process_received_upstream_packet(buf, recv_addr, port, &mut last_received_at).await,
+                                Ok((_size, recv_addr)) => pool.process_received_upstream_packet(&socket, buf, recv_addr, port, &mut last_received_at).await,
                             }
                         }
                         _ = shutdown_rx.changed() => {
@@ -209,12 +206,13 @@ impl SessionPool {
         initialised.await.map_err(|error| eyre::eyre!(error))??;
 
         self.ports_to_sockets.write().await.insert(port, tx.clone());
-        self.create_session_from_existing_socket(key, tx, port, asn_info)
+        self.create_session_from_existing_socket(key, tx, port, asn_info, token)
             .await
     }
 
     async fn process_received_upstream_packet(
         self: &Arc<Self>,
+        socket: &DualStackLocalSocketRc,
         packet: PoolBuffer,
         mut recv_addr: SocketAddr,
         port: u16,
@@ -243,21 +241,31 @@ impl SessionPool {
         *last_received_at = Some(received_at);
 
         let timer = crate::metrics::processing_time(crate::metrics::WRITE).start_timer();
+        let mut replies = Vec::new();
         let result = Self::process_recv_packet(
             self.config.clone(),
             &self.downstream_sender,
             recv_addr,
             downstream_addr,
             asn_info,
+            self.metadata(downstream_addr),
             packet,
+            &self.buffer_pool,
+            &mut replies,
         )
         .await;
+
+        for reply in replies {
+            tracing::trace!(%recv_addr, length = reply.len(), "sending reply upstream");
+            let (result, _) = socket.send_to(reply, recv_addr).await;
+            if result.is_ok() {
+                crate::metrics::packets_replied_total(crate::metrics::WRITE, asn_info).inc();
+            }
+            record_sent(result, asn_info);
+        }
         timer.stop_and_record();
         if let Err(error) = result {
-            error.log();
-            let label = format!("proxy::Session::process_recv_packet: {error}");
-            crate::metrics::packets_dropped_total(crate::metrics::WRITE, &label, asn_info).inc();
-            crate::metrics::errors_total(crate::metrics::WRITE, &label, asn_info).inc();
+            record_dropped(error, asn_info);
         }
     }
 
@@ -269,6 +277,7 @@ impl SessionPool {
         self: &'pool Arc<Self>,
         key @ SessionKey { dest, .. }: SessionKey,
         asn_info: Option<IpNetEntry>,
+        token: Option<bytes::Bytes>,
     ) -> Result<UpstreamSender, super::PipelineError> {
         tracing::trace!(source=%key.source, dest=%key.dest, "SessionPool::get");
         // If we already have a session for the key pairing, return that session.
@@ -285,7 +294,8 @@ impl SessionPool {
             let no_sockets = self.ports_to_sockets.read().await.is_empty();
             return if no_sockets {
                 // Initial case where we have no allocated or reserved sockets.
-                self.create_new_session_from_new_socket(key, asn_info).await
+                self.create_new_session_from_new_socket(key, asn_info, token)
+                    .await
             } else {
                 // Where we have no allocated sockets for a destination, assign
                 // the first available one.
@@ -301,7 +311,7 @@ impl SessionPool {
                     })
                     .map_err(super::PipelineError::Session)?;
 
-                self.create_session_from_existing_socket(key, sender, port, asn_info)
+                self.create_session_from_existing_socket(key, sender, port, asn_info, token)
                     .await
             };
         };
@@ -326,11 +336,12 @@ impl SessionPool {
                 })
                 .map_err(super::PipelineError::Session)?
                 .insert(port);
-            self.create_session_from_existing_socket(key, socket, port, asn_info)
+            self.create_session_from_existing_socket(key, socket, port, asn_info, token)
                 .await
         } else {
             drop(storage);
-            self.create_new_session_from_new_socket(key, asn_info).await
+            self.create_new_session_from_new_socket(key, asn_info, token)
+                .await
         }
     }
 
@@ -341,6 +352,7 @@ impl SessionPool {
         upstream_sender: UpstreamSender,
         socket_port: u16,
         asn_info: Option<IpNetEntry>,
+        token: Option<bytes::Bytes>,
     ) -> Result<UpstreamSender, super::PipelineError> {
         tracing::trace!(source=%key.source, dest=%key.dest, "reusing socket for session");
         let mut storage = self.storage.write().await;
@@ -365,12 +377,21 @@ impl SessionPool {
         }
 
         drop(storage);
+        // Finding the locality requires a scan of the cluster map, so it's
+        // only done when someone is listening for session events.
+        let locality = self
+            .events
+            .has_subscribers()
+            .then(|| self.locality_of(key.dest))
+            .flatten();
         let session = Session::new(
             key,
             upstream_sender.clone(),
             socket_port,
             self.clone(),
             asn_info,
+            locality,
+            token,
         )?;
         tracing::trace!("inserting session into map");
         self.session_map.insert(key, session);
@@ -378,29 +399,111 @@ impl SessionPool {
         Ok(upstream_sender)
     }
 
-    /// process_recv_packet processes a packet that is received by this session.
+    /// Returns the locality of the endpoint with the address `dest`, if any.
+    fn locality_of(&self, dest: SocketAddr) -> Option<Locality> {
+        let address = EndpointAddress::from(dest);
+        let clusters = self.config.clusters.read();
+        let entry = clusters.iter().find(|entry| {
+            entry
+                .value()
+                .endpoints
+                .iter()
+                .any(|ep| ep.address == address)
+        })?;
+        entry.key().clone()
+    }
+
+    /// process_recv_packet processes a packet that is received by this session,
+    /// adding the replies of the filters to send back to `source` to `replies`.
+    #[allow(clippy::too_many_arguments)]
     async fn process_recv_packet(
         config: Arc<crate::Config>,
         downstream_sender: &DownstreamSender,
         source: SocketAddr,
         dest: SocketAddr,
         asn_info: Option<&IpNetEntry>,
+        session: SessionMetadata,
         packet: PoolBuffer,
+        buffer_pool: &Arc<BufferPool>,
+        replies: &mut Vec<bytes::Bytes>,
     ) -> Result<(), Error> {
         tracing::trace!(%source, %dest, length = packet.len(), "received packet from upstream");
 
         let mut context = crate::filters::WriteContext::new(source.into(), dest.into(), packet);
+        context.session = session;
+
+        let filters = config.filters_for(dest);
+        if let Err(error) = filters.write(&mut context).await {
+            filters.record(Outcome::Errored);
+            return Err(error.into());
+        }
+
+        *replies = std::mem::take(&mut context.replies);
+        if context.is_handled() {
+            filters.record(Outcome::Forwarded);
+            return Ok(());
+        }
 
-        config.filters.load().write(&mut context).await?;
+        let crate::filters::WriteContext {
+            contents: packet,
+            destinations,
+            delay,
+            forwarded,
+            ..
+        } = context;
+
+        // The extra destinations, e.g. spectators, each get their own copy of
+        // the packet, and of any further packets a filter forwarded, and
+        // aren't attributed to the session's ASN.
+        let mut packets = Vec::with_capacity((destinations.len() + 1) * (forwarded.len() + 1));
+        for destination in destinations {
+            let destination = match destination.to_socket_addr().await {
+                Ok(destination) => destination,
+                Err(error) => {
+                    tracing::debug!(%destination, %error, "couldn't resolve downstream destination");
+                    continue;
+                }
+            };
+
+            tracing::trace!(%source, %destination, length = packet.len(), "sending copy of packet downstream");
+            for contents in std::iter::once(&packet).chain(&forwarded) {
+                packets.push((buffer_pool.clone().alloc_slice(contents), None, destination));
+            }
+        }
 
-        let packet = context.contents;
         tracing::trace!(%source, %dest, length = packet.len(), "sending packet downstream");
-        downstream_sender
-            .try_send((packet, asn_info.cloned(), dest))
-            .map_err(|error| match error {
-                async_channel::TrySendError::Closed(_) => Error::ChannelClosed,
-                async_channel::TrySendError::Full(_) => Error::ChannelFull,
-            })?;
+        let asn_info = asn_info.cloned();
+        packets.push((packet, asn_info.clone(), dest));
+        packets.extend(
+            forwarded
+                .into_iter()
+                .map(|contents| (contents, asn_info.clone(), dest)),
+        );
+
+        if delay.is_zero() {
+            let sent = send_downstream(downstream_sender, packets);
+            filters.record(if sent.is_ok() {
+                Outcome::Forwarded
+            } else {
+                Outcome::Dropped
+            });
+            return sent;
+        }
+
+        let downstream_sender = downstream_sender.clone();
+        uring_inner_spawn!(async move {
+            tokio::time::sleep(delay).await;
+            let sent = send_downstream(&downstream_sender, packets);
+            filters.record(if sent.is_ok() {
+                Outcome::Forwarded
+            } else {
+                Outcome::Dropped
+            });
+            if let Err(error) = sent {
+                record_dropped(error, asn_info.as_ref());
+            }
+        });
+
         Ok(())
     }
 
@@ -409,16 +512,110 @@ impl SessionPool {
         &self.session_map
     }
 
+    /// Returns the metadata shared by every packet to and from `downstream`,
+    /// creating it if it doesn't exist yet. The metadata expires once no
+    /// packets have been seen for the session timeout.
+    pub fn metadata(&self, downstream: SocketAddr) -> SessionMetadata {
+        use crate::collections::ttl::Entry;
+
+        match self.session_metadata.entry(downstream) {
+            Entry::Occupied(entry) => entry.get().value.clone(),
+            Entry::Vacant(entry) => entry.insert(SessionMetadata::default()).value.clone(),
+        }
+    }
+
+    /// Records that the client with the session `id` is at `source`. If the
+    /// client was last seen at a different address, its sessions are migrated
+    /// to `source`, keeping their upstream sockets so that the gameserver
+    /// doesn't see a new peer.
+    pub async fn identify(&self, id: bytes::Bytes, source: SocketAddr) {
+        if let Some(entry) = self.session_ids.get(&id) {
+            if entry.value == source {
+                return;
+            }
+        }
+
+        match self.session_ids.insert(id, source) {
+            Some(previous) if previous != source => self.migrate(previous, source).await,
+            _ => {}
+        }
+    }
+
+    /// Moves the sessions and metadata of the downstream address `from` to
+    /// `to`, unless `to` already has a session with the same destination.
+    async fn migrate(&self, from: SocketAddr, to: SocketAddr) {
+        let mut storage = self.storage.write().await;
+        let candidates: Vec<_> = storage
+            .destination_to_sources
+            .iter()
+            .filter(|(_, source)| **source == from)
+            .map(|(key, _)| *key)
+            .collect();
+
+        let mut migrated = 0;
+        for (dest, port) in candidates {
+            if self
+                .session_map
+                .contains_key(&SessionKey { source: to, dest })
+            {
+                continue;
+            }
+
+            let Some(mut session) = self.session_map.take(&SessionKey { source: from, dest })
+            else {
+                continue;
+            };
+
+            session.key.source = to;
+            storage.destination_to_sources.insert((dest, port), to);
+            self.session_map.insert(session.key, session);
+            migrated += 1;
+        }
+
+        // `to` keeps the ASN of its own IP, and `from`'s is only reused when
+        // the IPs are the same and there's nothing to look it up in.
+        let previous = storage.sources_to_asn_info.remove(&from);
+        if let std::collections::hash_map::Entry::Vacant(entry) =
+            storage.sources_to_asn_info.entry(to)
+        {
+            let asn_info = crate::net::maxmind_db::MaxmindDb::lookup(to.ip())
+                .or_else(|| previous.filter(|_| from.ip() == to.ip()));
+            if let Some(asn_info) = asn_info {
+                entry.insert(asn_info);
+            }
+        }
+        drop(storage);
+
+        if let Some(previous) = self.session_metadata.take(&from) {
+            let current = self.metadata(to);
+            let mut current = current.write();
+            for (key, value) in previous.read().iter() {
+                current.entry(*key).or_insert_with(|| value.clone());
+            }
+        }
+
+        metrics::migrations_total().inc_by(migrated);
+        tracing::debug!(%from, %to, sessions = migrated, "migrated client sessions");
+    }
+
+    /// Returns the publisher of session lifecycle events.
+    pub fn events(&self) -> &SessionEvents {
+        &self.events
+    }
+
     /// Sends packet data to the appropiate session based on its `key`.
+    /// `token` is the routing token of the packet, which is only used when
+    /// the packet creates a new session.
     pub async fn send(
         self: &Arc<Self>,
         key: SessionKey,
         asn_info: Option<IpNetEntry>,
+        token: Option<bytes::Bytes>,
         packet: FrozenPoolBuffer,
     ) -> Result<(), super::PipelineError> {
         use tokio::sync::mpsc::error::TrySendError;
 
-        self.get(key, asn_info.clone())
+        self.get(key, asn_info.clone(), token)
             .await?
             .try_send((packet, asn_info, key.dest))
             .map_err(|error| match error {
@@ -484,6 +681,43 @@ impl SessionPool {
     }
 }
 
+/// Records the metrics of a packet sent upstream.
+fn record_sent(result: std::io::Result<usize>, asn_info: Option<&IpNetEntry>) {
+    match result {
+        Ok(size) => {
+            crate::metrics::packets_total(crate::metrics::READ, asn_info).inc();
+            crate::metrics::bytes_total(crate::metrics::READ, asn_info).inc_by(size as u64);
+        }
+        Err(error) => {
+            tracing::trace!(%error, "sending packet upstream failed");
+            let source = error.to_string();
+            crate::metrics::errors_total(crate::metrics::READ, &source, asn_info).inc();
+            crate::metrics::packets_dropped_total(crate::metrics::READ, &source, asn_info).inc();
+        }
+    }
+}
+
+/// Sends a packet, and any copies of it, downstream, returning the last error
+/// encountered if any of them couldn't be queued.
+fn send_downstream(sender: &DownstreamSender, packets: Vec<ChannelData>) -> Result<(), Error> {
+    let mut sent = Ok(());
+    for data in packets {
+        let result = sender.try_send(data).map_err(|error| match error {
+            async_channel::TrySendError::Closed(_) => Error::ChannelClosed,
+            async_channel::TrySendError::Full(_) => Error::ChannelFull,
+        });
+        sent = result.and(sent);
+    }
+    sent
+}
+
+fn record_dropped(error: Error, asn_info: Option<&IpNetEntry>) {
+    error.log();
+    let label = format!("proxy::Session::process_recv_packet: {error}");
+    crate::metrics::packets_dropped_total(crate::metrics::WRITE, &label, asn_info).inc();
+    crate::metrics::errors_total(crate::metrics::WRITE, &label, asn_info).inc();
+}
+
 impl Drop for SessionPool {
     fn drop(&mut self) {
         drop(std::mem::take(&mut self.session_map));
@@ -503,6 +737,10 @@ pub struct Session {
     upstream_sender: UpstreamSender,
     /// The GeoIP information of the source.
     asn_info: Option<IpNetEntry>,
+    /// The locality of the destination, if known.
+    locality: Option<Locality>,
+    /// The routing token that established the session, if any.
+    token: Option<bytes::Bytes>,
     /// The socket pool of the session.
     pool: Arc<SessionPool>,
 }
@@ -514,6 +752,8 @@ impl Session {
         socket_port: u16,
         pool: Arc<SessionPool>,
         asn_info: Option<IpNetEntry>,
+        locality: Option<Locality>,
+        token: Option<bytes::Bytes>,
     ) -> Result<Self, super::PipelineError> {
         let s = Self {
             key,
@@ -521,6 +761,8 @@ impl Session {
             pool,
             socket_port,
             asn_info,
+            locality,
+            token,
             created_at: Instant::now(),
         };
 
@@ -539,6 +781,7 @@ impl Session {
         self::metrics::total_sessions().inc();
         s.active_session_metric().inc();
         tracing::debug!(source = %key.source, dest = %key.dest, "Session created");
+        s.publish(SessionEventKind::Created);
         Ok(s)
     }
 
@@ -546,10 +789,22 @@ impl Session {
         metrics::active_sessions(self.asn_info.as_ref())
     }
 
+    fn publish(&self, kind: SessionEventKind) {
+        self.pool.events.publish(SessionEvent {
+            kind,
+            source: self.key.source,
+            destination: self.key.dest,
+            locality: self.locality.clone(),
+            token: self.token.clone(),
+            timestamp: UtcTimestamp::now().unix(),
+        });
+    }
+
     fn async_drop(&mut self) -> impl std::future::Future<Output = ()> {
         self.active_session_metric().dec();
         metrics::duration_secs().observe(self.created_at.elapsed().as_secs() as f64);
         tracing::debug!(source = %self.key.source, dest_address = %self.key.dest, "Session closed");
+        self.publish(SessionEventKind::Expired);
         SessionPool::release_socket(self.pool.clone(), self.key, self.socket_port)
     }
 }
@@ -607,6 +862,7 @@ mod tests {
                 sender,
                 Arc::new(BufferPool::default()),
                 rx,
+                SessionEvents::default(),
             ),
             tx,
             receiver,
@@ -622,7 +878,7 @@ mod tests {
         )
             .into();
 
-        let _session = pool.get(key, None).await.unwrap();
+        let _session = pool.get(key, None, None).await.unwrap();
 
         assert!(pool.drop_session(key).await);
 
@@ -643,8 +899,8 @@ mod tests {
         )
             .into();
 
-        let _session1 = pool.get(key1, None).await.unwrap();
-        let _session2 = pool.get(key2, None).await.unwrap();
+        let _session1 = pool.get(key1, None, None).await.unwrap();
+        let _session2 = pool.get(key2, None, None).await.unwrap();
 
         assert!(pool.drop_session(key1).await);
         assert!(!pool.has_no_allocated_sockets().await);
@@ -668,8 +924,8 @@ mod tests {
         )
             .into();
 
-        let _socket1 = pool.get(key1, None).await.unwrap();
-        let _socket2 = pool.get(key2, None).await.unwrap();
+        let _socket1 = pool.get(key1, None, None).await.unwrap();
+        let _socket2 = pool.get(key2, None, None).await.unwrap();
         assert_ne!(
             pool.session_map.get(&key1).unwrap().socket_port,
             pool.session_map.get(&key2).unwrap().socket_port
@@ -693,8 +949,8 @@ mod tests {
         )
             .into();
 
-        let _socket1 = pool.get(key1, None).await.unwrap();
-        let _socket2 = pool.get(key2, None).await.unwrap();
+        let _socket1 = pool.get(key1, None, None).await.unwrap();
+        let _socket2 = pool.get(key2, None, None).await.unwrap();
 
         assert_eq!(
             pool.session_map.get(&key1).unwrap().socket_port,
@@ -716,13 +972,13 @@ mod tests {
         )
             .into();
 
-        let socket1 = pool.get(key1, None).await.unwrap();
+        let socket1 = pool.get(key1, None, None).await.unwrap();
 
         let task = tokio::spawn(async move {
             let _ = socket1;
         });
 
-        let _socket2 = pool.get(key2, None).await.unwrap();
+        let _socket2 = pool.get(key2, None, None).await.unwrap();
 
         task.await.unwrap();
     }
@@ -741,13 +997,13 @@ mod tests {
         )
             .into();
 
-        let socket1 = pool.get(key1, None).await.unwrap();
+        let socket1 = pool.get(key1, None, None).await.unwrap();
 
         let task = tokio::spawn(async move {
             let _ = socket1;
         });
 
-        let _socket2 = pool.get(key2, None).await.unwrap();
+        let _socket2 = pool.get(key2, None, None).await.unwrap();
 
         task.await.unwrap();
     }
@@ -767,7 +1023,7 @@ mod tests {
         let key: SessionKey = (source, dest).into();
         let msg = b"helloworld";
 
-        pool.send(key, None, alloc_buffer(msg).freeze())
+        pool.send(key, None, None, alloc_buffer(msg).freeze())
             .await
             .unwrap();
 
@@ -778,4 +1034,86 @@ mod tests {
 
         assert_eq!(msg, &*data);
     }
+
+    #[tokio::test]
+    async fn metadata_shared_by_downstream() {
+        use crate::net::endpoint::metadata::{Key, Value};
+
+        let (pool, _sender, _receiver) = new_pool().await;
+        let client: SocketAddr = (std::net::Ipv4Addr::LOCALHOST, 8080u16).into();
+        let other: SocketAddr = (std::net::Ipv4Addr::LOCALHOST, 8081u16).into();
+        let key = Key::from_static("player");
+
+        pool.metadata(client).insert(key, Value::Number(1));
+
+        assert_eq!(Some(Value::Number(1)), pool.metadata(client).get(&key));
+        assert!(!pool.metadata(other).contains_key(&key));
+    }
+
+    #[tokio::test]
+    async fn migrate_by_session_id() {
+        let (pool, _sender, _receiver) = new_pool().await;
+        let from: SocketAddr = (std::net::Ipv4Addr::LOCALHOST, 8080u16).into();
+        let to: SocketAddr = (std::net::Ipv4Addr::LOCALHOST, 8081u16).into();
+        let dest: SocketAddr = (std::net::Ipv4Addr::UNSPECIFIED, 8080u16).into();
+        let id = bytes::Bytes::from_static(b"abc");
+
+        pool.identify(id.clone(), from).await;
+        let _session = pool.get((from, dest).into(), None, None).await.unwrap();
+        let port = pool
+            .session_map
+            .get(&(from, dest).into())
+            .unwrap()
+            .socket_port;
+
+        pool.identify(id, to).await;
+
+        assert!(!pool.session_map.contains_key(&(from, dest).into()));
+        assert_eq!(
+            port,
+            pool.session_map
+                .get(&(to, dest).into())
+                .unwrap()
+                .socket_port
+        );
+        assert_eq!(
+            Some(&to),
+            pool.storage
+                .read()
+                .await
+                .destination_to_sources
+                .get(&(dest, port))
+        );
+    }
+
+    #[tokio::test]
+    async fn migrate_asn_info() {
+        let (pool, _sender, _receiver) = new_pool().await;
+        let from: SocketAddr = (std::net::Ipv4Addr::LOCALHOST, 8080u16).into();
+        let to: SocketAddr = (std::net::Ipv4Addr::LOCALHOST, 8081u16).into();
+        let elsewhere: SocketAddr = (std::net::Ipv4Addr::new(127, 0, 0, 2), 8080u16).into();
+        let asn = |r#as: u64| -> IpNetEntry {
+            serde_json::from_value(serde_json::json!({ "as": r#as })).unwrap()
+        };
+
+        // `to`'s own ASN isn't replaced by `from`'s.
+        {
+            let mut storage = pool.storage.write().await;
+            storage.sources_to_asn_info.insert(from, asn(1));
+            storage.sources_to_asn_info.insert(to, asn(2));
+        }
+        pool.migrate(from, to).await;
+        {
+            let storage = pool.storage.read().await;
+            assert!(!storage.sources_to_asn_info.contains_key(&from));
+            assert_eq!(2, storage.sources_to_asn_info[&to].r#as);
+        }
+
+        // Nor is it given to a different IP, without a database to look its
+        // own ASN up in.
+        pool.migrate(to, from).await;
+        pool.migrate(from, elsewhere).await;
+        let storage = pool.storage.read().await;
+        assert!(storage.sources_to_asn_info.is_empty());
+    }
 }
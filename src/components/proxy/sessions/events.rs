@@ -0,0 +1,219 @@
+/*
+ * Copyright 2024 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{convert::Infallible, net::SocketAddr};
+
+use tokio::sync::broadcast;
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
+
+use crate::{
+    filters::capture::CAPTURED_BYTES,
+    net::endpoint::{metadata, Locality},
+};
+
+/// The number of events buffered for each subscriber before the oldest events
+/// are dropped.
+const EVENT_CAPACITY: usize = 1024;
+
+/// What happened to a session.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionEventKind {
+    Created,
+    Expired,
+}
+
+impl SessionEventKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Created => "created",
+            Self::Expired => "expired",
+        }
+    }
+}
+
+/// A change in the lifecycle of a session.
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct SessionEvent {
+    pub kind: SessionEventKind,
+    /// The downstream client address.
+    pub source: SocketAddr,
+    /// The upstream endpoint address.
+    pub destination: SocketAddr,
+    /// The locality of the upstream endpoint, if it has one.
+    pub locality: Option<Locality>,
+    /// The routing token captured from the first packet of the session, if any.
+    #[serde(serialize_with = "serialize_token")]
+    pub token: Option<bytes::Bytes>,
+    /// The UNIX timestamp in seconds of when the event occurred.
+    pub timestamp: i64,
+}
+
+fn serialize_token<S: serde::Serializer>(
+    token: &Option<bytes::Bytes>,
+    ser: S,
+) -> Result<S::Ok, S::Error> {
+    match token {
+        Some(token) => ser.serialize_some(&crate::codec::base64::encode(token)),
+        None => ser.serialize_none(),
+    }
+}
+
+/// Broadcasts [`SessionEvent`]s to any number of subscribers.
+///
+/// Publishing never blocks, subscribers that fall behind by more than
+/// [`EVENT_CAPACITY`] events lose the oldest events instead of applying
+/// backpressure to packet processing.
+#[derive(Clone, Debug)]
+pub struct SessionEvents {
+    sender: broadcast::Sender<SessionEvent>,
+    token_key: metadata::Key,
+}
+
+impl SessionEvents {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            sender: broadcast::channel(capacity).0,
+            token_key: metadata::Key::from_static(CAPTURED_BYTES),
+        }
+    }
+
+    /// Sets the dynamic metadata key the routing token of a session is read
+    /// from, by default the key the `Capture` filter writes to.
+    pub fn with_token_key(mut self, key: metadata::Key) -> Self {
+        self.token_key = key;
+        self
+    }
+
+    /// The dynamic metadata key the routing token of a session is read from.
+    pub fn token_key(&self) -> metadata::Key {
+        self.token_key
+    }
+
+    /// Publishes an event to all current subscribers, if there are any.
+    pub fn publish(&self, event: SessionEvent) {
+        if !self.has_subscribers() {
+            return;
+        }
+
+        tracing::trace!(kind = event.kind.as_str(), source = %event.source, destination = %event.destination, "publishing session event");
+        // Only fails if every receiver was dropped in the meantime.
+        let _ = self.sender.send(event);
+    }
+
+    /// Whether anyone is currently subscribed to events.
+    pub fn has_subscribers(&self) -> bool {
+        self.sender.receiver_count() > 0
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<SessionEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Subscribes to events, encoding each one as a [server-sent event][sse].
+    /// Events missed due to lagging behind are skipped and counted in the
+    /// `session_events_dropped` metric.
+    ///
+    /// [sse]: https://html.spec.whatwg.org/multipage/server-sent-events.html
+    pub fn server_sent_events(&self) -> impl Stream<Item = Result<String, Infallible>> {
+        BroadcastStream::new(self.subscribe()).filter_map(|event| match event {
+            Ok(event) => match serde_json::to_string(&event) {
+                Ok(data) => Some(Ok(format!(
+                    "event: {}\ndata: {data}\n\n",
+                    event.kind.as_str()
+                ))),
+                Err(error) => {
+                    tracing::warn!(%error, "failed to encode session event");
+                    None
+                }
+            },
+            Err(tokio_stream::wrappers::errors::BroadcastStreamRecvError::Lagged(count)) => {
+                tracing::debug!(count, "session event subscriber lagged, dropping events");
+                super::metrics::dropped_events().inc_by(count);
+                None
+            }
+        })
+    }
+}
+
+impl Default for SessionEvents {
+    fn default() -> Self {
+        Self::new(EVENT_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(kind: SessionEventKind) -> SessionEvent {
+        SessionEvent {
+            kind,
+            source: (std::net::Ipv4Addr::LOCALHOST, 8080).into(),
+            destination: (std::net::Ipv4Addr::LOCALHOST, 9090).into(),
+            locality: None,
+            token: Some(bytes::Bytes::from_static(b"abc")),
+            timestamp: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn lagging_subscriber_drops_events() {
+        let events = SessionEvents::new(2);
+        let mut rx = events.subscribe();
+
+        for _ in 0..4 {
+            events.publish(event(SessionEventKind::Created));
+        }
+        events.publish(event(SessionEventKind::Expired));
+
+        assert!(matches!(
+            rx.recv().await,
+            Err(broadcast::error::RecvError::Lagged(3))
+        ));
+        assert_eq!(SessionEventKind::Created, rx.recv().await.unwrap().kind);
+        assert_eq!(SessionEventKind::Expired, rx.recv().await.unwrap().kind);
+    }
+
+    #[tokio::test]
+    async fn server_sent_events() {
+        let events = SessionEvents::default();
+        let mut stream = Box::pin(events.server_sent_events());
+
+        events.publish(event(SessionEventKind::Expired));
+
+        let data = serde_json::to_string(&event(SessionEventKind::Expired)).unwrap();
+        assert_eq!(
+            format!("event: expired\ndata: {data}\n\n"),
+            stream.next().await.unwrap().unwrap()
+        );
+    }
+
+    #[test]
+    fn serialize() {
+        assert_eq!(
+            serde_json::json!({
+                "kind": "created",
+                "source": "127.0.0.1:8080",
+                "destination": "127.0.0.1:9090",
+                "locality": null,
+                "token": "YWJj",
+                "timestamp": 0,
+            }),
+            serde_json::to_value(event(SessionEventKind::Created)).unwrap()
+        );
+    }
+}
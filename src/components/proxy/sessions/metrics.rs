@@ -76,3 +76,37 @@ pub(crate) fn duration_secs() -> &'static Histogram {
 
     &DURATION_SECS
 }
+
+pub(crate) fn dropped_events() -> &'static IntCounter {
+    static DROPPED_EVENTS: Lazy<IntCounter> = Lazy::new(|| {
+        register(
+            IntCounter::with_opts(
+                Opts::new(
+                    "events_dropped",
+                    "total number of session events dropped because a subscriber fell behind",
+                )
+                .subsystem(SUBSYSTEM),
+            )
+            .unwrap(),
+        )
+    });
+
+    &DROPPED_EVENTS
+}
+
+pub(crate) fn migrations_total() -> &'static IntCounter {
+    static MIGRATIONS_TOTAL: Lazy<IntCounter> = Lazy::new(|| {
+        register(
+            IntCounter::with_opts(
+                Opts::new(
+                    "migrations_total",
+                    "total number of sessions migrated to a new client address",
+                )
+                .subsystem(SUBSYSTEM),
+            )
+            .unwrap(),
+        )
+    });
+
+    &MIGRATIONS_TOTAL
+}
@@ -47,6 +47,11 @@ impl Relay {
             ready.idle_request_interval,
         )?);
 
+        tokio::spawn(crate::net::cluster::prune_expired_tokens(
+            config.clusters.clone(),
+            shutdown_rx.clone(),
+        ));
+
         let _provider_task = self.provider.map(|provider| {
             let config = config.clone();
             let provider_is_healthy = ready.provider_is_healthy.clone();
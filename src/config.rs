@@ -19,10 +19,7 @@
 use std::{
     collections::{BTreeSet, HashMap},
     net::IpAddr,
-    sync::{
-        atomic::{AtomicU64, Ordering::Relaxed},
-        Arc,
-    },
+    sync::atomic::{AtomicU64, Ordering::Relaxed},
     time::Duration,
 };
 
@@ -44,6 +41,7 @@ pub use self::{
     config_type::ConfigType, error::ValidationError, providers::Providers, slot::Slot, watch::Watch,
 };
 
+pub mod canary;
 mod config_type;
 mod error;
 pub mod providers;
@@ -78,7 +76,10 @@ pub enum DatacenterConfig {
 pub struct Config {
     #[serde(default)]
     pub clusters: Watch<ClusterMap>,
-    #[serde(default)]
+    /// Read from the `filters`, `read_filters`, and `write_filters` keys, see
+    /// [`FilterChainConfig`].
+    #[serde(flatten, with = "filter_chain")]
+    #[schemars(with = "FilterChainConfig")]
     pub filters: Slot<crate::filters::FilterChain>,
     #[serde(default = "default_proxy_id")]
     pub id: Slot<String>,
@@ -86,6 +87,11 @@ pub struct Config {
     pub version: Slot<Version>,
     #[serde(flatten)]
     pub datacenter: DatacenterConfig,
+    /// Trials filter chains received over xDS on a fraction of sessions
+    /// before they replace [`Self::filters`], when enabled.
+    #[serde(skip)]
+    #[schemars(skip)]
+    pub filter_rollout: canary::FilterRollout,
 }
 
 impl xds::config::Configuration for Config {
@@ -194,7 +200,14 @@ impl Config {
             }
         }
 
-        replace_if_present!(filters, id);
+        replace_if_present!(id);
+
+        if let Some(filters) = FilterChainConfig::take_from(&mut map)? {
+            tracing::trace!(?filters, "replacing filters");
+            self.filters.try_replace(Slot::new(
+                crate::filters::FilterChain::try_create_directional(filters)?,
+            ));
+        }
 
         if let Some(value) = map.remove("clusters") {
             let cmd: cluster::ClusterMapDeser = serde_json::from_value(value)?;
@@ -250,10 +263,9 @@ impl Config {
                 }
             },
             ResourceType::Listener => {
-                resources.push(resource_type.encode_to_any(&Listener {
-                    filter_chains: vec![(&*self.filters.load()).try_into()?],
-                    ..<_>::default()
-                })?);
+                resources.push(
+                    resource_type.encode_to_any(&Listener::try_from(&*self.filters.load())?)?,
+                );
             }
             ResourceType::FilterChain => {
                 resources.push(resource_type.encode_to_any(
@@ -303,10 +315,10 @@ impl Config {
                 resources.push(XdsResource {
                     name: "listener".into(),
                     version: "0".into(),
-                    resource: Some(ResourceType::Listener.encode_to_any(&Listener {
-                        filter_chains: vec![(&*self.filters.load()).try_into()?],
-                        ..<_>::default()
-                    })?),
+                    resource: Some(
+                        ResourceType::Listener
+                            .encode_to_any(&Listener::try_from(&*self.filters.load())?)?,
+                    ),
                     aliases: Vec::new(),
                     ttl: None,
                     cache_control: None,
@@ -438,22 +450,11 @@ impl Config {
         tracing::trace!(resource=?response, "applying resource");
 
         match response {
-            Resource::Listener(mut listener) => {
-                let chain: crate::filters::FilterChain = if listener.filter_chains.is_empty() {
-                    Default::default()
-                } else {
-                    crate::filters::FilterChain::try_create_fallible(
-                        listener.filter_chains.swap_remove(0).filters.into_iter(),
-                    )?
-                };
-
-                self.filters.store(Arc::new(chain));
+            Resource::Listener(listener) => {
+                self.store_filters((*listener).try_into()?);
             }
             Resource::FilterChain(fc) => {
-                self.filters
-                    .store(Arc::new(crate::filters::FilterChain::try_create_fallible(
-                        fc.filters.into_iter(),
-                    )?));
+                self.store_filters(fc.try_into()?);
             }
             Resource::Datacenter(dc) => {
                 let DatacenterConfig::NonAgent { datacenters } = &self.datacenter else {
@@ -506,20 +507,13 @@ impl Config {
             ResourceType::Listener => {
                 for res in resources {
                     let (resource, _) = res?;
-                    let Resource::Listener(mut listener) = resource else {
+                    let Resource::Listener(listener) = resource else {
                         return Err(eyre::eyre!("a non-listener resource was present"));
                     };
 
-                    let chain: crate::filters::FilterChain = if listener.filter_chains.is_empty() {
-                        Default::default()
-                    } else {
-                        crate::filters::FilterChain::try_create_fallible(
-                            listener.filter_chains.swap_remove(0).filters.into_iter(),
-                        )?
-                    };
-
-                    self.filters.store(Arc::new(chain));
-                    local_versions.insert(listener.name, "".into());
+                    let name = listener.name.clone();
+                    self.store_filters((*listener).try_into()?);
+                    local_versions.insert(name, "".into());
                 }
             }
             ResourceType::FilterChain => {
@@ -529,10 +523,7 @@ impl Config {
                         return Err(eyre::eyre!("a non-filterchain resource was present"));
                     };
 
-                    self.filters
-                        .store(Arc::new(crate::filters::FilterChain::try_create_fallible(
-                            fc.filters.into_iter(),
-                        )?));
+                    self.store_filters(fc.try_into()?);
                     local_versions.insert(String::new(), "".into());
                 }
             }
@@ -608,6 +599,19 @@ impl Config {
         Ok(())
     }
 
+    /// Stores a filter chain received over xDS, trialling it on a fraction of
+    /// sessions first if [`Self::filter_rollout`] is enabled.
+    pub fn store_filters(&self, chain: crate::filters::FilterChain) {
+        self.filter_rollout.store(&self.filters, chain);
+    }
+
+    /// Returns the filter chain for packets to and from the client at
+    /// `session`.
+    #[inline]
+    pub fn filters_for(&self, session: std::net::SocketAddr) -> canary::Selected {
+        self.filter_rollout.select(&self.filters, session)
+    }
+
     #[inline]
     pub fn apply_metrics(&self) {
         let clusters = self.clusters.read();
@@ -625,6 +629,7 @@ impl Config {
                 icao_code: Default::default(),
                 qcmp_port: Default::default(),
             },
+            filter_rollout: Default::default(),
         }
     }
 
@@ -637,6 +642,7 @@ impl Config {
             datacenter: DatacenterConfig::NonAgent {
                 datacenters: Default::default(),
             },
+            filter_rollout: Default::default(),
         }
     }
 
@@ -891,6 +897,83 @@ pub struct Filter {
     pub name: String,
     pub label: Option<String>,
     pub config: Option<serde_json::Value>,
+    /// Only execute the filter for packets the predicate is true for.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "serde_yaml::with::singleton_map_recursive"
+    )]
+    #[schemars(with = "Option<crate::filters::predicate::Predicate>")]
+    pub when: Option<crate::filters::predicate::Predicate>,
+}
+
+/// The filters of a filter chain, either the same filters for both
+/// directions, or separate filters for either direction.
+#[derive(Clone, Debug, Default, Deserialize, Eq, Serialize, PartialEq, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct FilterChainConfig {
+    /// The filters for packets in both directions, executed in order for
+    /// packets from clients, and in reverse order for packets from endpoints.
+    #[serde(default)]
+    pub filters: Vec<Filter>,
+    /// The filters for packets from clients, instead of `filters`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_filters: Option<Vec<Filter>>,
+    /// The filters for packets from endpoints, executed in order, instead of
+    /// `filters`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub write_filters: Option<Vec<Filter>>,
+}
+
+impl FilterChainConfig {
+    /// Removes the `filters`, `read_filters`, and `write_filters` keys from
+    /// `map`, returning the config they form if any were present.
+    pub fn take_from(
+        map: &mut serde_json::Map<String, serde_json::Value>,
+    ) -> Result<Option<Self>, serde_json::Error> {
+        let filters = ["filters", "read_filters", "write_filters"]
+            .into_iter()
+            .filter_map(|key| map.remove_entry(key))
+            .collect::<serde_json::Map<_, _>>();
+
+        if filters.is_empty() {
+            return Ok(None);
+        }
+
+        serde_json::from_value(filters.into()).map(Some)
+    }
+}
+
+impl From<&'_ crate::filters::FilterChain> for FilterChainConfig {
+    fn from(chain: &crate::filters::FilterChain) -> Self {
+        Self {
+            filters: chain.iter().collect(),
+            read_filters: chain.read_filters().map(Iterator::collect),
+            write_filters: chain.write_filters().map(Iterator::collect),
+        }
+    }
+}
+
+/// (De)serializes [`Config::filters`] as a flattened [`FilterChainConfig`].
+mod filter_chain {
+    use super::{FilterChainConfig, Slot};
+    use crate::filters::FilterChain;
+
+    pub fn serialize<S: serde::Serializer>(
+        filters: &Slot<FilterChain>,
+        ser: S,
+    ) -> Result<S::Ok, S::Error> {
+        serde::Serialize::serialize(&FilterChainConfig::from(&*filters.load()), ser)
+    }
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(
+        de: D,
+    ) -> Result<Slot<FilterChain>, D::Error> {
+        let config = <FilterChainConfig as serde::Deserialize>::deserialize(de)?;
+        FilterChain::try_create_directional(config)
+            .map(Slot::new)
+            .map_err(serde::de::Error::custom)
+    }
 }
 
 use crate::generated::envoy::config::listener::v3 as listener;
@@ -925,6 +1008,8 @@ impl TryFrom<listener::Filter> for Filter {
             // TODO: keep the label across xDS
             label: None,
             config,
+            // Predicates are kept in the metadata of the Listener's filter chain.
+            when: None,
         })
     }
 }
@@ -942,10 +1027,17 @@ impl TryFrom<crate::net::cluster::proto::Filter> for Filter {
             None
         };
 
+        let when = value
+            .when
+            .map(|when| serde_json::from_str(&when))
+            .transpose()
+            .map_err(|err| CreationError::DeserializeFailed(err.to_string()))?;
+
         Ok(Self {
             name: value.name,
             label: value.label,
             config,
+            when,
         })
     }
 }
@@ -979,6 +1071,7 @@ impl From<(String, FilterInstance)> for Filter {
             name,
             label: instance.label().map(String::from),
             config: Some(serde_json::Value::clone(instance.config())),
+            when: instance.when().cloned(),
         }
     }
 }
@@ -1149,6 +1242,7 @@ id: server-proxy
                                 .into_iter()
                                 .map(From::from)
                                 .collect(),
+                            labels: <_>::default(),
                         },
                     ),
                     Endpoint::with_metadata(
@@ -1157,6 +1251,7 @@ id: server-proxy
                             .unwrap(),
                         Metadata {
                             tokens: vec!["nkuy70x"].into_iter().map(From::from).collect(),
+                            labels: <_>::default(),
                         },
                     ),
                 ]
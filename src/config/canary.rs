@@ -0,0 +1,740 @@
+/*
+ * Copyright 2024 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Trialling new filter chains on a fraction of sessions before they're
+//! applied to every session.
+
+use std::{
+    collections::VecDeque,
+    hash::{Hash, Hasher},
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering::Relaxed},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use arc_swap::ArcSwapOption;
+use once_cell::sync::Lazy;
+use prometheus::{IntCounter, IntCounterVec, Opts};
+
+use super::Slot;
+use crate::filters::FilterChain;
+
+/// How often [`evaluate_canaries`] checks whether the active canary can be
+/// promoted or rolled back.
+pub const EVALUATION_INTERVAL: Duration = Duration::from_secs(1);
+
+pub const DEFAULT_DURATION: Duration = Duration::from_secs(60);
+pub const DEFAULT_MAX_DURATION: Duration = Duration::from_secs(600);
+pub const DEFAULT_MIN_PACKETS: u64 = 1000;
+pub const DEFAULT_MAX_RATE_INCREASE: f64 = 0.01;
+
+/// The number of buckets sessions are hashed into when deciding whether they
+/// use the canary chain.
+const BUCKETS: u64 = 10_000;
+/// The number of rolled back chains remembered, so they aren't trialled again.
+const MAX_REJECTED: usize = 16;
+
+/// Settings for trialling new filter chains.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CanarySettings {
+    /// The fraction of sessions, greater than `0` and at most `1`, that use
+    /// a new filter chain while it's being trialled.
+    pub fraction: f64,
+    /// How long a new filter chain is trialled for before it's evaluated.
+    pub duration: Duration,
+    /// The minimum number of packets a new filter chain has to have processed
+    /// before it's evaluated.
+    pub min_packets: u64,
+    /// How long a new filter chain is trialled for at most, it's evaluated
+    /// with the packets it has processed so far once this has passed, even
+    /// if that's fewer than `min_packets`.
+    pub max_duration: Duration,
+    /// How much higher a new filter chain's error or drop rate can be than
+    /// the previous chain's before it's rolled back, e.g. `0.01` allows one
+    /// more percent of packets to be dropped.
+    pub max_rate_increase: f64,
+}
+
+impl CanarySettings {
+    pub fn validate(&self) -> crate::Result<()> {
+        eyre::ensure!(
+            self.fraction > 0.0 && self.fraction <= 1.0,
+            "canary fraction must be greater than 0 and at most 1, got {}",
+            self.fraction
+        );
+        eyre::ensure!(
+            self.max_rate_increase >= 0.0,
+            "canary max rate increase must not be negative, got {}",
+            self.max_rate_increase
+        );
+        eyre::ensure!(
+            self.max_duration >= self.duration,
+            "canary max duration must be at least the canary duration, got {:?} and {:?}",
+            self.max_duration,
+            self.duration
+        );
+
+        Ok(())
+    }
+}
+
+/// The outcome of processing a packet with a filter chain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The packet was processed and sent on.
+    Forwarded,
+    /// The filter chain returned an error, dropping the packet.
+    Errored,
+    /// The filter chain processed the packet, but it couldn't be sent on.
+    Dropped,
+}
+
+impl Outcome {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Forwarded => "forwarded",
+            Self::Errored => "errored",
+            Self::Dropped => "dropped",
+        }
+    }
+}
+
+/// A decision made when evaluating a canary.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Decision {
+    /// The canary hasn't run for long enough, or hasn't seen enough packets.
+    Wait,
+    /// The canary replaced the previous chain for every session.
+    Promote,
+    /// The canary was discarded, leaving the previous chain in place.
+    Rollback,
+}
+
+fn packets_total(chain: &str, outcome: Outcome) -> IntCounter {
+    static PACKETS_TOTAL: Lazy<IntCounterVec> = Lazy::new(|| {
+        prometheus::register_int_counter_vec_with_registry! {
+            Opts::new(
+                "packets_total",
+                "total number of packets processed by the current and canary filter chains while a canary is trialled",
+            )
+            .subsystem("canary"),
+            &["chain", "outcome"],
+            crate::metrics::registry(),
+        }
+        .unwrap()
+    });
+
+    PACKETS_TOTAL.with_label_values(&[chain, outcome.as_str()])
+}
+
+/// The packets processed by one of the chains of a trial.
+///
+/// They're counted for each trial, rather than read from the global metrics,
+/// as sessions selected during an earlier trial may still record packets
+/// after it ended. The per-filter duration histograms and the dropped packet
+/// metrics are labelled by filter rather than by chain, and so are shared by
+/// the current and canary chains, and can't tell their packets apart.
+struct Stats {
+    counts: [AtomicU64; 3],
+    /// The `canary_packets_total` counters the packets are also recorded in.
+    metrics: [IntCounter; 3],
+}
+
+impl Stats {
+    fn new(chain: &str) -> Self {
+        Self {
+            counts: <_>::default(),
+            metrics: [Outcome::Forwarded, Outcome::Errored, Outcome::Dropped]
+                .map(|outcome| packets_total(chain, outcome)),
+        }
+    }
+
+    fn record(&self, outcome: Outcome) {
+        let index = match outcome {
+            Outcome::Forwarded => 0,
+            Outcome::Errored => 1,
+            Outcome::Dropped => 2,
+        };
+
+        self.counts[index].fetch_add(1, Relaxed);
+        self.metrics[index].inc();
+    }
+
+    fn rates(&self) -> Rates {
+        let [forwarded, errors, drops] = self.counts.each_ref().map(|count| count.load(Relaxed));
+        let packets = forwarded + errors + drops;
+        let rate = |count: u64| {
+            if packets == 0 {
+                0.0
+            } else {
+                count as f64 / packets as f64
+            }
+        };
+
+        Rates {
+            packets,
+            error_rate: rate(errors),
+            // Packets dropped by an error are dropped too.
+            drop_rate: rate(errors + drops),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Rates {
+    packets: u64,
+    error_rate: f64,
+    drop_rate: f64,
+}
+
+/// A new filter chain being trialled against the current one.
+struct Canary {
+    /// Identifies the trial in logs.
+    trial: u64,
+    chain: Arc<FilterChain>,
+    digest: u64,
+    started: Instant,
+    stable: Stats,
+    canary: Stats,
+}
+
+/// The filter chain chosen for a session, which records the outcome of the
+/// packets it processes while a canary is active.
+pub struct Selected {
+    chain: Arc<FilterChain>,
+    trial: Option<(Arc<Canary>, bool)>,
+}
+
+impl Selected {
+    /// Returns whether the chain is a canary being trialled.
+    pub fn is_canary(&self) -> bool {
+        matches!(self.trial, Some((_, true)))
+    }
+
+    /// Records the outcome of processing a packet with the chain.
+    pub fn record(&self, outcome: Outcome) {
+        match &self.trial {
+            Some((canary, true)) => canary.canary.record(outcome),
+            Some((canary, false)) => canary.stable.record(outcome),
+            None => {}
+        }
+    }
+}
+
+impl std::ops::Deref for Selected {
+    type Target = FilterChain;
+
+    fn deref(&self) -> &Self::Target {
+        &self.chain
+    }
+}
+
+/// Decides which filter chain sessions use, trialling new chains on a
+/// fraction of sessions when enabled.
+///
+/// The chain in [`Config::filters`][super::Config::filters] is always the
+/// last good chain, it's only replaced once a canary has been promoted.
+#[derive(Clone, Default)]
+pub struct FilterRollout {
+    settings: Arc<ArcSwapOption<CanarySettings>>,
+    canary: Arc<ArcSwapOption<Canary>>,
+    /// Serialises storing and evaluating canaries, the packet path never takes
+    /// this lock.
+    lock: Arc<parking_lot::Mutex<()>>,
+    trials: Arc<AtomicU64>,
+    /// The digests of the most recently rolled back chains.
+    rejected: Arc<parking_lot::Mutex<VecDeque<u64>>>,
+}
+
+impl FilterRollout {
+    /// Enables trialling new filter chains with `settings`.
+    pub fn enable(&self, settings: CanarySettings) {
+        self.settings.store(Some(Arc::new(settings)));
+    }
+
+    /// Returns whether new filter chains are trialled before being applied.
+    pub fn is_enabled(&self) -> bool {
+        self.settings.load().is_some()
+    }
+
+    /// Returns whether a canary is currently being trialled.
+    pub fn is_active(&self) -> bool {
+        self.canary.load().is_some()
+    }
+
+    /// Stores `chain` as the new filter chain. When enabled, the chain is
+    /// trialled as a canary first, replacing any canary already in progress.
+    /// Chains replacing an empty chain, such as the first chain a proxy
+    /// receives, are applied immediately, as there is nothing to fall back to.
+    pub fn store(&self, filters: &Slot<FilterChain>, chain: FilterChain) {
+        let _lock = self.lock.lock();
+
+        let Some(settings) = self.settings.load_full() else {
+            filters.store(Arc::new(chain));
+            return;
+        };
+
+        let current = filters.load();
+        if current.is_empty() {
+            self.canary.store(None);
+            filters.store(Arc::new(chain));
+            return;
+        }
+
+        if *current == chain {
+            if self.canary.swap(None).is_some() {
+                tracing::info!("canary filter chain superseded by the current filter chain");
+            }
+            return;
+        }
+
+        if let Some(canary) = &*self.canary.load() {
+            if *canary.chain == chain {
+                return;
+            }
+
+            tracing::info!(
+                trial = canary.trial,
+                "canary filter chain superseded by a newer filter chain"
+            );
+        }
+
+        let digest = digest(&chain);
+        if self.rejected.lock().contains(&digest) {
+            if self.canary.swap(None).is_some() {
+                tracing::info!("canary filter chain superseded by a rolled back filter chain");
+            }
+            tracing::warn!(
+                digest,
+                "not trialling filter chain, it was already rolled back"
+            );
+            return;
+        }
+
+        let trial = self.trials.fetch_add(1, Relaxed);
+        tracing::info!(
+            trial,
+            fraction = settings.fraction,
+            duration = ?settings.duration,
+            "trialling new filter chain"
+        );
+        self.canary.store(Some(Arc::new(Canary {
+            trial,
+            chain: Arc::new(chain),
+            digest,
+            started: Instant::now(),
+            stable: Stats::new("current"),
+            canary: Stats::new("canary"),
+        })));
+    }
+
+    /// Selects the filter chain for the session of the client at `session`.
+    /// Each session consistently uses the same chain for the lifetime of a
+    /// canary.
+    pub fn select(&self, filters: &Slot<FilterChain>, session: SocketAddr) -> Selected {
+        let Some(canary) = self.canary.load_full() else {
+            return Selected {
+                chain: filters.load(),
+                trial: None,
+            };
+        };
+
+        let fraction = self
+            .settings
+            .load()
+            .as_ref()
+            .map_or(0.0, |settings| settings.fraction);
+
+        if in_canary(session, fraction) {
+            Selected {
+                chain: canary.chain.clone(),
+                trial: Some((canary, true)),
+            }
+        } else {
+            Selected {
+                chain: filters.load(),
+                trial: Some((canary, false)),
+            }
+        }
+    }
+
+    /// Evaluates the active canary, if any, promoting it into `filters` when
+    /// its error and drop rates are within the allowed increase over the
+    /// current chain's, and rolling it back otherwise.
+    pub fn evaluate(&self, filters: &Slot<FilterChain>, now: Instant) -> Option<Decision> {
+        let _lock = self.lock.lock();
+        let canary = self.canary.load_full()?;
+        let settings = self.settings.load_full()?;
+
+        let canary_rates = canary.canary.rates();
+        let elapsed = now.saturating_duration_since(canary.started);
+        if elapsed < settings.duration
+            || (canary_rates.packets < settings.min_packets && elapsed < settings.max_duration)
+        {
+            return Some(Decision::Wait);
+        }
+
+        if canary_rates.packets < settings.min_packets {
+            tracing::info!(
+                trial = canary.trial,
+                packets = canary_rates.packets,
+                min_packets = settings.min_packets,
+                "canary filter chain reached its maximum duration, evaluating it with fewer packets than the minimum"
+            );
+        }
+
+        let stable_rates = canary.stable.rates();
+        let rollback = canary_rates.error_rate - stable_rates.error_rate
+            > settings.max_rate_increase
+            || canary_rates.drop_rate - stable_rates.drop_rate > settings.max_rate_increase;
+
+        self.canary.store(None);
+        let decision = if rollback {
+            tracing::warn!(
+                trial = canary.trial,
+                canary = ?canary_rates,
+                previous = ?stable_rates,
+                max_rate_increase = settings.max_rate_increase,
+                "rolling back canary filter chain"
+            );
+
+            let mut rejected = self.rejected.lock();
+            if rejected.len() == MAX_REJECTED {
+                rejected.pop_front();
+            }
+            rejected.push_back(canary.digest);
+            Decision::Rollback
+        } else {
+            tracing::info!(
+                trial = canary.trial,
+                canary = ?canary_rates,
+                previous = ?stable_rates,
+                "promoting canary filter chain"
+            );
+            filters.store(canary.chain.clone());
+            Decision::Promote
+        };
+
+        Some(decision)
+    }
+}
+
+impl std::fmt::Debug for FilterRollout {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("FilterRollout")
+            .field("settings", &self.settings.load())
+            .field("active", &self.is_active())
+            .finish()
+    }
+}
+
+/// Only the settings are compared, as the canary is runtime state.
+impl PartialEq for FilterRollout {
+    fn eq(&self, rhs: &Self) -> bool {
+        *self.settings.load() == *rhs.settings.load()
+    }
+}
+
+/// Returns a digest of the configuration of every filter of `chain`.
+fn digest(chain: &FilterChain) -> u64 {
+    let filters = (
+        chain.iter().collect::<Vec<_>>(),
+        chain.read_filters().map(Iterator::collect::<Vec<_>>),
+        chain.write_filters().map(Iterator::collect::<Vec<_>>),
+    );
+
+    seahash::hash(&serde_json::to_vec(&filters).unwrap_or_default())
+}
+
+fn in_canary(session: SocketAddr, fraction: f64) -> bool {
+    let mut hasher = seahash::SeaHasher::new();
+    session.hash(&mut hasher);
+    ((hasher.finish() % BUCKETS) as f64) < fraction * BUCKETS as f64
+}
+
+/// Periodically evaluates the active canary of `config`, if any, until
+/// shutdown.
+pub async fn evaluate_canaries(config: Arc<super::Config>, mut shutdown_rx: crate::ShutdownRx) {
+    let mut interval = tokio::time::interval(EVALUATION_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = shutdown_rx.changed() => return,
+        }
+
+        config
+            .filter_rollout
+            .evaluate(&config.filters, Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filters::{Debug, StaticFilter};
+
+    fn chain(id: &str) -> FilterChain {
+        FilterChain::try_create([crate::config::Filter {
+            name: Debug::NAME.into(),
+            label: None,
+            config: Some(serde_json::json!({ "id": id })),
+            when: None,
+        }])
+        .unwrap()
+    }
+
+    fn rollout() -> FilterRollout {
+        let rollout = FilterRollout::default();
+        rollout.enable(CanarySettings {
+            fraction: 0.5,
+            duration: Duration::from_secs(10),
+            min_packets: 10,
+            max_duration: Duration::from_secs(30),
+            max_rate_increase: 0.1,
+        });
+        rollout
+    }
+
+    fn sessions(rollout: &FilterRollout, filters: &Slot<FilterChain>) -> (Selected, Selected) {
+        let mut stable = None;
+        let mut canary = None;
+
+        for port in 1..1000 {
+            let selected = rollout.select(filters, (std::net::Ipv4Addr::LOCALHOST, port).into());
+            if selected.is_canary() {
+                canary.get_or_insert(selected);
+            } else {
+                stable.get_or_insert(selected);
+            }
+        }
+
+        (stable.unwrap(), canary.unwrap())
+    }
+
+    #[test]
+    fn disabled() {
+        let rollout = FilterRollout::default();
+        let filters = Slot::new(chain("a"));
+
+        rollout.store(&filters, chain("b"));
+        assert!(!rollout.is_active());
+        assert_eq!(*filters.load(), chain("b"));
+    }
+
+    #[test]
+    fn first_chain_applied_immediately() {
+        let rollout = rollout();
+        let filters = Slot::<FilterChain>::default();
+
+        rollout.store(&filters, chain("a"));
+        assert!(!rollout.is_active());
+        assert_eq!(*filters.load(), chain("a"));
+    }
+
+    #[test]
+    fn sticky_selection() {
+        let rollout = rollout();
+        let filters = Slot::new(chain("a"));
+        rollout.store(&filters, chain("b"));
+        assert!(rollout.is_active());
+        assert_eq!(*filters.load(), chain("a"));
+
+        let (stable, canary) = sessions(&rollout, &filters);
+        assert_eq!(*stable, chain("a"));
+        assert_eq!(*canary, chain("b"));
+
+        let session = (std::net::Ipv4Addr::LOCALHOST, 7777).into();
+        let first = rollout.select(&filters, session).is_canary();
+        for _ in 0..10 {
+            assert_eq!(first, rollout.select(&filters, session).is_canary());
+        }
+
+        let in_canary = (1..=10_000)
+            .filter(|port| {
+                rollout
+                    .select(&filters, (std::net::Ipv4Addr::LOCALHOST, *port).into())
+                    .is_canary()
+            })
+            .count();
+        assert!((4000..6000).contains(&in_canary), "{in_canary}");
+    }
+
+    #[test]
+    fn superseded() {
+        let rollout = rollout();
+        let filters = Slot::new(chain("a"));
+
+        rollout.store(&filters, chain("b"));
+        rollout.store(&filters, chain("c"));
+        let (_, canary) = sessions(&rollout, &filters);
+        assert_eq!(*canary, chain("c"));
+
+        rollout.store(&filters, chain("a"));
+        assert!(!rollout.is_active());
+        assert_eq!(*filters.load(), chain("a"));
+    }
+
+    #[test]
+    fn promote() {
+        let rollout = rollout();
+        let filters = Slot::new(chain("a"));
+        rollout.store(&filters, chain("b"));
+        let started = Instant::now();
+
+        let (stable, canary) = sessions(&rollout, &filters);
+        for _ in 0..100 {
+            stable.record(Outcome::Forwarded);
+            canary.record(Outcome::Forwarded);
+        }
+        canary.record(Outcome::Errored);
+
+        assert_eq!(
+            Some(Decision::Wait),
+            rollout.evaluate(&filters, started + Duration::from_secs(1))
+        );
+        assert_eq!(
+            Some(Decision::Promote),
+            rollout.evaluate(&filters, started + Duration::from_secs(11))
+        );
+        assert!(!rollout.is_active());
+        assert_eq!(*filters.load(), chain("b"));
+        assert_eq!(
+            None,
+            rollout.evaluate(&filters, started + Duration::from_secs(12))
+        );
+    }
+
+    #[test]
+    fn rollback() {
+        let rollout = rollout();
+        let filters = Slot::new(chain("a"));
+        rollout.store(&filters, chain("b"));
+        let started = Instant::now();
+
+        let (stable, canary) = sessions(&rollout, &filters);
+        for _ in 0..5 {
+            canary.record(Outcome::Dropped);
+        }
+        assert_eq!(
+            Some(Decision::Wait),
+            rollout.evaluate(&filters, started + Duration::from_secs(11))
+        );
+
+        for _ in 0..100 {
+            stable.record(Outcome::Forwarded);
+            canary.record(Outcome::Forwarded);
+        }
+        for _ in 0..20 {
+            canary.record(Outcome::Dropped);
+        }
+
+        assert_eq!(
+            Some(Decision::Rollback),
+            rollout.evaluate(&filters, started + Duration::from_secs(11))
+        );
+        assert!(!rollout.is_active());
+        assert_eq!(*filters.load(), chain("a"));
+
+        // The rolled back chain isn't trialled again, but others are.
+        rollout.store(&filters, chain("b"));
+        assert!(!rollout.is_active());
+        rollout.store(&filters, chain("c"));
+        assert!(rollout.is_active());
+    }
+
+    #[test]
+    fn previous_trial() {
+        let rollout = rollout();
+        let filters = Slot::new(chain("a"));
+        rollout.store(&filters, chain("b"));
+        let (old_stable, old_canary) = sessions(&rollout, &filters);
+
+        rollout.store(&filters, chain("c"));
+        let started = Instant::now();
+        let (stable, canary) = sessions(&rollout, &filters);
+        for _ in 0..100 {
+            stable.record(Outcome::Forwarded);
+            canary.record(Outcome::Forwarded);
+        }
+
+        // Sessions selected during the previous trial don't count towards
+        // the current one.
+        for _ in 0..100 {
+            old_stable.record(Outcome::Forwarded);
+            old_canary.record(Outcome::Errored);
+        }
+
+        assert_eq!(
+            Some(Decision::Promote),
+            rollout.evaluate(&filters, started + Duration::from_secs(11))
+        );
+        assert_eq!(*filters.load(), chain("c"));
+    }
+
+    #[test]
+    fn max_duration() {
+        let rollout = rollout();
+        let filters = Slot::new(chain("a"));
+        rollout.store(&filters, chain("b"));
+        let started = Instant::now();
+
+        let (stable, canary) = sessions(&rollout, &filters);
+        stable.record(Outcome::Forwarded);
+        canary.record(Outcome::Forwarded);
+
+        assert_eq!(
+            Some(Decision::Wait),
+            rollout.evaluate(&filters, started + Duration::from_secs(29))
+        );
+        assert_eq!(
+            Some(Decision::Promote),
+            rollout.evaluate(&filters, started + Duration::from_secs(30))
+        );
+        assert_eq!(*filters.load(), chain("b"));
+
+        rollout.store(&filters, chain("c"));
+        let (_, canary) = sessions(&rollout, &filters);
+        canary.record(Outcome::Errored);
+
+        assert_eq!(
+            Some(Decision::Rollback),
+            rollout.evaluate(&filters, started + Duration::from_secs(60))
+        );
+        assert_eq!(*filters.load(), chain("b"));
+    }
+
+    #[test]
+    fn validate() {
+        let settings = CanarySettings {
+            fraction: 0.5,
+            duration: DEFAULT_DURATION,
+            min_packets: DEFAULT_MIN_PACKETS,
+            max_duration: DEFAULT_MAX_DURATION,
+            max_rate_increase: DEFAULT_MAX_RATE_INCREASE,
+        };
+        assert!(settings.validate().is_ok());
+        assert!(CanarySettings {
+            max_duration: Duration::from_secs(1),
+            ..settings
+        }
+        .validate()
+        .is_err());
+    }
+}
@@ -45,15 +45,10 @@ pub fn update_filters_from_configmap(
 
             let data = configmap.data.ok_or_else(|| eyre::eyre!("configmap data missing"))?;
             let data = data.get("quilkin.yaml").ok_or_else(|| eyre::eyre!("quilkin.yaml property not found"))?;
-            let data: serde_json::Map<String, serde_json::Value> = serde_yaml::from_str(data)?;
-
-            if let Some(filters) = data
-                .get("filters")
-                    .cloned()
-                    .map(serde_json::from_value)
-                    .transpose()?
-            {
-                config.filters.store(Arc::new(filters));
+            let mut data: serde_json::Map<String, serde_json::Value> = serde_yaml::from_str(data)?;
+
+            if let Some(filters) = crate::config::FilterChainConfig::take_from(&mut data)? {
+                config.filters.store(Arc::new(crate::filters::FilterChain::try_create_directional(filters)?));
             }
 
             yield Ok(());
@@ -82,7 +82,10 @@ impl GameServer {
             let ep = Endpoint::with_metadata(
                 (address, port).into(),
                 crate::net::endpoint::metadata::MetadataView::with_unknown(
-                    crate::net::endpoint::Metadata { tokens },
+                    crate::net::endpoint::Metadata {
+                        tokens,
+                        labels: <_>::default(),
+                    },
                     extra_metadata,
                 ),
             );
@@ -92,7 +95,7 @@ impl GameServer {
     }
 
     #[inline]
-    fn tokens(&self) -> std::collections::BTreeSet<Vec<u8>> {
+    fn tokens(&self) -> crate::net::endpoint::Set {
         self.metadata
             .annotations
             .as_ref()
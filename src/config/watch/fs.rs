@@ -111,6 +111,7 @@ mod tests {
                     (std::net::Ipv4Addr::LOCALHOST, 4321).into(),
                     crate::net::endpoint::Metadata {
                         tokens: <_>::from([Vec::from(*b"1x7ijy6")]),
+                        labels: <_>::default(),
                     },
                 )]
                 .into(),
@@ -22,21 +22,35 @@ mod factory;
 mod read;
 mod registry;
 mod set;
+mod streams;
 mod write;
 
+pub mod a2s;
+pub mod broadcast;
 pub mod capture;
+pub mod chaos;
 pub mod compress;
 pub mod concatenate;
 pub mod debug;
 pub mod drop;
+pub mod fan_out;
+pub mod fec;
 pub mod firewall;
+pub mod fragment;
+pub mod label_router;
 pub mod load_balancer;
 pub mod local_rate_limit;
 pub mod r#match;
+pub mod metadata;
 pub mod metrics;
+pub mod mirror;
+pub mod parse_header;
 pub mod pass;
+pub mod predicate;
+pub mod signed_token_router;
 pub mod timestamp;
 pub mod token_router;
+pub mod traffic_split;
 
 /// Prelude containing all types and traits required to implement [`Filter`] and
 /// [`FilterFactory`].
@@ -50,23 +64,35 @@ pub mod prelude {
 // Core Filter types
 #[doc(inline)]
 pub use self::{
+    a2s::A2s,
+    broadcast::Broadcast,
     capture::Capture,
+    chaos::Chaos,
     compress::Compress,
     concatenate::Concatenate,
     debug::Debug,
     drop::Drop,
     error::{ConvertProtoConfigError, CreationError, FilterError},
     factory::{CreateFilterArgs, DynFilterFactory, FilterFactory, FilterInstance},
+    fan_out::FanOut,
+    fec::Fec,
     firewall::Firewall,
+    fragment::Fragment,
+    label_router::LabelRouter,
     load_balancer::LoadBalancer,
     local_rate_limit::LocalRateLimit,
+    metadata::Metadata,
+    mirror::Mirror,
+    parse_header::ParseHeader,
     pass::Pass,
     r#match::Match,
     read::ReadContext,
     registry::FilterRegistry,
     set::{FilterMap, FilterSet},
+    signed_token_router::SignedTokenRouter,
     timestamp::Timestamp,
     token_router::{HashedTokenRouter, TokenRouter},
+    traffic_split::TrafficSplit,
     write::WriteContext,
 };
 
@@ -168,6 +194,7 @@ where
                 .into()
                 .map(|config| serde_json::to_value(&config))
                 .transpose()?,
+            when: None,
         })
     }
 }
@@ -0,0 +1,621 @@
+/*
+ * Copyright 2024 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    hash::{Hash, Hasher},
+    sync::{Arc, Once, Weak},
+    time::Duration,
+};
+
+use bytes::Bytes;
+use dashmap::{mapref::entry::Entry, DashMap};
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+
+use crate::{filters::prelude::*, net::endpoint::EndpointAddress};
+
+use crate::generated::quilkin::filters::a2s::v1alpha1 as proto;
+
+/// The header of A2S messages sent in a single packet.
+const SINGLE_PACKET: [u8; 4] = [0xff; 4];
+/// The header of each packet of A2S responses split across several packets.
+const MULTI_PACKET: [u8; 4] = [0xfe, 0xff, 0xff, 0xff];
+/// The offset of the total number of packets in a multi-packet response.
+const MULTI_PACKET_TOTAL: usize = 8;
+/// The payload of `A2S_INFO` requests, before the challenge.
+const INFO_PAYLOAD: &[u8] = b"Source Engine Query\0";
+/// The type of the responses carrying a challenge number.
+const S2C_CHALLENGE: u8 = b'A';
+/// The challenge sent in requests to ask for a challenge number.
+const NO_CHALLENGE: [u8; 4] = [0xff; 4];
+/// How long the refresh of a response waits for each packet from an
+/// endpoint.
+const REFRESH_TIMEOUT: Duration = Duration::from_secs(1);
+/// How long responses are still refreshed after they were last requested.
+const EXPIRY: Duration = Duration::from_secs(60);
+
+/// Filter that answers the A2S queries of server browsers with responses
+/// cached from each endpoint, rather than forwarding every query to the
+/// gameservers.
+pub struct A2s {
+    config: Config,
+    cache: Arc<Cache>,
+    refresh: Once,
+    challenge_seeds: [u64; 4],
+}
+
+/// The responses of each endpoint to each query. Responses which haven't
+/// been fetched yet are `None`.
+type Cache = DashMap<(EndpointAddress, Query), Cached>;
+
+struct Cached {
+    packets: Option<Arc<[Bytes]>>,
+    requested_at: Instant,
+}
+
+impl A2s {
+    fn new(config: Config) -> Result<Self, CreationError> {
+        if config.refresh_interval == 0 {
+            return Err(CreationError::FieldInvalid {
+                field: "refresh_interval".into(),
+                reason: "refresh interval must be at least one second".into(),
+            });
+        }
+
+        Ok(Self {
+            config,
+            cache: <_>::default(),
+            refresh: Once::new(),
+            challenge_seeds: rand::random(),
+        })
+    }
+
+    /// Returns the challenge number of `source`, which is the same for every
+    /// query from it, so no state is kept for the challenges handed out.
+    fn challenge(&self, source: &EndpointAddress) -> [u8; 4] {
+        let [k1, k2, k3, k4] = self.challenge_seeds;
+        let mut hasher = seahash::SeaHasher::with_seeds(k1, k2, k3, k4);
+        source.hash(&mut hasher);
+        (hasher.finish() as u32).to_le_bytes()
+    }
+
+    /// Returns the cached response of `address` to `query`, if it has been
+    /// fetched.
+    fn cached(&self, address: EndpointAddress, query: Query) -> Option<Arc<[Bytes]>> {
+        match self.cache.entry((address, query)) {
+            Entry::Occupied(mut entry) => {
+                entry.get_mut().requested_at = Instant::now();
+                entry.get().packets.clone()
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(Cached {
+                    packets: None,
+                    requested_at: Instant::now(),
+                });
+                None
+            }
+        }
+    }
+}
+
+impl StaticFilter for A2s {
+    const NAME: &'static str = "quilkin.filters.a2s.v1alpha1.A2s";
+    type Configuration = Config;
+    type BinaryConfiguration = proto::A2s;
+
+    fn try_from_config(config: Option<Self::Configuration>) -> Result<Self, CreationError> {
+        Self::new(config.unwrap_or_default())
+    }
+}
+
+#[async_trait::async_trait]
+impl Filter for A2s {
+    async fn read(&self, ctx: &mut ReadContext) -> Result<(), FilterError> {
+        let Some(request) = Request::parse(&ctx.contents) else {
+            return Ok(());
+        };
+
+        // The responses are from the endpoint the query would be forwarded
+        // to, which is the only endpoint when the proxy is in front of a
+        // single gameserver. With several endpoints and none chosen, there's
+        // no telling which gameserver the client is querying, so the query is
+        // passed on instead.
+        let Some(address) = ctx.destinations.first().cloned().or_else(|| {
+            (ctx.endpoints.num_of_endpoints() == 1)
+                .then(|| ctx.endpoints.nth_endpoint(0))
+                .flatten()
+                .map(|endpoint| endpoint.address)
+        }) else {
+            return Ok(());
+        };
+
+        self.refresh.call_once(|| {
+            tokio::spawn(refresh(
+                Arc::downgrade(&self.cache),
+                Duration::from_secs(self.config.refresh_interval.into()),
+            ));
+        });
+
+        // Until a response has been cached, queries are answered by the
+        // endpoint itself, including its own challenges.
+        let Some(packets) = self.cached(address, request.query) else {
+            tracing::trace!(query = ?request.query, "no cached A2S response");
+            return Ok(());
+        };
+
+        let challenge = self.challenge(&ctx.source);
+        if request.query.is_challenged(&self.config) && request.challenge != Some(challenge) {
+            tracing::trace!(query = ?request.query, "replying with A2S challenge");
+            let mut reply = SINGLE_PACKET.to_vec();
+            reply.push(S2C_CHALLENGE);
+            reply.extend(challenge);
+            ctx.reply(reply);
+        } else {
+            tracing::trace!(query = ?request.query, "replying with cached A2S response");
+            for packet in packets.iter() {
+                ctx.reply(packet.clone());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The queries answered from the cache.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum Query {
+    Info,
+    Player,
+    Rules,
+}
+
+impl Query {
+    fn header(self) -> u8 {
+        match self {
+            Self::Info => b'T',
+            Self::Player => b'U',
+            Self::Rules => b'V',
+        }
+    }
+
+    fn is_challenged(self, config: &Config) -> bool {
+        match self {
+            Self::Info => config.info_challenge,
+            Self::Player | Self::Rules => true,
+        }
+    }
+
+    /// Returns the request for the query with the `challenge`.
+    fn request(self, challenge: Option<[u8; 4]>) -> Vec<u8> {
+        let mut request = SINGLE_PACKET.to_vec();
+        request.push(self.header());
+        match self {
+            Self::Info => {
+                request.extend(INFO_PAYLOAD);
+                request.extend(challenge.into_iter().flatten());
+            }
+            Self::Player | Self::Rules => request.extend(challenge.unwrap_or(NO_CHALLENGE)),
+        }
+        request
+    }
+}
+
+/// An A2S query received from a client.
+#[derive(Debug, PartialEq)]
+struct Request {
+    query: Query,
+    challenge: Option<[u8; 4]>,
+}
+
+impl Request {
+    fn parse(packet: &[u8]) -> Option<Self> {
+        let (&header, payload) = packet.strip_prefix(&SINGLE_PACKET)?.split_first()?;
+
+        let (query, challenge) = match header {
+            b'T' => (Query::Info, payload.strip_prefix(INFO_PAYLOAD)?),
+            b'U' => (Query::Player, payload),
+            b'V' => (Query::Rules, payload),
+            _ => return None,
+        };
+
+        let challenge = match challenge {
+            [] if query == Query::Info => None,
+            &[a, b, c, d] => Some([a, b, c, d]).filter(|challenge| *challenge != NO_CHALLENGE),
+            _ => return None,
+        };
+
+        Some(Self { query, challenge })
+    }
+}
+
+/// Refreshes the cached responses every `interval`, until the filter is
+/// dropped.
+async fn refresh(cache: Weak<Cache>, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+
+    loop {
+        ticker.tick().await;
+        let Some(cache) = cache.upgrade() else {
+            return;
+        };
+
+        cache.retain(|_, cached| cached.requested_at.elapsed() < EXPIRY);
+        let keys = cache
+            .iter()
+            .map(|entry| entry.key().clone())
+            .collect::<Vec<_>>();
+
+        let responses = futures::future::join_all(keys.into_iter().map(|key| async move {
+            let response = fetch(&key.0, key.1).await;
+            if let Err(error) = &response {
+                tracing::debug!(address = %key.0, query = ?key.1, %error, "failed to refresh A2S response");
+            }
+            (key, response.ok())
+        }))
+        .await;
+
+        for (key, packets) in responses {
+            if let Some(mut cached) = cache.get_mut(&key) {
+                cached.packets = packets;
+            }
+        }
+    }
+}
+
+/// Queries `address`, following its challenge, and returns the packets of
+/// its response.
+async fn fetch(address: &EndpointAddress, query: Query) -> std::io::Result<Arc<[Bytes]>> {
+    use std::io::{Error, ErrorKind};
+
+    let address = address.to_socket_addr().await?;
+    let unspecified: std::net::SocketAddr = if address.is_ipv4() {
+        (std::net::Ipv4Addr::UNSPECIFIED, 0).into()
+    } else {
+        (std::net::Ipv6Addr::UNSPECIFIED, 0).into()
+    };
+    let socket = tokio::net::UdpSocket::bind(unspecified).await?;
+    socket.connect(address).await?;
+
+    let mut buffer = vec![0; u16::MAX as usize];
+    socket.send(&query.request(None)).await?;
+    let mut packet = recv(&socket, &mut buffer).await?;
+    if let Some(&[S2C_CHALLENGE, a, b, c, d]) = packet.strip_prefix(&SINGLE_PACKET) {
+        socket.send(&query.request(Some([a, b, c, d]))).await?;
+        packet = recv(&socket, &mut buffer).await?;
+    }
+
+    let invalid = || Error::new(ErrorKind::InvalidData, "invalid A2S response");
+    if packet.starts_with(&SINGLE_PACKET) {
+        if packet.get(SINGLE_PACKET.len()) == Some(&S2C_CHALLENGE) {
+            return Err(invalid());
+        }
+
+        Ok(vec![packet].into())
+    } else if packet.starts_with(&MULTI_PACKET) {
+        let total = *packet.get(MULTI_PACKET_TOTAL).ok_or_else(invalid)?;
+        let mut packets = vec![packet];
+        while packets.len() < usize::from(total) {
+            let packet = recv(&socket, &mut buffer).await?;
+            if !packet.starts_with(&MULTI_PACKET) {
+                return Err(invalid());
+            }
+            packets.push(packet);
+        }
+
+        Ok(packets.into())
+    } else {
+        Err(invalid())
+    }
+}
+
+/// Receives the next packet from `socket`, waiting at most
+/// [`REFRESH_TIMEOUT`].
+async fn recv(socket: &tokio::net::UdpSocket, buffer: &mut [u8]) -> std::io::Result<Bytes> {
+    let size = tokio::time::timeout(REFRESH_TIMEOUT, socket.recv(buffer))
+        .await
+        .map_err(|_| std::io::Error::from(std::io::ErrorKind::TimedOut))??;
+    Ok(Bytes::copy_from_slice(&buffer[..size]))
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, schemars::JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// how often the cached responses are refreshed from the endpoints, in
+    /// seconds
+    #[serde(default = "default_refresh_interval")]
+    pub refresh_interval: u32,
+    /// whether clients must complete the challenge for `A2S_INFO` queries, as
+    /// they always must for `A2S_PLAYER` and `A2S_RULES`
+    #[serde(default = "default_info_challenge")]
+    pub info_challenge: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            refresh_interval: default_refresh_interval(),
+            info_challenge: default_info_challenge(),
+        }
+    }
+}
+
+/// default value for [`Config::refresh_interval`]
+fn default_refresh_interval() -> u32 {
+    5
+}
+
+/// default value for [`Config::info_challenge`]
+fn default_info_challenge() -> bool {
+    true
+}
+
+impl From<Config> for proto::A2s {
+    fn from(config: Config) -> Self {
+        Self {
+            refresh_interval: Some(config.refresh_interval),
+            info_challenge: Some(config.info_challenge),
+        }
+    }
+}
+
+impl From<proto::A2s> for Config {
+    fn from(p: proto::A2s) -> Self {
+        Self {
+            refresh_interval: p.refresh_interval.unwrap_or_else(default_refresh_interval),
+            info_challenge: p.info_challenge.unwrap_or_else(default_info_challenge),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        net::{cluster::ClusterMap, endpoint::Endpoint},
+        test::alloc_buffer,
+    };
+
+    const SOURCE: ([u8; 4], u16) = ([127, 0, 0, 1], 80);
+
+    fn info(challenge: &[u8]) -> Vec<u8> {
+        [&SINGLE_PACKET[..], b"T", INFO_PAYLOAD, challenge].concat()
+    }
+
+    fn player(challenge: [u8; 4]) -> Vec<u8> {
+        [&SINGLE_PACKET[..], b"U", &challenge].concat()
+    }
+
+    fn context(address: &EndpointAddress, packet: &[u8]) -> ReadContext {
+        let endpoints = ClusterMap::new_default([Endpoint::new(address.clone())].into());
+        ReadContext::new(endpoints.into(), SOURCE.into(), alloc_buffer(packet))
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            Some(Request {
+                query: Query::Info,
+                challenge: None
+            }),
+            Request::parse(&info(&[]))
+        );
+        assert_eq!(
+            Some(Request {
+                query: Query::Info,
+                challenge: Some([1, 2, 3, 4])
+            }),
+            Request::parse(&info(&[1, 2, 3, 4]))
+        );
+        assert_eq!(
+            Some(Request {
+                query: Query::Player,
+                challenge: None
+            }),
+            Request::parse(&player(NO_CHALLENGE))
+        );
+        assert_eq!(
+            Some(Request {
+                query: Query::Rules,
+                challenge: Some([1, 2, 3, 4])
+            }),
+            Request::parse(&[0xff, 0xff, 0xff, 0xff, b'V', 1, 2, 3, 4])
+        );
+
+        for packet in [
+            &b"hello"[..],
+            &[0xff, 0xff, 0xff, 0xff],
+            &[0xff, 0xff, 0xff, 0xff, b'W', 1, 2, 3, 4],
+            &[0xff, 0xff, 0xff, 0xff, b'U', 1, 2, 3],
+            &[0xff, 0xff, 0xff, 0xff, b'T', 1, 2, 3, 4],
+            &info(&[1, 2])[..],
+        ] {
+            assert_eq!(None, Request::parse(packet), "{packet:?}");
+        }
+    }
+
+    #[test]
+    fn request() {
+        assert_eq!(info(&[]), Query::Info.request(None));
+        assert_eq!(info(&[1, 2, 3, 4]), Query::Info.request(Some([1, 2, 3, 4])));
+        assert_eq!(player(NO_CHALLENGE), Query::Player.request(None));
+        assert_eq!(
+            player([1, 2, 3, 4]),
+            Query::Player.request(Some([1, 2, 3, 4]))
+        );
+    }
+
+    #[tokio::test]
+    async fn read() {
+        let filter = A2s::from_config(Some(<_>::default()));
+        // Don't refresh from the endpoint, which doesn't exist.
+        filter.refresh.call_once(|| {});
+        let address = EndpointAddress::from((std::net::Ipv4Addr::LOCALHOST, 81));
+
+        let mut ctx = context(&address, b"hello");
+        filter.read(&mut ctx).await.unwrap();
+        assert!(!ctx.is_handled());
+
+        // Queries are forwarded until a response is cached.
+        let mut ctx = context(&address, &info(&[]));
+        filter.read(&mut ctx).await.unwrap();
+        assert!(!ctx.is_handled());
+
+        let response = Bytes::from_static(b"\xff\xff\xff\xffIinfo");
+        for query in [Query::Info, Query::Player] {
+            filter.cache.insert(
+                (address.clone(), query),
+                Cached {
+                    packets: Some(vec![response.clone()].into()),
+                    requested_at: Instant::now(),
+                },
+            );
+        }
+
+        let challenge = filter.challenge(&SOURCE.into());
+        let challenge_reply = [&SINGLE_PACKET[..], &[S2C_CHALLENGE], &challenge].concat();
+        for (packet, reply) in [
+            (info(&[]), challenge_reply.clone()),
+            (info(&[9, 9, 9, 9]), challenge_reply.clone()),
+            (player(NO_CHALLENGE), challenge_reply),
+            (info(&challenge), response.to_vec()),
+            (player(challenge), response.to_vec()),
+        ] {
+            let mut ctx = context(&address, &packet);
+            filter.read(&mut ctx).await.unwrap();
+            assert!(ctx.is_handled(), "{packet:?}");
+            assert_eq!(vec![Bytes::from(reply)], ctx.replies, "{packet:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn several_endpoints() {
+        let filter = A2s::from_config(Some(<_>::default()));
+        filter.refresh.call_once(|| {});
+        let addresses =
+            [81, 82].map(|port| EndpointAddress::from((std::net::Ipv4Addr::LOCALHOST, port)));
+        let response = Bytes::from_static(b"\xff\xff\xff\xffIinfo");
+        for address in &addresses {
+            filter.cache.insert(
+                (address.clone(), Query::Info),
+                Cached {
+                    packets: Some(vec![response.clone()].into()),
+                    requested_at: Instant::now(),
+                },
+            );
+        }
+
+        let endpoints = std::sync::Arc::new(ClusterMap::new_default(
+            addresses.iter().cloned().map(Endpoint::new).collect(),
+        ));
+        let challenge = filter.challenge(&SOURCE.into());
+
+        // Without a chosen endpoint, the query isn't answered.
+        let mut ctx = ReadContext::new(
+            endpoints.clone(),
+            SOURCE.into(),
+            alloc_buffer(info(&challenge)),
+        );
+        filter.read(&mut ctx).await.unwrap();
+        assert!(!ctx.is_handled());
+
+        // Otherwise it's answered with the response of the chosen endpoint.
+        let mut ctx = ReadContext::new(endpoints, SOURCE.into(), alloc_buffer(info(&challenge)));
+        ctx.destinations.push(addresses[1].clone());
+        filter.read(&mut ctx).await.unwrap();
+        assert_eq!(vec![response], ctx.replies);
+    }
+
+    #[tokio::test]
+    async fn info_without_challenge() {
+        let filter = A2s::from_config(Some(Config {
+            info_challenge: false,
+            ..<_>::default()
+        }));
+        filter.refresh.call_once(|| {});
+        let address = EndpointAddress::from((std::net::Ipv4Addr::LOCALHOST, 81));
+        let response = Bytes::from_static(b"\xff\xff\xff\xffIinfo");
+        filter.cache.insert(
+            (address.clone(), Query::Info),
+            Cached {
+                packets: Some(vec![response.clone()].into()),
+                requested_at: Instant::now(),
+            },
+        );
+
+        let mut ctx = context(&address, &info(&[]));
+        filter.read(&mut ctx).await.unwrap();
+        assert_eq!(vec![response], ctx.replies);
+    }
+
+    #[tokio::test]
+    async fn refresh() {
+        let server = tokio::net::UdpSocket::bind((std::net::Ipv4Addr::LOCALHOST, 0))
+            .await
+            .unwrap();
+        let address = EndpointAddress::from(server.local_addr().unwrap());
+        let responses = [
+            Bytes::from_static(&[0xfe, 0xff, 0xff, 0xff, 1, 0, 0, 0, 2, 0, b'a']),
+            Bytes::from_static(&[0xfe, 0xff, 0xff, 0xff, 1, 0, 0, 0, 2, 1, b'b']),
+        ];
+
+        // A gameserver answering `A2S_PLAYER` queries with a challenge, then
+        // with a response split in two packets.
+        tokio::spawn({
+            let responses = responses.clone();
+            async move {
+                let mut buffer = [0; 1500];
+                loop {
+                    let (size, source) = server.recv_from(&mut buffer).await.unwrap();
+                    if buffer[..size] == player(NO_CHALLENGE) {
+                        server
+                            .send_to(b"\xff\xff\xff\xffA\x01\x02\x03\x04", source)
+                            .await
+                            .unwrap();
+                    } else if buffer[..size] == player([1, 2, 3, 4]) {
+                        for response in &responses {
+                            server.send_to(response, source).await.unwrap();
+                        }
+                    }
+                }
+            }
+        });
+
+        let filter = A2s::from_config(Some(Config {
+            refresh_interval: 1,
+            ..<_>::default()
+        }));
+        let challenge = filter.challenge(&SOURCE.into());
+
+        let mut ctx = context(&address, &player(challenge));
+        filter.read(&mut ctx).await.unwrap();
+        assert!(!ctx.is_handled());
+
+        let replies = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                let mut ctx = context(&address, &player(challenge));
+                filter.read(&mut ctx).await.unwrap();
+                if ctx.is_handled() {
+                    break ctx.replies;
+                }
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(&responses[..], &replies);
+    }
+}
@@ -0,0 +1,306 @@
+/*
+ * Copyright 2024 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{filters::prelude::*, net::endpoint::Locality};
+
+use crate::generated::quilkin::filters::broadcast::v1alpha1 as proto;
+
+/// Filter that sends each packet to every endpoint matching a locality and
+/// label selector, rather than to a single endpoint.
+pub struct Broadcast {
+    config: Config,
+}
+
+impl Broadcast {
+    fn new(config: Config) -> Result<Self, CreationError> {
+        if config.max_endpoints == 0 {
+            return Err(CreationError::FieldInvalid {
+                field: "max_endpoints".into(),
+                reason: "value must be at least 1".into(),
+            });
+        }
+
+        Ok(Self { config })
+    }
+
+    /// Whether the endpoints in `locality` are selected, which they all are
+    /// when the filter has no locality. A selected region or zone includes
+    /// every zone or sub-zone within it.
+    fn selects_locality(&self, locality: Option<&Locality>) -> bool {
+        let Some(selector) = &self.config.locality else {
+            return true;
+        };
+
+        locality.map_or(false, |locality| {
+            selector.region() == locality.region()
+                && selector
+                    .zone()
+                    .map_or(true, |zone| locality.zone() == Some(zone))
+                && selector
+                    .sub_zone()
+                    .map_or(true, |sub_zone| locality.sub_zone() == Some(sub_zone))
+        })
+    }
+}
+
+impl StaticFilter for Broadcast {
+    const NAME: &'static str = "quilkin.filters.broadcast.v1alpha1.Broadcast";
+    type Configuration = Config;
+    type BinaryConfiguration = proto::Broadcast;
+
+    fn try_from_config(config: Option<Self::Configuration>) -> Result<Self, CreationError> {
+        Self::new(Self::ensure_config_exists(config)?)
+    }
+}
+
+#[async_trait::async_trait]
+impl Filter for Broadcast {
+    async fn read(&self, ctx: &mut ReadContext) -> Result<(), FilterError> {
+        ctx.destinations.clear();
+        for set in ctx.endpoints.iter() {
+            if !self.selects_locality(set.key().as_ref()) {
+                continue;
+            }
+
+            ctx.destinations.extend(
+                set.endpoints
+                    .iter()
+                    .filter(|endpoint| {
+                        self.config.labels.iter().all(|(key, value)| {
+                            endpoint.metadata.known.labels.get(key) == Some(value)
+                        })
+                    })
+                    .map(|endpoint| endpoint.address.clone()),
+            );
+        }
+
+        let max_endpoints = self.config.max_endpoints as usize;
+        if ctx.destinations.is_empty() {
+            Err(FilterError::new(Error::NoEndpointMatch))
+        } else if ctx.destinations.len() > max_endpoints {
+            let matched = ctx.destinations.len();
+            ctx.destinations.clear();
+            Err(FilterError::new(Error::TooManyEndpoints(
+                matched,
+                max_endpoints,
+            )))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("no endpoint matched the selector")]
+    NoEndpointMatch,
+    #[error("{0} endpoints matched the selector, more than the maximum of {1}")]
+    TooManyEndpoints(usize, usize),
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, schemars::JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// the locality of the endpoints, which includes every zone or sub-zone
+    /// within it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locality: Option<Locality>,
+    /// the labels the endpoints must all have, with the same values
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+    /// the most endpoints a packet may be sent to, packets matching more
+    /// endpoints are dropped
+    #[serde(rename = "maxEndpoints", default = "default_max_endpoints")]
+    pub max_endpoints: u32,
+}
+
+/// Default value for [`Config::max_endpoints`]
+fn default_max_endpoints() -> u32 {
+    16
+}
+
+impl From<Config> for proto::Broadcast {
+    fn from(config: Config) -> Self {
+        Self {
+            locality: config.locality.map(|locality| locality.to_string()),
+            labels: config.labels.into_iter().collect(),
+            max_endpoints: Some(config.max_endpoints),
+        }
+    }
+}
+
+impl TryFrom<proto::Broadcast> for Config {
+    type Error = ConvertProtoConfigError;
+
+    fn try_from(p: proto::Broadcast) -> Result<Self, Self::Error> {
+        let locality = p
+            .locality
+            .map(|locality| {
+                locality.parse().map_err(|error| {
+                    ConvertProtoConfigError::new(
+                        format!("invalid locality `{locality}`: {error}"),
+                        Some("locality".into()),
+                    )
+                })
+            })
+            .transpose()?;
+
+        Ok(Self {
+            locality,
+            labels: p.labels.into_iter().collect(),
+            max_endpoints: p.max_endpoints.unwrap_or_else(default_max_endpoints),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        net::{
+            cluster::ClusterMap,
+            endpoint::{Endpoint, EndpointAddress, Metadata},
+        },
+        test::alloc_buffer,
+    };
+
+    fn endpoint(port: u16, labels: &[(&str, &str)]) -> Endpoint {
+        Endpoint::with_metadata(
+            (std::net::Ipv4Addr::LOCALHOST, port).into(),
+            Metadata {
+                labels: labels
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .collect(),
+                ..<_>::default()
+            },
+        )
+    }
+
+    fn endpoints() -> ClusterMap {
+        let endpoints = ClusterMap::default();
+        endpoints.insert(
+            Some(Locality::new("us", "east1", "a")),
+            [
+                endpoint(1, &[("service", "lobby")]),
+                endpoint(2, &[("service", "presence")]),
+            ]
+            .into(),
+        );
+        endpoints.insert(
+            Some(Locality::new("us", "east1", "b")),
+            [endpoint(3, &[("service", "lobby")])].into(),
+        );
+        endpoints.insert(
+            Some(Locality::new("eu", "west1", "a")),
+            [endpoint(4, &[("service", "lobby")])].into(),
+        );
+        endpoints.insert(None, [endpoint(5, &[("service", "lobby")])].into());
+        endpoints
+    }
+
+    async fn read(config: Config) -> Result<Vec<u16>, FilterError> {
+        let filter = Broadcast::from_config(Some(config));
+        let mut ctx = ReadContext::new(
+            endpoints().into(),
+            (std::net::Ipv4Addr::LOCALHOST, 80).into(),
+            alloc_buffer(b"heartbeat"),
+        );
+        filter.read(&mut ctx).await?;
+
+        let mut ports = ctx
+            .destinations
+            .iter()
+            .map(EndpointAddress::port)
+            .collect::<Vec<_>>();
+        ports.sort();
+        Ok(ports)
+    }
+
+    fn config(locality: Option<&str>, labels: &[(&str, &str)]) -> Config {
+        Config {
+            locality: locality.map(|locality| locality.parse().unwrap()),
+            labels: labels
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            max_endpoints: default_max_endpoints(),
+        }
+    }
+
+    #[tokio::test]
+    async fn selectors() {
+        for (locality, labels, expected) in [
+            (None, &[][..], &[1u16, 2, 3, 4, 5][..]),
+            (Some("us"), &[], &[1, 2, 3]),
+            (Some("us:east1"), &[], &[1, 2, 3]),
+            (Some("us:east1:b"), &[], &[3]),
+            (None, &[("service", "lobby")], &[1, 3, 4, 5]),
+            (Some("us:east1"), &[("service", "lobby")], &[1, 3]),
+        ] {
+            assert_eq!(
+                expected,
+                read(config(locality, labels)).await.unwrap(),
+                "{locality:?} {labels:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn errors() {
+        assert!(read(config(Some("asia"), &[])).await.is_err());
+        assert!(read(config(None, &[("service", "matchmaker")]))
+            .await
+            .is_err());
+        assert!(read(Config {
+            max_endpoints: 2,
+            ..config(Some("us"), &[])
+        })
+        .await
+        .is_err());
+    }
+
+    #[test]
+    fn invalid_max_endpoints() {
+        assert!(Broadcast::try_from_config(Some(Config {
+            max_endpoints: 0,
+            ..config(None, &[])
+        }))
+        .is_err());
+    }
+
+    #[test]
+    fn convert_proto_config() {
+        let config = || Config {
+            locality: Some("us:east1".parse().unwrap()),
+            labels: [("service".into(), "lobby".into())].into(),
+            max_endpoints: 4,
+        };
+        assert_eq!(
+            config(),
+            Config::try_from(proto::Broadcast::from(config())).unwrap()
+        );
+        assert!(Config::try_from(proto::Broadcast {
+            locality: Some(String::new()),
+            ..<_>::default()
+        })
+        .is_err());
+    }
+}
@@ -16,6 +16,8 @@
 
 mod affix;
 mod config;
+mod offset;
+mod quic;
 mod regex;
 
 use crate::generated::quilkin::filters::capture::v1alpha1 as proto;
@@ -29,7 +31,9 @@ pub const CAPTURED_BYTES: &str = "quilkin.dev/capture";
 
 pub use self::{
     affix::{Prefix, Suffix},
-    config::{Config, Strategy},
+    config::{Config, Field, Strategy},
+    offset::Offset,
+    quic::Quic,
     regex::Regex,
 };
 
@@ -37,41 +41,122 @@ pub use self::{
 pub trait CaptureStrategy {
     /// Capture packet data from the contents, and optionally returns a value if
     /// anything was captured.
-    fn capture(&self, contents: &mut PoolBuffer) -> Option<metadata::Value>;
+    fn capture(&self, contents: &mut PoolBuffer) -> Option<Captured>;
+}
+
+/// A value captured from a packet.
+#[derive(Debug, PartialEq)]
+pub struct Captured {
+    pub value: metadata::Value,
+    /// Where the value is in the packet, when it's a single range of bytes
+    /// that was left in the packet.
+    pub range: Option<std::ops::Range<usize>>,
+}
+
+impl Captured {
+    /// A value left in the packet at `range`.
+    pub fn at(value: metadata::Value, range: std::ops::Range<usize>) -> Self {
+        Self {
+            value,
+            range: Some(range),
+        }
+    }
+}
+
+impl From<metadata::Value> for Captured {
+    fn from(value: metadata::Value) -> Self {
+        Self { value, range: None }
+    }
+}
+
+/// Returns the key under which the [`Capture`] filter puts where the value
+/// under `key` is in the packet, as a list of its start and end, when it's
+/// left in the packet.
+pub fn range_key(key: metadata::Key) -> metadata::Key {
+    format!("{key}/range").into()
 }
 
 pub struct Capture {
-    capture: Box<dyn CaptureStrategy + Sync + Send>,
-    metadata_key: metadata::Key,
-    is_present_key: metadata::Key,
+    fields: Vec<CaptureField>,
 }
 
 impl Capture {
     fn new(config: Config) -> Self {
-        Self {
-            capture: config.strategy.into_capture(),
-            is_present_key: format!("{}/is_present", config.metadata_key).into(),
+        let first = Field {
             metadata_key: config.metadata_key,
+            strategy: config.strategy,
+        };
+
+        Self {
+            fields: std::iter::once(first)
+                .chain(config.captures)
+                .map(CaptureField::new)
+                .collect(),
         }
     }
 }
 
-#[async_trait::async_trait]
-impl Filter for Capture {
-    #[cfg_attr(feature = "instrument", tracing::instrument(skip(self, ctx)))]
-    async fn read(&self, ctx: &mut ReadContext) -> Result<(), FilterError> {
+/// A single value captured from each packet.
+struct CaptureField {
+    capture: Box<dyn CaptureStrategy + Sync + Send>,
+    metadata_key: metadata::Key,
+    is_present_key: metadata::Key,
+    range_key: metadata::Key,
+}
+
+impl CaptureField {
+    fn new(field: Field) -> Self {
+        Self {
+            capture: field.strategy.into_capture(),
+            is_present_key: format!("{}/is_present", field.metadata_key).into(),
+            range_key: range_key(field.metadata_key),
+            metadata_key: field.metadata_key,
+        }
+    }
+
+    /// Captures the value into `ctx`, returning whether a value was captured.
+    fn read(&self, ctx: &mut ReadContext) -> bool {
         let capture = self.capture.capture(&mut ctx.contents);
         ctx.metadata.insert(
             self.is_present_key,
             metadata::Value::Bool(capture.is_some()),
         );
 
-        if let Some(value) = capture {
+        if let Some(Captured { value, range }) = capture {
             tracing::trace!(key=%self.metadata_key, %value, "captured value");
             ctx.metadata.insert(self.metadata_key, value);
-            Ok(())
+            match range {
+                Some(range) => ctx.metadata.insert(
+                    self.range_key,
+                    metadata::Value::List(vec![
+                        metadata::Value::Number(range.start as u64),
+                        metadata::Value::Number(range.end as u64),
+                    ]),
+                ),
+                None => ctx.metadata.remove(&self.range_key),
+            };
+            true
         } else {
             tracing::trace!(key = %self.metadata_key, "No value captured");
+            false
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Filter for Capture {
+    #[cfg_attr(feature = "instrument", tracing::instrument(skip(self, ctx)))]
+    async fn read(&self, ctx: &mut ReadContext) -> Result<(), FilterError> {
+        // Every field is captured so each `is_present` key is set, even when
+        // an earlier field has nothing to capture.
+        let mut captured = true;
+        for field in &self.fields {
+            captured &= field.read(ctx);
+        }
+
+        if captured {
+            Ok(())
+        } else {
             Err(FilterError::new(NoValueCaptured))
         }
     }
@@ -146,6 +231,7 @@ mod tests {
                 size: 3,
                 remove: true,
             }),
+            captures: vec![],
         };
 
         let filter = Capture::from_config(config.into());
@@ -160,6 +246,7 @@ mod tests {
                 size: 99,
                 remove: true,
             }),
+            captures: vec![],
         };
         let filter = Capture::from_config(config.into());
         let endpoints = crate::net::cluster::ClusterMap::new_default(
@@ -183,6 +270,7 @@ mod tests {
                 remove: false,
             }),
             metadata_key: TOKEN_KEY.into(),
+            captures: vec![],
         };
         let filter = Capture::from_config(config.into());
         assert_write_no_change(&filter).await;
@@ -195,7 +283,10 @@ mod tests {
         };
         let mut contents = alloc_buffer(b"helloabc");
         let result = end.capture(&mut contents).unwrap();
-        assert_eq!(Value::Bytes(b"abc".to_vec().into()), result);
+        assert_eq!(
+            Captured::at(Value::Bytes(b"abc".to_vec().into()), 5..8),
+            result
+        );
         assert_eq!(b"helloabc", &*contents);
     }
 
@@ -207,13 +298,16 @@ mod tests {
         };
         let mut contents = alloc_buffer(b"helloabc");
         let result = end.capture(&mut contents).unwrap();
-        assert_eq!(Value::Bytes(b"abc".to_vec().into()), result);
+        assert_eq!(
+            Captured::at(Value::Bytes(b"abc".to_vec().into()), 5..8),
+            result
+        );
         assert_eq!(b"helloabc", &*contents);
 
         end.remove = true;
 
         let result = end.capture(&mut contents).unwrap();
-        assert_eq!(Value::Bytes(b"abc".to_vec().into()), result);
+        assert_eq!(Captured::from(Value::Bytes(b"abc".to_vec().into())), result);
         assert_eq!(b"hello", &*contents);
     }
 
@@ -226,16 +320,164 @@ mod tests {
         let mut contents = alloc_buffer(b"abchello");
 
         let result = beg.capture(&mut contents);
-        assert_eq!(Some(Value::Bytes(b"abc".to_vec().into())), result);
+        assert_eq!(
+            Some(Captured::at(Value::Bytes(b"abc".to_vec().into()), 0..3)),
+            result
+        );
         assert_eq!(b"abchello", &*contents);
 
         beg.remove = true;
 
         let result = beg.capture(&mut contents);
-        assert_eq!(Some(Value::Bytes(b"abc".to_vec().into())), result);
+        assert_eq!(Some(Value::Bytes(b"abc".to_vec().into()).into()), result);
         assert_eq!(b"hello", &*contents);
     }
 
+    #[test]
+    fn offset_capture() {
+        let mut offset = Offset {
+            offset: 2,
+            size: 3,
+            remove: false,
+        };
+        let mut contents = alloc_buffer(b"heabcllo");
+
+        let result = offset.capture(&mut contents);
+        assert_eq!(
+            Some(Captured::at(Value::Bytes(b"abc".to_vec().into()), 2..5)),
+            result
+        );
+        assert_eq!(b"heabcllo", &*contents);
+
+        offset.remove = true;
+
+        let result = offset.capture(&mut contents);
+        assert_eq!(Some(Value::Bytes(b"abc".to_vec().into()).into()), result);
+        assert_eq!(b"hello", &*contents);
+
+        offset.offset = 3;
+        assert_eq!(None, offset.capture(&mut contents));
+        assert_eq!(b"hello", &*contents);
+    }
+
+    #[test]
+    fn quic_capture() {
+        let mut quic = Quic {
+            connection_id_length: 4,
+            size: None,
+        };
+
+        // Long header: flags, version, connection ID length, connection ID,
+        // source connection ID length, source connection ID, ...
+        let mut contents =
+            alloc_buffer([0xc3, 0, 0, 0, 1, 8, 1, 2, 3, 4, 5, 6, 7, 8, 2, 9, 9, 0xff]);
+        let result = quic.capture(&mut contents);
+        assert_eq!(
+            Some(Captured::at(
+                Value::Bytes(vec![1, 2, 3, 4, 5, 6, 7, 8].into()),
+                6..14
+            )),
+            result
+        );
+        assert_eq!(18, contents.len());
+
+        // Short header: flags, connection ID, packet number, ...
+        let mut contents = alloc_buffer([0x43, 1, 2, 3, 4, 0xff, 0xff]);
+        let result = quic.capture(&mut contents);
+        assert_eq!(
+            Some(Captured::at(Value::Bytes(vec![1, 2, 3, 4].into()), 1..5)),
+            result
+        );
+
+        quic.size = Some(2);
+        let result = quic.capture(&mut contents);
+        assert_eq!(
+            Some(Captured::at(Value::Bytes(vec![1, 2].into()), 1..3)),
+            result
+        );
+
+        for truncated in [&[][..], &[0x43, 1, 2], &[0xc3, 0, 0, 0, 1, 8, 1, 2, 3]] {
+            assert_eq!(None, quic.capture(&mut alloc_buffer(truncated)));
+        }
+    }
+
+    #[tokio::test]
+    async fn read_multiple_fields() {
+        let config = Config {
+            metadata_key: "session".into(),
+            strategy: Offset {
+                offset: 4,
+                size: 8,
+                remove: false,
+            }
+            .into(),
+            captures: vec![
+                Field::new(
+                    "sequence",
+                    Offset {
+                        offset: 12,
+                        size: 4,
+                        remove: false,
+                    },
+                ),
+                Field::new(
+                    TOKEN_KEY,
+                    Suffix {
+                        size: 3,
+                        remove: true,
+                    },
+                ),
+            ],
+        };
+        let filter = Capture::from_config(config.into());
+        let mut ctx = ReadContext::new(
+            <_>::default(),
+            "127.0.0.1:80".parse().unwrap(),
+            alloc_buffer(b"HEADsessionIseqNpayloadabc"),
+        );
+
+        filter.read(&mut ctx).await.unwrap();
+
+        assert_eq!(b"HEADsessionIseqNpayload", &*ctx.contents);
+        for (key, value) in [
+            ("session", &b"sessionI"[..]),
+            ("sequence", b"seqN"),
+            (TOKEN_KEY, b"abc"),
+        ] {
+            assert_eq!(
+                Some(&Value::Bytes(value.to_vec().into())),
+                ctx.metadata.get(&metadata::Key::from(key)),
+                "{key}"
+            );
+            assert_eq!(
+                Some(&Value::Bool(true)),
+                ctx.metadata
+                    .get(&metadata::Key::from(format!("{key}/is_present")))
+            );
+        }
+
+        // A field missing from the packet is an error, but the other fields
+        // are still captured.
+        let mut ctx = ReadContext::new(
+            <_>::default(),
+            "127.0.0.1:80".parse().unwrap(),
+            alloc_buffer(b"HEADsessionIabc"),
+        );
+
+        assert!(filter.read(&mut ctx).await.is_err());
+        assert_eq!(
+            Some(&Value::Bool(false)),
+            ctx.metadata
+                .get(&metadata::Key::from("sequence/is_present"))
+        );
+        assert_eq!(
+            Some(&Value::Bool(true)),
+            ctx.metadata
+                .get(&metadata::Key::from(format!("{TOKEN_KEY}/is_present")))
+        );
+        assert_eq!(b"HEADsessionI", &*ctx.contents);
+    }
+
     async fn assert_end_strategy<F>(filter: &F, key: metadata::Key, remove: bool)
     where
         F: Filter + ?Sized,
@@ -251,10 +493,16 @@ mod tests {
 
         filter.read(&mut context).await.unwrap();
 
+        let range = context.metadata.get(&range_key(key));
         if remove {
             assert_eq!(b"hello", &*context.contents);
+            assert_eq!(None, range);
         } else {
             assert_eq!(b"helloabc", &*context.contents);
+            assert_eq!(
+                Some(&Value::List(vec![Value::Number(5), Value::Number(8)])),
+                range
+            );
         }
 
         let token = context.metadata.get(&key).unwrap().as_bytes().unwrap();
@@ -1,3 +1,4 @@
+use super::Captured;
 use crate::{net::endpoint::metadata::Value, pool::PoolBuffer};
 use bytes::Bytes;
 
@@ -18,14 +19,19 @@ pub struct Prefix {
 }
 
 impl super::CaptureStrategy for Prefix {
-    fn capture(&self, contents: &mut PoolBuffer) -> Option<Value> {
+    fn capture(&self, contents: &mut PoolBuffer) -> Option<Captured> {
         is_valid_size(contents, self.size).then(|| {
             if self.remove {
                 Value::Bytes(Bytes::copy_from_slice(
                     contents.split_prefix(self.size as _),
                 ))
+                .into()
             } else {
-                Value::Bytes(Bytes::copy_from_slice(&contents[..self.size as _]))
+                let range = 0..self.size as usize;
+                Captured::at(
+                    Value::Bytes(Bytes::copy_from_slice(&contents[range.clone()])),
+                    range,
+                )
             }
         })
     }
@@ -42,15 +48,19 @@ pub struct Suffix {
 }
 
 impl super::CaptureStrategy for Suffix {
-    fn capture(&self, contents: &mut PoolBuffer) -> Option<Value> {
+    fn capture(&self, contents: &mut PoolBuffer) -> Option<Captured> {
         is_valid_size(contents, self.size).then(|| {
             if self.remove {
                 Value::Bytes(Bytes::copy_from_slice(
                     contents.split_suffix(self.size as _),
                 ))
+                .into()
             } else {
-                let index = contents.len() - self.size as usize;
-                Value::Bytes(Bytes::copy_from_slice(&contents[index..]))
+                let range = contents.len() - self.size as usize..contents.len();
+                Captured::at(
+                    Value::Bytes(Bytes::copy_from_slice(&contents[range.clone()])),
+                    range,
+                )
             }
         })
     }
@@ -16,8 +16,8 @@
 
 use serde::{Deserialize, Serialize};
 
-use super::{proto, Prefix, Regex, Suffix, CAPTURED_BYTES};
-use crate::filters::ConvertProtoConfigError;
+use super::{proto, Offset, Prefix, Quic, Regex, Suffix, CAPTURED_BYTES};
+use crate::{filters::ConvertProtoConfigError, net::endpoint::metadata::Key};
 
 /// Strategy to apply for acquiring a set of bytes in the UDP packet
 #[derive(Serialize, Deserialize, Debug, PartialEq, schemars::JsonSchema)]
@@ -32,6 +32,12 @@ pub enum Strategy {
     /// Look for the set of bytes at the end of the packet
     #[serde(rename = "REGEX")]
     Regex(Regex),
+    /// Look for the set of bytes at an offset from the beginning of the packet
+    #[serde(rename = "OFFSET")]
+    Offset(Offset),
+    /// Look for the destination connection ID of a QUIC packet
+    #[serde(rename = "QUIC")]
+    Quic(Quic),
 }
 
 impl Strategy {
@@ -40,6 +46,8 @@ impl Strategy {
             Self::Prefix(value) => Box::from(value),
             Self::Suffix(value) => Box::from(value),
             Self::Regex(value) => Box::from(value),
+            Self::Offset(value) => Box::from(value),
+            Self::Quic(value) => Box::from(value),
         }
     }
 }
@@ -62,16 +70,65 @@ impl From<Regex> for Strategy {
     }
 }
 
+impl From<Offset> for Strategy {
+    fn from(offset: Offset) -> Self {
+        Self::Offset(offset)
+    }
+}
+
+impl From<Quic> for Strategy {
+    fn from(quic: Quic) -> Self {
+        Self::Quic(quic)
+    }
+}
+
 #[derive(Debug, PartialEq, schemars::JsonSchema)]
 pub struct Config {
     /// The key to use when storing the captured value in the filter context.
     /// If a match was found it is available
     /// under `{{metadata_key}}/is_present`.
-    pub metadata_key: crate::net::endpoint::metadata::Key,
+    pub metadata_key: Key,
+    /// The capture strategy.
+    pub strategy: Strategy,
+    /// Further values to capture from the same packet, in order, after the
+    /// value captured by `strategy`.
+    #[schemars(default)]
+    pub captures: Vec<Field>,
+}
+
+/// An additional value captured by the filter into its own key.
+#[derive(Debug, PartialEq, schemars::JsonSchema)]
+pub struct Field {
+    /// The key to use when storing the captured value in the filter context.
+    /// If a match was found it is available
+    /// under `{{metadata_key}}/is_present`.
+    pub metadata_key: Key,
     /// The capture strategy.
     pub strategy: Strategy,
 }
 
+impl Field {
+    pub fn new(metadata_key: impl Into<Key>, strategy: impl Into<Strategy>) -> Self {
+        Self {
+            metadata_key: metadata_key.into(),
+            strategy: strategy.into(),
+        }
+    }
+}
+
+fn serialize_strategy<S>(s: &mut S, strategy: &Strategy) -> Result<(), S::Error>
+where
+    S: serde::ser::SerializeStruct,
+{
+    match strategy {
+        Strategy::Prefix(value) => s.serialize_field("prefix", value),
+        Strategy::Suffix(value) => s.serialize_field("suffix", value),
+        Strategy::Regex(value) => s.serialize_field("regex", value),
+        Strategy::Offset(value) => s.serialize_field("offset", value),
+        Strategy::Quic(value) => s.serialize_field("quic", value),
+    }
+}
+
 impl Serialize for Config {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -79,107 +136,148 @@ impl Serialize for Config {
     {
         use serde::ser::SerializeStruct;
 
-        let mut s = serializer.serialize_struct("Config", 2)?;
+        let len = if self.captures.is_empty() { 2 } else { 3 };
+        let mut s = serializer.serialize_struct("Config", len)?;
         s.serialize_field("metadataKey", &self.metadata_key)?;
-        match &self.strategy {
-            Strategy::Prefix(value) => s.serialize_field("prefix", value)?,
-            Strategy::Suffix(value) => s.serialize_field("suffix", value)?,
-            Strategy::Regex(value) => s.serialize_field("regex", value)?,
+        serialize_strategy(&mut s, &self.strategy)?;
+        if !self.captures.is_empty() {
+            s.serialize_field("captures", &self.captures)?;
         }
 
         s.end()
     }
 }
 
-impl<'de> serde::Deserialize<'de> for Config {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+impl Serialize for Field {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
-        D: serde::Deserializer<'de>,
+        S: serde::Serializer,
     {
-        #[derive(Deserialize)]
-        #[serde(field_identifier, rename_all = "lowercase")]
-        enum Field {
-            #[serde(rename = "metadataKey")]
-            MetadataKey,
-            Prefix,
-            Suffix,
-            Regex,
-        }
-
-        struct ConfigVisitor;
-
-        impl<'de> serde::de::Visitor<'de> for ConfigVisitor {
-            type Value = Config;
+        use serde::ser::SerializeStruct;
 
-            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-                formatter.write_str("Capture config")
-            }
+        let mut s = serializer.serialize_struct("Field", 2)?;
+        s.serialize_field("metadataKey", &self.metadata_key)?;
+        serialize_strategy(&mut s, &self.strategy)?;
 
-            fn visit_map<V>(self, mut map: V) -> Result<Self::Value, V::Error>
-            where
-                V: serde::de::MapAccess<'de>,
-            {
-                let mut metadata_key = None;
-                let mut strategy = None;
-                let strategy_exists_err = || {
-                    Err(serde::de::Error::custom(
-                        "Multiple strategies found, only one capture strategy is permitted",
-                    ))
-                };
+        s.end()
+    }
+}
 
-                while let Some(key) = map.next_key()? {
-                    match key {
-                        Field::MetadataKey => {
-                            if metadata_key.is_some() {
-                                return Err(serde::de::Error::duplicate_field("metadataKey"));
-                            }
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "lowercase")]
+enum FieldName {
+    #[serde(rename = "metadataKey")]
+    MetadataKey,
+    Prefix,
+    Suffix,
+    Regex,
+    Offset,
+    Quic,
+    Captures,
+}
 
-                            metadata_key = Some(map.next_value()?);
-                        }
+/// Deserializes either a [`Config`] or, when `nested` is set, a [`Field`]
+/// which has no default key and can't have further captures.
+struct ConfigVisitor {
+    nested: bool,
+}
 
-                        Field::Prefix => {
-                            if strategy.is_some() {
-                                return (strategy_exists_err)();
-                            }
+impl<'de> serde::de::Visitor<'de> for ConfigVisitor {
+    type Value = (Option<Key>, Strategy, Vec<Field>);
 
-                            strategy = Some(Strategy::Prefix(map.next_value()?));
-                        }
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("Capture config")
+    }
 
-                        Field::Suffix => {
-                            if strategy.is_some() {
-                                return (strategy_exists_err)();
-                            }
+    fn visit_map<V>(self, mut map: V) -> Result<Self::Value, V::Error>
+    where
+        V: serde::de::MapAccess<'de>,
+    {
+        let mut metadata_key = None;
+        let mut strategy = None;
+        let mut captures = None;
+
+        while let Some(key) = map.next_key()? {
+            let next = match key {
+                FieldName::MetadataKey => {
+                    if metadata_key.is_some() {
+                        return Err(serde::de::Error::duplicate_field("metadataKey"));
+                    }
 
-                            strategy = Some(Strategy::Suffix(map.next_value()?));
-                        }
+                    metadata_key = Some(map.next_value()?);
+                    continue;
+                }
 
-                        Field::Regex => {
-                            if strategy.is_some() {
-                                return (strategy_exists_err)();
-                            }
+                FieldName::Captures => {
+                    if self.nested {
+                        return Err(serde::de::Error::custom(
+                            "`captures` is only permitted at the top level of the config",
+                        ));
+                    }
 
-                            strategy = Some(Strategy::Regex(map.next_value()?));
-                        }
+                    if captures.is_some() {
+                        return Err(serde::de::Error::duplicate_field("captures"));
                     }
-                }
 
-                let metadata_key = metadata_key.unwrap_or_else(|| {
-                    crate::net::endpoint::metadata::Key::from_static(CAPTURED_BYTES)
-                });
-                let strategy = strategy.ok_or_else(|| {
-                    serde::de::Error::custom(
-                        "Capture strategy of `regex`, `suffix`, or `prefix` is required",
-                    )
-                })?;
+                    captures = Some(map.next_value()?);
+                    continue;
+                }
 
-                Ok(Config {
-                    metadata_key,
-                    strategy,
-                })
+                FieldName::Prefix => Strategy::Prefix(map.next_value()?),
+                FieldName::Suffix => Strategy::Suffix(map.next_value()?),
+                FieldName::Regex => Strategy::Regex(map.next_value()?),
+                FieldName::Offset => Strategy::Offset(map.next_value()?),
+                FieldName::Quic => Strategy::Quic(map.next_value()?),
+            };
+
+            if strategy.is_some() {
+                return Err(serde::de::Error::custom(
+                    "Multiple strategies found, only one capture strategy is permitted",
+                ));
             }
+
+            strategy = Some(next);
         }
 
-        deserializer.deserialize_map(ConfigVisitor)
+        let strategy = strategy.ok_or_else(|| {
+            serde::de::Error::custom(
+                "Capture strategy of `regex`, `suffix`, `prefix`, `offset`, or `quic` is required",
+            )
+        })?;
+
+        Ok((metadata_key, strategy, captures.unwrap_or_default()))
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Config {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let (metadata_key, strategy, captures) =
+            deserializer.deserialize_map(ConfigVisitor { nested: false })?;
+
+        Ok(Config {
+            metadata_key: metadata_key.unwrap_or_else(|| Key::from_static(CAPTURED_BYTES)),
+            strategy,
+            captures,
+        })
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Field {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let (metadata_key, strategy, _) =
+            deserializer.deserialize_map(ConfigVisitor { nested: true })?;
+
+        Ok(Field {
+            metadata_key: metadata_key
+                .ok_or_else(|| serde::de::Error::missing_field("metadataKey"))?,
+            strategy,
+        })
     }
 }
 
@@ -188,6 +286,17 @@ impl From<Config> for proto::Capture {
         Self {
             metadata_key: Some(config.metadata_key.to_string()),
             strategy: Some(config.strategy.into()),
+            captures: config.captures.into_iter().map(From::from).collect(),
+        }
+    }
+}
+
+impl From<Field> for proto::Capture {
+    fn from(field: Field) -> Self {
+        Self {
+            metadata_key: Some(field.metadata_key.to_string()),
+            strategy: Some(field.strategy.into()),
+            captures: Vec::new(),
         }
     }
 }
@@ -196,17 +305,46 @@ impl TryFrom<proto::Capture> for Config {
     type Error = ConvertProtoConfigError;
 
     fn try_from(p: proto::Capture) -> Result<Self, Self::Error> {
+        let captures = p
+            .captures
+            .into_iter()
+            .map(Field::try_from)
+            .collect::<Result<_, _>>()?;
+        let Field {
+            metadata_key,
+            strategy,
+        } = Field::try_from(proto::Capture {
+            captures: Vec::new(),
+            ..p
+        })?;
+
+        Ok(Self {
+            metadata_key,
+            strategy,
+            captures,
+        })
+    }
+}
+
+impl TryFrom<proto::Capture> for Field {
+    type Error = ConvertProtoConfigError;
+
+    fn try_from(p: proto::Capture) -> Result<Self, Self::Error> {
+        if !p.captures.is_empty() {
+            return Err(ConvertProtoConfigError::new(
+                "only permitted at the top level of the config",
+                Some("captures".into()),
+            ));
+        }
+
         let strategy = p
             .strategy
             .ok_or_else(|| ConvertProtoConfigError::new("Missing", Some("strategy".into())))?;
 
         Ok(Self {
-            metadata_key: p
-                .metadata_key
-                .map(crate::net::endpoint::metadata::Key::from)
-                .ok_or_else(|| {
-                    ConvertProtoConfigError::new("Missing", Some("metadata_key".into()))
-                })?,
+            metadata_key: p.metadata_key.map(Key::from).ok_or_else(|| {
+                ConvertProtoConfigError::new("Missing", Some("metadata_key".into()))
+            })?,
             strategy: strategy.try_into()?,
         })
     }
@@ -226,6 +364,15 @@ impl From<Strategy> for proto::capture::Strategy {
             Strategy::Regex(regex) => Self::Regex(proto::capture::Regex {
                 regex: Some(regex.pattern.as_str().into()),
             }),
+            Strategy::Offset(offset) => Self::Offset(proto::capture::Offset {
+                offset: offset.offset,
+                size: offset.size,
+                remove: Some(offset.remove),
+            }),
+            Strategy::Quic(quic) => Self::Quic(proto::capture::Quic {
+                connection_id_length: quic.connection_id_length.into(),
+                size: quic.size.map(From::from),
+            }),
         }
     }
 }
@@ -255,6 +402,23 @@ impl TryFrom<proto::capture::Strategy> for Strategy {
                     })?,
                 })
             }
+            capture::Strategy::Offset(offset) => Self::Offset(Offset {
+                offset: offset.offset,
+                size: offset.size,
+                remove: offset.remove.unwrap_or_default(),
+            }),
+            capture::Strategy::Quic(quic) => {
+                let byte = |value: u32, field: &str| {
+                    u8::try_from(value).map_err(|error| {
+                        ConvertProtoConfigError::new(error, Some(format!("Quic.{field}")))
+                    })
+                };
+
+                Self::Quic(Quic {
+                    connection_id_length: byte(quic.connection_id_length, "connection_id_length")?,
+                    size: quic.size.map(|size| byte(size, "size")).transpose()?,
+                })
+            }
         })
     }
 }
@@ -265,23 +429,122 @@ mod tests {
 
     #[test]
     fn convert_proto_config() {
-        let test_cases = vec![(
-            "should succeed when all valid values are provided",
-            proto::Capture {
-                strategy: Some(proto::capture::Strategy::Suffix(proto::capture::Suffix {
-                    size: 42,
-                    remove: Some(true),
-                })),
-                metadata_key: Some("foobar".into()),
-            },
-            Some(Config {
-                metadata_key: "foobar".into(),
-                strategy: Strategy::Suffix(Suffix {
-                    size: 42,
-                    remove: true,
+        let test_cases = vec![
+            (
+                "should succeed when all valid values are provided",
+                proto::Capture {
+                    strategy: Some(proto::capture::Strategy::Suffix(proto::capture::Suffix {
+                        size: 42,
+                        remove: Some(true),
+                    })),
+                    metadata_key: Some("foobar".into()),
+                    captures: vec![],
+                },
+                Some(Config {
+                    metadata_key: "foobar".into(),
+                    strategy: Strategy::Suffix(Suffix {
+                        size: 42,
+                        remove: true,
+                    }),
+                    captures: vec![],
                 }),
-            }),
-        )];
+            ),
+            (
+                "should succeed with an offset and further captures",
+                proto::Capture {
+                    strategy: Some(proto::capture::Strategy::Offset(proto::capture::Offset {
+                        offset: 4,
+                        size: 8,
+                        remove: None,
+                    })),
+                    metadata_key: Some("session".into()),
+                    captures: vec![proto::Capture {
+                        strategy: Some(proto::capture::Strategy::Offset(proto::capture::Offset {
+                            offset: 12,
+                            size: 4,
+                            remove: Some(true),
+                        })),
+                        metadata_key: Some("sequence".into()),
+                        captures: vec![],
+                    }],
+                },
+                Some(Config {
+                    metadata_key: "session".into(),
+                    strategy: Strategy::Offset(Offset {
+                        offset: 4,
+                        size: 8,
+                        remove: false,
+                    }),
+                    captures: vec![Field::new(
+                        "sequence",
+                        Offset {
+                            offset: 12,
+                            size: 4,
+                            remove: true,
+                        },
+                    )],
+                }),
+            ),
+            (
+                "should succeed with a quic strategy",
+                proto::Capture {
+                    strategy: Some(proto::capture::Strategy::Quic(proto::capture::Quic {
+                        connection_id_length: 8,
+                        size: Some(4),
+                    })),
+                    metadata_key: Some("connection".into()),
+                    captures: vec![],
+                },
+                Some(Config {
+                    metadata_key: "connection".into(),
+                    strategy: Strategy::Quic(Quic {
+                        connection_id_length: 8,
+                        size: Some(4),
+                    }),
+                    captures: vec![],
+                }),
+            ),
+            (
+                "should fail when the quic connection ID length is too large",
+                proto::Capture {
+                    strategy: Some(proto::capture::Strategy::Quic(proto::capture::Quic {
+                        connection_id_length: 256,
+                        size: None,
+                    })),
+                    metadata_key: Some("connection".into()),
+                    captures: vec![],
+                },
+                None,
+            ),
+            (
+                "should fail when a further capture has captures",
+                proto::Capture {
+                    strategy: Some(proto::capture::Strategy::Prefix(proto::capture::Prefix {
+                        size: 1,
+                        remove: None,
+                    })),
+                    metadata_key: Some("first".into()),
+                    captures: vec![proto::Capture {
+                        strategy: Some(proto::capture::Strategy::Prefix(proto::capture::Prefix {
+                            size: 1,
+                            remove: None,
+                        })),
+                        metadata_key: Some("second".into()),
+                        captures: vec![proto::Capture {
+                            strategy: Some(proto::capture::Strategy::Prefix(
+                                proto::capture::Prefix {
+                                    size: 1,
+                                    remove: None,
+                                },
+                            )),
+                            metadata_key: Some("third".into()),
+                            captures: vec![],
+                        }],
+                    }],
+                },
+                None,
+            ),
+        ];
 
         for (name, proto_config, expected) in test_cases {
             let result = Config::try_from(proto_config);
@@ -296,4 +559,58 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn captures() {
+        let config: Config = serde_yaml::from_str(
+            "
+metadataKey: session
+offset:
+  offset: 4
+  size: 8
+captures:
+  - metadataKey: sequence
+    offset:
+      offset: 12
+      size: 4
+      remove: true
+",
+        )
+        .unwrap();
+
+        assert_eq!(
+            Config {
+                metadata_key: "session".into(),
+                strategy: Offset {
+                    offset: 4,
+                    size: 8,
+                    remove: false,
+                }
+                .into(),
+                captures: vec![Field::new(
+                    "sequence",
+                    Offset {
+                        offset: 12,
+                        size: 4,
+                        remove: true,
+                    }
+                )],
+            },
+            config
+        );
+        assert_eq!(
+            config,
+            serde_yaml::from_str(&serde_yaml::to_string(&config).unwrap()).unwrap()
+        );
+
+        for invalid in [
+            // Further captures have no default key.
+            "{ prefix: { size: 1 }, captures: [{ suffix: { size: 1 } }] }",
+            "{ prefix: { size: 1 }, captures: [{ metadataKey: a, suffix: { size: 1 }, captures: [] }] }",
+            "{ metadataKey: a, captures: [{ metadataKey: b, suffix: { size: 1 } }] }",
+            "{ prefix: { size: 1 }, offset: { offset: 1, size: 1 } }",
+        ] {
+            assert!(serde_yaml::from_str::<Config>(invalid).is_err(), "{invalid}");
+        }
+    }
 }
@@ -0,0 +1,30 @@
+use super::Captured;
+use crate::{net::endpoint::metadata::Value, pool::PoolBuffer};
+use bytes::Bytes;
+
+/// Capture a fixed number of bytes at an offset from the start of the packet.
+#[derive(Debug, Eq, PartialEq, serde::Deserialize, schemars::JsonSchema, serde::Serialize)]
+pub struct Offset {
+    /// The number of bytes from the start of the packet to the captured bytes.
+    pub offset: u32,
+    /// The number of bytes to capture.
+    pub size: u32,
+    /// Whether captured bytes are removed from the original packet.
+    #[serde(default)]
+    pub remove: bool,
+}
+
+impl super::CaptureStrategy for Offset {
+    fn capture(&self, contents: &mut PoolBuffer) -> Option<Captured> {
+        let start = self.offset as usize;
+        let end = start.checked_add(self.size as usize)?;
+        let value = Value::Bytes(Bytes::copy_from_slice(contents.get(start..end)?));
+
+        if self.remove {
+            contents.remove(start..end);
+            Some(value.into())
+        } else {
+            Some(Captured::at(value, start..end))
+        }
+    }
+}
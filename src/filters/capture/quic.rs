@@ -0,0 +1,60 @@
+use std::ops::Range;
+
+use super::Captured;
+use crate::{net::endpoint::metadata::Value, pool::PoolBuffer};
+use bytes::Bytes;
+
+/// The bit of the first byte of a QUIC packet set for long headers.
+const LONG_HEADER: u8 = 0x80;
+/// The length of the first byte and version of a QUIC long header, which are
+/// followed by the length of the destination connection ID.
+const LONG_HEADER_VERSION_END: usize = 5;
+
+/// Capture the destination connection ID of a QUIC packet, from either its
+/// long or short header. The packet is never modified.
+#[derive(Debug, Eq, PartialEq, serde::Deserialize, schemars::JsonSchema, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Quic {
+    /// The length of the connection IDs issued by the servers, which short
+    /// headers don't include.
+    pub connection_id_length: u8,
+    /// The number of bytes to capture from the start of the connection ID,
+    /// such as a server ID encoded in it. The whole connection ID is
+    /// captured when not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u8>,
+}
+
+impl Quic {
+    /// Returns where the destination connection ID of a QUIC packet is.
+    fn connection_id(&self, packet: &[u8]) -> Option<Range<usize>> {
+        let first = *packet.first()?;
+
+        let (start, length) = if first & LONG_HEADER == LONG_HEADER {
+            let length = usize::from(*packet.get(LONG_HEADER_VERSION_END)?);
+            (LONG_HEADER_VERSION_END + 1, length)
+        } else {
+            (1, usize::from(self.connection_id_length))
+        };
+
+        (start + length <= packet.len()).then_some(start..start + length)
+    }
+}
+
+impl super::CaptureStrategy for Quic {
+    fn capture(&self, contents: &mut PoolBuffer) -> Option<Captured> {
+        let mut range = self.connection_id(contents)?;
+        if let Some(size) = self.size {
+            let end = range.start + usize::from(size);
+            if end > range.end {
+                return None;
+            }
+            range.end = end;
+        }
+
+        Some(Captured::at(
+            Value::Bytes(Bytes::copy_from_slice(&contents[range.clone()])),
+            range,
+        ))
+    }
+}
@@ -1,3 +1,4 @@
+use super::Captured;
 use crate::{net::endpoint::metadata::Value, pool::PoolBuffer};
 
 /// Capture from the start of the packet.
@@ -10,17 +11,16 @@ pub struct Regex {
 }
 
 impl super::CaptureStrategy for Regex {
-    fn capture(&self, contents: &mut PoolBuffer) -> Option<Value> {
-        let matches = self
-            .pattern
-            .find_iter(contents)
-            .map(|mat| Value::Bytes(bytes::Bytes::copy_from_slice(mat.as_bytes())))
-            .collect::<Vec<_>>();
+    fn capture(&self, contents: &mut PoolBuffer) -> Option<Captured> {
+        let mut matches = self.pattern.find_iter(contents).collect::<Vec<_>>();
+        let value =
+            |mat: &regex::bytes::Match| Value::Bytes(bytes::Bytes::copy_from_slice(mat.as_bytes()));
 
         if matches.len() > 1 {
-            Some(Value::List(matches))
+            Some(Value::List(matches.iter().map(value).collect()).into())
         } else {
-            matches.into_iter().next()
+            let mat = matches.pop()?;
+            Some(Captured::at(value(&mat), mat.range()))
         }
     }
 }
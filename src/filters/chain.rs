@@ -36,82 +36,103 @@ const BUCKET_FACTOR: f64 = 2.5;
 /// the bucketing there as we don't care about granularity past this value.
 const BUCKET_COUNT: usize = 11;
 
+/// The name of the Listener filter chain holding the filters for packets from
+/// clients, when they differ from the filters for both directions.
+pub const READ_FILTER_CHAIN: &str = "quilkin.dev/read";
+
+/// The name of the Listener filter chain holding the filters for packets from
+/// endpoints, when they differ from the filters for both directions.
+pub const WRITE_FILTER_CHAIN: &str = "quilkin.dev/write";
+
+/// The key of the Listener filter chain metadata holding the JSON encoded
+/// [`Predicate`][crate::filters::predicate::Predicate] of each filter, keyed
+/// by the filter's index in the chain.
+pub const WHEN_METADATA: &str = "quilkin.dev/when";
+
+type FilterList = Vec<(String, FilterInstance)>;
+
 /// A chain of [`Filter`]s to be executed in order.
 ///
 /// Executes each filter, passing the [`ReadContext`] and [`WriteContext`]
 /// between each filter's execution, returning the result of data that has gone
 /// through all of the filters in the chain. If any of the filters in the chain
 /// return `None`, then the chain is broken, and `None` is returned.
+///
+/// By default the same filters are used in both directions, in reverse order
+/// for writes. Separate read and write filters can be set instead, with the
+/// write filters executed in the order given.
 #[derive(Clone, Default)]
 pub struct FilterChain {
-    filters: Vec<(String, FilterInstance)>,
+    filters: FilterList,
+    read_filters: Option<FilterList>,
+    write_filters: Option<FilterList>,
     filter_read_duration_seconds: Vec<Histogram>,
     filter_write_duration_seconds: Vec<Histogram>,
 }
 
 impl FilterChain {
     pub fn new(filters: Vec<(String, FilterInstance)>) -> Result<Self, CreationError> {
-        let subsystem = "filter";
+        Self::with_directions(filters, None, None)
+    }
 
+    /// Creates a chain that executes `read_filters` and `write_filters`
+    /// instead of `filters` in their direction, when present.
+    pub fn with_directions(
+        filters: Vec<(String, FilterInstance)>,
+        read_filters: Option<Vec<(String, FilterInstance)>>,
+        write_filters: Option<Vec<(String, FilterInstance)>>,
+    ) -> Result<Self, CreationError> {
         Ok(Self {
-            filter_read_duration_seconds: filters
-                .iter()
-                .map(|(name, _)| {
-                    Histogram::with_opts(
-                        histogram_opts(
-                            "read_duration_seconds",
-                            subsystem,
-                            "Seconds taken to execute a given filter's `read`.",
-                            Some(
-                                exponential_buckets(BUCKET_START, BUCKET_FACTOR, BUCKET_COUNT)
-                                    .unwrap(),
-                            ),
-                        )
-                        .const_label(FILTER_LABEL, name),
-                    )
-                    .and_then(|histogram| histogram.register_if_not_exists())
-                })
-                .collect::<Result<_, prometheus::Error>>()?,
-            filter_write_duration_seconds: filters
-                .iter()
-                .map(|(name, _)| {
-                    Histogram::with_opts(
-                        histogram_opts(
-                            "write_duration_seconds",
-                            subsystem,
-                            "Seconds taken to execute a given filter's `write`.",
-                            Some(exponential_buckets(0.000125, 2.5, 11).unwrap()),
-                        )
-                        .const_label(FILTER_LABEL, name),
-                    )
-                    .and_then(|histogram| histogram.register_if_not_exists())
-                })
-                .collect::<Result<_, prometheus::Error>>()?,
+            filter_read_duration_seconds: histograms(
+                read_filters.as_ref().unwrap_or(&filters),
+                "read_duration_seconds",
+                "Seconds taken to execute a given filter's `read`.",
+            )?,
+            filter_write_duration_seconds: histograms(
+                write_filters.as_ref().unwrap_or(&filters),
+                "write_duration_seconds",
+                "Seconds taken to execute a given filter's `write`.",
+            )?,
             filters,
+            read_filters,
+            write_filters,
         })
     }
 
+    /// Returns the number of filters in the chain, across both directions.
     #[inline]
     pub fn len(&self) -> usize {
         self.filters.len()
+            + self.read_filters.as_ref().map_or(0, Vec::len)
+            + self.write_filters.as_ref().map_or(0, Vec::len)
     }
 
     #[inline]
     pub fn is_empty(&self) -> bool {
-        self.filters.is_empty()
+        self.len() == 0
+    }
+
+    /// Returns whether the chain has separate read or write filters.
+    #[inline]
+    pub fn is_directional(&self) -> bool {
+        self.read_filters.is_some() || self.write_filters.is_some()
     }
 
+    /// Returns the configuration of the filters used in both directions.
     pub fn iter(&self) -> impl Iterator<Item = crate::config::Filter> + '_ {
-        self.filters
-            .iter()
-            .map(|(name, instance)| crate::config::Filter {
-                name: name.clone(),
-                label: instance.label().map(String::from),
-                config: match instance.config() {
-                    serde_json::Value::Null => None,
-                    value => Some(value.clone()),
-                },
-            })
+        configs(&self.filters)
+    }
+
+    /// Returns the configuration of the filters used for reads instead of
+    /// [`Self::iter`], if any.
+    pub fn read_filters(&self) -> Option<impl Iterator<Item = crate::config::Filter> + '_> {
+        self.read_filters.as_deref().map(configs)
+    }
+
+    /// Returns the configuration of the filters used for writes instead of
+    /// [`Self::iter`], if any.
+    pub fn write_filters(&self) -> Option<impl Iterator<Item = crate::config::Filter> + '_> {
+        self.write_filters.as_deref().map(configs)
     }
 
     /// Validates the filter configurations in the provided config and constructs
@@ -123,19 +144,7 @@ impl FilterChain {
     where
         Item: TryInto<FilterConfig, Error = CreationError>,
     {
-        let mut filters = Vec::new();
-
-        for filter_config in filter_configs {
-            let filter_config = filter_config.try_into()?;
-            let filter = FilterRegistry::get(
-                &filter_config.name,
-                CreateFilterArgs::fixed(filter_config.config),
-            )?;
-
-            filters.push((filter_config.name, filter));
-        }
-
-        Self::new(filters)
+        Self::new(create_fallible(filter_configs)?)
     }
 
     /// Validates the filter configurations in the provided config and constructs
@@ -143,19 +152,101 @@ impl FilterChain {
     pub fn try_create(
         filter_configs: impl IntoIterator<Item = FilterConfig>,
     ) -> Result<Self, CreationError> {
-        let mut filters = Vec::new();
+        Self::new(create(filter_configs)?)
+    }
+
+    /// Validates the filter configurations for each direction and constructs
+    /// a FilterChain if all configurations are valid.
+    pub fn try_create_directional(
+        config: crate::config::FilterChainConfig,
+    ) -> Result<Self, CreationError> {
+        Self::with_directions(
+            create(config.filters)?,
+            config.read_filters.map(create).transpose()?,
+            config.write_filters.map(create).transpose()?,
+        )
+    }
+}
 
-        for filter_config in filter_configs {
+fn histograms(
+    filters: &[(String, FilterInstance)],
+    name: &str,
+    help: &str,
+) -> Result<Vec<Histogram>, CreationError> {
+    Ok(filters
+        .iter()
+        .map(|(filter, _)| {
+            Histogram::with_opts(
+                histogram_opts(
+                    name,
+                    "filter",
+                    help,
+                    Some(exponential_buckets(BUCKET_START, BUCKET_FACTOR, BUCKET_COUNT).unwrap()),
+                )
+                .const_label(FILTER_LABEL, filter),
+            )
+            .and_then(|histogram| histogram.register_if_not_exists())
+        })
+        .collect::<Result<_, prometheus::Error>>()?)
+}
+
+fn configs(
+    filters: &[(String, FilterInstance)],
+) -> impl Iterator<Item = crate::config::Filter> + '_ {
+    filters
+        .iter()
+        .map(|(name, instance)| crate::config::Filter {
+            name: name.clone(),
+            label: instance.label().map(String::from),
+            config: match instance.config() {
+                serde_json::Value::Null => None,
+                value => Some(value.clone()),
+            },
+            when: instance.when().cloned(),
+        })
+}
+
+fn create(
+    filter_configs: impl IntoIterator<Item = FilterConfig>,
+) -> Result<FilterList, CreationError> {
+    filter_configs
+        .into_iter()
+        .map(|filter_config| {
             let filter = FilterRegistry::get(
                 &filter_config.name,
                 CreateFilterArgs::fixed(filter_config.config),
-            )?;
+            )?
+            .with_when(filter_config.when);
 
-            filters.push((filter_config.name, filter));
-        }
+            Ok((filter_config.name, filter))
+        })
+        .collect()
+}
 
-        Self::new(filters)
-    }
+fn create_fallible<Item>(
+    filter_configs: impl IntoIterator<Item = Item>,
+) -> Result<FilterList, CreationError>
+where
+    Item: TryInto<FilterConfig, Error = CreationError>,
+{
+    filter_configs
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect::<Result<Vec<_>, _>>()
+        .and_then(create)
+}
+
+fn eq(lhs: &[(String, FilterInstance)], rhs: &[(String, FilterInstance)]) -> bool {
+    lhs.len() == rhs.len()
+        && lhs
+            .iter()
+            .zip(rhs)
+            .all(|((lhs_name, lhs_instance), (rhs_name, rhs_instance))| {
+                lhs_name == rhs_name
+                    && lhs_instance.config() == rhs_instance.config()
+                    && lhs_instance.label() == rhs_instance.label()
+                    && lhs_instance.when() == rhs_instance.when()
+            })
 }
 
 impl std::fmt::Debug for FilterChain {
@@ -166,67 +257,208 @@ impl std::fmt::Debug for FilterChain {
             filters.field(id, instance.config());
         }
 
+        if let Some(read_filters) = &self.read_filters {
+            filters.field("read_filters", &configs(read_filters).collect::<Vec<_>>());
+        }
+
+        if let Some(write_filters) = &self.write_filters {
+            filters.field("write_filters", &configs(write_filters).collect::<Vec<_>>());
+        }
+
         filters.finish()
     }
 }
 
 impl PartialEq for FilterChain {
     fn eq(&self, rhs: &Self) -> bool {
-        if self.filters.len() != rhs.filters.len() {
-            return false;
-        }
-
-        self.filters.iter().zip(&rhs.filters).all(
-            |((lhs_name, lhs_instance), (rhs_name, rhs_instance))| {
-                lhs_name == rhs_name
-                    && lhs_instance.config() == rhs_instance.config()
-                    && lhs_instance.label() == rhs_instance.label()
-            },
-        )
+        eq(&self.filters, &rhs.filters)
+            && match (&self.read_filters, &rhs.read_filters) {
+                (Some(lhs), Some(rhs)) => eq(lhs, rhs),
+                (lhs, rhs) => lhs.is_none() && rhs.is_none(),
+            }
+            && match (&self.write_filters, &rhs.write_filters) {
+                (Some(lhs), Some(rhs)) => eq(lhs, rhs),
+                (lhs, rhs) => lhs.is_none() && rhs.is_none(),
+            }
     }
 }
 
 use crate::generated::envoy::config::listener::v3::FilterChain as EnvoyFilterChain;
 
-impl TryFrom<FilterChain> for EnvoyFilterChain {
-    type Error = CreationError;
-
-    fn try_from(chain: FilterChain) -> Result<Self, Self::Error> {
-        Self::try_from(&chain)
+fn envoy_filter_chain(
+    name: &str,
+    filters: &[(String, FilterInstance)],
+) -> Result<EnvoyFilterChain, CreationError> {
+    let mut predicates = prost_types::Struct::default();
+
+    for (index, filter) in configs(filters).enumerate() {
+        if let Some(when) = &filter.when {
+            predicates.fields.insert(
+                index.to_string(),
+                prost_types::Value {
+                    kind: Some(prost_types::value::Kind::StringValue(
+                        serde_json::to_string(when)?,
+                    )),
+                },
+            );
+        }
     }
+
+    let metadata = (!predicates.fields.is_empty()).then(|| {
+        crate::generated::envoy::config::core::v3::Metadata {
+            filter_metadata: [(WHEN_METADATA.into(), predicates)].into(),
+            ..<_>::default()
+        }
+    });
+
+    Ok(EnvoyFilterChain {
+        name: name.into(),
+        filters: configs(filters)
+            .map(TryFrom::try_from)
+            .collect::<Result<_, CreationError>>()?,
+        metadata,
+        ..<_>::default()
+    })
 }
 
-impl TryFrom<&'_ FilterChain> for EnvoyFilterChain {
+/// Converts the filters of an Envoy filter chain, restoring their predicates
+/// from the chain's metadata.
+fn from_envoy_filter_chain(chain: EnvoyFilterChain) -> Result<FilterList, CreationError> {
+    let mut predicates = chain
+        .metadata
+        .and_then(|mut metadata| metadata.filter_metadata.remove(WHEN_METADATA))
+        .unwrap_or_default();
+
+    let filters = chain
+        .filters
+        .into_iter()
+        .enumerate()
+        .map(|(index, filter)| {
+            let mut filter = FilterConfig::try_from(filter)?;
+            filter.when = match predicates.fields.remove(&index.to_string()) {
+                Some(prost_types::Value {
+                    kind: Some(prost_types::value::Kind::StringValue(when)),
+                }) => Some(serde_json::from_str(&when)?),
+                Some(_) => {
+                    return Err(CreationError::FieldInvalid {
+                        field: WHEN_METADATA.into(),
+                        reason: format!("the predicate of filter {index} isn't a string"),
+                    })
+                }
+                None => None,
+            };
+            Ok(filter)
+        })
+        .collect::<Result<Vec<_>, CreationError>>()?;
+
+    create(filters)
+}
+
+/// The filters for both directions are the first filter chain in the
+/// listener, so clients unaware of separate read and write filters still
+/// find them, and use them in both directions as they always have. Separate
+/// read and write filters are added as named chains.
+impl TryFrom<&'_ FilterChain> for crate::net::xds::listener::Listener {
     type Error = CreationError;
 
     fn try_from(chain: &FilterChain) -> Result<Self, Self::Error> {
+        let mut filter_chains = vec![envoy_filter_chain("", &chain.filters)?];
+
+        if let Some(read_filters) = &chain.read_filters {
+            filter_chains.push(envoy_filter_chain(READ_FILTER_CHAIN, read_filters)?);
+        }
+
+        if let Some(write_filters) = &chain.write_filters {
+            filter_chains.push(envoy_filter_chain(WRITE_FILTER_CHAIN, write_filters)?);
+        }
+
         Ok(Self {
-            filters: chain
-                .iter()
-                .map(TryFrom::try_from)
-                .collect::<Result<_, Self::Error>>()?,
+            filter_chains,
             ..<_>::default()
         })
     }
 }
 
+impl TryFrom<crate::net::xds::listener::Listener> for FilterChain {
+    type Error = CreationError;
+
+    fn try_from(listener: crate::net::xds::listener::Listener) -> Result<Self, Self::Error> {
+        let mut filters = None;
+        let mut read_filters = None;
+        let mut write_filters = None;
+
+        for chain in listener.filter_chains {
+            match &*chain.name {
+                READ_FILTER_CHAIN => read_filters = Some(from_envoy_filter_chain(chain)?),
+                WRITE_FILTER_CHAIN => write_filters = Some(from_envoy_filter_chain(chain)?),
+                _ if filters.is_none() => filters = Some(from_envoy_filter_chain(chain)?),
+                _ => {}
+            }
+        }
+
+        Self::with_directions(filters.unwrap_or_default(), read_filters, write_filters)
+    }
+}
+
+fn proto_filters(
+    filters: &[(String, FilterInstance)],
+) -> Result<Vec<crate::net::cluster::proto::Filter>, CreationError> {
+    configs(filters)
+        .map(|filter| {
+            Ok(crate::net::cluster::proto::Filter {
+                name: filter.name,
+                label: filter.label,
+                config: filter.config.map(|v| v.to_string()),
+                when: filter
+                    .when
+                    .as_ref()
+                    .map(serde_json::to_string)
+                    .transpose()?,
+            })
+        })
+        .collect()
+}
+
 impl TryFrom<&'_ FilterChain> for crate::net::cluster::proto::FilterChain {
     type Error = CreationError;
 
     fn try_from(value: &'_ FilterChain) -> Result<Self, Self::Error> {
-        Ok(Self {
-            filters: value
-                .iter()
-                .map(|filter| crate::net::cluster::proto::Filter {
-                    name: filter.name,
-                    label: filter.label,
-                    config: filter.config.map(|v| v.to_string()),
+        let list = |filters: &Option<FilterList>| {
+            filters
+                .as_deref()
+                .map(|filters| {
+                    proto_filters(filters)
+                        .map(|filters| crate::net::cluster::proto::FilterList { filters })
                 })
-                .collect(),
+                .transpose()
+        };
+
+        Ok(Self {
+            filters: proto_filters(&value.filters)?,
+            read_filters: list(&value.read_filters)?,
+            write_filters: list(&value.write_filters)?,
         })
     }
 }
 
+impl TryFrom<crate::net::cluster::proto::FilterChain> for FilterChain {
+    type Error = CreationError;
+
+    fn try_from(value: crate::net::cluster::proto::FilterChain) -> Result<Self, Self::Error> {
+        Self::with_directions(
+            create_fallible(value.filters)?,
+            value
+                .read_filters
+                .map(|list| create_fallible(list.filters))
+                .transpose()?,
+            value
+                .write_filters
+                .map(|list| create_fallible(list.filters))
+                .transpose()?,
+        )
+    }
+}
+
 impl std::ops::Index<usize> for FilterChain {
     type Output = (String, FilterInstance);
 
@@ -235,27 +467,56 @@ impl std::ops::Index<usize> for FilterChain {
     }
 }
 
+/// Deserializes from either a list of filters, or a
+/// [`FilterChainConfig`][crate::config::FilterChainConfig].
 impl<'de> serde::Deserialize<'de> for FilterChain {
     fn deserialize<D: serde::Deserializer<'de>>(de: D) -> Result<Self, D::Error> {
-        let filters = <Vec<FilterConfig>>::deserialize(de)?;
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = FilterChain;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a list of filters, or a map of filters for each direction")
+            }
+
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(
+                self,
+                seq: A,
+            ) -> Result<Self::Value, A::Error> {
+                let filters = <Vec<FilterConfig> as serde::Deserialize>::deserialize(
+                    serde::de::value::SeqAccessDeserializer::new(seq),
+                )?;
 
-        Self::try_create(filters).map_err(serde::de::Error::custom)
+                FilterChain::try_create(filters).map_err(serde::de::Error::custom)
+            }
+
+            fn visit_map<A: serde::de::MapAccess<'de>>(
+                self,
+                map: A,
+            ) -> Result<Self::Value, A::Error> {
+                let config = <crate::config::FilterChainConfig as serde::Deserialize>::deserialize(
+                    serde::de::value::MapAccessDeserializer::new(map),
+                )?;
+
+                FilterChain::try_create_directional(config).map_err(serde::de::Error::custom)
+            }
+        }
+
+        de.deserialize_any(Visitor)
     }
 }
 
+/// Serializes as a list of filters, unless the chain has separate read or
+/// write filters, in which case it's serialized as a
+/// [`FilterChainConfig`][crate::config::FilterChainConfig].
 impl serde::Serialize for FilterChain {
     fn serialize<S: serde::Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
-        let filters = self
-            .filters
-            .iter()
-            .map(|(name, instance)| crate::config::Filter {
-                name: name.clone(),
-                label: instance.label().map(String::from),
-                config: Some(serde_json::Value::clone(instance.config())),
-            })
-            .collect::<Vec<_>>();
-
-        filters.serialize(ser)
+        if self.is_directional() {
+            crate::config::FilterChainConfig::from(self).serialize(ser)
+        } else {
+            self.iter().collect::<Vec<_>>().serialize(ser)
+        }
     }
 }
 
@@ -272,19 +533,60 @@ impl schemars::JsonSchema for FilterChain {
     }
 }
 
+async fn write_with<'chain>(
+    filters: impl Iterator<Item = (&'chain (String, FilterInstance), &'chain Histogram)>,
+    ctx: &mut WriteContext,
+) -> Result<(), FilterError> {
+    for ((id, instance), histogram) in filters {
+        if instance.when().map_or(false, |when| !when.write(ctx)) {
+            tracing::trace!(%id, "write skipping filter");
+            continue;
+        }
+
+        tracing::trace!(%id, "write filtering packet");
+        let timer = histogram.start_timer();
+        let result = instance.filter().write(ctx).await;
+        timer.stop_and_record();
+        match result {
+            Ok(()) if ctx.is_handled() => {
+                tracing::trace!(%id, "write replied to packet");
+                return Ok(());
+            }
+            Ok(()) => tracing::trace!(%id, "write passing packet"),
+            Err(error) => {
+                tracing::trace!(%id, "write dropping packet");
+                return Err(error);
+            }
+        }
+    }
+
+    Ok(())
+}
+
 #[async_trait::async_trait]
 impl Filter for FilterChain {
     async fn read(&self, ctx: &mut ReadContext) -> Result<(), FilterError> {
         for ((id, instance), histogram) in self
-            .filters
+            .read_filters
+            .as_ref()
+            .unwrap_or(&self.filters)
             .iter()
             .zip(self.filter_read_duration_seconds.iter())
         {
+            if instance.when().map_or(false, |when| !when.read(ctx)) {
+                tracing::trace!(%id, "read skipping filter");
+                continue;
+            }
+
             tracing::trace!(%id, "read filtering packet");
             let timer = histogram.start_timer();
             let result = instance.filter().read(ctx).await;
             timer.stop_and_record();
             match result {
+                Ok(()) if ctx.is_handled() => {
+                    tracing::trace!(%id, "read replied to packet");
+                    return Ok(());
+                }
                 Ok(()) => tracing::trace!(%id, "read passing packet"),
                 Err(error) => {
                     tracing::trace!(%id, "read dropping packet");
@@ -309,26 +611,27 @@ impl Filter for FilterChain {
     }
 
     async fn write(&self, ctx: &mut WriteContext) -> Result<(), FilterError> {
-        for ((id, instance), histogram) in self
-            .filters
-            .iter()
-            .rev()
-            .zip(self.filter_write_duration_seconds.iter().rev())
-        {
-            tracing::trace!(%id, "write filtering packet");
-            let timer = histogram.start_timer();
-            let result = instance.filter().write(ctx).await;
-            timer.stop_and_record();
-            match result {
-                Ok(()) => tracing::trace!(%id, "write passing packet"),
-                Err(error) => {
-                    tracing::trace!(%id, "write dropping packet");
-                    return Err(error);
-                }
+        match &self.write_filters {
+            Some(filters) => {
+                write_with(
+                    filters
+                        .iter()
+                        .zip(self.filter_write_duration_seconds.iter()),
+                    ctx,
+                )
+                .await
+            }
+            None => {
+                write_with(
+                    self.filters
+                        .iter()
+                        .rev()
+                        .zip(self.filter_write_duration_seconds.iter().rev()),
+                    ctx,
+                )
+                .await
             }
         }
-
-        Ok(())
     }
 }
 
@@ -336,7 +639,10 @@ impl Filter for FilterChain {
 mod tests {
     use crate::{
         config,
-        filters::Debug,
+        filters::{
+            predicate::{Predicate, Range},
+            Debug,
+        },
         net::endpoint::Endpoint,
         test::{alloc_buffer, TestConfig, TestFilter},
     };
@@ -352,6 +658,7 @@ mod tests {
             name: provider.name().into(),
             label: None,
             config: Some(serde_json::Map::default().into()),
+            when: None,
         }];
 
         let chain = FilterChain::try_create(filter_configs).unwrap();
@@ -362,6 +669,7 @@ mod tests {
             name: "this is so wrong".into(),
             label: None,
             config: Default::default(),
+            when: None,
         }];
         let result = FilterChain::try_create(filter_configs);
         assert!(result.is_err());
@@ -503,6 +811,7 @@ mod tests {
                     name: "TestFilter".into(),
                     label: None,
                     config: None,
+                    when: None,
                 },
                 crate::config::Filter {
                     name: "TestFilter2".into(),
@@ -510,10 +819,288 @@ mod tests {
                     config: Some(serde_json::json!({
                         "k1": "v1",
                         "k2": 2
-                    }))
+                    })),
+                    when: None,
                 },
             ],
             configs
         )
     }
+
+    #[tokio::test]
+    async fn chain_directional() {
+        let test_filter = || {
+            (
+                TestFilter::NAME.into(),
+                FilterInstance::new(serde_json::json!(null), Box::new(TestFilter)),
+            )
+        };
+        let chain = FilterChain::with_directions(
+            vec![test_filter()],
+            Some(vec![test_filter(), test_filter()]),
+            Some(Vec::new()),
+        )
+        .unwrap();
+        assert!(chain.is_directional());
+        assert_eq!(3, chain.len());
+
+        let endpoints_fixture = endpoints();
+        let mut context = ReadContext::new(
+            endpoints_fixture.clone(),
+            "127.0.0.1:70".parse().unwrap(),
+            alloc_buffer(b"hello"),
+        );
+
+        chain.read(&mut context).await.unwrap();
+        assert_eq!(
+            b"hello:odr:127.0.0.1:70:odr:127.0.0.1:70",
+            &*context.contents
+        );
+
+        let mut context = WriteContext::new(
+            endpoints_fixture
+                .endpoints()
+                .first()
+                .unwrap()
+                .address
+                .clone(),
+            "127.0.0.1:70".parse().unwrap(),
+            alloc_buffer(b"hello"),
+        );
+
+        chain.write(&mut context).await.unwrap();
+        assert_eq!(b"hello", &*context.contents);
+    }
+
+    #[test]
+    fn directional_config() {
+        let yaml = "
+version: v1alpha1
+filters:
+  - name: quilkin.filters.debug.v1alpha1.Debug
+read_filters:
+  - name: quilkin.filters.debug.v1alpha1.Debug
+    config:
+      id: read
+write_filters:
+  - name: quilkin.filters.debug.v1alpha1.Debug
+    config:
+      id: write
+  - name: quilkin.filters.pass.v1alpha1.Pass
+";
+        let config = crate::Config::from_reader(yaml.as_bytes()).unwrap();
+        let chain = config.filters.load();
+        assert_eq!(1, chain.iter().count());
+        assert_eq!(1, chain.read_filters().unwrap().count());
+        assert_eq!(2, chain.write_filters().unwrap().count());
+
+        let listener = crate::net::xds::listener::Listener::try_from(&*chain).unwrap();
+        assert_eq!(
+            vec!["", READ_FILTER_CHAIN, WRITE_FILTER_CHAIN],
+            listener
+                .filter_chains
+                .iter()
+                .map(|chain| &*chain.name)
+                .collect::<Vec<_>>()
+        );
+
+        // Proxies without directional support only read the first chain,
+        // which holds the filters for both directions unchanged.
+        let legacy = FilterChain::try_from(crate::net::xds::listener::Listener {
+            filter_chains: listener.filter_chains[..1].to_vec(),
+            ..<_>::default()
+        })
+        .unwrap();
+        assert!(!legacy.is_directional());
+        assert_eq!(
+            chain.iter().collect::<Vec<_>>(),
+            legacy.iter().collect::<Vec<_>>()
+        );
+
+        assert_eq!(*chain, FilterChain::try_from(listener).unwrap());
+
+        let proto = crate::net::cluster::proto::FilterChain::try_from(&*chain).unwrap();
+        assert_eq!(*chain, FilterChain::try_from(proto).unwrap());
+
+        let yaml = serde_yaml::to_string(&*chain).unwrap();
+        assert_eq!(*chain, serde_yaml::from_str::<FilterChain>(&yaml).unwrap());
+    }
+
+    #[tokio::test]
+    async fn when() {
+        let chain = FilterChain::new(vec![
+            (
+                TestFilter::NAME.into(),
+                FilterInstance::new(serde_json::json!(null), Box::new(TestFilter)).with_when(Some(
+                    Predicate::Length(Range {
+                        min: None,
+                        max: Some(5),
+                    }),
+                )),
+            ),
+            (
+                TestFilter::NAME.into(),
+                FilterInstance::new(serde_json::json!(null), Box::new(TestFilter))
+                    .with_when(Some(Predicate::Source(vec!["10.0.0.0/8".parse().unwrap()]))),
+            ),
+        ])
+        .unwrap();
+
+        let mut context = ReadContext::new(
+            endpoints(),
+            "127.0.0.1:70".parse().unwrap(),
+            alloc_buffer(b"hello"),
+        );
+        chain.read(&mut context).await.unwrap();
+        assert_eq!(b"hello:odr:127.0.0.1:70", &*context.contents);
+
+        let mut context = ReadContext::new(
+            endpoints(),
+            "10.0.0.1:70".parse().unwrap(),
+            alloc_buffer(b"hello, world"),
+        );
+        chain.read(&mut context).await.unwrap();
+        assert_eq!(b"hello, world:odr:10.0.0.1:70", &*context.contents);
+    }
+
+    #[tokio::test]
+    async fn reply() {
+        struct Reply;
+
+        #[async_trait::async_trait]
+        impl Filter for Reply {
+            async fn read(&self, ctx: &mut ReadContext) -> Result<(), FilterError> {
+                ctx.reply(&b"pong"[..]);
+                Ok(())
+            }
+
+            async fn write(&self, ctx: &mut WriteContext) -> Result<(), FilterError> {
+                ctx.reply(&b"pong"[..]);
+                Ok(())
+            }
+        }
+
+        let chain = FilterChain::new(vec![
+            (
+                TestFilter::NAME.into(),
+                FilterInstance::new(serde_json::json!(null), Box::new(TestFilter)),
+            ),
+            (
+                "reply".into(),
+                FilterInstance::new(serde_json::json!(null), Box::new(Reply)),
+            ),
+            (
+                TestFilter::NAME.into(),
+                FilterInstance::new(serde_json::json!(null), Box::new(TestFilter)),
+            ),
+        ])
+        .unwrap();
+
+        let mut context = ReadContext::new(
+            endpoints(),
+            "127.0.0.1:70".parse().unwrap(),
+            alloc_buffer(b"ping"),
+        );
+        chain.read(&mut context).await.unwrap();
+
+        assert!(context.is_handled());
+        assert_eq!(vec![bytes::Bytes::from_static(b"pong")], context.replies);
+        // Only the filter before the reply runs, and the packet has no
+        // destinations to be forwarded to.
+        assert_eq!(b"ping:odr:127.0.0.1:70", &*context.contents);
+        assert!(context.destinations.is_empty());
+
+        let mut context = WriteContext::new(
+            "127.0.0.1:80".parse().unwrap(),
+            "127.0.0.1:70".parse().unwrap(),
+            alloc_buffer(b"ping"),
+        );
+        chain.write(&mut context).await.unwrap();
+
+        assert!(context.is_handled());
+        assert_eq!(vec![bytes::Bytes::from_static(b"pong")], context.replies);
+        assert_eq!(b"ping:our:127.0.0.1:80:127.0.0.1:70", &*context.contents);
+    }
+
+    #[tokio::test]
+    async fn emit() {
+        struct Emit;
+
+        #[async_trait::async_trait]
+        impl Filter for Emit {
+            async fn read(&self, ctx: &mut ReadContext) -> Result<(), FilterError> {
+                ctx.emit(&b"ack"[..]);
+                Ok(())
+            }
+        }
+
+        let chain = FilterChain::new(vec![
+            (
+                "emit".into(),
+                FilterInstance::new(serde_json::json!(null), Box::new(Emit)),
+            ),
+            (
+                TestFilter::NAME.into(),
+                FilterInstance::new(serde_json::json!(null), Box::new(TestFilter)),
+            ),
+        ])
+        .unwrap();
+
+        let mut context = ReadContext::new(
+            endpoints(),
+            "127.0.0.1:70".parse().unwrap(),
+            alloc_buffer(b"ping"),
+        );
+        chain.read(&mut context).await.unwrap();
+
+        // The packet is still forwarded after the emitted reply.
+        assert!(!context.is_handled());
+        assert_eq!(vec![bytes::Bytes::from_static(b"ack")], context.replies);
+        assert_eq!(b"ping:odr:127.0.0.1:70", &*context.contents);
+        assert!(!context.destinations.is_empty());
+    }
+
+    #[test]
+    fn when_config() {
+        let yaml = "
+version: v1alpha1
+filters:
+  - name: quilkin.filters.debug.v1alpha1.Debug
+    when:
+      not:
+        metadata:
+          key: quilkin.dev/capture
+          regex: ^abc
+  - name: quilkin.filters.pass.v1alpha1.Pass
+";
+        let config = crate::Config::from_reader(yaml.as_bytes()).unwrap();
+        let chain = config.filters.load();
+        assert!(chain[0].1.when().is_some());
+        assert!(chain[1].1.when().is_none());
+
+        let listener = crate::net::xds::listener::Listener::try_from(&*chain).unwrap();
+        assert!(listener.filter_chains[0].metadata.is_some());
+        assert_eq!(*chain, FilterChain::try_from(listener).unwrap());
+
+        let proto = crate::net::cluster::proto::FilterChain::try_from(&*chain).unwrap();
+        assert_eq!(*chain, FilterChain::try_from(proto).unwrap());
+
+        let yaml = serde_yaml::to_string(&*chain).unwrap();
+        assert_eq!(*chain, serde_yaml::from_str::<FilterChain>(&yaml).unwrap());
+    }
+
+    #[test]
+    fn undirected_listener() {
+        let chain = FilterChain::try_create([config::Filter {
+            name: Debug::NAME.into(),
+            label: None,
+            config: None,
+            when: None,
+        }])
+        .unwrap();
+
+        let listener = crate::net::xds::listener::Listener::try_from(&chain).unwrap();
+        assert_eq!(1, listener.filter_chains.len());
+        assert_eq!(chain, FilterChain::try_from(listener).unwrap());
+    }
 }
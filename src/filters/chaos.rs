@@ -0,0 +1,843 @@
+/*
+ * Copyright 2024 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    filters::{firewall::Cidr, prelude::*},
+    net::endpoint::{
+        metadata::{self, DynamicMetadata, SessionMetadata},
+        EndpointAddress,
+    },
+};
+
+use crate::generated::quilkin::filters::chaos::v1alpha1 as proto;
+
+/// Filter that impairs packets like a bad network would, with delay, jitter,
+/// loss, duplication and reordering, for testing how clients and servers
+/// cope with them.
+///
+/// Delayed packets are held without holding up the packets after them, see
+/// [`ReadContext::delay`].
+pub struct Chaos {
+    config: Config,
+    on_read: Option<Impaired>,
+    on_write: Option<Impaired>,
+}
+
+impl Chaos {
+    fn new(config: Config) -> Result<Self, CreationError> {
+        if let Some(impairment) = &config.on_read {
+            impairment.validate("on_read")?;
+        }
+        if let Some(impairment) = &config.on_write {
+            impairment.validate("on_write")?;
+        }
+
+        Ok(Self {
+            on_read: config.on_read.clone().map(Impaired::new),
+            on_write: config.on_write.clone().map(Impaired::new),
+            config,
+        })
+    }
+
+    /// Whether packets from or to `address` are impaired, which is every
+    /// packet unless the filter is scoped by `sources` or `metadataKey`.
+    async fn is_in_scope(
+        &self,
+        address: &EndpointAddress,
+        metadata: &DynamicMetadata,
+        session: &SessionMetadata,
+    ) -> Result<bool, FilterError> {
+        if !self.config.sources.is_empty() {
+            let ip = address.to_socket_addr().await?.ip();
+            if !self.config.sources.iter().any(|cidr| cidr.contains(ip)) {
+                return Ok(false);
+            }
+        }
+
+        let Some(key) = &self.config.metadata_key else {
+            return Ok(true);
+        };
+
+        Ok(
+            matches!(metadata.get(key), Some(metadata::Value::Bool(true)))
+                || matches!(session.get(key), Some(metadata::Value::Bool(true))),
+        )
+    }
+}
+
+impl StaticFilter for Chaos {
+    const NAME: &'static str = "quilkin.filters.chaos.v1alpha1.Chaos";
+    type Configuration = Config;
+    type BinaryConfiguration = proto::Chaos;
+
+    fn try_from_config(config: Option<Self::Configuration>) -> Result<Self, CreationError> {
+        Self::new(Self::ensure_config_exists(config)?)
+    }
+}
+
+#[async_trait::async_trait]
+impl Filter for Chaos {
+    async fn read(&self, ctx: &mut ReadContext) -> Result<(), FilterError> {
+        let Some(impaired) = &self.on_read else {
+            return Ok(());
+        };
+
+        if !self
+            .is_in_scope(&ctx.source, &ctx.metadata, &ctx.session)
+            .await?
+        {
+            return Ok(());
+        }
+
+        if impaired.is_lost() {
+            return Err(FilterError::new(Error::Lost));
+        }
+
+        ctx.delay += impaired.delay();
+
+        if impaired.is_duplicated() {
+            // No destinations means every endpoint, which is only filled in
+            // at the end of the filter chain.
+            if ctx.destinations.is_empty() {
+                ctx.destinations = ctx
+                    .endpoints
+                    .endpoints()
+                    .into_iter()
+                    .map(|endpoint| endpoint.address)
+                    .collect();
+            }
+
+            ctx.destinations.extend_from_within(..);
+        }
+
+        Ok(())
+    }
+
+    async fn write(&self, ctx: &mut WriteContext) -> Result<(), FilterError> {
+        let Some(impaired) = &self.on_write else {
+            return Ok(());
+        };
+
+        // The packet is sent to the client, so the client is who's scoped.
+        if !self
+            .is_in_scope(&ctx.dest, &ctx.metadata, &ctx.session)
+            .await?
+        {
+            return Ok(());
+        }
+
+        if impaired.is_lost() {
+            return Err(FilterError::new(Error::Lost));
+        }
+
+        ctx.delay += impaired.delay();
+
+        if impaired.is_duplicated() {
+            ctx.destinations.push(ctx.dest.clone());
+        }
+
+        Ok(())
+    }
+}
+
+/// An [`Impairment`] applied to the packets of one direction, along with the
+/// state of its loss model.
+struct Impaired {
+    impairment: Impairment,
+    /// Whether the Gilbert-Elliott model is in its bad, bursty state.
+    bad: AtomicBool,
+}
+
+impl Impaired {
+    fn new(impairment: Impairment) -> Self {
+        Self {
+            impairment,
+            bad: AtomicBool::new(false),
+        }
+    }
+
+    fn is_lost(&self) -> bool {
+        let probability = match &self.impairment.loss {
+            None => return false,
+            Some(Loss::Random { probability }) => *probability,
+            Some(Loss::GilbertElliott {
+                p,
+                r,
+                good_loss,
+                bad_loss,
+            }) => {
+                // Each packet first moves the model between its states, so
+                // losses come in bursts while it's in the bad state.
+                let bad = if self.bad.load(Ordering::Relaxed) {
+                    rand::random::<f64>() >= *r
+                } else {
+                    rand::random::<f64>() < *p
+                };
+                self.bad.store(bad, Ordering::Relaxed);
+
+                if bad {
+                    *bad_loss
+                } else {
+                    *good_loss
+                }
+            }
+        };
+
+        rand::random::<f64>() < probability
+    }
+
+    fn delay(&self) -> Duration {
+        let mut delay = self
+            .impairment
+            .delay
+            .as_ref()
+            .map_or(Duration::ZERO, Delay::sample);
+
+        // Holding a packet back lets the packets after it overtake it.
+        if let Some(reorder) = &self.impairment.reorder {
+            if rand::random::<f64>() < reorder.probability {
+                delay += Duration::from_millis(reorder.delay.into());
+            }
+        }
+
+        delay
+    }
+
+    fn is_duplicated(&self) -> bool {
+        rand::random::<f64>() < self.impairment.duplicate
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("packet was lost")]
+    Lost,
+}
+
+/// How the jitter of a [`Delay`] is distributed.
+#[derive(
+    Clone, Copy, Debug, Default, Deserialize, Serialize, Eq, PartialEq, schemars::JsonSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum Distribution {
+    /// Any value between minus and plus the jitter is as likely.
+    #[default]
+    Uniform,
+    /// The jitter is the standard deviation of a normal distribution.
+    Normal,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Default, Eq, PartialEq, schemars::JsonSchema)]
+pub struct Delay {
+    /// the average delay added to packets, in milliseconds
+    #[serde(default)]
+    pub latency: u32,
+    /// how much the delay varies from the latency, in milliseconds
+    #[serde(default)]
+    pub jitter: u32,
+    /// how the delay varies
+    #[serde(default)]
+    pub distribution: Distribution,
+}
+
+impl Delay {
+    fn sample(&self) -> Duration {
+        let jitter = f64::from(self.jitter);
+        let offset = match self.distribution {
+            _ if self.jitter == 0 => 0.0,
+            Distribution::Uniform => (rand::random::<f64>() * 2.0 - 1.0) * jitter,
+            Distribution::Normal => {
+                // Box-Muller transform, `1 - u` is never zero so its
+                // logarithm is finite.
+                let (u, v) = rand::random::<(f64, f64)>();
+                (-2.0 * (1.0 - u).ln()).sqrt() * (std::f64::consts::TAU * v).cos() * jitter
+            }
+        };
+
+        Duration::from_secs_f64((f64::from(self.latency) + offset).max(0.0) / 1000.0)
+    }
+}
+
+/// How packets are lost.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum Loss {
+    /// Each packet is lost independently.
+    Random {
+        /// the probability a packet is lost, between 0 and 1
+        probability: f64,
+    },
+    /// Packets are lost in bursts, following a Gilbert-Elliott model with a
+    /// good and a bad state.
+    GilbertElliott {
+        /// the probability of moving from the good to the bad state
+        p: f64,
+        /// the probability of moving from the bad to the good state
+        r: f64,
+        /// the probability a packet is lost in the good state
+        #[serde(rename = "goodLoss", default)]
+        good_loss: f64,
+        /// the probability a packet is lost in the bad state
+        #[serde(rename = "badLoss", default = "default_bad_loss")]
+        bad_loss: f64,
+    },
+}
+
+/// Default value for [`Loss::GilbertElliott::bad_loss`]
+fn default_bad_loss() -> f64 {
+    1.0
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, schemars::JsonSchema)]
+pub struct Reorder {
+    /// the probability a packet is held back, between 0 and 1
+    pub probability: f64,
+    /// how long a packet is held back, in milliseconds, on top of its delay
+    pub delay: u32,
+}
+
+/// The impairments applied to the packets of one direction.
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq, schemars::JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Impairment {
+    /// the delay added to every packet
+    #[serde(default)]
+    pub delay: Option<Delay>,
+    /// how packets are lost
+    #[serde(default, with = "serde_yaml::with::singleton_map")]
+    #[schemars(with = "Option<Loss>")]
+    pub loss: Option<Loss>,
+    /// the probability a packet is sent twice, between 0 and 1
+    #[serde(default)]
+    pub duplicate: f64,
+    /// how packets are reordered
+    #[serde(default)]
+    pub reorder: Option<Reorder>,
+}
+
+impl Impairment {
+    fn validate(&self, direction: &str) -> Result<(), CreationError> {
+        let probability = |field: &str, value: f64| {
+            if (0.0..=1.0).contains(&value) {
+                Ok(())
+            } else {
+                Err(CreationError::FieldInvalid {
+                    field: format!("{direction}.{field}"),
+                    reason: "value must be between 0 and 1".into(),
+                })
+            }
+        };
+
+        match &self.loss {
+            None => {}
+            Some(Loss::Random { probability: value }) => probability("loss.probability", *value)?,
+            Some(Loss::GilbertElliott {
+                p,
+                r,
+                good_loss,
+                bad_loss,
+            }) => {
+                probability("loss.p", *p)?;
+                probability("loss.r", *r)?;
+                probability("loss.goodLoss", *good_loss)?;
+                probability("loss.badLoss", *bad_loss)?;
+            }
+        }
+
+        probability("duplicate", self.duplicate)?;
+        if let Some(reorder) = &self.reorder {
+            probability("reorder.probability", reorder.probability)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, schemars::JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// only packets from, or sent to, addresses in these ranges are
+    /// impaired, every address is when empty
+    #[serde(default)]
+    pub sources: Vec<Cidr>,
+    /// when set, only packets whose dynamic or session metadata has this key
+    /// set to `true` are impaired
+    #[serde(rename = "metadataKey", default)]
+    pub metadata_key: Option<metadata::Key>,
+    /// the impairments of packets received from clients
+    #[serde(default)]
+    pub on_read: Option<Impairment>,
+    /// the impairments of packets received from endpoints
+    #[serde(default)]
+    pub on_write: Option<Impairment>,
+}
+
+impl From<Distribution> for proto::chaos::delay::Distribution {
+    fn from(distribution: Distribution) -> Self {
+        match distribution {
+            Distribution::Uniform => Self::Uniform,
+            Distribution::Normal => Self::Normal,
+        }
+    }
+}
+
+impl From<proto::chaos::delay::Distribution> for Distribution {
+    fn from(distribution: proto::chaos::delay::Distribution) -> Self {
+        match distribution {
+            proto::chaos::delay::Distribution::Uniform => Self::Uniform,
+            proto::chaos::delay::Distribution::Normal => Self::Normal,
+        }
+    }
+}
+
+impl From<Impairment> for proto::chaos::Impairment {
+    fn from(impairment: Impairment) -> Self {
+        use proto::chaos::impairment::Loss as ProtoLoss;
+
+        Self {
+            delay: impairment.delay.map(|delay| proto::chaos::Delay {
+                latency: Some(delay.latency),
+                jitter: Some(delay.jitter),
+                distribution: Some(proto::chaos::delay::DistributionValue {
+                    value: proto::chaos::delay::Distribution::from(delay.distribution) as i32,
+                }),
+            }),
+            loss: impairment.loss.map(|loss| match loss {
+                Loss::Random { probability } => {
+                    ProtoLoss::Random(proto::chaos::RandomLoss { probability })
+                }
+                Loss::GilbertElliott {
+                    p,
+                    r,
+                    good_loss,
+                    bad_loss,
+                } => ProtoLoss::GilbertElliott(proto::chaos::GilbertElliottLoss {
+                    p,
+                    r,
+                    good_loss: Some(good_loss),
+                    bad_loss: Some(bad_loss),
+                }),
+            }),
+            duplicate: Some(impairment.duplicate),
+            reorder: impairment.reorder.map(|reorder| proto::chaos::Reorder {
+                probability: reorder.probability,
+                delay: Some(reorder.delay),
+            }),
+        }
+    }
+}
+
+impl TryFrom<proto::chaos::Impairment> for Impairment {
+    type Error = ConvertProtoConfigError;
+
+    fn try_from(p: proto::chaos::Impairment) -> Result<Self, Self::Error> {
+        use proto::chaos::impairment::Loss as ProtoLoss;
+
+        let delay = p
+            .delay
+            .map(|delay| {
+                let distribution = delay
+                    .distribution
+                    .map(|distribution| {
+                        proto::chaos::delay::Distribution::try_from(distribution.value)
+                            .map(Distribution::from)
+                            .map_err(|_| {
+                                ConvertProtoConfigError::new(
+                                    format!("invalid distribution `{}`", distribution.value),
+                                    Some("distribution".into()),
+                                )
+                            })
+                    })
+                    .transpose()?
+                    .unwrap_or_default();
+
+                Ok::<_, ConvertProtoConfigError>(Delay {
+                    latency: delay.latency.unwrap_or_default(),
+                    jitter: delay.jitter.unwrap_or_default(),
+                    distribution,
+                })
+            })
+            .transpose()?;
+
+        Ok(Self {
+            delay,
+            loss: p.loss.map(|loss| match loss {
+                ProtoLoss::Random(loss) => Loss::Random {
+                    probability: loss.probability,
+                },
+                ProtoLoss::GilbertElliott(loss) => Loss::GilbertElliott {
+                    p: loss.p,
+                    r: loss.r,
+                    good_loss: loss.good_loss.unwrap_or_default(),
+                    bad_loss: loss.bad_loss.unwrap_or_else(default_bad_loss),
+                },
+            }),
+            duplicate: p.duplicate.unwrap_or_default(),
+            reorder: p.reorder.map(|reorder| Reorder {
+                probability: reorder.probability,
+                delay: reorder.delay.unwrap_or_default(),
+            }),
+        })
+    }
+}
+
+impl From<Config> for proto::Chaos {
+    fn from(config: Config) -> Self {
+        Self {
+            sources: config
+                .sources
+                .into_iter()
+                .map(|source| source.to_string())
+                .collect(),
+            metadata_key: config.metadata_key.map(|key| key.to_string()),
+            on_read: config.on_read.map(From::from),
+            on_write: config.on_write.map(From::from),
+        }
+    }
+}
+
+impl TryFrom<proto::Chaos> for Config {
+    type Error = ConvertProtoConfigError;
+
+    fn try_from(p: proto::Chaos) -> Result<Self, Self::Error> {
+        let sources = p
+            .sources
+            .into_iter()
+            .map(|source| {
+                source.parse().map_err(|error| {
+                    ConvertProtoConfigError::new(
+                        format!("invalid source `{source}`: {error}"),
+                        Some("sources".into()),
+                    )
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            sources,
+            metadata_key: p.metadata_key.map(metadata::Key::new),
+            on_read: p.on_read.map(TryFrom::try_from).transpose()?,
+            on_write: p.on_write.map(TryFrom::try_from).transpose()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        net::{cluster::ClusterMap, endpoint::Endpoint},
+        test::alloc_buffer,
+    };
+
+    fn read_context() -> ReadContext {
+        let endpoints = ClusterMap::new_default(
+            [
+                Endpoint::new((std::net::Ipv4Addr::LOCALHOST, 1).into()),
+                Endpoint::new((std::net::Ipv4Addr::LOCALHOST, 2).into()),
+            ]
+            .into(),
+        );
+        ReadContext::new(
+            endpoints.into(),
+            (std::net::Ipv4Addr::new(10, 0, 0, 1), 80).into(),
+            alloc_buffer(b"hello"),
+        )
+    }
+
+    fn write_context() -> WriteContext {
+        WriteContext::new(
+            (std::net::Ipv4Addr::LOCALHOST, 1).into(),
+            (std::net::Ipv4Addr::new(10, 0, 0, 1), 80).into(),
+            alloc_buffer(b"hello"),
+        )
+    }
+
+    fn chaos(impairment: Impairment) -> Chaos {
+        Chaos::from_config(Some(Config {
+            on_read: Some(impairment.clone()),
+            on_write: Some(impairment),
+            ..<_>::default()
+        }))
+    }
+
+    #[tokio::test]
+    async fn delay() {
+        let filter = chaos(Impairment {
+            delay: Some(Delay {
+                latency: 50,
+                ..<_>::default()
+            }),
+            reorder: Some(Reorder {
+                probability: 1.0,
+                delay: 20,
+            }),
+            ..<_>::default()
+        });
+
+        let mut ctx = read_context();
+        filter.read(&mut ctx).await.unwrap();
+        assert_eq!(Duration::from_millis(70), ctx.delay);
+
+        let mut ctx = write_context();
+        filter.write(&mut ctx).await.unwrap();
+        assert_eq!(Duration::from_millis(70), ctx.delay);
+    }
+
+    #[test]
+    fn jitter() {
+        let delay = |distribution| Delay {
+            latency: 50,
+            jitter: 10,
+            distribution,
+        };
+
+        let uniform = delay(Distribution::Uniform);
+        assert!((0..1000).all(|_| {
+            let delay = uniform.sample();
+            Duration::from_millis(40) <= delay && delay <= Duration::from_millis(60)
+        }));
+
+        let normal = delay(Distribution::Normal);
+        let samples = (0..1000).map(|_| normal.sample()).collect::<Vec<_>>();
+        let within = samples
+            .iter()
+            .filter(|delay| {
+                (Duration::from_millis(40)..=Duration::from_millis(60)).contains(*delay)
+            })
+            .count();
+        // About 68% of samples are within one standard deviation.
+        assert!((600..780).contains(&within), "{within}");
+
+        // Delays never go negative.
+        let wide = Delay {
+            latency: 0,
+            jitter: 100,
+            distribution: Distribution::Normal,
+        };
+        assert!((0..100).all(|_| wide.sample() <= Duration::from_secs(1)));
+    }
+
+    #[tokio::test]
+    async fn loss() {
+        let filter = chaos(Impairment {
+            loss: Some(Loss::Random { probability: 1.0 }),
+            ..<_>::default()
+        });
+        assert!(filter.read(&mut read_context()).await.is_err());
+        assert!(filter.write(&mut write_context()).await.is_err());
+
+        let filter = chaos(Impairment {
+            loss: Some(Loss::Random { probability: 0.0 }),
+            ..<_>::default()
+        });
+        assert!(filter.read(&mut read_context()).await.is_ok());
+        assert!(filter.write(&mut write_context()).await.is_ok());
+    }
+
+    #[test]
+    fn gilbert_elliott() {
+        let impaired = |p, r| {
+            Impaired::new(Impairment {
+                loss: Some(Loss::GilbertElliott {
+                    p,
+                    r,
+                    good_loss: 0.0,
+                    bad_loss: 1.0,
+                }),
+                ..<_>::default()
+            })
+        };
+
+        // Never entering the bad state, nothing is lost.
+        let impaired_good = impaired(0.0, 1.0);
+        assert!((0..100).all(|_| !impaired_good.is_lost()));
+
+        // Never leaving the bad state, everything is lost.
+        let impaired_bad = impaired(1.0, 0.0);
+        assert!((0..100).all(|_| impaired_bad.is_lost()));
+
+        // Losses come in bursts, alternating between the states.
+        let alternating = impaired(1.0, 1.0);
+        let losses = (0..10).map(|_| alternating.is_lost()).collect::<Vec<_>>();
+        assert_eq!(
+            [true, false, true, false, true, false, true, false, true, false][..],
+            losses
+        );
+    }
+
+    #[tokio::test]
+    async fn duplicate() {
+        let filter = chaos(Impairment {
+            duplicate: 1.0,
+            ..<_>::default()
+        });
+
+        let mut ctx = read_context();
+        filter.read(&mut ctx).await.unwrap();
+        assert_eq!(4, ctx.destinations.len());
+
+        let mut ctx = write_context();
+        filter.write(&mut ctx).await.unwrap();
+        assert_eq!(vec![ctx.dest.clone()], ctx.destinations);
+    }
+
+    #[tokio::test]
+    async fn scope() {
+        let lossy = || Impairment {
+            loss: Some(Loss::Random { probability: 1.0 }),
+            ..<_>::default()
+        };
+        let filter = Chaos::from_config(Some(Config {
+            sources: vec!["10.0.0.0/8".parse().unwrap()],
+            on_read: Some(lossy()),
+            on_write: Some(lossy()),
+            ..<_>::default()
+        }));
+        assert!(filter.read(&mut read_context()).await.is_err());
+        assert!(filter.write(&mut write_context()).await.is_err());
+
+        let mut ctx = read_context();
+        ctx.source = (std::net::Ipv4Addr::new(192, 168, 0, 1), 80).into();
+        assert!(filter.read(&mut ctx).await.is_ok());
+
+        let key = metadata::Key::from_static("test.account");
+        let filter = Chaos::from_config(Some(Config {
+            metadata_key: Some(key),
+            on_read: Some(lossy()),
+            on_write: Some(lossy()),
+            ..<_>::default()
+        }));
+        assert!(filter.read(&mut read_context()).await.is_ok());
+        assert!(filter.write(&mut write_context()).await.is_ok());
+
+        let mut ctx = read_context();
+        ctx.metadata.insert(key, true.into());
+        assert!(filter.read(&mut ctx).await.is_err());
+
+        // Flagging the session impairs both directions.
+        let mut ctx = write_context();
+        ctx.session.insert(key, true.into());
+        assert!(filter.write(&mut ctx).await.is_err());
+    }
+
+    #[test]
+    fn validate() {
+        let config = |impairment| Config {
+            on_write: Some(impairment),
+            ..<_>::default()
+        };
+
+        assert!(Chaos::try_from_config(Some(config(Impairment {
+            duplicate: 1.5,
+            ..<_>::default()
+        })))
+        .is_err());
+        assert!(Chaos::try_from_config(Some(config(Impairment {
+            loss: Some(Loss::GilbertElliott {
+                p: 0.1,
+                r: -0.1,
+                good_loss: 0.0,
+                bad_loss: 1.0,
+            }),
+            ..<_>::default()
+        })))
+        .is_err());
+    }
+
+    #[test]
+    fn convert_proto_config() {
+        let config = || Config {
+            sources: vec!["10.0.0.0/8".parse().unwrap()],
+            metadata_key: Some("test.account".into()),
+            on_read: Some(Impairment {
+                delay: Some(Delay {
+                    latency: 50,
+                    jitter: 10,
+                    distribution: Distribution::Normal,
+                }),
+                loss: Some(Loss::GilbertElliott {
+                    p: 0.01,
+                    r: 0.3,
+                    good_loss: 0.0,
+                    bad_loss: 0.9,
+                }),
+                duplicate: 0.01,
+                reorder: Some(Reorder {
+                    probability: 0.05,
+                    delay: 20,
+                }),
+            }),
+            on_write: Some(Impairment {
+                loss: Some(Loss::Random { probability: 0.02 }),
+                ..<_>::default()
+            }),
+        };
+        assert_eq!(
+            config(),
+            Config::try_from(proto::Chaos::from(config())).unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_config() {
+        let config: Config = serde_yaml::from_str(
+            "
+sources:
+  - 10.0.0.0/8
+on_read:
+  delay:
+    latency: 50
+    jitter: 10
+    distribution: normal
+  loss:
+    gilbertElliott:
+      p: 0.01
+      r: 0.3
+",
+        )
+        .unwrap();
+
+        assert_eq!(
+            config,
+            Config {
+                sources: vec!["10.0.0.0/8".parse().unwrap()],
+                on_read: Some(Impairment {
+                    delay: Some(Delay {
+                        latency: 50,
+                        jitter: 10,
+                        distribution: Distribution::Normal,
+                    }),
+                    loss: Some(Loss::GilbertElliott {
+                        p: 0.01,
+                        r: 0.3,
+                        good_loss: 0.0,
+                        bad_loss: 1.0,
+                    }),
+                    ..<_>::default()
+                }),
+                ..<_>::default()
+            }
+        );
+    }
+}
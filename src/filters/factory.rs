@@ -18,7 +18,7 @@ use std::sync::Arc;
 
 use crate::{
     config::ConfigType,
-    filters::{CreationError, Filter, StaticFilter},
+    filters::{predicate::Predicate, CreationError, Filter, StaticFilter},
 };
 
 /// An owned pointer to a dynamic [`FilterFactory`] instance.
@@ -27,7 +27,11 @@ pub type DynFilterFactory = Box<dyn FilterFactory>;
 /// The value returned by [`FilterFactory::create_filter`].
 #[derive(Clone)]
 #[non_exhaustive]
-pub struct FilterInstance(Arc<FilterInstanceData>);
+pub struct FilterInstance {
+    data: Arc<FilterInstanceData>,
+    /// The condition for executing the filter in a chain.
+    when: Option<Arc<Predicate>>,
+}
 
 struct FilterInstanceData {
     /// The configuration used to create the filter.
@@ -41,23 +45,36 @@ struct FilterInstanceData {
 impl FilterInstance {
     /// Constructs a [`FilterInstance`].
     pub fn new(config: serde_json::Value, filter: Box<dyn Filter>) -> Self {
-        Self(Arc::new(FilterInstanceData {
-            config,
-            label: None,
-            filter,
-        }))
+        Self {
+            data: Arc::new(FilterInstanceData {
+                config,
+                label: None,
+                filter,
+            }),
+            when: None,
+        }
+    }
+
+    /// Sets the predicate deciding whether the filter is executed for a packet.
+    pub fn with_when(mut self, when: Option<Predicate>) -> Self {
+        self.when = when.map(Arc::new);
+        self
     }
 
     pub fn config(&self) -> &serde_json::Value {
-        &self.0.config
+        &self.data.config
     }
 
     pub fn label(&self) -> Option<&str> {
-        self.0.label.as_deref()
+        self.data.label.as_deref()
+    }
+
+    pub fn when(&self) -> Option<&Predicate> {
+        self.when.as_deref()
     }
 
     pub fn filter(&self) -> &dyn Filter {
-        &*self.0.filter
+        &*self.data.filter
     }
 }
 
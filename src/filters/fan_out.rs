@@ -0,0 +1,500 @@
+/*
+ * Copyright 2024 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{collections::HashSet, time::Duration};
+
+use bytes::Bytes;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    collections::ttl::TtlMap,
+    config::Base64Standard,
+    filters::{capture::CAPTURED_BYTES, prelude::*},
+    net::endpoint::{metadata, EndpointAddress},
+};
+
+use crate::generated::quilkin::filters::fan_out::v1alpha1 as proto;
+
+/// How often spectators which haven't joined again within the TTL are
+/// removed.
+const SPECTATOR_EXPIRY_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// The most groups with spectators at once.
+const MAX_GROUPS: usize = 16 * 1024;
+
+/// Filter that replicates the packets an endpoint sends to a client to every
+/// spectator that joined the client's group, while the endpoint only sees the
+/// client's session.
+pub struct FanOut {
+    config: Config,
+    /// The group each spectator joined, keyed by the spectator's address.
+    spectators: TtlMap<EndpointAddress, Bytes>,
+    /// The spectators of each group, which may still contain expired
+    /// spectators until the group's next packet, or until the groups are
+    /// next pruned.
+    groups: DashMap<Bytes, HashSet<EndpointAddress>>,
+    /// When the groups were last pruned of expired spectators.
+    pruned_at: parking_lot::Mutex<tokio::time::Instant>,
+}
+
+impl FanOut {
+    fn new(config: Config) -> Result<Self, CreationError> {
+        if config.join.is_empty() {
+            return Err(CreationError::FieldInvalid {
+                field: "join".into(),
+                reason: "value must not be empty".into(),
+            });
+        }
+
+        if config.max_spectators == 0 {
+            return Err(CreationError::FieldInvalid {
+                field: "maxSpectators".into(),
+                reason: "value must be at least 1".into(),
+            });
+        }
+
+        if config.ttl == 0 {
+            return Err(CreationError::FieldInvalid {
+                field: "ttl".into(),
+                reason: "value must be at least 1 second".into(),
+            });
+        }
+
+        Ok(Self {
+            spectators: TtlMap::new(
+                Duration::from_secs(config.ttl.into()),
+                SPECTATOR_EXPIRY_POLL_INTERVAL,
+            ),
+            groups: DashMap::new(),
+            pruned_at: parking_lot::Mutex::new(tokio::time::Instant::now()),
+            config,
+        })
+    }
+
+    /// Adds `spectator` to the group of `token`, or keeps it in the group
+    /// when it already joined, unless the group already has
+    /// [`Config::max_spectators`] spectators, or it's a new group and there
+    /// are already [`MAX_GROUPS`] groups.
+    fn join(&self, spectator: EndpointAddress, token: Bytes) -> Result<(), Error> {
+        self.prune_expired();
+
+        // Checked before taking the entry, which locks its shard.
+        if self.groups.len() >= MAX_GROUPS && !self.groups.contains_key(&token) {
+            return Err(Error::TooManyGroups);
+        }
+
+        let previous = {
+            let mut group = self.groups.entry(token.clone()).or_default();
+            group.retain(|spectator| self.spectators.contains_key(spectator));
+
+            if !group.contains(&spectator)
+                && group.len() >= usize::try_from(self.config.max_spectators).unwrap_or(usize::MAX)
+            {
+                return Err(Error::GroupFull);
+            }
+
+            group.insert(spectator.clone());
+            self.spectators.insert(spectator.clone(), token.clone())
+        };
+
+        if let Some(previous) = previous.filter(|previous| *previous != token) {
+            if let Some(mut group) = self.groups.get_mut(&previous) {
+                group.remove(&spectator);
+            }
+
+            self.groups
+                .remove_if(&previous, |_, group| group.is_empty());
+        }
+
+        Ok(())
+    }
+
+    /// Removes the expired spectators from every group, and the groups left
+    /// without any, at most once every [`SPECTATOR_EXPIRY_POLL_INTERVAL`].
+    fn prune_expired(&self) {
+        let now = tokio::time::Instant::now();
+        {
+            let mut pruned_at = self.pruned_at.lock();
+            if now.duration_since(*pruned_at) < SPECTATOR_EXPIRY_POLL_INTERVAL {
+                return;
+            }
+
+            *pruned_at = now;
+        }
+
+        self.groups.retain(|_, group| {
+            group.retain(|spectator| self.spectators.contains_key(spectator));
+            !group.is_empty()
+        });
+    }
+
+    /// Returns the token in the packet's metadata.
+    fn token(&self, metadata: &metadata::DynamicMetadata) -> Result<Option<Bytes>, Error> {
+        match metadata.get(&self.config.metadata_key) {
+            Some(metadata::Value::Bytes(token)) => Ok(Some(token.clone())),
+            Some(value) => Err(Error::InvalidType(self.config.metadata_key, value.clone())),
+            None => Ok(None),
+        }
+    }
+}
+
+impl StaticFilter for FanOut {
+    const NAME: &'static str = "quilkin.filters.fan_out.v1alpha1.FanOut";
+    type Configuration = Config;
+    type BinaryConfiguration = proto::FanOut;
+
+    fn try_from_config(config: Option<Self::Configuration>) -> Result<Self, CreationError> {
+        Self::new(Self::ensure_config_exists(config)?)
+    }
+}
+
+#[async_trait::async_trait]
+impl Filter for FanOut {
+    async fn read(&self, ctx: &mut ReadContext) -> Result<(), FilterError> {
+        let Some(token) = self.token(&ctx.metadata).map_err(FilterError::new)? else {
+            return Ok(());
+        };
+
+        if *ctx.contents == *self.config.join {
+            tracing::trace!(source = %ctx.source, "spectator joined");
+            self.join(ctx.source.clone(), token)
+                .map_err(FilterError::new)?;
+            // Acknowledge the join, the packet isn't forwarded to the endpoint.
+            ctx.reply(Bytes::copy_from_slice(&self.config.join));
+            return Ok(());
+        }
+
+        // Only the client's session is seen by the endpoint.
+        if self.spectators.contains_key(&ctx.source) {
+            return Err(FilterError::new(Error::Spectator));
+        }
+
+        ctx.session
+            .insert(self.config.metadata_key, metadata::Value::Bytes(token));
+        Ok(())
+    }
+
+    async fn write(&self, ctx: &mut WriteContext) -> Result<(), FilterError> {
+        let Some(metadata::Value::Bytes(token)) = ctx.session.get(&self.config.metadata_key) else {
+            return Ok(());
+        };
+
+        let Some(mut group) = self.groups.get_mut(&token) else {
+            return Ok(());
+        };
+
+        group.retain(|spectator| self.spectators.contains_key(spectator));
+        ctx.destinations.extend(group.iter().cloned());
+
+        if group.is_empty() {
+            drop(group);
+            self.groups.remove_if(&token, |_, group| group.is_empty());
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("key `{0}` was found but wasn't bytes, found {1:?}")]
+    InvalidType(metadata::Key, metadata::Value),
+    #[error("spectators can only send join packets")]
+    Spectator,
+    #[error("the group already has the maximum number of spectators")]
+    GroupFull,
+    #[error("there are already the maximum number of groups")]
+    TooManyGroups,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, schemars::JsonSchema)]
+pub struct Config {
+    /// the key to use when retrieving the token from the Filter's dynamic metadata
+    #[serde(rename = "metadataKey", default = "default_metadata_key")]
+    pub metadata_key: metadata::Key,
+    /// the contents of the packets spectators send to join the group of their
+    /// token, once the token has been captured and removed
+    #[serde(
+        default,
+        deserialize_with = "Base64Standard::deserialize",
+        serialize_with = "Base64Standard::serialize"
+    )]
+    pub join: Vec<u8>,
+    /// how long spectators stay in their group after their last join packet,
+    /// in seconds
+    #[serde(default = "default_ttl")]
+    pub ttl: u32,
+    /// the maximum number of spectators in each group, further join packets
+    /// are dropped until a spectator leaves the group
+    #[serde(rename = "maxSpectators", default = "default_max_spectators")]
+    pub max_spectators: u32,
+}
+
+/// Default value for [`Config::metadata_key`]
+fn default_metadata_key() -> metadata::Key {
+    metadata::Key::from_static(CAPTURED_BYTES)
+}
+
+/// Default value for [`Config::ttl`]
+fn default_ttl() -> u32 {
+    30
+}
+
+/// Default value for [`Config::max_spectators`]
+fn default_max_spectators() -> u32 {
+    16
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            metadata_key: default_metadata_key(),
+            join: Vec::new(),
+            ttl: default_ttl(),
+            max_spectators: default_max_spectators(),
+        }
+    }
+}
+
+impl From<Config> for proto::FanOut {
+    fn from(config: Config) -> Self {
+        Self {
+            metadata_key: Some(config.metadata_key.to_string()),
+            join: config.join,
+            ttl: Some(config.ttl),
+            max_spectators: Some(config.max_spectators),
+        }
+    }
+}
+
+impl From<proto::FanOut> for Config {
+    fn from(p: proto::FanOut) -> Self {
+        Self {
+            metadata_key: p
+                .metadata_key
+                .map(metadata::Key::new)
+                .unwrap_or_else(default_metadata_key),
+            join: p.join,
+            ttl: p.ttl.unwrap_or_else(default_ttl),
+            max_spectators: p.max_spectators.unwrap_or_else(default_max_spectators),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::alloc_buffer;
+
+    const TOKEN: &[u8] = b"match";
+
+    fn read_context(source: EndpointAddress, contents: &[u8]) -> ReadContext {
+        let mut ctx = ReadContext::new(<_>::default(), source, alloc_buffer(contents));
+        ctx.metadata.insert(
+            metadata::Key::from_static(CAPTURED_BYTES),
+            metadata::Value::Bytes(Bytes::from_static(TOKEN)),
+        );
+        ctx
+    }
+
+    #[tokio::test]
+    async fn fan_out() {
+        tokio::time::pause();
+
+        let filter = FanOut::from_config(Some(Config {
+            join: b"JOIN".to_vec(),
+            ttl: 5,
+            ..<_>::default()
+        }));
+        let client = EndpointAddress::from(([127, 0, 0, 1], 80));
+        let spectators = [
+            EndpointAddress::from(([127, 0, 0, 2], 80)),
+            EndpointAddress::from(([127, 0, 0, 3], 80)),
+        ];
+
+        for spectator in &spectators {
+            let mut ctx = read_context(spectator.clone(), b"JOIN");
+            filter.read(&mut ctx).await.unwrap();
+            assert!(ctx.is_handled());
+            assert_eq!(vec![Bytes::from_static(b"JOIN")], ctx.replies);
+        }
+
+        // Spectators can't send anything else to the endpoint.
+        let mut ctx = read_context(spectators[0].clone(), b"hello");
+        assert!(filter.read(&mut ctx).await.is_err());
+
+        let mut ctx = read_context(client.clone(), b"hello");
+        filter.read(&mut ctx).await.unwrap();
+        assert!(!ctx.is_handled());
+        let session = ctx.session;
+
+        let write = || {
+            let mut ctx = WriteContext::new(
+                ([127, 0, 0, 1], 8080).into(),
+                client.clone(),
+                alloc_buffer(b"state"),
+            );
+            ctx.session = session.clone();
+            ctx
+        };
+
+        let mut ctx = write();
+        filter.write(&mut ctx).await.unwrap();
+        let mut destinations = ctx.destinations;
+        destinations.sort();
+        assert_eq!(&spectators[..], &destinations);
+
+        // Only the spectator which joined again is still in the group.
+        tokio::time::advance(Duration::from_secs(3)).await;
+        let mut ctx = read_context(spectators[1].clone(), b"JOIN");
+        filter.read(&mut ctx).await.unwrap();
+        tokio::time::advance(Duration::from_secs(4)).await;
+
+        let mut ctx = write();
+        filter.write(&mut ctx).await.unwrap();
+        assert_eq!(&spectators[1..], &ctx.destinations);
+
+        tokio::time::advance(Duration::from_secs(7)).await;
+        let mut ctx = write();
+        filter.write(&mut ctx).await.unwrap();
+        assert!(ctx.destinations.is_empty());
+        assert!(filter.groups.is_empty());
+    }
+
+    #[tokio::test]
+    async fn no_token() {
+        let filter = FanOut::from_config(Some(Config {
+            join: b"JOIN".to_vec(),
+            ..<_>::default()
+        }));
+        let mut ctx = ReadContext::new(
+            <_>::default(),
+            ([127, 0, 0, 1], 80).into(),
+            alloc_buffer(b""),
+        );
+        filter.read(&mut ctx).await.unwrap();
+        assert!(!ctx.is_handled());
+
+        let mut ctx = WriteContext::new(
+            ([127, 0, 0, 1], 8080).into(),
+            ([127, 0, 0, 1], 80).into(),
+            alloc_buffer(b"state"),
+        );
+        filter.write(&mut ctx).await.unwrap();
+        assert!(ctx.destinations.is_empty());
+    }
+
+    #[tokio::test]
+    async fn max_spectators() {
+        tokio::time::pause();
+
+        let filter = FanOut::from_config(Some(Config {
+            join: b"JOIN".to_vec(),
+            ttl: 5,
+            max_spectators: 1,
+            ..<_>::default()
+        }));
+        let spectators = [
+            EndpointAddress::from(([127, 0, 0, 2], 80)),
+            EndpointAddress::from(([127, 0, 0, 3], 80)),
+        ];
+
+        let mut ctx = read_context(spectators[0].clone(), b"JOIN");
+        filter.read(&mut ctx).await.unwrap();
+
+        // The group is full, but its spectator can still join again.
+        let mut ctx = read_context(spectators[1].clone(), b"JOIN");
+        assert!(filter.read(&mut ctx).await.is_err());
+        assert!(ctx.replies.is_empty());
+        let mut ctx = read_context(spectators[0].clone(), b"JOIN");
+        filter.read(&mut ctx).await.unwrap();
+
+        // Once the spectator left, another one can join.
+        tokio::time::advance(Duration::from_secs(3)).await;
+        tokio::time::advance(Duration::from_secs(4)).await;
+        let mut ctx = read_context(spectators[1].clone(), b"JOIN");
+        filter.read(&mut ctx).await.unwrap();
+        assert_eq!(vec![Bytes::from_static(b"JOIN")], ctx.replies);
+    }
+
+    #[tokio::test]
+    async fn remove_empty_groups() {
+        tokio::time::pause();
+
+        let filter = FanOut::from_config(Some(Config {
+            join: b"JOIN".to_vec(),
+            ttl: 5,
+            ..<_>::default()
+        }));
+        let spectator = EndpointAddress::from(([127, 0, 0, 2], 80));
+        let other = EndpointAddress::from(([127, 0, 0, 3], 80));
+
+        filter
+            .join(spectator.clone(), Bytes::from_static(b"one"))
+            .unwrap();
+        filter.join(other, Bytes::from_static(b"two")).unwrap();
+
+        // The group the spectator moved from is removed once it's empty.
+        filter.join(spectator, Bytes::from_static(b"two")).unwrap();
+        assert!(!filter.groups.contains_key(&b"one"[..]));
+        assert_eq!(1, filter.groups.len());
+
+        // As are groups whose spectators all expired, without any packets
+        // from their clients.
+        tokio::time::advance(Duration::from_secs(3)).await;
+        tokio::time::advance(Duration::from_secs(4)).await;
+        filter
+            .join(
+                EndpointAddress::from(([127, 0, 0, 4], 80)),
+                Bytes::from_static(b"three"),
+            )
+            .unwrap();
+        assert!(!filter.groups.contains_key(&b"two"[..]));
+        assert_eq!(1, filter.groups.len());
+    }
+
+    #[test]
+    fn invalid_config() {
+        assert!(FanOut::try_from_config(None).is_err());
+        assert!(FanOut::try_from_config(Some(Config::default())).is_err());
+        for config in [
+            Config {
+                join: b"JOIN".to_vec(),
+                ttl: 0,
+                ..<_>::default()
+            },
+            Config {
+                join: b"JOIN".to_vec(),
+                max_spectators: 0,
+                ..<_>::default()
+            },
+        ] {
+            assert!(FanOut::try_from_config(Some(config)).is_err());
+        }
+    }
+
+    #[test]
+    fn convert_proto_config() {
+        let config = || Config {
+            metadata_key: "spectate".into(),
+            join: b"JOIN".to_vec(),
+            ttl: 10,
+            max_spectators: 4,
+        };
+        assert_eq!(config(), Config::from(proto::FanOut::from(config())));
+        assert_eq!(Config::default(), Config::from(proto::FanOut::default()));
+    }
+}
@@ -0,0 +1,943 @@
+/*
+ * Copyright 2024 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{collections::VecDeque, sync::Arc};
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    filters::{
+        prelude::*,
+        streams::{self, Streams},
+    },
+    metrics::Direction,
+    net::endpoint::EndpointAddress,
+    pool::{BufferPool, PoolBuffer},
+};
+
+use crate::generated::quilkin::filters::fec::v1alpha1 as proto;
+
+/// The largest number of packets in a group.
+const MAX_GROUP_SIZE: u8 = 64;
+/// How many of the most recent groups of each client are kept to recover
+/// packets from, parity packets are sent right after their group.
+const MAX_GROUPS: usize = 4;
+
+/// Filter that adds forward error correction to packets, so packets lost on
+/// a lossy link can be recovered on the other side of it.
+///
+/// When encoding, packets are numbered in groups, and after each group of
+/// `groupSize` packets, `parity` packets are forwarded, each the XOR of every
+/// `parity`th packet of the group. When decoding, a packet is recovered from
+/// its parity packet when it's the only one of them lost.
+pub struct Fec {
+    on_read: Coder,
+    on_write: Coder,
+}
+
+impl Fec {
+    fn new(config: Config) -> Result<Self, CreationError> {
+        config.on_read.validate("on_read")?;
+        config.on_write.validate("on_write")?;
+
+        let pool = streams::buffer_pool();
+        Ok(Self {
+            on_read: Coder::new(config.on_read, pool.clone(), Direction::Read),
+            on_write: Coder::new(config.on_write, pool, Direction::Write),
+        })
+    }
+}
+
+impl StaticFilter for Fec {
+    const NAME: &'static str = "quilkin.filters.fec.v1alpha1.Fec";
+    type Configuration = Config;
+    type BinaryConfiguration = proto::Fec;
+
+    fn try_from_config(config: Option<Self::Configuration>) -> Result<Self, CreationError> {
+        Self::new(Self::ensure_config_exists(config)?)
+    }
+}
+
+#[async_trait::async_trait]
+impl Filter for Fec {
+    async fn read(&self, ctx: &mut ReadContext) -> Result<(), FilterError> {
+        match self.on_read.coding.action {
+            Action::DoNothing => {}
+            Action::Encode => {
+                let parities = self.on_read.encode(&ctx.source, &mut ctx.contents);
+                for parity in parities {
+                    ctx.forward(parity);
+                }
+            }
+            Action::Decode => {
+                if !self
+                    .on_read
+                    .decode(&ctx.source, &mut ctx.contents)
+                    .map_err(FilterError::new)?
+                {
+                    ctx.consume();
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn write(&self, ctx: &mut WriteContext) -> Result<(), FilterError> {
+        // The packets are coded per client, which is the destination.
+        match self.on_write.coding.action {
+            Action::DoNothing => {}
+            Action::Encode => {
+                let parities = self.on_write.encode(&ctx.dest, &mut ctx.contents);
+                for parity in parities {
+                    ctx.forward(parity);
+                }
+            }
+            Action::Decode => {
+                if !self
+                    .on_write
+                    .decode(&ctx.dest, &mut ctx.contents)
+                    .map_err(FilterError::new)?
+                {
+                    ctx.consume();
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Encodes or decodes the packets of one direction, keeping the state of
+/// each client.
+struct Coder {
+    coding: Coding,
+    streams: Streams<Stream>,
+    pool: Arc<BufferPool>,
+    packets_recovered_total: prometheus::IntCounter,
+}
+
+impl Coder {
+    fn new(coding: Coding, pool: Arc<BufferPool>, direction: Direction) -> Self {
+        Self {
+            coding,
+            streams: Streams::new(streams::MAX_STREAMS, streams::MAX_BUFFERED),
+            pool,
+            packets_recovered_total: crate::filters::metrics::counter(
+                Fec::NAME,
+                "packets_recovered_total",
+                "Total number of lost packets recovered from parity packets",
+                direction,
+            ),
+        }
+    }
+
+    /// Adds the header to `contents`, returning the group's parity packets
+    /// once it's the last packet of its group.
+    fn encode(&self, key: &EndpointAddress, contents: &mut PoolBuffer) -> Vec<PoolBuffer> {
+        let Coding {
+            group_size, parity, ..
+        } = self.coding;
+
+        let result = self.streams.with(key, contents.len(), |stream| {
+            let encoder = &mut stream.encoder;
+            let header = Header {
+                kind: Kind::Data,
+                group: encoder.group,
+                index: encoder.index,
+                group_size,
+                parity,
+            };
+
+            if encoder.parities.is_empty() {
+                encoder.parities.resize_with(parity.into(), Parity::default);
+            }
+            encoder.parities[usize::from(encoder.index % parity)].add(contents);
+
+            encoder.index += 1;
+            let mut parities = Vec::new();
+            if encoder.index == group_size {
+                parities = std::mem::take(&mut encoder.parities);
+                encoder.group = encoder.group.wrapping_add(1);
+                encoder.index = 0;
+            }
+
+            (header, parities)
+        });
+
+        let Ok((header, parities)) = result else {
+            // Without room for the client's state, the packet isn't covered
+            // by any parity packet, but is still forwarded.
+            let header = Header {
+                kind: Kind::Unprotected,
+                group: 0,
+                index: 0,
+                group_size,
+                parity,
+            };
+            contents.prepend_from_slice(&header.to_bytes());
+            return Vec::new();
+        };
+
+        contents.prepend_from_slice(&header.to_bytes());
+        parities
+            .into_iter()
+            .zip(0..)
+            .map(|(parity, index)| {
+                let header = Header {
+                    kind: Kind::Parity,
+                    index,
+                    ..header
+                };
+
+                let mut packet = self
+                    .pool
+                    .clone()
+                    .alloc_sized(Header::LEN + 2 + parity.contents.len());
+                packet.extend_from_slice(&header.to_bytes());
+                packet.extend_from_slice(&parity.length.to_be_bytes());
+                packet.extend_from_slice(&parity.contents);
+                packet
+            })
+            .collect()
+    }
+
+    /// Removes the header from `contents`, or replaces a parity packet with
+    /// the packet it recovers, returning whether there's a packet to forward.
+    fn decode(&self, key: &EndpointAddress, contents: &mut PoolBuffer) -> Result<bool, Error> {
+        let header = Header::parse(contents)?;
+        match header.kind {
+            Kind::Data => {
+                let payload = &contents[Header::LEN..];
+                // Without room for the client's state, the packet can't be
+                // kept to recover others with, but is still forwarded.
+                if !self
+                    .streams
+                    .with(key, payload.len(), |stream| {
+                        stream.decoder.receive(&header, payload)
+                    })
+                    .unwrap_or(true)
+                {
+                    // Already recovered from a parity packet.
+                    return Ok(false);
+                }
+
+                contents.split_prefix(Header::LEN);
+                Ok(true)
+            }
+            Kind::Parity => {
+                let Some((length, payload)) = contents[Header::LEN..].split_first_chunk::<2>()
+                else {
+                    return Err(Error::TooShort);
+                };
+
+                let length = u16::from_be_bytes(*length);
+                let Ok(recovered) = self.streams.with(key, payload.len(), |stream| {
+                    stream.decoder.recover(&header, length, payload)
+                }) else {
+                    // Without room for the client's state, there are no
+                    // packets to recover from.
+                    return Ok(false);
+                };
+
+                let Some(recovered) = recovered? else {
+                    return Ok(false);
+                };
+
+                let mut packet = self.pool.clone().alloc_sized(recovered.len());
+                packet.extend_from_slice(&recovered);
+                *contents = packet;
+                self.packets_recovered_total.inc();
+                Ok(true)
+            }
+            Kind::Unprotected => {
+                contents.split_prefix(Header::LEN);
+                Ok(true)
+            }
+        }
+    }
+}
+
+/// The coding state of a client.
+#[derive(Default)]
+struct Stream {
+    encoder: Encoder,
+    decoder: Decoder,
+}
+
+impl streams::Stream for Stream {
+    fn buffered(&self) -> usize {
+        let parities: usize = self
+            .encoder
+            .parities
+            .iter()
+            .map(|parity| parity.contents.len())
+            .sum();
+        parities + self.decoder.buffered
+    }
+}
+
+#[derive(Default)]
+struct Encoder {
+    group: u16,
+    index: u8,
+    parities: Vec<Parity>,
+}
+
+/// The XOR of some of the packets of a group, and of their lengths.
+#[derive(Default)]
+struct Parity {
+    length: u16,
+    contents: Vec<u8>,
+}
+
+impl Parity {
+    fn add(&mut self, packet: &[u8]) {
+        self.length ^= packet.len() as u16;
+        xor(&mut self.contents, packet);
+    }
+}
+
+#[derive(Default)]
+struct Decoder {
+    groups: VecDeque<Group>,
+    /// The number of bytes of the groups' packets.
+    buffered: usize,
+}
+
+/// The packets received, or recovered, of a group.
+struct Group {
+    id: u16,
+    packets: Vec<Option<Bytes>>,
+}
+
+impl Decoder {
+    fn group(&mut self, header: &Header) -> &mut Group {
+        let position = match self
+            .groups
+            .iter()
+            .position(|group| group.id == header.group)
+        {
+            Some(position) => position,
+            None => {
+                if self.groups.len() == MAX_GROUPS {
+                    if let Some(group) = self.groups.pop_front() {
+                        self.buffered -= group
+                            .packets
+                            .iter()
+                            .flatten()
+                            .map(Bytes::len)
+                            .sum::<usize>();
+                    }
+                }
+
+                self.groups.push_back(Group {
+                    id: header.group,
+                    packets: vec![None; header.group_size.into()],
+                });
+                self.groups.len() - 1
+            }
+        };
+
+        &mut self.groups[position]
+    }
+
+    /// Keeps a data packet to recover the packets of its group with,
+    /// returning whether it's new rather than one that was already received
+    /// or recovered.
+    fn receive(&mut self, header: &Header, payload: &[u8]) -> bool {
+        let group = self.group(header);
+        match group.packets.get_mut(usize::from(header.index)) {
+            Some(Some(_)) => false,
+            Some(packet) => {
+                *packet = Some(Bytes::copy_from_slice(payload));
+                self.buffered += payload.len();
+                true
+            }
+            // The group's size changed, it can't be recovered from.
+            None => true,
+        }
+    }
+
+    /// Recovers the packet a parity packet covers, if it's the only one of
+    /// them that's missing.
+    fn recover(
+        &mut self,
+        header: &Header,
+        mut length: u16,
+        payload: &[u8],
+    ) -> Result<Option<Bytes>, Error> {
+        let group = self.group(header);
+        if group.packets.len() != usize::from(header.group_size) {
+            return Ok(None);
+        }
+
+        let mut recovered = payload.to_vec();
+        let mut missing = None;
+        let covered =
+            (usize::from(header.index)..group.packets.len()).step_by(header.parity.into());
+        for index in covered {
+            match &group.packets[index] {
+                Some(packet) => {
+                    xor(&mut recovered, packet);
+                    length ^= packet.len() as u16;
+                }
+                None if missing.is_none() => missing = Some(index),
+                None => return Ok(None),
+            }
+        }
+
+        let Some(missing) = missing else {
+            return Ok(None);
+        };
+
+        if usize::from(length) > recovered.len() {
+            return Err(Error::Corrupt);
+        }
+
+        recovered.truncate(length.into());
+        let recovered = Bytes::from(recovered);
+        group.packets[missing] = Some(recovered.clone());
+        self.buffered += recovered.len();
+        Ok(Some(recovered))
+    }
+}
+
+/// XORs `bytes` into `into`, as if the shorter of them was padded with zeroes.
+fn xor(into: &mut Vec<u8>, bytes: &[u8]) {
+    if into.len() < bytes.len() {
+        into.resize(bytes.len(), 0);
+    }
+
+    for (a, b) in into.iter_mut().zip(bytes) {
+        *a ^= b;
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u8)]
+enum Kind {
+    Data = 0,
+    Parity = 1,
+    /// A data packet that isn't covered by any parity packet.
+    Unprotected = 2,
+}
+
+/// The header at the start of every encoded packet, followed by the XOR of
+/// the covered packets' lengths in parity packets.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct Header {
+    kind: Kind,
+    group: u16,
+    /// The packet's index in the group, or which of the group's parity
+    /// packets it is.
+    index: u8,
+    group_size: u8,
+    parity: u8,
+}
+
+impl Header {
+    const LEN: usize = 6;
+
+    fn parse(packet: &[u8]) -> Result<Self, Error> {
+        let Some(&[kind, group_high, group_low, index, group_size, parity]) =
+            packet.first_chunk::<{ Self::LEN }>()
+        else {
+            return Err(Error::TooShort);
+        };
+
+        let kind = match kind {
+            0 => Kind::Data,
+            1 => Kind::Parity,
+            2 => Kind::Unprotected,
+            _ => return Err(Error::InvalidHeader),
+        };
+
+        let indices = match kind {
+            Kind::Data | Kind::Unprotected => group_size,
+            Kind::Parity => parity,
+        };
+        if !(1..=MAX_GROUP_SIZE).contains(&group_size)
+            || !(1..=group_size).contains(&parity)
+            || index >= indices
+        {
+            return Err(Error::InvalidHeader);
+        }
+
+        Ok(Self {
+            kind,
+            group: u16::from_be_bytes([group_high, group_low]),
+            index,
+            group_size,
+            parity,
+        })
+    }
+
+    fn to_bytes(self) -> [u8; Self::LEN] {
+        let [group_high, group_low] = self.group.to_be_bytes();
+        [
+            self.kind as u8,
+            group_high,
+            group_low,
+            self.index,
+            self.group_size,
+            self.parity,
+        ]
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("packet is too short for a FEC header")]
+    TooShort,
+    #[error("packet has an invalid FEC header")]
+    InvalidHeader,
+    #[error("parity packet doesn't match the packets of its group")]
+    Corrupt,
+}
+
+/// Whether to do nothing, encode or decode the packet.
+#[derive(
+    Clone, Copy, Deserialize, Debug, Default, Eq, PartialEq, Serialize, schemars::JsonSchema,
+)]
+pub enum Action {
+    #[serde(rename = "DO_NOTHING")]
+    #[default]
+    DoNothing,
+    #[serde(rename = "ENCODE")]
+    Encode,
+    #[serde(rename = "DECODE")]
+    Decode,
+}
+
+impl From<Action> for proto::fec::Action {
+    fn from(action: Action) -> Self {
+        match action {
+            Action::DoNothing => Self::DoNothing,
+            Action::Encode => Self::Encode,
+            Action::Decode => Self::Decode,
+        }
+    }
+}
+
+impl From<proto::fec::Action> for Action {
+    fn from(action: proto::fec::Action) -> Self {
+        match action {
+            proto::fec::Action::DoNothing => Self::DoNothing,
+            proto::fec::Action::Encode => Self::Encode,
+            proto::fec::Action::Decode => Self::Decode,
+        }
+    }
+}
+
+/// How the packets of one direction are coded.
+#[derive(Clone, Copy, Deserialize, Debug, Eq, PartialEq, Serialize, schemars::JsonSchema)]
+pub struct Coding {
+    #[serde(default)]
+    pub action: Action,
+    /// the number of packets in a group, when encoding
+    #[serde(rename = "groupSize", default = "default_group_size")]
+    pub group_size: u8,
+    /// the number of parity packets sent after each group, when encoding
+    #[serde(default = "default_parity")]
+    pub parity: u8,
+}
+
+impl Default for Coding {
+    fn default() -> Self {
+        Self {
+            action: Action::default(),
+            group_size: default_group_size(),
+            parity: default_parity(),
+        }
+    }
+}
+
+impl Coding {
+    fn validate(&self, direction: &str) -> Result<(), CreationError> {
+        if !(1..=MAX_GROUP_SIZE).contains(&self.group_size) {
+            return Err(CreationError::FieldInvalid {
+                field: format!("{direction}.groupSize"),
+                reason: format!("value must be between 1 and {MAX_GROUP_SIZE}"),
+            });
+        }
+
+        if !(1..=self.group_size).contains(&self.parity) {
+            return Err(CreationError::FieldInvalid {
+                field: format!("{direction}.parity"),
+                reason: "value must be between 1 and the group size".into(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Default value for [`Coding::group_size`]
+fn default_group_size() -> u8 {
+    8
+}
+
+/// Default value for [`Coding::parity`]
+fn default_parity() -> u8 {
+    1
+}
+
+#[derive(
+    Clone, Copy, Default, Deserialize, Debug, Eq, PartialEq, Serialize, schemars::JsonSchema,
+)]
+pub struct Config {
+    #[serde(default)]
+    pub on_read: Coding,
+    #[serde(default)]
+    pub on_write: Coding,
+}
+
+impl From<Coding> for proto::fec::Coding {
+    fn from(coding: Coding) -> Self {
+        Self {
+            action: Some(proto::fec::ActionValue {
+                value: proto::fec::Action::from(coding.action) as i32,
+            }),
+            group_size: Some(coding.group_size.into()),
+            parity: Some(coding.parity.into()),
+        }
+    }
+}
+
+impl TryFrom<proto::fec::Coding> for Coding {
+    type Error = ConvertProtoConfigError;
+
+    fn try_from(p: proto::fec::Coding) -> Result<Self, Self::Error> {
+        let field = |value: Option<u32>, name: &str, default: fn() -> u8| {
+            value.map_or(Ok(default()), |value| {
+                u8::try_from(value).map_err(|_| {
+                    ConvertProtoConfigError::new(
+                        format!("invalid {name} `{value}`"),
+                        Some(name.into()),
+                    )
+                })
+            })
+        };
+
+        Ok(Self {
+            action: p
+                .action
+                .map(|action| action.value())
+                .map(Action::from)
+                .unwrap_or_default(),
+            group_size: field(p.group_size, "group_size", default_group_size)?,
+            parity: field(p.parity, "parity", default_parity)?,
+        })
+    }
+}
+
+impl From<Config> for proto::Fec {
+    fn from(config: Config) -> Self {
+        Self {
+            on_read: Some(config.on_read.into()),
+            on_write: Some(config.on_write.into()),
+        }
+    }
+}
+
+impl TryFrom<proto::Fec> for Config {
+    type Error = ConvertProtoConfigError;
+
+    fn try_from(p: proto::Fec) -> Result<Self, Self::Error> {
+        Ok(Self {
+            on_read: p
+                .on_read
+                .map(TryFrom::try_from)
+                .transpose()?
+                .unwrap_or_default(),
+            on_write: p
+                .on_write
+                .map(TryFrom::try_from)
+                .transpose()?
+                .unwrap_or_default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::alloc_buffer;
+
+    fn fec(on_read: Coding, on_write: Coding) -> Fec {
+        Fec::from_config(Some(Config { on_read, on_write }))
+    }
+
+    fn coding(action: Action, group_size: u8, parity: u8) -> Coding {
+        Coding {
+            action,
+            group_size,
+            parity,
+        }
+    }
+
+    fn client() -> EndpointAddress {
+        (std::net::Ipv4Addr::LOCALHOST, 80).into()
+    }
+
+    /// Encodes `packets` from the client, returning the encoded packets
+    /// followed by the parity packets of each group.
+    async fn encode(filter: &Fec, packets: &[&[u8]]) -> Vec<PoolBuffer> {
+        let mut encoded = Vec::new();
+        for packet in packets {
+            let mut ctx = ReadContext::new(<_>::default(), client(), alloc_buffer(packet));
+            filter.read(&mut ctx).await.unwrap();
+            encoded.push(ctx.contents);
+            encoded.append(&mut ctx.forwarded);
+        }
+        encoded
+    }
+
+    /// Decodes the packets sent to the client, returning the packets that
+    /// are forwarded.
+    async fn decode(filter: &Fec, packets: Vec<PoolBuffer>) -> Vec<Vec<u8>> {
+        let mut decoded = Vec::new();
+        for packet in packets {
+            let mut ctx = WriteContext::new(
+                (std::net::Ipv4Addr::LOCALHOST, 26000).into(),
+                client(),
+                packet,
+            );
+            filter.write(&mut ctx).await.unwrap();
+            if !ctx.is_handled() {
+                decoded.push(ctx.contents.to_vec());
+            }
+        }
+        decoded
+    }
+
+    #[tokio::test]
+    async fn encode_decode() {
+        let encoder = fec(coding(Action::Encode, 4, 1), <_>::default());
+        let decoder = fec(<_>::default(), coding(Action::Decode, 8, 1));
+
+        let packets: [&[u8]; 4] = [b"one", b"two", b"three", b"four"];
+        let encoded = encode(&encoder, &packets).await;
+        assert_eq!(5, encoded.len());
+        assert_eq!(
+            Header {
+                kind: Kind::Parity,
+                group: 0,
+                index: 0,
+                group_size: 4,
+                parity: 1,
+            },
+            Header::parse(&encoded[4]).unwrap()
+        );
+
+        // Every packet gets through, the parity packet is consumed.
+        assert_eq!(
+            packets.map(<[u8]>::to_vec),
+            &*decode(&decoder, encoded).await
+        );
+    }
+
+    #[tokio::test]
+    async fn recover() {
+        let encoder = fec(coding(Action::Encode, 4, 1), <_>::default());
+        let decoder = fec(<_>::default(), coding(Action::Decode, 8, 1));
+
+        let packets: [&[u8]; 8] = [
+            b"one", b"two", b"three", b"four", b"five", b"six", b"seven", b"eight",
+        ];
+        let mut encoded = encode(&encoder, &packets).await;
+        assert_eq!(10, encoded.len());
+
+        // Losing a packet from each group, the longest packet of the first.
+        encoded.remove(7);
+        encoded.remove(2);
+
+        // The counter is shared with the other tests.
+        let recovered = decoder.on_write.packets_recovered_total.get();
+        assert_eq!(
+            [
+                &b"one"[..],
+                b"two",
+                b"four",
+                b"three",
+                b"five",
+                b"six",
+                b"eight",
+                b"seven"
+            ]
+            .map(<[u8]>::to_vec),
+            &*decode(&decoder, encoded).await
+        );
+        assert!(decoder.on_write.packets_recovered_total.get() >= recovered + 2);
+    }
+
+    #[tokio::test]
+    async fn burst() {
+        let encoder = fec(coding(Action::Encode, 4, 2), <_>::default());
+        let decoder = fec(<_>::default(), coding(Action::Decode, 8, 1));
+
+        let packets: [&[u8]; 4] = [b"one", b"two", b"three", b"four"];
+        let mut encoded = encode(&encoder, &packets).await;
+        assert_eq!(6, encoded.len());
+
+        // With interleaved parity, two lost packets in a row are recovered.
+        encoded.drain(1..3);
+        assert_eq!(
+            [&b"one"[..], b"four", b"three", b"two"].map(<[u8]>::to_vec),
+            &*decode(&decoder, encoded).await
+        );
+
+        // Two lost packets covered by the same parity packet aren't.
+        let mut encoded = encode(&encoder, &packets).await;
+        encoded.remove(2);
+        encoded.remove(0);
+        assert_eq!(
+            [&b"two"[..], b"four"].map(<[u8]>::to_vec),
+            &*decode(&decoder, encoded).await
+        );
+    }
+
+    #[tokio::test]
+    async fn late_packet() {
+        let encoder = fec(coding(Action::Encode, 2, 1), <_>::default());
+        let decoder = fec(<_>::default(), coding(Action::Decode, 8, 1));
+
+        let mut encoded = encode(&encoder, &[&b"one"[..], b"two"]).await;
+        // The packet arrives after it was recovered, and isn't forwarded twice.
+        encoded.swap(1, 2);
+        assert_eq!(
+            [&b"one"[..], b"two"].map(<[u8]>::to_vec),
+            &*decode(&decoder, encoded).await
+        );
+    }
+
+    #[tokio::test]
+    async fn full() {
+        let mut encoder = fec(coding(Action::Encode, 2, 1), <_>::default());
+        let mut decoder = fec(<_>::default(), coding(Action::Decode, 8, 1));
+        encoder.on_read.streams = Streams::new(0, 0);
+
+        // Without room for the client's state, packets are forwarded without
+        // parity packets.
+        let packets: [&[u8]; 2] = [b"one", b"two"];
+        let encoded = encode(&encoder, &packets).await;
+        assert_eq!(2, encoded.len());
+        assert_eq!(Kind::Unprotected, Header::parse(&encoded[0]).unwrap().kind);
+        assert_eq!(
+            packets.map(<[u8]>::to_vec),
+            &*decode(&decoder, encoded).await
+        );
+
+        // Nor are packets kept to recover others with, which are forwarded
+        // while parity packets are consumed.
+        let encoded = encode(&fec(coding(Action::Encode, 2, 1), <_>::default()), &packets).await;
+        decoder.on_write.streams = Streams::new(0, 0);
+        assert_eq!(
+            packets.map(<[u8]>::to_vec),
+            &*decode(&decoder, encoded).await
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid() {
+        let decoder = fec(<_>::default(), coding(Action::Decode, 8, 1));
+        for packet in [&b"hello"[..], &[3, 0, 0, 0, 4, 1], &[0, 0, 0, 4, 4, 1]] {
+            let mut ctx = WriteContext::new(
+                (std::net::Ipv4Addr::LOCALHOST, 26000).into(),
+                client(),
+                alloc_buffer(packet),
+            );
+            assert!(decoder.write(&mut ctx).await.is_err());
+        }
+    }
+
+    #[test]
+    fn header() {
+        let header = Header {
+            kind: Kind::Data,
+            group: 0x1234,
+            index: 3,
+            group_size: 8,
+            parity: 2,
+        };
+        assert_eq!([0, 0x12, 0x34, 3, 8, 2], header.to_bytes());
+        assert_eq!(header, Header::parse(&header.to_bytes()).unwrap());
+
+        assert!(Header::parse(&[0, 0, 0, 0, 8]).is_err());
+        assert!(Header::parse(&[1, 0, 0, 2, 8, 2]).is_err());
+        assert!(Header::parse(&[0, 0, 0, 0, 8, 9]).is_err());
+        assert!(Header::parse(&[0, 0, 0, 0, MAX_GROUP_SIZE + 1, 1]).is_err());
+    }
+
+    #[test]
+    fn validate() {
+        let config = |group_size, parity| Config {
+            on_read: coding(Action::Encode, group_size, parity),
+            ..<_>::default()
+        };
+
+        assert!(Fec::try_from_config(Some(config(0, 1))).is_err());
+        assert!(Fec::try_from_config(Some(config(MAX_GROUP_SIZE + 1, 1))).is_err());
+        assert!(Fec::try_from_config(Some(config(4, 0))).is_err());
+        assert!(Fec::try_from_config(Some(config(4, 5))).is_err());
+    }
+
+    #[test]
+    fn convert_proto_config() {
+        let config = Config {
+            on_read: coding(Action::Decode, 8, 1),
+            on_write: coding(Action::Encode, 10, 2),
+        };
+        assert_eq!(config, Config::try_from(proto::Fec::from(config)).unwrap());
+        assert_eq!(
+            Config::default(),
+            Config::try_from(proto::Fec::default()).unwrap()
+        );
+        assert!(Config::try_from(proto::Fec {
+            on_read: Some(proto::fec::Coding {
+                group_size: Some(256),
+                ..<_>::default()
+            }),
+            on_write: None,
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn parse_config() {
+        let config: Config = serde_yaml::from_str(
+            "
+on_read:
+  action: DECODE
+on_write:
+  action: ENCODE
+  groupSize: 10
+  parity: 2
+",
+        )
+        .unwrap();
+
+        assert_eq!(
+            config,
+            Config {
+                on_read: coding(Action::Decode, 8, 1),
+                on_write: coding(Action::Encode, 10, 2),
+            }
+        );
+    }
+}
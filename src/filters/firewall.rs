@@ -21,7 +21,7 @@ use tracing::debug;
 use crate::filters::prelude::*;
 use crate::generated::quilkin::filters::firewall::v1alpha1 as proto;
 
-pub use config::{Action, Config, PortRange, PortRangeError, Rule};
+pub use config::{Action, Cidr, Config, PortRange, PortRangeError, Rule};
 
 /// Filter for allowing/blocking traffic by IP and port.
 pub struct Firewall {
@@ -0,0 +1,885 @@
+/*
+ * Copyright 2024 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Once, Weak},
+    time::Duration,
+};
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+
+use crate::{
+    filters::{
+        prelude::*,
+        streams::{self, Full, Streams},
+    },
+    metrics::Direction,
+    net::endpoint::EndpointAddress,
+    pool::{BufferPool, PoolBuffer},
+};
+
+use crate::generated::quilkin::filters::fragment::v1alpha1 as proto;
+
+/// The first byte of a packet that wasn't fragmented.
+const WHOLE: u8 = 0;
+/// The first byte of a fragment, followed by the message's ID, the
+/// fragment's index and the number of fragments.
+const FRAGMENT: u8 = 1;
+const FRAGMENT_HEADER_LEN: usize = 5;
+/// The smallest `maxSize`, leaving room for a useful amount of the message
+/// in each fragment.
+const MIN_SIZE: u16 = 64;
+
+/// Filter that splits packets larger than a maximum size into numbered
+/// fragments, and reassembles them on the other side of a link.
+///
+/// Reassembly keeps a bounded number of incomplete messages for each client,
+/// which are discarded once they time out or newer messages replace them.
+pub struct Fragment {
+    on_read: Fragmenter,
+    on_write: Fragmenter,
+}
+
+impl Fragment {
+    fn new(config: Config) -> Result<Self, CreationError> {
+        config.on_read.validate("on_read")?;
+        config.on_write.validate("on_write")?;
+
+        let pool = streams::buffer_pool();
+        Ok(Self {
+            on_read: Fragmenter::new(config.on_read, pool.clone(), Direction::Read),
+            on_write: Fragmenter::new(config.on_write, pool, Direction::Write),
+        })
+    }
+}
+
+impl StaticFilter for Fragment {
+    const NAME: &'static str = "quilkin.filters.fragment.v1alpha1.Fragment";
+    type Configuration = Config;
+    type BinaryConfiguration = proto::Fragment;
+
+    fn try_from_config(config: Option<Self::Configuration>) -> Result<Self, CreationError> {
+        Self::new(Self::ensure_config_exists(config)?)
+    }
+}
+
+#[async_trait::async_trait]
+impl Filter for Fragment {
+    async fn read(&self, ctx: &mut ReadContext) -> Result<(), FilterError> {
+        match self.on_read.config.action {
+            Action::DoNothing => {}
+            Action::Fragment => {
+                let fragments = self
+                    .on_read
+                    .fragment(&ctx.source, &mut ctx.contents)
+                    .map_err(FilterError::new)?;
+                for fragment in fragments {
+                    ctx.forward(fragment);
+                }
+            }
+            Action::Reassemble => {
+                if !self
+                    .on_read
+                    .reassemble(&ctx.source, &mut ctx.contents)
+                    .map_err(FilterError::new)?
+                {
+                    ctx.consume();
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn write(&self, ctx: &mut WriteContext) -> Result<(), FilterError> {
+        // The messages are numbered per client, which is the destination.
+        match self.on_write.config.action {
+            Action::DoNothing => {}
+            Action::Fragment => {
+                let fragments = self
+                    .on_write
+                    .fragment(&ctx.dest, &mut ctx.contents)
+                    .map_err(FilterError::new)?;
+                for fragment in fragments {
+                    ctx.forward(fragment);
+                }
+            }
+            Action::Reassemble => {
+                if !self
+                    .on_write
+                    .reassemble(&ctx.dest, &mut ctx.contents)
+                    .map_err(FilterError::new)?
+                {
+                    ctx.consume();
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Fragments or reassembles the packets of one direction, keeping the state
+/// of each client.
+struct Fragmenter {
+    config: Fragmentation,
+    streams: Arc<Streams<Stream>>,
+    /// Starts the task expiring incomplete messages with the first packet.
+    expiry: Once,
+    pool: Arc<BufferPool>,
+    messages_fragmented_total: prometheus::IntCounter,
+    messages_reassembled_total: prometheus::IntCounter,
+    messages_expired_total: prometheus::IntCounter,
+}
+
+impl Fragmenter {
+    fn new(config: Fragmentation, pool: Arc<BufferPool>, direction: Direction) -> Self {
+        Self {
+            config,
+            streams: Arc::new(Streams::new(streams::MAX_STREAMS, streams::MAX_BUFFERED)),
+            expiry: Once::new(),
+            pool,
+            messages_fragmented_total: crate::filters::metrics::counter(
+                Fragment::NAME,
+                "messages_fragmented_total",
+                "Total number of packets split into fragments",
+                direction,
+            ),
+            messages_reassembled_total: crate::filters::metrics::counter(
+                Fragment::NAME,
+                "messages_reassembled_total",
+                "Total number of packets reassembled from their fragments",
+                direction,
+            ),
+            messages_expired_total: crate::filters::metrics::counter(
+                Fragment::NAME,
+                "messages_expired_total",
+                "Total number of incomplete packets discarded before all of their fragments arrived",
+                direction,
+            ),
+        }
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_millis(self.config.timeout.into())
+    }
+
+    /// Adds the header to `contents`, splitting it into fragments if it's
+    /// larger than `maxSize`. `contents` becomes the first fragment, and the
+    /// rest of them are returned.
+    fn fragment(
+        &self,
+        key: &EndpointAddress,
+        contents: &mut PoolBuffer,
+    ) -> Result<Vec<PoolBuffer>, Error> {
+        let len = contents.len();
+        if len > self.config.max_message_size as usize {
+            return Err(Error::TooLarge(len));
+        }
+
+        if len < usize::from(self.config.max_size) {
+            contents.prepend_from_slice(&[WHOLE]);
+            return Ok(Vec::new());
+        }
+
+        let chunk = usize::from(self.config.max_size) - FRAGMENT_HEADER_LEN;
+        let count = u8::try_from(len.div_ceil(chunk)).map_err(|_| Error::TooManyFragments(len))?;
+        let id = self.streams.with(key, 0, |stream| {
+            let id = stream.next_id;
+            stream.next_id = id.wrapping_add(1);
+            id
+        })?;
+
+        let [id_high, id_low] = id.to_be_bytes();
+        let header = |index: u8| [FRAGMENT, id_high, id_low, index, count];
+        let fragments = contents[chunk..]
+            .chunks(chunk)
+            .zip(1..)
+            .map(|(fragment, index)| {
+                let mut packet = self
+                    .pool
+                    .clone()
+                    .alloc_sized(FRAGMENT_HEADER_LEN + fragment.len());
+                packet.extend_from_slice(&header(index));
+                packet.extend_from_slice(fragment);
+                packet
+            })
+            .collect();
+
+        contents.truncate(chunk);
+        contents.prepend_from_slice(&header(0));
+        self.messages_fragmented_total.inc();
+        Ok(fragments)
+    }
+
+    /// Removes the header from `contents`, or keeps a fragment until every
+    /// fragment of its message has arrived, replacing the last one with the
+    /// whole message. Returns whether there's a packet to forward.
+    fn reassemble(&self, key: &EndpointAddress, contents: &mut PoolBuffer) -> Result<bool, Error> {
+        match contents.first() {
+            Some(&WHOLE) => {
+                contents.split_prefix(1);
+                return Ok(true);
+            }
+            Some(&FRAGMENT) => {}
+            Some(_) => return Err(Error::InvalidHeader),
+            None => return Err(Error::TooShort),
+        }
+
+        let Some(&[_, id_high, id_low, index, count]) =
+            contents.first_chunk::<FRAGMENT_HEADER_LEN>()
+        else {
+            return Err(Error::TooShort);
+        };
+
+        if index >= count {
+            return Err(Error::InvalidHeader);
+        }
+
+        self.expiry.call_once(|| {
+            tokio::spawn(expire_messages(
+                Arc::downgrade(&self.streams),
+                self.timeout(),
+                self.messages_expired_total.clone(),
+            ));
+        });
+
+        let id = u16::from_be_bytes([id_high, id_low]);
+        let fragment = &contents[FRAGMENT_HEADER_LEN..];
+        let fragments = self.streams.with(key, fragment.len(), |stream| {
+            self.messages_expired_total
+                .inc_by(stream.expire(self.timeout()) as u64);
+
+            let position = match stream.messages.iter().position(|message| message.id == id) {
+                Some(position) => position,
+                None => {
+                    if stream.messages.len() >= self.config.max_messages as usize {
+                        stream.messages.pop_front();
+                        self.messages_expired_total.inc();
+                    }
+
+                    stream.messages.push_back(Message {
+                        id,
+                        fragments: vec![None; count.into()],
+                        received: 0,
+                        size: 0,
+                        started: Instant::now(),
+                    });
+                    stream.messages.len() - 1
+                }
+            };
+
+            let message = &mut stream.messages[position];
+            let Some(slot) = message.fragments.get_mut(usize::from(index)) else {
+                return Err(Error::InvalidHeader);
+            };
+
+            if slot.is_none() {
+                *slot = Some(Bytes::copy_from_slice(fragment));
+                message.received += 1;
+                message.size += fragment.len();
+            }
+
+            if message.size > self.config.max_message_size as usize {
+                let size = message.size;
+                stream.messages.remove(position);
+                return Err(Error::TooLarge(size));
+            }
+
+            if message.received < message.fragments.len() {
+                return Ok(None);
+            }
+
+            Ok(stream
+                .messages
+                .remove(position)
+                .map(|message| message.fragments))
+        })??;
+
+        let Some(fragments) = fragments else {
+            return Ok(false);
+        };
+
+        let size = fragments.iter().flatten().map(Bytes::len).sum();
+        let mut packet = self.pool.clone().alloc_sized(size);
+        for fragment in fragments.iter().flatten() {
+            packet.extend_from_slice(fragment);
+        }
+
+        *contents = packet;
+        self.messages_reassembled_total.inc();
+        Ok(true)
+    }
+}
+
+/// Discards the incomplete messages which timed out, even when their client
+/// sends nothing more, until the filter is dropped.
+async fn expire_messages(
+    streams: Weak<Streams<Stream>>,
+    timeout: Duration,
+    messages_expired_total: prometheus::IntCounter,
+) {
+    let mut interval = tokio::time::interval(timeout);
+    loop {
+        interval.tick().await;
+        let Some(streams) = streams.upgrade() else {
+            return;
+        };
+
+        if streams.buffered() == 0 {
+            continue;
+        }
+
+        streams.for_each(|stream| {
+            messages_expired_total.inc_by(stream.expire(timeout) as u64);
+        });
+    }
+}
+
+/// The fragmentation state of a client.
+#[derive(Default)]
+struct Stream {
+    next_id: u16,
+    /// The messages being reassembled, oldest first.
+    messages: VecDeque<Message>,
+}
+
+impl Stream {
+    /// Discards the incomplete messages older than `timeout`, returning how
+    /// many were discarded.
+    fn expire(&mut self, timeout: Duration) -> usize {
+        let incomplete = self.messages.len();
+        self.messages
+            .retain(|message| message.started.elapsed() < timeout);
+        incomplete - self.messages.len()
+    }
+}
+
+impl streams::Stream for Stream {
+    fn buffered(&self) -> usize {
+        self.messages.iter().map(|message| message.size).sum()
+    }
+}
+
+/// A message being reassembled.
+struct Message {
+    id: u16,
+    fragments: Vec<Option<Bytes>>,
+    received: usize,
+    size: usize,
+    started: Instant,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("packet is too short for a fragment header")]
+    TooShort,
+    #[error("packet has an invalid fragment header")]
+    InvalidHeader,
+    #[error("message of {0} bytes is larger than the maximum message size")]
+    TooLarge(usize),
+    #[error("message of {0} bytes needs more than 255 fragments")]
+    TooManyFragments(usize),
+    #[error(transparent)]
+    Full(#[from] Full),
+}
+
+/// Whether to do nothing, fragment or reassemble the packet.
+#[derive(
+    Clone, Copy, Deserialize, Debug, Default, Eq, PartialEq, Serialize, schemars::JsonSchema,
+)]
+pub enum Action {
+    #[serde(rename = "DO_NOTHING")]
+    #[default]
+    DoNothing,
+    #[serde(rename = "FRAGMENT")]
+    Fragment,
+    #[serde(rename = "REASSEMBLE")]
+    Reassemble,
+}
+
+impl From<Action> for proto::fragment::Action {
+    fn from(action: Action) -> Self {
+        match action {
+            Action::DoNothing => Self::DoNothing,
+            Action::Fragment => Self::Fragment,
+            Action::Reassemble => Self::Reassemble,
+        }
+    }
+}
+
+impl From<proto::fragment::Action> for Action {
+    fn from(action: proto::fragment::Action) -> Self {
+        match action {
+            proto::fragment::Action::DoNothing => Self::DoNothing,
+            proto::fragment::Action::Fragment => Self::Fragment,
+            proto::fragment::Action::Reassemble => Self::Reassemble,
+        }
+    }
+}
+
+/// How the packets of one direction are fragmented or reassembled.
+#[derive(Clone, Copy, Deserialize, Debug, Eq, PartialEq, Serialize, schemars::JsonSchema)]
+pub struct Fragmentation {
+    #[serde(default)]
+    pub action: Action,
+    /// the largest size of a packet once fragmented, including its header
+    #[serde(rename = "maxSize", default = "default_max_size")]
+    pub max_size: u16,
+    /// the largest size of a message, larger messages are dropped
+    #[serde(rename = "maxMessageSize", default = "default_max_message_size")]
+    pub max_message_size: u32,
+    /// the most messages of each client being reassembled at once
+    #[serde(rename = "maxMessages", default = "default_max_messages")]
+    pub max_messages: u32,
+    /// how long the fragments of a message are kept waiting for the rest of
+    /// them, in milliseconds
+    #[serde(default = "default_timeout")]
+    pub timeout: u32,
+}
+
+impl Default for Fragmentation {
+    fn default() -> Self {
+        Self {
+            action: Action::default(),
+            max_size: default_max_size(),
+            max_message_size: default_max_message_size(),
+            max_messages: default_max_messages(),
+            timeout: default_timeout(),
+        }
+    }
+}
+
+impl Fragmentation {
+    fn validate(&self, direction: &str) -> Result<(), CreationError> {
+        let invalid = |field: &str, reason: String| CreationError::FieldInvalid {
+            field: format!("{direction}.{field}"),
+            reason,
+        };
+
+        if self.max_size < MIN_SIZE {
+            return Err(invalid(
+                "maxSize",
+                format!("value must be at least {MIN_SIZE}"),
+            ));
+        }
+        if self.max_messages == 0 {
+            return Err(invalid("maxMessages", "value must be at least 1".into()));
+        }
+        if self.timeout == 0 {
+            return Err(invalid("timeout", "value must be at least 1".into()));
+        }
+
+        Ok(())
+    }
+}
+
+/// Default value for [`Fragmentation::max_size`]
+fn default_max_size() -> u16 {
+    1200
+}
+
+/// Default value for [`Fragmentation::max_message_size`]
+fn default_max_message_size() -> u32 {
+    64 * 1024
+}
+
+/// Default value for [`Fragmentation::max_messages`]
+fn default_max_messages() -> u32 {
+    4
+}
+
+/// Default value for [`Fragmentation::timeout`]
+fn default_timeout() -> u32 {
+    1000
+}
+
+#[derive(
+    Clone, Copy, Default, Deserialize, Debug, Eq, PartialEq, Serialize, schemars::JsonSchema,
+)]
+pub struct Config {
+    #[serde(default)]
+    pub on_read: Fragmentation,
+    #[serde(default)]
+    pub on_write: Fragmentation,
+}
+
+impl From<Fragmentation> for proto::fragment::Fragmentation {
+    fn from(fragmentation: Fragmentation) -> Self {
+        Self {
+            action: Some(proto::fragment::ActionValue {
+                value: proto::fragment::Action::from(fragmentation.action) as i32,
+            }),
+            max_size: Some(fragmentation.max_size.into()),
+            max_message_size: Some(fragmentation.max_message_size),
+            max_messages: Some(fragmentation.max_messages),
+            timeout: Some(fragmentation.timeout),
+        }
+    }
+}
+
+impl TryFrom<proto::fragment::Fragmentation> for Fragmentation {
+    type Error = ConvertProtoConfigError;
+
+    fn try_from(p: proto::fragment::Fragmentation) -> Result<Self, Self::Error> {
+        let max_size = p
+            .max_size
+            .map(|max_size| {
+                u16::try_from(max_size).map_err(|_| {
+                    ConvertProtoConfigError::new(
+                        format!("invalid max_size `{max_size}`"),
+                        Some("max_size".into()),
+                    )
+                })
+            })
+            .transpose()?
+            .unwrap_or_else(default_max_size);
+
+        Ok(Self {
+            action: p
+                .action
+                .map(|action| action.value())
+                .map(Action::from)
+                .unwrap_or_default(),
+            max_size,
+            max_message_size: p.max_message_size.unwrap_or_else(default_max_message_size),
+            max_messages: p.max_messages.unwrap_or_else(default_max_messages),
+            timeout: p.timeout.unwrap_or_else(default_timeout),
+        })
+    }
+}
+
+impl From<Config> for proto::Fragment {
+    fn from(config: Config) -> Self {
+        Self {
+            on_read: Some(config.on_read.into()),
+            on_write: Some(config.on_write.into()),
+        }
+    }
+}
+
+impl TryFrom<proto::Fragment> for Config {
+    type Error = ConvertProtoConfigError;
+
+    fn try_from(p: proto::Fragment) -> Result<Self, Self::Error> {
+        Ok(Self {
+            on_read: p
+                .on_read
+                .map(TryFrom::try_from)
+                .transpose()?
+                .unwrap_or_default(),
+            on_write: p
+                .on_write
+                .map(TryFrom::try_from)
+                .transpose()?
+                .unwrap_or_default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::alloc_buffer;
+
+    fn fragmentation(action: Action) -> Fragmentation {
+        Fragmentation {
+            action,
+            max_size: MIN_SIZE,
+            ..<_>::default()
+        }
+    }
+
+    fn fragment(on_read: Fragmentation, on_write: Fragmentation) -> Fragment {
+        Fragment::from_config(Some(Config { on_read, on_write }))
+    }
+
+    fn client() -> EndpointAddress {
+        (std::net::Ipv4Addr::LOCALHOST, 80).into()
+    }
+
+    /// Fragments a packet from the client, returning its fragments.
+    async fn split(filter: &Fragment, packet: &[u8]) -> Vec<PoolBuffer> {
+        let mut ctx = ReadContext::new(<_>::default(), client(), alloc_buffer(packet));
+        filter.read(&mut ctx).await.unwrap();
+        std::iter::once(ctx.contents).chain(ctx.forwarded).collect()
+    }
+
+    /// Reassembles a packet sent to the client, returning it once it's
+    /// complete.
+    async fn join(filter: &Fragment, packet: PoolBuffer) -> Result<Option<Vec<u8>>, FilterError> {
+        let mut ctx = WriteContext::new(
+            (std::net::Ipv4Addr::LOCALHOST, 26000).into(),
+            client(),
+            packet,
+        );
+        filter.write(&mut ctx).await?;
+        Ok((!ctx.is_handled()).then(|| ctx.contents.to_vec()))
+    }
+
+    fn message(len: usize) -> Vec<u8> {
+        (0..len).map(|i| i as u8).collect()
+    }
+
+    #[tokio::test]
+    async fn whole() {
+        let fragmenter = fragment(fragmentation(Action::Fragment), <_>::default());
+        let reassembler = fragment(<_>::default(), fragmentation(Action::Reassemble));
+
+        let packets = split(&fragmenter, b"hello").await;
+        assert_eq!(1, packets.len());
+        assert_eq!(b"\0hello", &*packets[0]);
+
+        let packet = packets.into_iter().next().unwrap();
+        assert_eq!(
+            Some(b"hello".to_vec()),
+            join(&reassembler, packet).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn fragment_reassemble() {
+        let fragmenter = fragment(fragmentation(Action::Fragment), <_>::default());
+        let reassembler = fragment(<_>::default(), fragmentation(Action::Reassemble));
+
+        let packet = message(200);
+        let mut fragments = split(&fragmenter, &packet).await;
+        assert_eq!(4, fragments.len());
+        assert!(fragments
+            .iter()
+            .all(|fragment| fragment.len() <= usize::from(MIN_SIZE)));
+        assert_eq!([FRAGMENT, 0, 0, 0, 4], fragments[0][..FRAGMENT_HEADER_LEN]);
+        assert_eq!([FRAGMENT, 0, 0, 3, 4], fragments[3][..FRAGMENT_HEADER_LEN]);
+
+        // The fragments of another message, interleaved and out of order.
+        let mut other = split(&fragmenter, &message(100)).await;
+        assert_eq!(2, other.len());
+
+        fragments.swap(0, 2);
+        let mut received = Vec::new();
+        for fragment in [
+            fragments.remove(0),
+            other.pop().unwrap(),
+            fragments.remove(0),
+            fragments.remove(0),
+            other.pop().unwrap(),
+            fragments.remove(0),
+        ] {
+            received.push(join(&reassembler, fragment).await.unwrap());
+        }
+
+        assert_eq!(
+            vec![None, None, None, None, Some(message(100)), Some(packet)],
+            received
+        );
+    }
+
+    #[tokio::test]
+    async fn bounded() {
+        tokio::time::pause();
+        let fragmenter = fragment(fragmentation(Action::Fragment), <_>::default());
+        let reassembler = fragment(
+            <_>::default(),
+            Fragmentation {
+                max_messages: 2,
+                timeout: 100,
+                ..fragmentation(Action::Reassemble)
+            },
+        );
+
+        // Incomplete messages are discarded after the timeout.
+        let mut fragments = split(&fragmenter, &message(100)).await;
+        assert_eq!(None, join(&reassembler, fragments.remove(0)).await.unwrap());
+        tokio::time::advance(Duration::from_millis(200)).await;
+        assert_eq!(None, join(&reassembler, fragments.remove(0)).await.unwrap());
+
+        // And replaced by newer messages once there are too many.
+        let mut first = split(&fragmenter, &message(100)).await;
+        let mut second = split(&fragmenter, &message(100)).await;
+        let mut third = split(&fragmenter, &message(100)).await;
+        for fragments in [&mut first, &mut second, &mut third] {
+            assert_eq!(None, join(&reassembler, fragments.remove(0)).await.unwrap());
+        }
+        assert_eq!(None, join(&reassembler, first.remove(0)).await.unwrap());
+        assert_eq!(
+            Some(message(100)),
+            join(&reassembler, third.remove(0)).await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn expired_without_packets() {
+        tokio::time::pause();
+        let fragmenter = fragment(fragmentation(Action::Fragment), <_>::default());
+        let reassembler = fragment(
+            <_>::default(),
+            Fragmentation {
+                timeout: 100,
+                ..fragmentation(Action::Reassemble)
+            },
+        );
+
+        let mut fragments = split(&fragmenter, &message(100)).await;
+        assert_eq!(None, join(&reassembler, fragments.remove(0)).await.unwrap());
+        assert_ne!(0, reassembler.on_write.streams.buffered());
+
+        // The incomplete message is discarded although the client sends
+        // nothing more.
+        tokio::time::advance(Duration::from_millis(150)).await;
+        tokio::time::advance(Duration::from_millis(100)).await;
+        tokio::task::yield_now().await;
+        assert_eq!(0, reassembler.on_write.streams.buffered());
+    }
+
+    #[tokio::test]
+    async fn too_large() {
+        let fragmenter = fragment(
+            Fragmentation {
+                max_message_size: 1000,
+                ..fragmentation(Action::Fragment)
+            },
+            <_>::default(),
+        );
+        let mut ctx = ReadContext::new(<_>::default(), client(), alloc_buffer(message(1001)));
+        assert!(fragmenter.read(&mut ctx).await.is_err());
+
+        // Messages that would need more than 255 fragments can't be sent.
+        let fragmenter = fragment(fragmentation(Action::Fragment), <_>::default());
+        let mut ctx = ReadContext::new(<_>::default(), client(), alloc_buffer(message(60_000)));
+        assert!(fragmenter.read(&mut ctx).await.is_err());
+
+        // The reassembled message can't grow past the maximum either.
+        let reassembler = fragment(
+            <_>::default(),
+            Fragmentation {
+                max_message_size: 100,
+                ..fragmentation(Action::Reassemble)
+            },
+        );
+        let mut fragments = split(
+            &fragment(fragmentation(Action::Fragment), <_>::default()),
+            &message(200),
+        )
+        .await;
+        assert_eq!(None, join(&reassembler, fragments.remove(0)).await.unwrap());
+        assert!(join(&reassembler, fragments.remove(0)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn invalid() {
+        let reassembler = fragment(<_>::default(), fragmentation(Action::Reassemble));
+        for packet in [
+            &b""[..],
+            &[2, 0],
+            &[FRAGMENT, 0, 0],
+            &[FRAGMENT, 0, 0, 4, 4],
+        ] {
+            assert!(join(&reassembler, alloc_buffer(packet)).await.is_err());
+        }
+    }
+
+    #[test]
+    fn validate() {
+        let config = |fragmentation| Config {
+            on_write: fragmentation,
+            ..<_>::default()
+        };
+
+        assert!(Fragment::try_from_config(Some(config(Fragmentation {
+            max_size: MIN_SIZE - 1,
+            ..<_>::default()
+        })))
+        .is_err());
+        assert!(Fragment::try_from_config(Some(config(Fragmentation {
+            max_messages: 0,
+            ..<_>::default()
+        })))
+        .is_err());
+        assert!(Fragment::try_from_config(Some(config(Fragmentation {
+            timeout: 0,
+            ..<_>::default()
+        })))
+        .is_err());
+    }
+
+    #[test]
+    fn convert_proto_config() {
+        let config = Config {
+            on_read: Fragmentation {
+                action: Action::Reassemble,
+                max_messages: 8,
+                timeout: 500,
+                ..<_>::default()
+            },
+            on_write: Fragmentation {
+                action: Action::Fragment,
+                max_size: 1400,
+                max_message_size: 16 * 1024,
+                ..<_>::default()
+            },
+        };
+        assert_eq!(
+            config,
+            Config::try_from(proto::Fragment::from(config)).unwrap()
+        );
+        assert_eq!(
+            Config::default(),
+            Config::try_from(proto::Fragment::default()).unwrap()
+        );
+        assert!(Config::try_from(proto::Fragment {
+            on_read: Some(proto::fragment::Fragmentation {
+                max_size: Some(70_000),
+                ..<_>::default()
+            }),
+            on_write: None,
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn parse_config() {
+        let config: Config = serde_yaml::from_str(
+            "
+on_read:
+  action: REASSEMBLE
+  timeout: 500
+on_write:
+  action: FRAGMENT
+  maxSize: 1400
+",
+        )
+        .unwrap();
+
+        assert_eq!(
+            config,
+            Config {
+                on_read: Fragmentation {
+                    action: Action::Reassemble,
+                    timeout: 500,
+                    ..<_>::default()
+                },
+                on_write: Fragmentation {
+                    action: Action::Fragment,
+                    max_size: 1400,
+                    ..<_>::default()
+                },
+            }
+        );
+    }
+}
@@ -0,0 +1,388 @@
+/*
+ * Copyright 2024 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    filters::{capture::CAPTURED_BYTES, prelude::*},
+    net::endpoint::metadata,
+};
+
+use crate::generated::quilkin::filters::label_router::v1alpha1 as proto;
+
+/// Filter that only allows packets to be passed to endpoints that have a
+/// label matching the value stored in the Filter's dynamic metadata.
+pub struct LabelRouter {
+    config: Config,
+}
+
+impl LabelRouter {
+    fn new(config: Config) -> Result<Self, CreationError> {
+        if config.label.is_empty() {
+            return Err(CreationError::FieldInvalid {
+                field: "label".into(),
+                reason: "label must not be empty".into(),
+            });
+        }
+
+        Ok(Self { config })
+    }
+
+    /// Converts the value from the Filter's dynamic metadata into the string
+    /// that is compared with endpoint labels.
+    fn value<'value>(&self, value: &'value metadata::Value) -> Result<Cow<'value, str>, Error> {
+        match value {
+            metadata::Value::String(value) => Ok(Cow::Borrowed(value)),
+            metadata::Value::Bytes(value) => std::str::from_utf8(value)
+                .map(Cow::Borrowed)
+                .map_err(|_| Error::InvalidType(self.config.metadata_key, value.clone().into())),
+            metadata::Value::Number(value) => Ok(Cow::Owned(value.to_string())),
+            metadata::Value::Bool(value) => Ok(Cow::Owned(value.to_string())),
+            metadata::Value::List(_) => {
+                Err(Error::InvalidType(self.config.metadata_key, value.clone()))
+            }
+        }
+    }
+}
+
+impl StaticFilter for LabelRouter {
+    const NAME: &'static str = "quilkin.filters.label_router.v1alpha1.LabelRouter";
+    type Configuration = Config;
+    type BinaryConfiguration = proto::LabelRouter;
+
+    fn try_from_config(config: Option<Self::Configuration>) -> Result<Self, CreationError> {
+        Self::new(Self::ensure_config_exists(config)?)
+    }
+}
+
+#[async_trait::async_trait]
+impl Filter for LabelRouter {
+    async fn read(&self, ctx: &mut ReadContext) -> Result<(), FilterError> {
+        let value = ctx
+            .metadata
+            .get(&self.config.metadata_key)
+            .ok_or(Error::NoValueFound(self.config.metadata_key))
+            .and_then(|value| self.value(value))
+            .map_err(FilterError::new)?;
+
+        ctx.destinations = ctx
+            .endpoints
+            .filter_endpoints(|endpoint| {
+                endpoint
+                    .metadata
+                    .known
+                    .labels
+                    .get(&self.config.label)
+                    .map_or(false, |label| self.config.match_type.matches(label, &value))
+            })
+            .into_iter()
+            .map(|endpoint| endpoint.address)
+            .collect();
+
+        if ctx.destinations.is_empty() {
+            Err(FilterError::new(Error::NoEndpointMatch(
+                self.config.label.clone(),
+                value.into_owned(),
+            )))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("no value found for `{0}`")]
+    NoValueFound(metadata::Key),
+    #[error("key `{0}` was found but couldn't be compared with a label, found {1:?}")]
+    InvalidType(metadata::Key, metadata::Value),
+    #[error("no endpoint matched label `{0}` with `{1}`")]
+    NoEndpointMatch(String, String),
+}
+
+/// How the value from the Filter's dynamic metadata is compared with an
+/// endpoint's label.
+#[derive(
+    Clone, Copy, Debug, Default, Deserialize, Serialize, Eq, PartialEq, schemars::JsonSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum MatchType {
+    /// The label is equal to the value.
+    #[default]
+    Exact,
+    /// The label starts with the value, e.g. a value of `1.2` matches a
+    /// `build` label of `1.2.3`.
+    Prefix,
+    /// The label is a comma separated list which contains the value, e.g. a
+    /// value of `ranked` matches a `game_modes` label of `casual,ranked`.
+    OneOf,
+}
+
+impl MatchType {
+    pub fn matches(self, label: &str, value: &str) -> bool {
+        match self {
+            Self::Exact => label == value,
+            Self::Prefix => label.starts_with(value),
+            Self::OneOf => label.split(',').any(|label| label.trim() == value),
+        }
+    }
+}
+
+impl From<MatchType> for proto::label_router::MatchType {
+    fn from(match_type: MatchType) -> Self {
+        match match_type {
+            MatchType::Exact => Self::Exact,
+            MatchType::Prefix => Self::Prefix,
+            MatchType::OneOf => Self::OneOf,
+        }
+    }
+}
+
+impl From<proto::label_router::MatchType> for MatchType {
+    fn from(match_type: proto::label_router::MatchType) -> Self {
+        match match_type {
+            proto::label_router::MatchType::Exact => Self::Exact,
+            proto::label_router::MatchType::Prefix => Self::Prefix,
+            proto::label_router::MatchType::OneOf => Self::OneOf,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, schemars::JsonSchema)]
+pub struct Config {
+    /// the key to use when retrieving the value from the Filter's dynamic metadata
+    #[serde(rename = "metadataKey", default = "default_metadata_key")]
+    pub metadata_key: metadata::Key,
+    /// the endpoint label that the value is compared with
+    pub label: String,
+    /// how the value is compared with the label
+    #[serde(rename = "matchType", default)]
+    pub match_type: MatchType,
+}
+
+/// Default value for [`Config::metadata_key`]
+fn default_metadata_key() -> metadata::Key {
+    metadata::Key::from_static(CAPTURED_BYTES)
+}
+
+impl From<Config> for proto::LabelRouter {
+    fn from(config: Config) -> Self {
+        Self {
+            metadata_key: Some(config.metadata_key.to_string()),
+            label: config.label,
+            match_type: Some(proto::label_router::MatchTypeValue {
+                value: proto::label_router::MatchType::from(config.match_type) as i32,
+            }),
+        }
+    }
+}
+
+impl TryFrom<proto::LabelRouter> for Config {
+    type Error = ConvertProtoConfigError;
+
+    fn try_from(p: proto::LabelRouter) -> Result<Self, Self::Error> {
+        let match_type = p
+            .match_type
+            .map(|value| {
+                proto::label_router::MatchType::try_from(value.value)
+                    .map(MatchType::from)
+                    .map_err(|_| {
+                        ConvertProtoConfigError::new(
+                            format!("invalid match type `{}`", value.value),
+                            Some("match_type".into()),
+                        )
+                    })
+            })
+            .transpose()?
+            .unwrap_or_default();
+
+        Ok(Self {
+            metadata_key: p
+                .metadata_key
+                .map(metadata::Key::new)
+                .unwrap_or_else(default_metadata_key),
+            label: p.label,
+            match_type,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        net::endpoint::{metadata::Value, Endpoint, Metadata},
+        test::assert_write_no_change,
+    };
+
+    use super::*;
+
+    fn endpoint(port: u16, labels: &[(&str, &str)]) -> Endpoint {
+        Endpoint::with_metadata(
+            (std::net::Ipv4Addr::LOCALHOST, port).into(),
+            Metadata {
+                tokens: <_>::default(),
+                labels: labels
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .collect(),
+            },
+        )
+    }
+
+    fn new_ctx(value: Value) -> ReadContext {
+        let endpoints = crate::net::cluster::ClusterMap::default();
+        endpoints.insert_default(
+            [
+                endpoint(80, &[("build", "1.2.3"), ("modes", "casual,ranked")]),
+                endpoint(90, &[("build", "1.3.0"), ("modes", "casual")]),
+                endpoint(100, &[]),
+            ]
+            .into(),
+        );
+
+        let pool = std::sync::Arc::new(crate::pool::BufferPool::new(1, 5));
+        let mut ctx = ReadContext::new(
+            endpoints.into(),
+            "127.0.0.1:200".parse().unwrap(),
+            pool.alloc_slice(b"hello"),
+        );
+        ctx.metadata.insert(CAPTURED_BYTES.into(), value);
+        ctx
+    }
+
+    fn filter(label: &str, match_type: MatchType) -> LabelRouter {
+        LabelRouter::from_config(
+            Config {
+                metadata_key: default_metadata_key(),
+                label: label.into(),
+                match_type,
+            }
+            .into(),
+        )
+    }
+
+    fn ports(ctx: &ReadContext) -> Vec<u16> {
+        let mut ports: Vec<_> = ctx.destinations.iter().map(|addr| addr.port).collect();
+        ports.sort();
+        ports
+    }
+
+    #[tokio::test]
+    async fn exact() {
+        let filter = filter("build", MatchType::Exact);
+
+        let mut ctx = new_ctx(Value::Bytes("1.2.3".into()));
+        filter.read(&mut ctx).await.unwrap();
+        assert_eq!(ports(&ctx), [80]);
+
+        let mut ctx = new_ctx(Value::String("1.3.0".into()));
+        filter.read(&mut ctx).await.unwrap();
+        assert_eq!(ports(&ctx), [90]);
+
+        let mut ctx = new_ctx(Value::String("1.2".into()));
+        assert!(filter.read(&mut ctx).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn prefix() {
+        let filter = filter("build", MatchType::Prefix);
+
+        let mut ctx = new_ctx(Value::Bytes("1.".into()));
+        filter.read(&mut ctx).await.unwrap();
+        assert_eq!(ports(&ctx), [80, 90]);
+
+        let mut ctx = new_ctx(Value::Bytes("1.2".into()));
+        filter.read(&mut ctx).await.unwrap();
+        assert_eq!(ports(&ctx), [80]);
+
+        let mut ctx = new_ctx(Value::Bytes("2".into()));
+        assert!(filter.read(&mut ctx).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn one_of() {
+        let filter = filter("modes", MatchType::OneOf);
+
+        let mut ctx = new_ctx(Value::Bytes("casual".into()));
+        filter.read(&mut ctx).await.unwrap();
+        assert_eq!(ports(&ctx), [80, 90]);
+
+        let mut ctx = new_ctx(Value::Bytes("ranked".into()));
+        filter.read(&mut ctx).await.unwrap();
+        assert_eq!(ports(&ctx), [80]);
+
+        let mut ctx = new_ctx(Value::Bytes("rank".into()));
+        assert!(filter.read(&mut ctx).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn invalid_value() {
+        let filter = filter("build", MatchType::Exact);
+
+        let mut ctx = new_ctx(Value::List(Vec::new()));
+        assert!(filter.read(&mut ctx).await.is_err());
+
+        let mut ctx = new_ctx(Value::Bytes(vec![0xff, 0xfe].into()));
+        assert!(filter.read(&mut ctx).await.is_err());
+
+        let pool = std::sync::Arc::new(crate::pool::BufferPool::new(1, 5));
+        let mut ctx = ReadContext::new(
+            <_>::default(),
+            "127.0.0.1:200".parse().unwrap(),
+            pool.alloc_slice(b"hello"),
+        );
+        assert!(filter.read(&mut ctx).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn write() {
+        assert_write_no_change(&filter("build", MatchType::Exact)).await;
+    }
+
+    #[test]
+    fn empty_label() {
+        assert!(LabelRouter::try_from_config(Some(Config {
+            metadata_key: default_metadata_key(),
+            label: String::new(),
+            match_type: MatchType::Exact,
+        }))
+        .is_err());
+    }
+
+    #[test]
+    fn convert_proto_config() {
+        let config = Config {
+            metadata_key: "foo".into(),
+            label: "build".into(),
+            match_type: MatchType::OneOf,
+        };
+        let binary = proto::LabelRouter::from(Config {
+            metadata_key: "foo".into(),
+            label: "build".into(),
+            match_type: MatchType::OneOf,
+        });
+        assert_eq!(config, Config::try_from(binary).unwrap());
+
+        assert!(Config::try_from(proto::LabelRouter {
+            metadata_key: None,
+            label: "build".into(),
+            match_type: Some(proto::label_router::MatchTypeValue { value: 42 }),
+        })
+        .is_err());
+    }
+}
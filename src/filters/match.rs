@@ -17,43 +17,86 @@
 mod config;
 mod metrics;
 
-use crate::{filters::prelude::*, net::endpoint::metadata};
+use crate::{
+    filters::{
+        predicate::{Comparison, MetadataPredicate, Predicate},
+        prelude::*,
+    },
+    net::endpoint::metadata,
+};
 
 use self::metrics::Metrics;
 
-pub use self::config::{Branch, Config, DirectionalConfig, Fallthrough};
+pub use self::config::{Branch, Condition, Config, DirectionalConfig, Fallthrough};
 use crate::generated::quilkin::filters::matches::v1alpha1 as proto;
 
+/// The comparisons a branch matches with.
+enum Matcher {
+    /// A comparison against the value of the `metadata_key`.
+    Value(Comparison),
+    All(Vec<MetadataPredicate>),
+    Any(Vec<MetadataPredicate>),
+}
+
+impl Matcher {
+    /// Returns whether the branch matches, or `None` when it's a comparison
+    /// against the value of the `metadata_key` and there's no value.
+    fn matches(
+        &self,
+        value: Option<&metadata::Value>,
+        metadata: &metadata::DynamicMetadata,
+    ) -> Option<bool> {
+        Some(match self {
+            Self::Value(comparison) => comparison.matches(value?),
+            Self::All(predicates) => predicates.iter().all(|p| p.matches(metadata)),
+            Self::Any(predicates) => predicates.iter().any(|p| p.matches(metadata)),
+        })
+    }
+}
+
+impl From<Condition> for Matcher {
+    fn from(condition: Condition) -> Self {
+        match condition {
+            Condition::All(predicates) => Self::All(predicates),
+            Condition::Any(predicates) => Self::Any(predicates),
+            condition => Self::Value(
+                condition
+                    .comparison()
+                    .expect("only `all` and `any` aren't a single comparison"),
+            ),
+        }
+    }
+}
+
 struct ConfigInstance {
     metadata_key: metadata::Key,
-    branches: Vec<(metadata::Value, (metadata::Key, FilterInstance))>,
+    branches: Vec<(Matcher, (metadata::Key, FilterInstance))>,
     fallthrough: (metadata::Key, FilterInstance),
 }
 
 impl ConfigInstance {
     fn new(config: config::DirectionalConfig) -> Result<Self, CreationError> {
-        let map_to_instance =
-            |filter: String, config_type: Option<serde_json::Value>| -> Result<_, CreationError> {
-                let instance = crate::filters::FilterRegistry::get(
-                    &filter,
-                    CreateFilterArgs::new(config_type.map(From::from)),
-                )?;
-                Ok((filter.into(), instance))
-            };
+        let map_to_instance = |filter: crate::config::Filter| -> Result<_, CreationError> {
+            let instance = crate::filters::FilterRegistry::get(
+                &filter.name,
+                CreateFilterArgs::new(filter.config.map(From::from)),
+            )?
+            .with_when(filter.when);
+            Ok((filter.name.into(), instance))
+        };
 
         let branches = config
             .branches
             .into_iter()
             .map(|branch| {
-                map_to_instance(branch.filter.name, branch.filter.config.clone())
-                    .map(|instance| (branch.value.clone(), instance))
+                map_to_instance(branch.filter).map(|instance| (branch.condition.into(), instance))
             })
             .collect::<Result<_, _>>()?;
 
         Ok(Self {
             metadata_key: config.metadata_key,
             branches,
-            fallthrough: map_to_instance(config.fallthrough.0.name, config.fallthrough.0.config)?,
+            fallthrough: map_to_instance(config.fallthrough.0)?,
         })
     }
 }
@@ -85,10 +128,8 @@ async fn match_filter<'config, 'ctx, Ctx, F>(
     config: &'config Option<ConfigInstance>,
     metrics: &'config Metrics,
     ctx: &'ctx mut Ctx,
-    get_metadata: impl for<'value> Fn(
-        &'value Ctx,
-        &'config metadata::Key,
-    ) -> Option<&'value metadata::Value>,
+    get_metadata: impl for<'value> Fn(&'value Ctx) -> &'value metadata::DynamicMetadata,
+    when: impl Fn(&Ctx, &Predicate) -> bool,
     and_then: impl Fn(&'ctx mut Ctx, &'config FilterInstance) -> F,
 ) -> Result<(), FilterError>
 where
@@ -96,15 +137,28 @@ where
 {
     match config {
         Some(config) => {
-            let value = (get_metadata)(ctx, &config.metadata_key).ok_or_else(|| {
-                FilterError::new(format!("no metadata found for {}", config.metadata_key))
-            })?;
+            let metadata = (get_metadata)(ctx);
+            let value = metadata.get(&config.metadata_key);
 
-            match config.branches.iter().find(|(key, _)| key == value) {
-                Some((value, instance)) => {
-                    tracing::trace!(key=%config.metadata_key, %value, filter=%instance.0, "Matched against branch");
+            let mut missing_value = false;
+            let matched = config.branches.iter().find(|(matcher, _)| {
+                matcher.matches(value, metadata).unwrap_or_else(|| {
+                    missing_value = true;
+                    false
+                })
+            });
+
+            let (id, instance) = match matched {
+                Some((_, instance)) => {
+                    tracing::trace!(key=%config.metadata_key, ?value, filter=%instance.0, "Matched against branch");
                     metrics.packets_matched_total.inc();
-                    (and_then)(ctx, &instance.1).await
+                    instance
+                }
+                None if missing_value => {
+                    return Err(FilterError::new(format!(
+                        "no metadata found for {}",
+                        config.metadata_key
+                    )));
                 }
                 None => {
                     tracing::trace!(
@@ -113,9 +167,19 @@ where
                         "No match found, calling fallthrough"
                     );
                     metrics.packets_fallthrough_total.inc();
-                    (and_then)(ctx, &config.fallthrough.1).await
+                    &config.fallthrough
                 }
+            };
+
+            if instance
+                .when()
+                .map_or(false, |predicate| !(when)(&*ctx, predicate))
+            {
+                tracing::trace!(filter=%id, "Skipping filter");
+                return Ok(());
             }
+
+            (and_then)(ctx, instance).await
         }
         None => Ok(()),
     }
@@ -130,7 +194,8 @@ impl Filter for Match {
             &self.on_read_filters,
             &self.metrics,
             ctx,
-            |ctx, metadata_key| ctx.metadata.get(metadata_key),
+            |ctx| &ctx.metadata,
+            |ctx, predicate| predicate.read(ctx),
             |ctx, instance| instance.filter().read(ctx),
         )
         .await
@@ -142,7 +207,8 @@ impl Filter for Match {
             &self.on_write_filters,
             &self.metrics,
             ctx,
-            |ctx, metadata_key| ctx.metadata.get(metadata_key),
+            |ctx| &ctx.metadata,
+            |ctx, predicate| predicate.write(ctx),
             |ctx, instance| instance.filter().write(ctx),
         )
         .await
@@ -177,7 +243,7 @@ mod tests {
             on_read: Some(DirectionalConfig {
                 metadata_key: key,
                 branches: vec![Branch {
-                    value: "abc".into(),
+                    condition: Condition::Value("abc".into()),
                     filter: Pass::as_filter_config(None).unwrap(),
                 }],
                 fallthrough: <_>::default(),
@@ -185,6 +251,8 @@ mod tests {
             on_write: None,
         };
         let filter = Match::new(config, metrics).unwrap();
+        let matched = filter.metrics.packets_matched_total.get();
+        let fallthrough = filter.metrics.packets_fallthrough_total.get();
         let endpoint: Endpoint = Default::default();
         let contents = b"hello";
 
@@ -198,8 +266,8 @@ mod tests {
             .await
             .unwrap();
 
-        assert_eq!(0, filter.metrics.packets_fallthrough_total.get());
-        assert_eq!(0, filter.metrics.packets_matched_total.get());
+        assert_eq!(fallthrough, filter.metrics.packets_fallthrough_total.get());
+        assert_eq!(matched, filter.metrics.packets_matched_total.get());
 
         // config so we can test match and fallthrough.
         let endpoints = crate::net::cluster::ClusterMap::new_default(
@@ -213,8 +281,8 @@ mod tests {
         ctx.metadata.insert(key, "abc".into());
 
         filter.read(&mut ctx).await.unwrap();
-        assert_eq!(1, filter.metrics.packets_matched_total.get());
-        assert_eq!(0, filter.metrics.packets_fallthrough_total.get());
+        assert_eq!(matched + 1, filter.metrics.packets_matched_total.get());
+        assert_eq!(fallthrough, filter.metrics.packets_fallthrough_total.get());
 
         let endpoints = crate::net::cluster::ClusterMap::new_default(
             [Endpoint::new("127.0.0.1:81".parse().unwrap())].into(),
@@ -228,7 +296,84 @@ mod tests {
 
         let result = filter.read(&mut ctx).await;
         assert!(result.is_err());
-        assert_eq!(1, filter.metrics.packets_matched_total.get());
-        assert_eq!(1, filter.metrics.packets_fallthrough_total.get());
+        assert_eq!(matched + 1, filter.metrics.packets_matched_total.get());
+        assert_eq!(
+            fallthrough + 1,
+            filter.metrics.packets_fallthrough_total.get()
+        );
+    }
+
+    #[tokio::test]
+    async fn conditions() {
+        let config: Config = serde_yaml::from_str(
+            "
+on_write:
+  metadataKey: myapp.com/token
+  branches:
+    - range: { min: 10, max: 20 }
+      name: quilkin.filters.pass.v1alpha1.Pass
+    - all:
+        - key: myapp.com/token
+          prefix: YWJj
+        - key: myapp.com/regions
+          contains: eu
+      name: quilkin.filters.pass.v1alpha1.Pass
+    - any:
+        - key: myapp.com/token
+          regex: ^xyz
+        - key: myapp.com/regions
+          contains: asia
+      name: quilkin.filters.pass.v1alpha1.Pass
+",
+        )
+        .unwrap();
+        let filter = Match::new(config, Metrics::new()).unwrap();
+        let matched = filter.metrics.packets_matched_total.get();
+        let fallthrough = filter.metrics.packets_fallthrough_total.get();
+
+        let write = |token: metadata::Value, regions: Vec<metadata::Value>| {
+            let mut ctx = WriteContext::new(
+                ([127, 0, 0, 1], 7000).into(),
+                ([127, 0, 0, 1], 7001).into(),
+                alloc_buffer(b"hello"),
+            );
+            ctx.metadata.insert("myapp.com/token".into(), token);
+            ctx.metadata
+                .insert("myapp.com/regions".into(), metadata::Value::List(regions));
+            ctx
+        };
+
+        for (token, regions, is_match) in [
+            (metadata::Value::Number(15), vec![], true),
+            (metadata::Value::Number(25), vec![], false),
+            (b"abcdef".into(), vec!["eu".into()], true),
+            (b"abcdef".into(), vec!["us".into()], false),
+            ("xyzzy".into(), vec![], true),
+            ("zzz".into(), vec!["asia".into()], true),
+            ("zzz".into(), vec!["eu".into()], false),
+        ] {
+            let mut ctx = write(token, regions);
+            assert_eq!(is_match, filter.write(&mut ctx).await.is_ok());
+        }
+
+        assert_eq!(matched + 4, filter.metrics.packets_matched_total.get());
+        assert_eq!(
+            fallthrough + 3,
+            filter.metrics.packets_fallthrough_total.get()
+        );
+
+        // Only the single value conditions need a value for `metadataKey`.
+        let mut ctx = write(b"".into(), vec!["asia".into()]);
+        ctx.metadata.remove(&"myapp.com/token".into());
+        filter.write(&mut ctx).await.unwrap();
+        assert_eq!(matched + 5, filter.metrics.packets_matched_total.get());
+
+        let mut ctx = write(b"".into(), vec!["eu".into()]);
+        ctx.metadata.remove(&"myapp.com/token".into());
+        assert!(filter.write(&mut ctx).await.is_err());
+        assert_eq!(
+            fallthrough + 3,
+            filter.metrics.packets_fallthrough_total.get()
+        );
     }
 }
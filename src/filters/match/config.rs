@@ -18,8 +18,12 @@ use serde::{Deserialize, Serialize};
 
 use super::proto;
 use crate::{
-    config::Filter,
-    filters::{ConvertProtoConfigError, StaticFilter},
+    config::{Base64Standard, Filter},
+    filters::{
+        predicate::{Comparison, MetadataPredicate, Range, Regex},
+        ConvertProtoConfigError, StaticFilter,
+    },
+    net::endpoint::metadata::Value,
 };
 
 /// Configuration for [`Match`][super::Match].
@@ -117,25 +121,111 @@ impl TryFrom<proto::r#match::Config> for DirectionalConfig {
     }
 }
 
-/// A specific match branch. The filter is run when `value` matches the value
-/// defined in `metadata_key`.
+/// A specific match branch. The filter is run when the `condition` is true
+/// for the packet's dynamic metadata.
 #[derive(Debug, Deserialize, Serialize, Eq, PartialEq, schemars::JsonSchema)]
 pub struct Branch {
-    /// The value to compare against the dynamic metadata.
-    pub value: crate::net::endpoint::metadata::Value,
+    /// The condition for running the filter.
+    #[serde(flatten)]
+    pub condition: Condition,
     /// The filter to run on successful matches.
     #[serde(flatten)]
     pub filter: Filter,
 }
 
+/// The condition for a branch to match, either a comparison against the
+/// value of `metadataKey`, or comparisons against the values of several keys.
+#[derive(Clone, Debug, Deserialize, Serialize, Eq, PartialEq, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum Condition {
+    /// The value is equal to this value.
+    Value(Value),
+    /// The value is a number within the range.
+    Range(Range),
+    /// The value is bytes or a string starting with these base64 encoded bytes.
+    Prefix(
+        #[serde(with = "Base64Standard")]
+        #[schemars(with = "String")]
+        Vec<u8>,
+    ),
+    /// The value is bytes or a string ending with these base64 encoded bytes.
+    Suffix(
+        #[serde(with = "Base64Standard")]
+        #[schemars(with = "String")]
+        Vec<u8>,
+    ),
+    /// The value is a string or bytes matching the regular expression.
+    Regex(Regex),
+    /// The value is a list containing this value.
+    Contains(Value),
+    /// All of the comparisons against the values of their keys are true.
+    All(Vec<MetadataPredicate>),
+    /// Any of the comparisons against the values of their keys are true.
+    Any(Vec<MetadataPredicate>),
+}
+
+impl Condition {
+    /// Returns the comparison against the value of `metadataKey`, if the
+    /// condition is a single comparison.
+    pub fn comparison(&self) -> Option<Comparison> {
+        Some(match self {
+            Self::Value(value) => Comparison::Equals(value.clone()),
+            Self::Range(range) => Comparison::Range(*range),
+            Self::Prefix(prefix) => Comparison::Prefix(prefix.clone()),
+            Self::Suffix(suffix) => Comparison::Suffix(suffix.clone()),
+            Self::Regex(regex) => Comparison::Regex(regex.clone()),
+            Self::Contains(value) => Comparison::Contains(value.clone()),
+            Self::All(_) | Self::Any(_) => return None,
+        })
+    }
+}
+
+impl From<Value> for Condition {
+    fn from(value: Value) -> Self {
+        Self::Value(value)
+    }
+}
+
+impl From<Comparison> for Condition {
+    fn from(comparison: Comparison) -> Self {
+        match comparison {
+            Comparison::Equals(value) => Self::Value(value),
+            Comparison::Range(range) => Self::Range(range),
+            Comparison::Prefix(prefix) => Self::Prefix(prefix),
+            Comparison::Suffix(suffix) => Self::Suffix(suffix),
+            Comparison::Regex(regex) => Self::Regex(regex),
+            Comparison::Contains(value) => Self::Contains(value),
+        }
+    }
+}
+
 impl TryFrom<Branch> for proto::r#match::Branch {
     type Error = crate::filters::CreationError;
 
     fn try_from(branch: Branch) -> Result<Self, Self::Error> {
-        Ok(Self {
-            value: Some(branch.value.into()),
+        let key_comparisons = |predicates: Vec<MetadataPredicate>| {
+            predicates
+                .into_iter()
+                .map(|predicate| proto::r#match::KeyComparison {
+                    key: predicate.key.to_string(),
+                    comparison: Some(predicate.comparison.into()),
+                })
+                .collect()
+        };
+
+        let mut proto = Self {
             filter: branch.filter.try_into().map(Some)?,
-        })
+            ..<_>::default()
+        };
+
+        match branch.condition {
+            Condition::Value(value) => proto.value = Some(value.into()),
+            Condition::All(predicates) => proto.all = key_comparisons(predicates),
+            Condition::Any(predicates) => proto.any = key_comparisons(predicates),
+            condition => proto.comparison = condition.comparison().map(From::from),
+        }
+
+        Ok(proto)
     }
 }
 
@@ -143,11 +233,31 @@ impl TryFrom<proto::r#match::Branch> for Branch {
     type Error = eyre::Report;
 
     fn try_from(branch: proto::r#match::Branch) -> Result<Self, Self::Error> {
+        let key_comparisons = |comparisons: Vec<proto::r#match::KeyComparison>| {
+            comparisons
+                .into_iter()
+                .map(MetadataPredicate::try_from)
+                .collect::<Result<_, _>>()
+        };
+
+        let condition = if let Some(value) = branch.value {
+            Condition::Value(value.try_into()?)
+        } else if let Some(comparison) = branch.comparison {
+            Comparison::try_from(comparison)?.into()
+        } else if !branch.all.is_empty() {
+            Condition::All(key_comparisons(branch.all)?)
+        } else if !branch.any.is_empty() {
+            Condition::Any(key_comparisons(branch.any)?)
+        } else {
+            return Err(ConvertProtoConfigError::new(
+                "Missing one of `value`, `comparison`, `all`, or `any`",
+                Some("value".into()),
+            )
+            .into());
+        };
+
         Ok(Self {
-            value: branch
-                .value
-                .ok_or_else(|| ConvertProtoConfigError::new("Missing", Some("value".into())))?
-                .try_into()?,
+            condition,
             filter: branch
                 .filter
                 .map(|filter| filter.try_into())
@@ -157,6 +267,69 @@ impl TryFrom<proto::r#match::Branch> for Branch {
     }
 }
 
+impl From<Comparison> for proto::r#match::Comparison {
+    fn from(comparison: Comparison) -> Self {
+        use proto::r#match::comparison::{Kind, Range};
+
+        Self {
+            kind: Some(match comparison {
+                Comparison::Equals(value) => Kind::Equals(value.into()),
+                Comparison::Range(range) => Kind::Range(Range {
+                    min: range.min,
+                    max: range.max,
+                }),
+                Comparison::Prefix(prefix) => Kind::Prefix(prefix),
+                Comparison::Suffix(suffix) => Kind::Suffix(suffix),
+                Comparison::Regex(regex) => Kind::Regex(regex.0.as_str().into()),
+                Comparison::Contains(value) => Kind::Contains(value.into()),
+            }),
+        }
+    }
+}
+
+impl TryFrom<proto::r#match::Comparison> for Comparison {
+    type Error = eyre::Report;
+
+    fn try_from(comparison: proto::r#match::Comparison) -> Result<Self, Self::Error> {
+        use proto::r#match::comparison::Kind;
+
+        Ok(
+            match comparison
+                .kind
+                .ok_or_else(|| ConvertProtoConfigError::new("Missing", Some("kind".into())))?
+            {
+                Kind::Equals(value) => Self::Equals(value.try_into()?),
+                Kind::Range(range) => Self::Range(Range {
+                    min: range.min,
+                    max: range.max,
+                }),
+                Kind::Prefix(prefix) => Self::Prefix(prefix),
+                Kind::Suffix(suffix) => Self::Suffix(suffix),
+                Kind::Regex(regex) => {
+                    Self::Regex(Regex(regex::bytes::Regex::new(&regex).map_err(
+                        |error| ConvertProtoConfigError::new(error, Some("regex".into())),
+                    )?))
+                }
+                Kind::Contains(value) => Self::Contains(value.try_into()?),
+            },
+        )
+    }
+}
+
+impl TryFrom<proto::r#match::KeyComparison> for MetadataPredicate {
+    type Error = eyre::Report;
+
+    fn try_from(comparison: proto::r#match::KeyComparison) -> Result<Self, Self::Error> {
+        Ok(Self {
+            key: comparison.key.into(),
+            comparison: comparison
+                .comparison
+                .ok_or_else(|| ConvertProtoConfigError::new("Missing", Some("comparison".into())))?
+                .try_into()?,
+        })
+    }
+}
+
 /// The behaviour when the none of branches match. Defaults to dropping packets.
 #[derive(Debug, Eq, PartialEq, Serialize, Deserialize, schemars::JsonSchema)]
 #[serde(transparent)]
@@ -197,7 +370,7 @@ on_read:
                 on_read: Some(DirectionalConfig {
                     metadata_key: "quilkin.dev/captured_bytes".into(),
                     branches: vec![Branch {
-                        value: String::from("abc").into(),
+                        condition: Value::from("abc").into(),
                         filter: crate::filters::Debug::as_filter_config(None).unwrap(),
                     }],
                     fallthrough: <_>::default(),
@@ -206,4 +379,50 @@ on_read:
             }
         )
     }
+
+    #[test]
+    fn proto_conditions() {
+        let yaml = "
+on_read:
+    metadataKey: quilkin.dev/captured_bytes
+    branches:
+        - value: abc
+          name: quilkin.filters.pass.v1alpha1.Pass
+        - range: { min: 1 }
+          name: quilkin.filters.pass.v1alpha1.Pass
+        - prefix: YWJj
+          name: quilkin.filters.pass.v1alpha1.Pass
+        - suffix: YWJj
+          name: quilkin.filters.pass.v1alpha1.Pass
+        - regex: ^abc
+          name: quilkin.filters.pass.v1alpha1.Pass
+        - contains: abc
+          name: quilkin.filters.pass.v1alpha1.Pass
+        - all:
+            - key: quilkin.dev/captured_bytes
+              equals: abc
+            - key: myapp.com/version
+              range: { max: 3 }
+          name: quilkin.filters.pass.v1alpha1.Pass
+        - any:
+            - key: myapp.com/regions
+              contains: eu
+          name: quilkin.filters.pass.v1alpha1.Pass
+        ";
+        let config = serde_yaml::from_str::<Config>(yaml).unwrap();
+
+        let branches = &config.on_read.as_ref().unwrap().branches;
+        assert_eq!(8, branches.len());
+        assert_eq!(
+            Some(Comparison::Range(Range {
+                min: Some(1),
+                max: None
+            })),
+            branches[1].condition.comparison()
+        );
+        assert_eq!(None, branches[6].condition.comparison());
+
+        let proto = proto::Match::try_from(serde_yaml::from_str::<Config>(yaml).unwrap()).unwrap();
+        assert_eq!(config, Config::try_from(proto).unwrap());
+    }
 }
@@ -0,0 +1,618 @@
+/*
+ * Copyright 2024 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    filters::prelude::*,
+    net::endpoint::metadata::{DynamicMetadata, Key, Value},
+};
+
+use crate::generated::quilkin::filters::metadata::v1alpha1 as proto;
+
+/// The largest number of bytes that can be decoded as an integer.
+const MAX_INTEGER_SIZE: u32 = 8;
+
+/// Filter that modifies the Filter's dynamic metadata, so values captured by
+/// one filter can be adapted to what the filters after it expect.
+pub struct Metadata {
+    config: Config,
+}
+
+impl Metadata {
+    fn new(config: Config) -> Result<Self, CreationError> {
+        for operation in config.on_read.iter().chain(&config.on_write) {
+            if let Operation::Integer { size, .. } = operation {
+                if *size == 0 || *size > MAX_INTEGER_SIZE {
+                    return Err(CreationError::FieldInvalid {
+                        field: "integer.size".into(),
+                        reason: format!("size must be between 1 and {MAX_INTEGER_SIZE} bytes"),
+                    });
+                }
+            }
+        }
+
+        Ok(Self { config })
+    }
+}
+
+impl StaticFilter for Metadata {
+    const NAME: &'static str = "quilkin.filters.metadata.v1alpha1.Metadata";
+    type Configuration = Config;
+    type BinaryConfiguration = proto::Metadata;
+
+    fn try_from_config(config: Option<Self::Configuration>) -> Result<Self, CreationError> {
+        Self::new(Self::ensure_config_exists(config)?)
+    }
+}
+
+#[async_trait::async_trait]
+impl Filter for Metadata {
+    async fn read(&self, ctx: &mut ReadContext) -> Result<(), FilterError> {
+        apply(&self.config.on_read, &mut ctx.metadata).map_err(FilterError::new)
+    }
+
+    async fn write(&self, ctx: &mut WriteContext) -> Result<(), FilterError> {
+        apply(&self.config.on_write, &mut ctx.metadata).map_err(FilterError::new)
+    }
+}
+
+fn apply(operations: &[Operation], metadata: &mut DynamicMetadata) -> Result<(), Error> {
+    for operation in operations {
+        operation.apply(metadata)?;
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("no value found for `{0}`")]
+    NoValueFound(Key),
+    #[error("key `{0}` was found but isn't {1}, found {2:?}")]
+    InvalidType(Key, &'static str, Value),
+    #[error("key `{0}` couldn't be decoded as {1}")]
+    InvalidEncoding(Key, Encoding),
+    #[error("key `{key}` is too short to read {size} bytes at offset {offset}")]
+    TooShort { key: Key, offset: u32, size: u32 },
+}
+
+/// A modification of the Filter's dynamic metadata. When `to` is optional and
+/// not set, the result replaces the value of `from`.
+#[derive(Clone, Debug, Deserialize, Serialize, Eq, PartialEq, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub enum Operation {
+    /// Sets `key` to `value`.
+    Set { key: Key, value: Value },
+    /// Copies the value of `from` to `to`.
+    Copy { from: Key, to: Key },
+    /// Moves the value of `from` to `to`.
+    Rename { from: Key, to: Key },
+    /// Removes `key`, if present.
+    Delete { key: Key },
+    /// Stores a 64 bit hash of the bytes or string of `from` as a number.
+    Hash {
+        from: Key,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        to: Option<Key>,
+    },
+    /// Encodes the bytes or string of `from`, storing the encoded string.
+    Encode {
+        from: Key,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        to: Option<Key>,
+        encoding: Encoding,
+    },
+    /// Decodes the encoded string of `from`, storing the decoded bytes.
+    Decode {
+        from: Key,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        to: Option<Key>,
+        encoding: Encoding,
+    },
+    /// Decodes `size` bytes starting at `offset` of the bytes of `from` as a
+    /// big endian unsigned integer, storing it as a number.
+    Integer {
+        from: Key,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        to: Option<Key>,
+        #[serde(default)]
+        offset: u32,
+        size: u32,
+    },
+    /// Concatenates the bytes or strings of each key in `from`, storing the
+    /// bytes in `to`.
+    Concatenate { from: Vec<Key>, to: Key },
+}
+
+impl Operation {
+    fn apply(&self, metadata: &mut DynamicMetadata) -> Result<(), Error> {
+        match self {
+            Self::Set { key, value } => {
+                metadata.insert(*key, value.clone());
+            }
+            Self::Copy { from, to } => {
+                let value = get(metadata, from)?.clone();
+                metadata.insert(*to, value);
+            }
+            Self::Rename { from, to } => {
+                let value = metadata.remove(from).ok_or(Error::NoValueFound(*from))?;
+                metadata.insert(*to, value);
+            }
+            Self::Delete { key } => {
+                metadata.remove(key);
+            }
+            Self::Hash { from, to } => {
+                let hash = seahash::hash(bytes(*from, get(metadata, from)?)?);
+                metadata.insert(to.unwrap_or(*from), Value::Number(hash));
+            }
+            Self::Encode { from, to, encoding } => {
+                let encoded = encoding.encode(bytes(*from, get(metadata, from)?)?);
+                metadata.insert(to.unwrap_or(*from), Value::String(encoded));
+            }
+            Self::Decode { from, to, encoding } => {
+                let decoded = encoding
+                    .decode(bytes(*from, get(metadata, from)?)?)
+                    .ok_or(Error::InvalidEncoding(*from, *encoding))?;
+                metadata.insert(to.unwrap_or(*from), Value::Bytes(decoded.into()));
+            }
+            Self::Integer {
+                from,
+                to,
+                offset,
+                size,
+            } => {
+                let value = match get(metadata, from)? {
+                    Value::Bytes(bytes) => bytes,
+                    value => return Err(Error::InvalidType(*from, "bytes", value.clone())),
+                };
+
+                let start = *offset as usize;
+                let slice = value
+                    .get(start..start + *size as usize)
+                    .ok_or(Error::TooShort {
+                        key: *from,
+                        offset: *offset,
+                        size: *size,
+                    })?;
+                let number = slice
+                    .iter()
+                    .fold(0u64, |number, byte| number << 8 | u64::from(*byte));
+                metadata.insert(to.unwrap_or(*from), Value::Number(number));
+            }
+            Self::Concatenate { from, to } => {
+                let mut concatenated = Vec::new();
+                for key in from {
+                    concatenated.extend_from_slice(bytes(*key, get(metadata, key)?)?);
+                }
+                metadata.insert(*to, Value::Bytes(concatenated.into()));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn get<'metadata>(
+    metadata: &'metadata DynamicMetadata,
+    key: &Key,
+) -> Result<&'metadata Value, Error> {
+    metadata.get(key).ok_or(Error::NoValueFound(*key))
+}
+
+/// Returns the bytes of a bytes or string value.
+fn bytes(key: Key, value: &Value) -> Result<&[u8], Error> {
+    match value {
+        Value::Bytes(bytes) => Ok(bytes),
+        Value::String(string) => Ok(string.as_bytes()),
+        value => Err(Error::InvalidType(key, "bytes or a string", value.clone())),
+    }
+}
+
+/// How bytes are encoded as a string.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Deserialize,
+    Serialize,
+    Eq,
+    PartialEq,
+    schemars::JsonSchema,
+    strum_macros::Display,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum Encoding {
+    /// Standard base64 with padding.
+    #[strum(serialize = "base64")]
+    Base64,
+    /// Lowercase hexadecimal, uppercase is also accepted when decoding.
+    #[strum(serialize = "hex")]
+    Hex,
+}
+
+impl Encoding {
+    fn encode(self, bytes: &[u8]) -> String {
+        match self {
+            Self::Base64 => crate::codec::base64::encode(bytes),
+            Self::Hex => {
+                use std::fmt::Write;
+
+                bytes
+                    .iter()
+                    .fold(String::with_capacity(bytes.len() * 2), |mut hex, byte| {
+                        let _ = write!(hex, "{byte:02x}");
+                        hex
+                    })
+            }
+        }
+    }
+
+    fn decode(self, encoded: &[u8]) -> Option<Vec<u8>> {
+        match self {
+            Self::Base64 => crate::codec::base64::decode(encoded).ok(),
+            Self::Hex => {
+                if encoded.len() % 2 != 0 {
+                    return None;
+                }
+
+                encoded
+                    .chunks(2)
+                    .map(|pair| {
+                        std::str::from_utf8(pair)
+                            .ok()
+                            .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+impl From<Encoding> for proto::metadata::Encoding {
+    fn from(encoding: Encoding) -> Self {
+        match encoding {
+            Encoding::Base64 => Self::Base64,
+            Encoding::Hex => Self::Hex,
+        }
+    }
+}
+
+impl From<proto::metadata::Encoding> for Encoding {
+    fn from(encoding: proto::metadata::Encoding) -> Self {
+        match encoding {
+            proto::metadata::Encoding::Base64 => Self::Base64,
+            proto::metadata::Encoding::Hex => Self::Hex,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Eq, PartialEq, schemars::JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// the operations applied, in order, to packets from clients
+    #[serde(default, with = "serde_yaml::with::singleton_map_recursive")]
+    #[schemars(with = "Vec<Operation>")]
+    pub on_read: Vec<Operation>,
+    /// the operations applied, in order, to packets from endpoints
+    #[serde(default, with = "serde_yaml::with::singleton_map_recursive")]
+    #[schemars(with = "Vec<Operation>")]
+    pub on_write: Vec<Operation>,
+}
+
+impl From<Operation> for proto::metadata::Operation {
+    fn from(operation: Operation) -> Self {
+        use proto::metadata::{self as p, operation::Operation as O};
+
+        let encoding = |encoding: Encoding| {
+            Some(p::EncodingValue {
+                value: p::Encoding::from(encoding) as i32,
+            })
+        };
+
+        Self {
+            operation: Some(match operation {
+                Operation::Set { key, value } => O::Set(p::Set {
+                    key: key.to_string(),
+                    value: Some(value.into()),
+                }),
+                Operation::Copy { from, to } => O::Copy(p::Move {
+                    from: from.to_string(),
+                    to: to.to_string(),
+                }),
+                Operation::Rename { from, to } => O::Rename(p::Move {
+                    from: from.to_string(),
+                    to: to.to_string(),
+                }),
+                Operation::Delete { key } => O::Delete(p::Delete {
+                    key: key.to_string(),
+                }),
+                Operation::Hash { from, to } => O::Hash(p::Hash {
+                    from: from.to_string(),
+                    to: to.map(|to| to.to_string()),
+                }),
+                Operation::Encode {
+                    from,
+                    to,
+                    encoding: e,
+                } => O::Encode(p::Transcode {
+                    from: from.to_string(),
+                    to: to.map(|to| to.to_string()),
+                    encoding: encoding(e),
+                }),
+                Operation::Decode {
+                    from,
+                    to,
+                    encoding: e,
+                } => O::Decode(p::Transcode {
+                    from: from.to_string(),
+                    to: to.map(|to| to.to_string()),
+                    encoding: encoding(e),
+                }),
+                Operation::Integer {
+                    from,
+                    to,
+                    offset,
+                    size,
+                } => O::Integer(p::Integer {
+                    from: from.to_string(),
+                    to: to.map(|to| to.to_string()),
+                    offset,
+                    size,
+                }),
+                Operation::Concatenate { from, to } => O::Concatenate(p::Concatenate {
+                    from: from.iter().map(Key::to_string).collect(),
+                    to: to.to_string(),
+                }),
+            }),
+        }
+    }
+}
+
+impl TryFrom<proto::metadata::Operation> for Operation {
+    type Error = ConvertProtoConfigError;
+
+    fn try_from(operation: proto::metadata::Operation) -> Result<Self, Self::Error> {
+        use proto::metadata::{self as p, operation::Operation as O};
+
+        let encoding = |encoding: Option<p::EncodingValue>| {
+            encoding
+                .map(|encoding| {
+                    p::Encoding::try_from(encoding.value)
+                        .map(Encoding::from)
+                        .map_err(|_| {
+                            ConvertProtoConfigError::new(
+                                format!("invalid encoding `{}`", encoding.value),
+                                Some("encoding".into()),
+                            )
+                        })
+                })
+                .transpose()
+                .map(|encoding| encoding.unwrap_or(Encoding::Base64))
+        };
+
+        Ok(
+            match operation
+                .operation
+                .ok_or_else(|| ConvertProtoConfigError::missing_field("operation"))?
+            {
+                O::Set(p::Set { key, value }) => Self::Set {
+                    key: key.into(),
+                    value: value
+                        .ok_or_else(|| ConvertProtoConfigError::missing_field("value"))?
+                        .try_into()
+                        .map_err(|error| {
+                            ConvertProtoConfigError::new(error, Some("value".into()))
+                        })?,
+                },
+                O::Copy(p::Move { from, to }) => Self::Copy {
+                    from: from.into(),
+                    to: to.into(),
+                },
+                O::Rename(p::Move { from, to }) => Self::Rename {
+                    from: from.into(),
+                    to: to.into(),
+                },
+                O::Delete(p::Delete { key }) => Self::Delete { key: key.into() },
+                O::Hash(p::Hash { from, to }) => Self::Hash {
+                    from: from.into(),
+                    to: to.map(From::from),
+                },
+                O::Encode(p::Transcode {
+                    from,
+                    to,
+                    encoding: e,
+                }) => Self::Encode {
+                    from: from.into(),
+                    to: to.map(From::from),
+                    encoding: encoding(e)?,
+                },
+                O::Decode(p::Transcode {
+                    from,
+                    to,
+                    encoding: e,
+                }) => Self::Decode {
+                    from: from.into(),
+                    to: to.map(From::from),
+                    encoding: encoding(e)?,
+                },
+                O::Integer(p::Integer {
+                    from,
+                    to,
+                    offset,
+                    size,
+                }) => Self::Integer {
+                    from: from.into(),
+                    to: to.map(From::from),
+                    offset,
+                    size,
+                },
+                O::Concatenate(p::Concatenate { from, to }) => Self::Concatenate {
+                    from: from.into_iter().map(From::from).collect(),
+                    to: to.into(),
+                },
+            },
+        )
+    }
+}
+
+impl From<Config> for proto::Metadata {
+    fn from(config: Config) -> Self {
+        Self {
+            on_read: config.on_read.into_iter().map(From::from).collect(),
+            on_write: config.on_write.into_iter().map(From::from).collect(),
+        }
+    }
+}
+
+impl TryFrom<proto::Metadata> for Config {
+    type Error = ConvertProtoConfigError;
+
+    fn try_from(p: proto::Metadata) -> Result<Self, Self::Error> {
+        Ok(Self {
+            on_read: p
+                .on_read
+                .into_iter()
+                .map(TryFrom::try_from)
+                .collect::<Result<_, _>>()?,
+            on_write: p
+                .on_write
+                .into_iter()
+                .map(TryFrom::try_from)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(yaml: &str) -> Config {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    fn apply_read(config: &Config, metadata: &mut DynamicMetadata) -> Result<(), Error> {
+        apply(&config.on_read, metadata)
+    }
+
+    #[test]
+    fn set_copy_rename_delete() {
+        let operations = config(
+            "
+on_read:
+  - set: { key: a, value: 1 }
+  - copy: { from: a, to: b }
+  - rename: { from: b, to: c }
+  - set: { key: d, value: hello }
+  - delete: { key: d }
+",
+        );
+        let mut metadata = DynamicMetadata::new();
+        apply_read(&operations, &mut metadata).unwrap();
+
+        assert_eq!(Some(&Value::Number(1)), metadata.get(&"a".into()));
+        assert_eq!(None, metadata.get(&"b".into()));
+        assert_eq!(Some(&Value::Number(1)), metadata.get(&"c".into()));
+        assert_eq!(None, metadata.get(&"d".into()));
+
+        assert!(matches!(
+            apply_read(
+                &config("on_read: [{ rename: { from: missing, to: a } }]"),
+                &mut DynamicMetadata::new(),
+            ),
+            Err(Error::NoValueFound(_))
+        ));
+    }
+
+    #[test]
+    fn derived() {
+        let operations = config(
+            "
+on_read:
+  - encode: { from: token, to: token.hex, encoding: hex }
+  - decode: { from: token.hex, to: token.decoded, encoding: hex }
+  - encode: { from: token, to: token.base64, encoding: base64 }
+  - decode: { from: token.base64, encoding: base64 }
+  - integer: { from: token, to: version, offset: 1, size: 2 }
+  - concatenate: { from: [prefix, token], to: joined }
+  - hash: { from: joined, to: hash }
+",
+        );
+        let mut metadata = DynamicMetadata::new();
+        metadata.insert("token".into(), [0xab, 0x01, 0x02, 0xff].into());
+        metadata.insert("prefix".into(), "id:".into());
+        apply_read(&operations, &mut metadata).unwrap();
+
+        let get = |key: &str| metadata.get(&key.into()).unwrap().clone();
+        assert_eq!(Value::String("ab0102ff".into()), get("token.hex"));
+        assert_eq!(Value::from([0xab, 0x01, 0x02, 0xff]), get("token.decoded"));
+        assert_eq!(Value::from([0xab, 0x01, 0x02, 0xff]), get("token.base64"));
+        assert_eq!(Value::Number(0x0102), get("version"));
+        assert_eq!(Value::from(*b"id:\xab\x01\x02\xff"), get("joined"));
+        assert_eq!(
+            Value::Number(seahash::hash(b"id:\xab\x01\x02\xff")),
+            get("hash")
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let mut metadata = DynamicMetadata::new();
+        metadata.insert("token".into(), [0xab].into());
+        metadata.insert("number".into(), Value::Number(1));
+        metadata.insert("text".into(), "xyz".into());
+
+        let mut run = |yaml: &str| apply_read(&config(yaml), &mut metadata);
+        assert!(matches!(
+            run("on_read: [{ integer: { from: token, size: 2 } }]"),
+            Err(Error::TooShort { .. })
+        ));
+        assert!(matches!(
+            run("on_read: [{ integer: { from: text, size: 1 } }]"),
+            Err(Error::InvalidType(..))
+        ));
+        assert!(matches!(
+            run("on_read: [{ hash: { from: number } }]"),
+            Err(Error::InvalidType(..))
+        ));
+        assert!(matches!(
+            run("on_read: [{ decode: { from: text, encoding: hex } }]"),
+            Err(Error::InvalidEncoding(..))
+        ));
+
+        assert!(Metadata::new(config("on_write: [{ integer: { from: a, size: 9 } }]")).is_err());
+    }
+
+    #[test]
+    fn proto() {
+        let yaml = "
+on_read:
+  - set: { key: a, value: 1 }
+  - copy: { from: a, to: b }
+  - rename: { from: b, to: c }
+  - delete: { key: c }
+on_write:
+  - hash: { from: a }
+  - encode: { from: a, to: b, encoding: hex }
+  - decode: { from: b, encoding: base64 }
+  - integer: { from: a, offset: 2, size: 4 }
+  - concatenate: { from: [a, b], to: c }
+";
+        let proto = proto::Metadata::from(config(yaml));
+        assert_eq!(config(yaml), Config::try_from(proto).unwrap());
+    }
+}
@@ -0,0 +1,594 @@
+/*
+ * Copyright 2024 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    collections::HashMap,
+    hash::{Hash, Hasher},
+    net::SocketAddr,
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+
+use arc_swap::ArcSwapOption;
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use tokio::{net::UdpSocket, sync::mpsc, time::Instant};
+
+use crate::{
+    filters::{capture::CAPTURED_BYTES, prelude::*},
+    metrics::Direction,
+    net::endpoint::{metadata, EndpointAddress},
+};
+
+use crate::generated::quilkin::filters::mirror::v1alpha1 as proto;
+
+/// How many mirrored packets can be waiting to be sent before packets are
+/// dropped from the mirror.
+const QUEUE_CAPACITY: usize = 1024;
+/// How long a mirrored session's socket is kept after its last packet.
+const SESSION_TIMEOUT: Duration = Duration::from_secs(60);
+/// The maximum number of mirrored sessions, and so of sockets, at once. The
+/// packets of further sessions are dropped from the mirror until a session
+/// times out.
+const MAX_SESSIONS: usize = 512;
+
+/// The endpoints with the target label, for a version of the endpoints.
+struct LabelledEndpoints {
+    /// The address of the [`ClusterMap`](crate::net::cluster::ClusterMap)
+    /// the endpoints are from.
+    clusters: usize,
+    version: u64,
+    addresses: Vec<EndpointAddress>,
+}
+
+/// A packet to send to the mirror, from the mirrored session's `source`.
+struct Mirrored {
+    source: EndpointAddress,
+    destination: EndpointAddress,
+    contents: Bytes,
+}
+
+/// Filter that sends a copy of a sample of packets to a shadow endpoint,
+/// whose replies are discarded.
+///
+/// The copies are sent by a background task, so mirroring never blocks the
+/// packet, and packets that can't be mirrored are only counted in metrics.
+pub struct Mirror {
+    config: Config,
+    sender: OnceLock<mpsc::Sender<Mirrored>>,
+    /// The endpoints of a [`Target::Label`], only filtered again when the
+    /// endpoints change.
+    labelled: ArcSwapOption<LabelledEndpoints>,
+    packets_mirrored_total: prometheus::IntCounter,
+    packets_dropped_total: prometheus::IntCounter,
+}
+
+impl Mirror {
+    fn new(config: Config) -> Result<Self, CreationError> {
+        if !(0.0..=1.0).contains(&config.rate) {
+            return Err(CreationError::FieldInvalid {
+                field: "rate".into(),
+                reason: "value must be between 0 and 1".into(),
+            });
+        }
+
+        Ok(Self {
+            config,
+            sender: OnceLock::new(),
+            labelled: ArcSwapOption::empty(),
+            packets_mirrored_total: crate::filters::metrics::counter(
+                Self::NAME,
+                "packets_mirrored_total",
+                "Total number of packets sent to the mirror",
+                Direction::Read,
+            ),
+            packets_dropped_total: crate::filters::metrics::counter(
+                Self::NAME,
+                "packets_mirror_dropped_total",
+                "Total number of packets that couldn't be sent to the mirror",
+                Direction::Read,
+            ),
+        })
+    }
+
+    fn hash(&self, ctx: &ReadContext) -> u64 {
+        if self.config.stickiness == Stickiness::Token {
+            if let Some(metadata::Value::Bytes(token)) = ctx.metadata.get(&self.config.metadata_key)
+            {
+                return seahash::hash(token);
+            }
+        }
+
+        let mut hasher = seahash::SeaHasher::new();
+        ctx.source.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Whether the packet is part of the sample. With stickiness, either every
+    /// packet of a session is or none is.
+    fn is_sampled(&self, hash: u64) -> bool {
+        if self.config.rate >= 1.0 {
+            return true;
+        }
+
+        let point = match self.config.stickiness {
+            Stickiness::None => rand::random::<f64>(),
+            Stickiness::Source | Stickiness::Token => hash as f64 / u64::MAX as f64,
+        };
+
+        point < self.config.rate
+    }
+
+    /// Returns the addresses of the endpoints with the label `key` equal to
+    /// `value`, filtering the endpoints again only when they've changed.
+    fn labelled(&self, ctx: &ReadContext, key: &str, value: &str) -> Arc<LabelledEndpoints> {
+        let clusters = Arc::as_ptr(&ctx.endpoints) as usize;
+        let version = ctx.endpoints.version();
+
+        if let Some(labelled) = &*self.labelled.load() {
+            if labelled.clusters == clusters && labelled.version == version {
+                return labelled.clone();
+            }
+        }
+
+        let labelled = Arc::new(LabelledEndpoints {
+            clusters,
+            version,
+            addresses: ctx
+                .endpoints
+                .filter_endpoints(|endpoint| {
+                    endpoint.metadata.known.labels.get(key).map(String::as_str) == Some(value)
+                })
+                .into_iter()
+                .map(|endpoint| endpoint.address)
+                .collect(),
+        });
+        self.labelled.store(Some(labelled.clone()));
+        labelled
+    }
+
+    /// Returns the queue of the task sending the mirrored packets, starting
+    /// it with the first packet.
+    fn sender(&self) -> &mpsc::Sender<Mirrored> {
+        self.sender.get_or_init(|| {
+            let (sender, receiver) = mpsc::channel(QUEUE_CAPACITY);
+            tokio::spawn(send_mirrored(receiver, self.packets_dropped_total.clone()));
+            sender
+        })
+    }
+}
+
+impl StaticFilter for Mirror {
+    const NAME: &'static str = "quilkin.filters.mirror.v1alpha1.Mirror";
+    type Configuration = Config;
+    type BinaryConfiguration = proto::Mirror;
+
+    fn try_from_config(config: Option<Self::Configuration>) -> Result<Self, CreationError> {
+        Self::new(Self::ensure_config_exists(config)?)
+    }
+}
+
+#[async_trait::async_trait]
+impl Filter for Mirror {
+    async fn read(&self, ctx: &mut ReadContext) -> Result<(), FilterError> {
+        let hash = self.hash(ctx);
+        if !self.is_sampled(hash) {
+            return Ok(());
+        }
+
+        // Every packet of a session is mirrored to the same endpoint.
+        let destination = match &self.config.target {
+            Target::Address(address) => address.clone(),
+            Target::Label { key, value } => {
+                let labelled = self.labelled(ctx, key, value);
+                if labelled.addresses.is_empty() {
+                    self.packets_dropped_total.inc();
+                    return Ok(());
+                }
+
+                labelled.addresses[(hash % labelled.addresses.len() as u64) as usize].clone()
+            }
+        };
+
+        let mirrored = Mirrored {
+            source: ctx.source.clone(),
+            destination,
+            contents: Bytes::copy_from_slice(&ctx.contents),
+        };
+
+        match self.sender().try_send(mirrored) {
+            Ok(()) => self.packets_mirrored_total.inc(),
+            Err(_) => self.packets_dropped_total.inc(),
+        }
+
+        Ok(())
+    }
+}
+
+/// Sends the mirrored packets from `receiver`, with a socket for each
+/// session so the mirror sees the same sessions as the endpoints, until the
+/// filter is dropped. Nothing is ever received on the sockets, so the
+/// mirror's replies are discarded. At most [`MAX_SESSIONS`] sessions are
+/// mirrored at once.
+async fn send_mirrored(
+    mut receiver: mpsc::Receiver<Mirrored>,
+    packets_dropped_total: prometheus::IntCounter,
+) {
+    let mut sockets = HashMap::<(EndpointAddress, SocketAddr), (UdpSocket, Instant)>::new();
+    let mut last_pruned = Instant::now();
+
+    while let Some(mirrored) = receiver.recv().await {
+        if last_pruned.elapsed() > SESSION_TIMEOUT {
+            sockets.retain(|_, (_, last_sent)| last_sent.elapsed() < SESSION_TIMEOUT);
+            last_pruned = Instant::now();
+        }
+
+        let result = async {
+            let destination = mirrored.destination.to_socket_addr().await?;
+            let key = (mirrored.source.clone(), destination);
+            if sockets.len() >= MAX_SESSIONS && !sockets.contains_key(&key) {
+                sockets.retain(|_, (_, last_sent)| last_sent.elapsed() < SESSION_TIMEOUT);
+                last_pruned = Instant::now();
+                if sockets.len() >= MAX_SESSIONS {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::Other,
+                        "too many mirrored sessions",
+                    ));
+                }
+            }
+
+            let (socket, last_sent) = match sockets.entry(key) {
+                std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+                std::collections::hash_map::Entry::Vacant(entry) => {
+                    let unspecified: SocketAddr = if destination.is_ipv4() {
+                        (std::net::Ipv4Addr::UNSPECIFIED, 0).into()
+                    } else {
+                        (std::net::Ipv6Addr::UNSPECIFIED, 0).into()
+                    };
+                    entry.insert((UdpSocket::bind(unspecified).await?, Instant::now()))
+                }
+            };
+
+            *last_sent = Instant::now();
+            socket.send_to(&mirrored.contents, destination).await
+        }
+        .await;
+
+        if let Err(error) = result {
+            tracing::debug!(%error, "failed to mirror packet");
+            packets_dropped_total.inc();
+        }
+    }
+}
+
+/// How packets are sampled.
+#[derive(
+    Clone, Copy, Debug, Default, Deserialize, Serialize, Eq, PartialEq, schemars::JsonSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum Stickiness {
+    /// Each packet is sampled independently.
+    #[default]
+    None,
+    /// Every packet from a source address is sampled, or none is.
+    Source,
+    /// Every packet with the token found under the filter's `metadataKey` is
+    /// sampled, or none is, falling back to the source address when there's
+    /// no token.
+    Token,
+}
+
+/// The endpoint packets are mirrored to.
+#[derive(Clone, Debug, Deserialize, Serialize, Eq, PartialEq, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum Target {
+    /// An endpoint address, which doesn't need to be one of the proxy's
+    /// endpoints.
+    Address(#[schemars(with = "String")] EndpointAddress),
+    /// One of the endpoints with a label equal to a value, chosen per session.
+    Label { key: String, value: String },
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, schemars::JsonSchema)]
+pub struct Config {
+    /// the endpoint packets are mirrored to
+    #[serde(flatten)]
+    pub target: Target,
+    /// the proportion of packets mirrored, between 0 and 1
+    #[serde(default = "default_rate")]
+    pub rate: f64,
+    /// how packets are sampled
+    #[serde(default)]
+    pub stickiness: Stickiness,
+    /// the key to use when retrieving the token from the Filter's dynamic
+    /// metadata, when `stickiness` is `token`
+    #[serde(rename = "metadataKey", default = "default_metadata_key")]
+    pub metadata_key: metadata::Key,
+}
+
+/// Default value for [`Config::rate`]
+fn default_rate() -> f64 {
+    1.0
+}
+
+/// Default value for [`Config::metadata_key`]
+fn default_metadata_key() -> metadata::Key {
+    metadata::Key::from_static(CAPTURED_BYTES)
+}
+
+impl From<Stickiness> for proto::mirror::Stickiness {
+    fn from(stickiness: Stickiness) -> Self {
+        match stickiness {
+            Stickiness::None => Self::None,
+            Stickiness::Source => Self::Source,
+            Stickiness::Token => Self::Token,
+        }
+    }
+}
+
+impl From<proto::mirror::Stickiness> for Stickiness {
+    fn from(stickiness: proto::mirror::Stickiness) -> Self {
+        match stickiness {
+            proto::mirror::Stickiness::None => Self::None,
+            proto::mirror::Stickiness::Source => Self::Source,
+            proto::mirror::Stickiness::Token => Self::Token,
+        }
+    }
+}
+
+impl From<Config> for proto::Mirror {
+    fn from(config: Config) -> Self {
+        use proto::mirror::Target as ProtoTarget;
+
+        Self {
+            target: Some(match config.target {
+                Target::Address(address) => ProtoTarget::Address(address.to_string()),
+                Target::Label { key, value } => {
+                    ProtoTarget::Label(proto::mirror::Label { key, value })
+                }
+            }),
+            rate: Some(config.rate),
+            stickiness: Some(proto::mirror::StickinessValue {
+                value: proto::mirror::Stickiness::from(config.stickiness) as i32,
+            }),
+            metadata_key: Some(config.metadata_key.to_string()),
+        }
+    }
+}
+
+impl TryFrom<proto::Mirror> for Config {
+    type Error = ConvertProtoConfigError;
+
+    fn try_from(p: proto::Mirror) -> Result<Self, Self::Error> {
+        use proto::mirror::Target as ProtoTarget;
+
+        let target = match p.target {
+            Some(ProtoTarget::Address(address)) => {
+                Target::Address(address.parse().map_err(|error| {
+                    ConvertProtoConfigError::new(
+                        format!("invalid address `{address}`: {error}"),
+                        Some("address".into()),
+                    )
+                })?)
+            }
+            Some(ProtoTarget::Label(proto::mirror::Label { key, value })) => {
+                Target::Label { key, value }
+            }
+            None => return Err(ConvertProtoConfigError::missing_field("target")),
+        };
+
+        let stickiness = p
+            .stickiness
+            .map(|stickiness| {
+                proto::mirror::Stickiness::try_from(stickiness.value)
+                    .map(Stickiness::from)
+                    .map_err(|_| {
+                        ConvertProtoConfigError::new(
+                            format!("invalid stickiness `{}`", stickiness.value),
+                            Some("stickiness".into()),
+                        )
+                    })
+            })
+            .transpose()?
+            .unwrap_or_default();
+
+        Ok(Self {
+            target,
+            rate: p.rate.unwrap_or_else(default_rate),
+            stickiness,
+            metadata_key: p
+                .metadata_key
+                .map(metadata::Key::new)
+                .unwrap_or_else(default_metadata_key),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        net::{cluster::ClusterMap, endpoint::Endpoint},
+        test::alloc_buffer,
+    };
+
+    fn config(target: Target, rate: f64, stickiness: Stickiness) -> Config {
+        Config {
+            target,
+            rate,
+            stickiness,
+            metadata_key: default_metadata_key(),
+        }
+    }
+
+    #[tokio::test]
+    async fn mirror() {
+        let shadow = UdpSocket::bind((std::net::Ipv4Addr::LOCALHOST, 0))
+            .await
+            .unwrap();
+        let filter = Mirror::from_config(Some(config(
+            Target::Address(shadow.local_addr().unwrap().into()),
+            1.0,
+            Stickiness::None,
+        )));
+
+        let mut ctx = ReadContext::new(
+            <_>::default(),
+            (std::net::Ipv4Addr::LOCALHOST, 80).into(),
+            alloc_buffer(b"hello"),
+        );
+        filter.read(&mut ctx).await.unwrap();
+
+        // The packet itself is left unchanged.
+        assert_eq!(b"hello", &*ctx.contents);
+        assert!(ctx.destinations.is_empty());
+
+        let mut buffer = [0; 16];
+        let size = tokio::time::timeout(Duration::from_secs(1), shadow.recv(&mut buffer))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(b"hello", &buffer[..size]);
+    }
+
+    #[tokio::test]
+    async fn label() {
+        let shadow = UdpSocket::bind((std::net::Ipv4Addr::LOCALHOST, 0))
+            .await
+            .unwrap();
+        let endpoints = Arc::new(ClusterMap::new_default(
+            [
+                Endpoint::new((std::net::Ipv4Addr::LOCALHOST, 1).into()),
+                Endpoint::with_metadata(
+                    shadow.local_addr().unwrap().into(),
+                    crate::net::endpoint::Metadata {
+                        labels: [("build".into(), "shadow".into())].into(),
+                        ..<_>::default()
+                    },
+                ),
+            ]
+            .into(),
+        ));
+        let filter = Mirror::from_config(Some(config(
+            Target::Label {
+                key: "build".into(),
+                value: "shadow".into(),
+            },
+            1.0,
+            Stickiness::Source,
+        )));
+
+        let ctx = || {
+            ReadContext::new(
+                endpoints.clone(),
+                (std::net::Ipv4Addr::LOCALHOST, 80).into(),
+                alloc_buffer(b"hello"),
+            )
+        };
+        filter.read(&mut ctx()).await.unwrap();
+
+        let mut buffer = [0; 16];
+        let size = tokio::time::timeout(Duration::from_secs(1), shadow.recv(&mut buffer))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(b"hello", &buffer[..size]);
+
+        // The labelled endpoints are only filtered again once they change.
+        let labelled = filter.labelled.load_full().unwrap();
+        assert_eq!(1, labelled.addresses.len());
+        filter.read(&mut ctx()).await.unwrap();
+        assert!(Arc::ptr_eq(
+            &labelled,
+            &filter.labelled.load_full().unwrap()
+        ));
+
+        endpoints
+            .remove_endpoint_if(|endpoint| endpoint.metadata.known.labels.contains_key("build"));
+        filter.read(&mut ctx()).await.unwrap();
+        assert!(filter.labelled.load_full().unwrap().addresses.is_empty());
+    }
+
+    #[test]
+    fn sampling() {
+        let target = || Target::Address((std::net::Ipv4Addr::LOCALHOST, 1).into());
+
+        let filter = Mirror::from_config(Some(config(target(), 0.0, Stickiness::None)));
+        assert!((0..100).all(|_| !filter.is_sampled(0)));
+
+        let filter = Mirror::from_config(Some(config(target(), 1.0, Stickiness::None)));
+        assert!((0..100).all(|_| filter.is_sampled(0)));
+
+        // With stickiness, the sample only depends on the session's hash.
+        let filter = Mirror::from_config(Some(config(target(), 0.5, Stickiness::Source)));
+        assert!(filter.is_sampled(0));
+        assert!(!filter.is_sampled(u64::MAX));
+        let sampled = (0..1000)
+            .map(|source: u16| {
+                let ctx = ReadContext::new(
+                    <_>::default(),
+                    (std::net::Ipv4Addr::LOCALHOST, source).into(),
+                    alloc_buffer(b""),
+                );
+                filter.is_sampled(filter.hash(&ctx))
+            })
+            .filter(|sampled| *sampled)
+            .count();
+        assert!((400..600).contains(&sampled), "{sampled}");
+
+        assert!(Mirror::try_from_config(Some(config(target(), 1.5, Stickiness::None))).is_err());
+    }
+
+    #[test]
+    fn convert_proto_config() {
+        let config = || Config {
+            target: Target::Label {
+                key: "build".into(),
+                value: "shadow".into(),
+            },
+            rate: 0.25,
+            stickiness: Stickiness::Token,
+            metadata_key: "session".into(),
+        };
+        assert_eq!(
+            config(),
+            Config::try_from(proto::Mirror::from(config())).unwrap()
+        );
+        assert!(Config::try_from(proto::Mirror::default()).is_err());
+    }
+
+    #[test]
+    fn parse_config() {
+        let config: Config = serde_yaml::from_str(
+            "
+address: 127.0.0.1:26000
+rate: 0.1
+stickiness: source
+",
+        )
+        .unwrap();
+
+        assert_eq!(
+            config,
+            Config {
+                target: Target::Address((std::net::Ipv4Addr::LOCALHOST, 26000).into()),
+                rate: 0.1,
+                stickiness: Stickiness::Source,
+                metadata_key: default_metadata_key(),
+            }
+        );
+    }
+}
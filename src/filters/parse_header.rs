@@ -0,0 +1,782 @@
+/*
+ * Copyright 2024 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    filters::prelude::*,
+    net::endpoint::metadata::{DynamicMetadata, Key, Value},
+    pool::PoolBuffer,
+};
+
+use crate::generated::quilkin::filters::parse_header::v1alpha1 as proto;
+
+/// The largest size in bytes of a fixed size integer.
+const MAX_INTEGER_SIZE: u8 = 8;
+/// The largest size in bytes of a varint holding a 64 bit integer.
+const MAX_VARINT_SIZE: usize = 10;
+
+/// Filter that decodes a packet header described by a schema into the
+/// Filter's dynamic metadata, or encodes a header from it.
+pub struct ParseHeader {
+    config: Config,
+}
+
+impl ParseHeader {
+    fn new(config: Config) -> Result<Self, CreationError> {
+        for field in &config.fields {
+            for integer in field.kind.integers() {
+                integer
+                    .validate()
+                    .map_err(|reason| CreationError::FieldInvalid {
+                        field: format!("fields.{}", field.key),
+                        reason,
+                    })?;
+            }
+        }
+
+        Ok(Self { config })
+    }
+}
+
+impl StaticFilter for ParseHeader {
+    const NAME: &'static str = "quilkin.filters.parse_header.v1alpha1.ParseHeader";
+    type Configuration = Config;
+    type BinaryConfiguration = proto::ParseHeader;
+
+    fn try_from_config(config: Option<Self::Configuration>) -> Result<Self, CreationError> {
+        Self::new(Self::ensure_config_exists(config)?)
+    }
+}
+
+#[async_trait::async_trait]
+impl Filter for ParseHeader {
+    async fn read(&self, ctx: &mut ReadContext) -> Result<(), FilterError> {
+        self.apply(self.config.on_read, &mut ctx.contents, &mut ctx.metadata)
+            .map_err(FilterError::new)
+    }
+
+    async fn write(&self, ctx: &mut WriteContext) -> Result<(), FilterError> {
+        self.apply(self.config.on_write, &mut ctx.contents, &mut ctx.metadata)
+            .map_err(FilterError::new)
+    }
+}
+
+impl ParseHeader {
+    fn apply(
+        &self,
+        mode: Mode,
+        contents: &mut PoolBuffer,
+        metadata: &mut DynamicMetadata,
+    ) -> Result<(), Error> {
+        match mode {
+            Mode::DoNothing => {}
+            Mode::Parse => {
+                self.parse(contents, metadata)?;
+            }
+            Mode::Strip => {
+                let length = self.parse(contents, metadata)?;
+                contents.split_prefix(length);
+            }
+            Mode::Prepend => {
+                let header = self.serialize(metadata)?;
+                contents.prepend_from_slice(&header);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Decodes the fields at the start of `contents` into `metadata`,
+    /// returning the length of the header.
+    fn parse(&self, contents: &[u8], metadata: &mut DynamicMetadata) -> Result<usize, Error> {
+        let mut reader = Reader { bytes: contents };
+
+        for Field { key, kind } in &self.config.fields {
+            let key = *key;
+            let value = match kind {
+                Kind::Integer(integer) => Value::Number(reader.integer(key, *integer)?),
+                Kind::Bytes { size } => {
+                    Value::Bytes(Bytes::copy_from_slice(reader.take(key, (*size).into())?))
+                }
+                Kind::LengthPrefixed { length } => {
+                    let size = reader.integer(key, *length)?;
+                    Value::Bytes(Bytes::copy_from_slice(reader.take(key, size)?))
+                }
+                Kind::Tlv {
+                    block_length,
+                    tag,
+                    length,
+                } => {
+                    let size = reader.integer(key, *block_length)?;
+                    let mut block = Reader {
+                        bytes: reader.take(key, size)?,
+                    };
+                    let mut entries = Vec::new();
+                    while !block.bytes.is_empty() {
+                        let tag = block.integer(key, *tag)?;
+                        let size = block.integer(key, *length)?;
+                        let value = Bytes::copy_from_slice(block.take(key, size)?);
+                        entries.push(Value::List(vec![Value::Number(tag), Value::Bytes(value)]));
+                    }
+                    Value::List(entries)
+                }
+            };
+
+            metadata.insert(key, value);
+        }
+
+        Ok(contents.len() - reader.bytes.len())
+    }
+
+    /// Encodes a header from the values of the fields in `metadata`.
+    fn serialize(&self, metadata: &DynamicMetadata) -> Result<Vec<u8>, Error> {
+        let mut header = Vec::new();
+
+        for Field { key, kind } in &self.config.fields {
+            let key = *key;
+            let value = metadata.get(&key).ok_or(Error::NoValueFound(key))?;
+            match kind {
+                Kind::Integer(integer) => match value {
+                    Value::Number(number) => integer.write(key, *number, &mut header)?,
+                    value => return Err(Error::InvalidType(key, "a number", value.clone())),
+                },
+                Kind::Bytes { size } => {
+                    let bytes = bytes(key, value)?;
+                    if bytes.len() != *size as usize {
+                        return Err(Error::DoesNotFit(key));
+                    }
+                    header.extend_from_slice(bytes);
+                }
+                Kind::LengthPrefixed { length } => {
+                    let bytes = bytes(key, value)?;
+                    length.write(key, bytes.len() as u64, &mut header)?;
+                    header.extend_from_slice(bytes);
+                }
+                Kind::Tlv {
+                    block_length,
+                    tag,
+                    length,
+                } => {
+                    let invalid =
+                        || Error::InvalidType(key, "a list of [type, value] lists", value.clone());
+                    let Value::List(entries) = value else {
+                        return Err(invalid());
+                    };
+
+                    let mut block = Vec::new();
+                    for entry in entries {
+                        let Value::List(entry) = entry else {
+                            return Err(invalid());
+                        };
+                        let [Value::Number(number), value] = entry.as_slice() else {
+                            return Err(invalid());
+                        };
+                        let value = bytes(key, value)?;
+                        tag.write(key, *number, &mut block)?;
+                        length.write(key, value.len() as u64, &mut block)?;
+                        block.extend_from_slice(value);
+                    }
+
+                    block_length.write(key, block.len() as u64, &mut header)?;
+                    header.extend_from_slice(&block);
+                }
+            }
+        }
+
+        Ok(header)
+    }
+}
+
+/// Returns the bytes of a bytes or string value.
+fn bytes(key: Key, value: &Value) -> Result<&[u8], Error> {
+    match value {
+        Value::Bytes(bytes) => Ok(bytes),
+        Value::String(string) => Ok(string.as_bytes()),
+        value => Err(Error::InvalidType(key, "bytes or a string", value.clone())),
+    }
+}
+
+/// Reads the fields of a header from the start of a packet.
+struct Reader<'bytes> {
+    bytes: &'bytes [u8],
+}
+
+impl<'bytes> Reader<'bytes> {
+    fn take(&mut self, key: Key, size: u64) -> Result<&'bytes [u8], Error> {
+        let size = usize::try_from(size)
+            .ok()
+            .filter(|size| *size <= self.bytes.len())
+            .ok_or(Error::TooShort(key))?;
+        let (taken, rest) = self.bytes.split_at(size);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn integer(&mut self, key: Key, integer: Integer) -> Result<u64, Error> {
+        let size = integer.size.unwrap_or_default().into();
+        match integer.encoding {
+            IntegerEncoding::BigEndian => Ok(self
+                .take(key, size)?
+                .iter()
+                .fold(0, |number, byte| number << 8 | u64::from(*byte))),
+            IntegerEncoding::LittleEndian => Ok(self
+                .take(key, size)?
+                .iter()
+                .rev()
+                .fold(0, |number, byte| number << 8 | u64::from(*byte))),
+            IntegerEncoding::Varint => {
+                let mut number = 0;
+                for index in 0..MAX_VARINT_SIZE {
+                    let byte = self.take(key, 1)?[0];
+                    let bits = u64::from(byte & 0x7f);
+                    // The last byte can only hold the 64th bit.
+                    if index == MAX_VARINT_SIZE - 1 && bits > 1 {
+                        break;
+                    }
+
+                    number |= bits << (7 * index);
+                    if byte & 0x80 == 0 {
+                        return Ok(number);
+                    }
+                }
+
+                Err(Error::InvalidVarint(key))
+            }
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("packet is too short to read `{0}`")]
+    TooShort(Key),
+    #[error("`{0}` isn't a valid varint")]
+    InvalidVarint(Key),
+    #[error("no value found for `{0}`")]
+    NoValueFound(Key),
+    #[error("key `{0}` was found but isn't {1}, found {2:?}")]
+    InvalidType(Key, &'static str, Value),
+    #[error("value of `{0}` doesn't fit the size of its field")]
+    DoesNotFit(Key),
+}
+
+/// What is done with the header of a packet.
+#[derive(
+    Clone, Copy, Debug, Default, Deserialize, Serialize, Eq, PartialEq, schemars::JsonSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum Mode {
+    /// The packet is left as is.
+    #[default]
+    DoNothing,
+    /// The header is decoded into the dynamic metadata.
+    Parse,
+    /// The header is decoded into the dynamic metadata and removed from the
+    /// packet.
+    Strip,
+    /// A header is encoded from the dynamic metadata and added to the start
+    /// of the packet.
+    Prepend,
+}
+
+/// A field of the header.
+#[derive(Clone, Debug, Deserialize, Serialize, Eq, PartialEq, schemars::JsonSchema)]
+pub struct Field {
+    /// The key the value of the field is stored under.
+    pub key: Key,
+    /// How the field is encoded.
+    #[serde(flatten)]
+    pub kind: Kind,
+}
+
+/// How a field is encoded, and the type of the value it's decoded to.
+#[derive(Clone, Debug, Deserialize, Serialize, Eq, PartialEq, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum Kind {
+    /// An unsigned integer, decoded to a number.
+    Integer(Integer),
+    /// A fixed number of bytes, decoded to bytes.
+    Bytes { size: u32 },
+    /// Bytes preceded by their length, decoded to bytes.
+    LengthPrefixed { length: Integer },
+    /// Type-length-value entries preceded by their total length in bytes,
+    /// decoded to a list of `[type, value]` lists.
+    #[serde(rename_all = "camelCase")]
+    Tlv {
+        block_length: Integer,
+        #[serde(rename = "type")]
+        tag: Integer,
+        length: Integer,
+    },
+}
+
+impl Kind {
+    fn integers(&self) -> Vec<Integer> {
+        match self {
+            Self::Integer(integer) => vec![*integer],
+            Self::Bytes { .. } => Vec::new(),
+            Self::LengthPrefixed { length } => vec![*length],
+            Self::Tlv {
+                block_length,
+                tag,
+                length,
+            } => vec![*block_length, *tag, *length],
+        }
+    }
+}
+
+/// An unsigned integer in the header.
+#[derive(
+    Clone, Copy, Debug, Default, Deserialize, Serialize, Eq, PartialEq, schemars::JsonSchema,
+)]
+#[serde(deny_unknown_fields)]
+pub struct Integer {
+    /// How the integer is encoded.
+    #[serde(default)]
+    pub encoding: IntegerEncoding,
+    /// The size in bytes of a big or little endian integer, from 1 to 8.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u8>,
+}
+
+impl Integer {
+    fn validate(&self) -> Result<(), String> {
+        match (self.encoding, self.size) {
+            (IntegerEncoding::Varint, None) => Ok(()),
+            (IntegerEncoding::Varint, Some(_)) => Err("varints don't have a size".into()),
+            (_, Some(size)) if (1..=MAX_INTEGER_SIZE).contains(&size) => Ok(()),
+            _ => Err(format!(
+                "integer size must be between 1 and {MAX_INTEGER_SIZE} bytes"
+            )),
+        }
+    }
+
+    fn write(&self, key: Key, number: u64, header: &mut Vec<u8>) -> Result<(), Error> {
+        let size = usize::from(self.size.unwrap_or_default());
+        match self.encoding {
+            IntegerEncoding::BigEndian | IntegerEncoding::LittleEndian
+                if size < 8 && number >> (size * 8) != 0 =>
+            {
+                return Err(Error::DoesNotFit(key));
+            }
+            IntegerEncoding::BigEndian => {
+                header.extend_from_slice(&number.to_be_bytes()[8 - size..]);
+            }
+            IntegerEncoding::LittleEndian => {
+                header.extend_from_slice(&number.to_le_bytes()[..size]);
+            }
+            IntegerEncoding::Varint => {
+                let mut number = number;
+                while number >= 0x80 {
+                    header.push(number as u8 | 0x80);
+                    number >>= 7;
+                }
+                header.push(number as u8);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// How an integer is encoded.
+#[derive(
+    Clone, Copy, Debug, Default, Deserialize, Serialize, Eq, PartialEq, schemars::JsonSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum IntegerEncoding {
+    /// A fixed size integer with the most significant byte first.
+    #[default]
+    BigEndian,
+    /// A fixed size integer with the least significant byte first.
+    LittleEndian,
+    /// An unsigned LEB128 variable length integer, as used by protobuf.
+    Varint,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Eq, PartialEq, schemars::JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// the fields of the header, in the order they appear in the packet
+    pub fields: Vec<Field>,
+    /// what is done with the header of packets from clients
+    #[serde(default)]
+    pub on_read: Mode,
+    /// what is done with the header of packets from endpoints
+    #[serde(default)]
+    pub on_write: Mode,
+}
+
+impl From<Mode> for proto::parse_header::ModeValue {
+    fn from(mode: Mode) -> Self {
+        use proto::parse_header::Mode as P;
+
+        let mode = match mode {
+            Mode::DoNothing => P::DoNothing,
+            Mode::Parse => P::Parse,
+            Mode::Strip => P::Strip,
+            Mode::Prepend => P::Prepend,
+        };
+
+        Self { value: mode as i32 }
+    }
+}
+
+impl TryFrom<Option<proto::parse_header::ModeValue>> for Mode {
+    type Error = ConvertProtoConfigError;
+
+    fn try_from(mode: Option<proto::parse_header::ModeValue>) -> Result<Self, Self::Error> {
+        use proto::parse_header::Mode as P;
+
+        let Some(mode) = mode else {
+            return Ok(Self::default());
+        };
+
+        Ok(
+            match P::try_from(mode.value).map_err(|_| {
+                ConvertProtoConfigError::new(format!("invalid mode `{}`", mode.value), None)
+            })? {
+                P::DoNothing => Self::DoNothing,
+                P::Parse => Self::Parse,
+                P::Strip => Self::Strip,
+                P::Prepend => Self::Prepend,
+            },
+        )
+    }
+}
+
+impl From<Integer> for proto::parse_header::Integer {
+    fn from(integer: Integer) -> Self {
+        use proto::parse_header::IntegerEncoding as P;
+
+        let encoding = match integer.encoding {
+            IntegerEncoding::BigEndian => P::BigEndian,
+            IntegerEncoding::LittleEndian => P::LittleEndian,
+            IntegerEncoding::Varint => P::Varint,
+        };
+
+        Self {
+            encoding: encoding as i32,
+            size: integer.size.map(u32::from),
+        }
+    }
+}
+
+impl TryFrom<Option<proto::parse_header::Integer>> for Integer {
+    type Error = ConvertProtoConfigError;
+
+    fn try_from(integer: Option<proto::parse_header::Integer>) -> Result<Self, Self::Error> {
+        use proto::parse_header::IntegerEncoding as P;
+
+        let integer = integer.ok_or_else(|| ConvertProtoConfigError::missing_field("integer"))?;
+        let encoding = match P::try_from(integer.encoding).map_err(|_| {
+            ConvertProtoConfigError::new(
+                format!("invalid encoding `{}`", integer.encoding),
+                Some("encoding".into()),
+            )
+        })? {
+            P::BigEndian => IntegerEncoding::BigEndian,
+            P::LittleEndian => IntegerEncoding::LittleEndian,
+            P::Varint => IntegerEncoding::Varint,
+        };
+
+        Ok(Self {
+            encoding,
+            size: integer
+                .size
+                .map(u8::try_from)
+                .transpose()
+                .map_err(|error| ConvertProtoConfigError::new(error, Some("size".into())))?,
+        })
+    }
+}
+
+impl From<Field> for proto::parse_header::Field {
+    fn from(field: Field) -> Self {
+        use proto::parse_header::{self as p, field::Kind as K};
+
+        Self {
+            key: field.key.to_string(),
+            kind: Some(match field.kind {
+                Kind::Integer(integer) => K::Integer(integer.into()),
+                Kind::Bytes { size } => K::Bytes(p::Bytes { size }),
+                Kind::LengthPrefixed { length } => K::LengthPrefixed(p::LengthPrefixed {
+                    length: Some(length.into()),
+                }),
+                Kind::Tlv {
+                    block_length,
+                    tag,
+                    length,
+                } => K::Tlv(p::Tlv {
+                    block_length: Some(block_length.into()),
+                    r#type: Some(tag.into()),
+                    length: Some(length.into()),
+                }),
+            }),
+        }
+    }
+}
+
+impl TryFrom<proto::parse_header::Field> for Field {
+    type Error = ConvertProtoConfigError;
+
+    fn try_from(field: proto::parse_header::Field) -> Result<Self, Self::Error> {
+        use proto::parse_header::{self as p, field::Kind as K};
+
+        Ok(Self {
+            key: field.key.into(),
+            kind: match field
+                .kind
+                .ok_or_else(|| ConvertProtoConfigError::missing_field("kind"))?
+            {
+                K::Integer(integer) => Kind::Integer(Some(integer).try_into()?),
+                K::Bytes(p::Bytes { size }) => Kind::Bytes { size },
+                K::LengthPrefixed(p::LengthPrefixed { length }) => Kind::LengthPrefixed {
+                    length: length.try_into()?,
+                },
+                K::Tlv(p::Tlv {
+                    block_length,
+                    r#type,
+                    length,
+                }) => Kind::Tlv {
+                    block_length: block_length.try_into()?,
+                    tag: r#type.try_into()?,
+                    length: length.try_into()?,
+                },
+            },
+        })
+    }
+}
+
+impl From<Config> for proto::ParseHeader {
+    fn from(config: Config) -> Self {
+        Self {
+            fields: config.fields.into_iter().map(From::from).collect(),
+            on_read: Some(config.on_read.into()),
+            on_write: Some(config.on_write.into()),
+        }
+    }
+}
+
+impl TryFrom<proto::ParseHeader> for Config {
+    type Error = ConvertProtoConfigError;
+
+    fn try_from(p: proto::ParseHeader) -> Result<Self, Self::Error> {
+        Ok(Self {
+            fields: p
+                .fields
+                .into_iter()
+                .map(TryFrom::try_from)
+                .collect::<Result<_, _>>()?,
+            on_read: p.on_read.try_into()?,
+            on_write: p.on_write.try_into()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::alloc_buffer;
+
+    /// A header with a magic number, a little endian version, a varint
+    /// session, a length prefixed name, and a block of options.
+    const HEADER: &str = "
+fields:
+  - key: magic
+    bytes: { size: 2 }
+  - key: version
+    integer: { encoding: littleEndian, size: 2 }
+  - key: session
+    integer: { encoding: varint }
+  - key: name
+    lengthPrefixed: { length: { size: 1 } }
+  - key: options
+    tlv:
+      blockLength: { size: 2 }
+      type: { size: 1 }
+      length: { encoding: varint }
+";
+
+    fn filter(yaml: &str) -> ParseHeader {
+        ParseHeader::new(serde_yaml::from_str(yaml).unwrap()).unwrap()
+    }
+
+    fn packet() -> Vec<u8> {
+        let mut packet = b"QK".to_vec();
+        packet.extend([3, 1]);
+        packet.extend([0xac, 0x02]);
+        packet.extend([4]);
+        packet.extend(b"eu-1");
+        packet.extend([0, 7, 1, 2, b'o', b'k', 9, 1, b'!']);
+        packet.extend(b"payload");
+        packet
+    }
+
+    fn expected(metadata: &DynamicMetadata) {
+        let option = |tag: u64, value: &[u8]| {
+            Value::List(vec![
+                Value::Number(tag),
+                Value::Bytes(value.to_vec().into()),
+            ])
+        };
+
+        assert_eq!(Some(&Value::from(b"QK")), metadata.get(&"magic".into()));
+        assert_eq!(Some(&Value::Number(259)), metadata.get(&"version".into()));
+        assert_eq!(Some(&Value::Number(300)), metadata.get(&"session".into()));
+        assert_eq!(Some(&Value::from(b"eu-1")), metadata.get(&"name".into()));
+        assert_eq!(
+            Some(&Value::List(vec![option(1, b"ok"), option(9, b"!")])),
+            metadata.get(&"options".into())
+        );
+    }
+
+    #[tokio::test]
+    async fn parse_and_strip() {
+        let mut ctx = ReadContext::new(
+            <_>::default(),
+            "127.0.0.1:80".parse().unwrap(),
+            alloc_buffer(packet()),
+        );
+
+        filter(&format!("{HEADER}on_read: parse"))
+            .read(&mut ctx)
+            .await
+            .unwrap();
+        expected(&ctx.metadata);
+        assert_eq!(packet(), &*ctx.contents);
+
+        let mut ctx = ReadContext::new(
+            <_>::default(),
+            "127.0.0.1:80".parse().unwrap(),
+            alloc_buffer(packet()),
+        );
+
+        filter(&format!("{HEADER}on_read: strip"))
+            .read(&mut ctx)
+            .await
+            .unwrap();
+        expected(&ctx.metadata);
+        assert_eq!(b"payload", &*ctx.contents);
+    }
+
+    #[tokio::test]
+    async fn prepend() {
+        let mut ctx = ReadContext::new(
+            <_>::default(),
+            "127.0.0.1:80".parse().unwrap(),
+            alloc_buffer(packet()),
+        );
+        filter(&format!("{HEADER}on_read: strip"))
+            .read(&mut ctx)
+            .await
+            .unwrap();
+
+        let mut write = WriteContext::new(
+            "127.0.0.1:80".parse().unwrap(),
+            "127.0.0.1:81".parse().unwrap(),
+            alloc_buffer(b"payload"),
+        );
+        write.metadata = ctx.metadata;
+        filter(&format!("{HEADER}on_write: prepend"))
+            .write(&mut write)
+            .await
+            .unwrap();
+
+        assert_eq!(packet(), &*write.contents);
+    }
+
+    #[test]
+    fn integers() {
+        for (encoding, size, number, encoded) in [
+            (IntegerEncoding::BigEndian, Some(1), 0xff, &[0xff][..]),
+            (IntegerEncoding::BigEndian, Some(3), 0x010203, &[1, 2, 3]),
+            (IntegerEncoding::LittleEndian, Some(3), 0x010203, &[3, 2, 1]),
+            (IntegerEncoding::BigEndian, Some(8), u64::MAX, &[0xff; 8]),
+            (IntegerEncoding::Varint, None, 0, &[0]),
+            (IntegerEncoding::Varint, None, 127, &[0x7f]),
+            (IntegerEncoding::Varint, None, 128, &[0x80, 1]),
+            (
+                IntegerEncoding::Varint,
+                None,
+                u64::MAX,
+                &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 1],
+            ),
+        ] {
+            let integer = Integer { encoding, size };
+            let mut written = Vec::new();
+            integer.write("key".into(), number, &mut written).unwrap();
+            assert_eq!(encoded, written, "{integer:?} {number}");
+
+            let mut reader = Reader { bytes: encoded };
+            assert_eq!(number, reader.integer("key".into(), integer).unwrap());
+            assert!(reader.bytes.is_empty());
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let read = |header: &str, contents: &[u8]| {
+            filter(header).parse(contents, &mut DynamicMetadata::new())
+        };
+
+        let error = read(&format!("{HEADER}on_read: parse"), &packet()[..10]).unwrap_err();
+        assert!(matches!(error, Error::TooShort(key) if key == Key::from("name")));
+        let error = read(
+            "fields: [{ key: a, integer: { encoding: varint } }]",
+            &[0xff; 11],
+        )
+        .unwrap_err();
+        assert!(matches!(error, Error::InvalidVarint(_)));
+
+        let mut metadata = DynamicMetadata::new();
+        let header = filter("fields: [{ key: a, integer: { size: 1 } }]");
+        assert!(matches!(
+            header.serialize(&metadata),
+            Err(Error::NoValueFound(_))
+        ));
+        metadata.insert("a".into(), Value::Number(256));
+        assert!(matches!(
+            header.serialize(&metadata),
+            Err(Error::DoesNotFit(_))
+        ));
+        metadata.insert("a".into(), "a".into());
+        assert!(matches!(
+            header.serialize(&metadata),
+            Err(Error::InvalidType(..))
+        ));
+
+        for invalid in [
+            "fields: [{ key: a, integer: {} }]",
+            "fields: [{ key: a, integer: { size: 9 } }]",
+            "fields: [{ key: a, integer: { encoding: varint, size: 1 } }]",
+            "fields: [{ key: a, lengthPrefixed: { length: { size: 0 } } }]",
+        ] {
+            let config = serde_yaml::from_str(invalid).unwrap();
+            assert!(ParseHeader::new(config).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn proto() {
+        let config = || serde_yaml::from_str::<Config>(&format!("{HEADER}on_read: strip")).unwrap();
+        let proto = proto::ParseHeader::from(config());
+
+        assert_eq!(config(), Config::try_from(proto).unwrap());
+    }
+}
@@ -0,0 +1,286 @@
+/*
+ * Copyright 2024 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Predicates deciding whether a filter in a chain is executed for a packet.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::Base64Standard,
+    filters::{firewall::Cidr, ReadContext, WriteContext},
+    net::endpoint::{
+        metadata::{DynamicMetadata, Key, Value},
+        AddressKind, EndpointAddress,
+    },
+};
+
+/// A condition on a packet, the filter it's attached to is only executed
+/// for packets it's true for.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum Predicate {
+    /// True when all of the predicates are true.
+    All(Vec<Predicate>),
+    /// True when any of the predicates are true.
+    Any(Vec<Predicate>),
+    /// True when the predicate is false.
+    Not(Box<Predicate>),
+    /// True when the packet's dynamic metadata has a value for the key
+    /// satisfying the comparison.
+    Metadata(MetadataPredicate),
+    /// True when the length of the packet in bytes is within the range.
+    Length(Range),
+    /// True when the packet's source is within any of the CIDR ranges.
+    Source(Vec<Cidr>),
+}
+
+impl Predicate {
+    /// Evaluates the predicate against a packet received from a client.
+    pub fn read(&self, ctx: &ReadContext) -> bool {
+        self.evaluate(&Packet {
+            metadata: &ctx.metadata,
+            source: &ctx.source,
+            length: ctx.contents.len(),
+        })
+    }
+
+    /// Evaluates the predicate against a packet received from an endpoint.
+    pub fn write(&self, ctx: &WriteContext) -> bool {
+        self.evaluate(&Packet {
+            metadata: &ctx.metadata,
+            source: &ctx.source,
+            length: ctx.contents.len(),
+        })
+    }
+
+    fn evaluate(&self, packet: &Packet) -> bool {
+        match self {
+            Self::All(predicates) => predicates.iter().all(|p| p.evaluate(packet)),
+            Self::Any(predicates) => predicates.iter().any(|p| p.evaluate(packet)),
+            Self::Not(predicate) => !predicate.evaluate(packet),
+            Self::Metadata(predicate) => predicate.matches(packet.metadata),
+            Self::Length(range) => range.contains(packet.length as u64),
+            Self::Source(cidrs) => match packet.source.host {
+                AddressKind::Ip(ip) => cidrs.iter().any(|cidr| cidr.contains(ip)),
+                AddressKind::Name(_) => false,
+            },
+        }
+    }
+}
+
+/// The parts of a packet predicates are evaluated against.
+struct Packet<'ctx> {
+    metadata: &'ctx DynamicMetadata,
+    source: &'ctx EndpointAddress,
+    length: usize,
+}
+
+/// Compares the value of a dynamic metadata key.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize, JsonSchema)]
+pub struct MetadataPredicate {
+    /// The key of the value to compare.
+    pub key: Key,
+    #[serde(flatten)]
+    pub comparison: Comparison,
+}
+
+impl MetadataPredicate {
+    /// Returns whether `metadata` has a value for the key satisfying the
+    /// comparison.
+    pub fn matches(&self, metadata: &DynamicMetadata) -> bool {
+        metadata
+            .get(&self.key)
+            .map_or(false, |value| self.comparison.matches(value))
+    }
+}
+
+/// A comparison against a metadata value.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum Comparison {
+    /// The value is equal to this value.
+    Equals(Value),
+    /// The value is a number within the range.
+    Range(Range),
+    /// The value is a string or bytes matching the regular expression.
+    Regex(Regex),
+    /// The value is bytes or a string starting with these base64 encoded bytes.
+    Prefix(
+        #[serde(with = "Base64Standard")]
+        #[schemars(with = "String")]
+        Vec<u8>,
+    ),
+    /// The value is bytes or a string ending with these base64 encoded bytes.
+    Suffix(
+        #[serde(with = "Base64Standard")]
+        #[schemars(with = "String")]
+        Vec<u8>,
+    ),
+    /// The value is a list containing this value.
+    Contains(Value),
+}
+
+impl Comparison {
+    /// Returns whether `value` satisfies the comparison.
+    pub fn matches(&self, value: &Value) -> bool {
+        match (self, value) {
+            (Self::Equals(expected), value) => expected == value,
+            (Self::Range(range), Value::Number(number)) => range.contains(*number),
+            (Self::Regex(regex), Value::String(string)) => regex.0.is_match(string.as_bytes()),
+            (Self::Regex(regex), Value::Bytes(bytes)) => regex.0.is_match(bytes),
+            (Self::Prefix(prefix), Value::Bytes(bytes)) => bytes.starts_with(prefix),
+            (Self::Prefix(prefix), Value::String(string)) => string.as_bytes().starts_with(prefix),
+            (Self::Suffix(suffix), Value::Bytes(bytes)) => bytes.ends_with(suffix),
+            (Self::Suffix(suffix), Value::String(string)) => string.as_bytes().ends_with(suffix),
+            (Self::Contains(expected), Value::List(values)) => values.contains(expected),
+            _ => false,
+        }
+    }
+}
+
+/// An inclusive range of numbers, unbounded on either side when the bound
+/// isn't set.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Range {
+    /// The smallest number in the range.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<u64>,
+    /// The largest number in the range.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<u64>,
+}
+
+impl Range {
+    pub fn contains(&self, number: u64) -> bool {
+        self.min.map_or(true, |min| number >= min) && self.max.map_or(true, |max| number <= max)
+    }
+}
+
+/// A regular expression, matched against strings and bytes.
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
+pub struct Regex(
+    #[serde(with = "serde_regex")]
+    #[schemars(with = "String")]
+    pub regex::bytes::Regex,
+);
+
+impl PartialEq for Regex {
+    fn eq(&self, rhs: &Self) -> bool {
+        self.0.as_str() == rhs.0.as_str()
+    }
+}
+
+impl Eq for Regex {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test::alloc_buffer;
+
+    fn predicate(yaml: &str) -> Predicate {
+        serde_yaml::with::singleton_map_recursive::deserialize(serde_yaml::Deserializer::from_str(
+            yaml,
+        ))
+        .unwrap()
+    }
+
+    fn read_context(source: &str, contents: &[u8]) -> ReadContext {
+        ReadContext::new(
+            Default::default(),
+            source.parse().unwrap(),
+            alloc_buffer(contents),
+        )
+    }
+
+    #[test]
+    fn metadata() {
+        let mut ctx = read_context("127.0.0.1:7000", b"hello");
+        ctx.metadata.insert("version".into(), Value::Number(3));
+        ctx.metadata
+            .insert("game".into(), Value::String("racing-eu".into()));
+
+        assert!(predicate("metadata: { key: version, equals: 3 }").read(&ctx));
+        assert!(!predicate("metadata: { key: version, equals: 4 }").read(&ctx));
+        assert!(predicate("metadata: { key: version, range: { min: 2, max: 3 } }").read(&ctx));
+        assert!(!predicate("metadata: { key: version, range: { min: 4 } }").read(&ctx));
+        assert!(predicate("metadata: { key: game, regex: '^racing-' }").read(&ctx));
+        assert!(!predicate("metadata: { key: game, range: { max: 10 } }").read(&ctx));
+        assert!(!predicate("metadata: { key: missing, regex: '.*' }").read(&ctx));
+    }
+
+    #[test]
+    fn bytes_and_lists() {
+        let mut ctx = read_context("127.0.0.1:7000", b"hello");
+        ctx.metadata.insert("token".into(), b"abcdef".into());
+        ctx.metadata.insert(
+            "regions".into(),
+            Value::List(vec!["eu".into(), "us".into()]),
+        );
+
+        // "YWJj" and "ZGVm" are "abc" and "def" base64 encoded.
+        assert!(predicate("metadata: { key: token, prefix: YWJj }").read(&ctx));
+        assert!(!predicate("metadata: { key: token, prefix: ZGVm }").read(&ctx));
+        assert!(predicate("metadata: { key: token, suffix: ZGVm }").read(&ctx));
+        assert!(!predicate("metadata: { key: token, suffix: YWJj }").read(&ctx));
+        assert!(predicate("metadata: { key: regions, contains: us }").read(&ctx));
+        assert!(!predicate("metadata: { key: regions, contains: asia }").read(&ctx));
+        assert!(!predicate("metadata: { key: token, contains: us }").read(&ctx));
+    }
+
+    #[test]
+    fn length_and_source() {
+        let ctx = read_context("10.0.0.5:7000", b"hello");
+
+        assert!(predicate("length: { min: 5, max: 5 }").read(&ctx));
+        assert!(!predicate("length: { max: 4 }").read(&ctx));
+        assert!(predicate("source: [192.168.0.0/16, 10.0.0.0/8]").read(&ctx));
+        assert!(!predicate("source: [192.168.0.0/16]").read(&ctx));
+
+        let ctx = WriteContext::new(
+            "[::ffff:10.0.0.5]:7000".parse().unwrap(),
+            "127.0.0.1:7000".parse().unwrap(),
+            alloc_buffer(b"hello"),
+        );
+        assert!(predicate("source: [10.0.0.0/8]").write(&ctx));
+    }
+
+    #[test]
+    fn boolean() {
+        let ctx = read_context("10.0.0.5:7000", b"hello");
+
+        assert!(predicate(
+            "
+all:
+  - length: { min: 1 }
+  - not:
+      source: [192.168.0.0/16]
+"
+        )
+        .read(&ctx));
+        assert!(predicate(
+            "
+any:
+  - length: { max: 1 }
+  - source: [10.0.0.0/8]
+"
+        )
+        .read(&ctx));
+        assert!(!predicate("any: []").read(&ctx));
+        assert!(predicate("all: []").read(&ctx));
+    }
+}
@@ -14,13 +14,18 @@
  * limitations under the License.
  */
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
+
+use bytes::Bytes;
 
 #[cfg(doc)]
 use crate::filters::Filter;
 use crate::{
     net::{
-        endpoint::{metadata::DynamicMetadata, EndpointAddress},
+        endpoint::{
+            metadata::{DynamicMetadata, SessionMetadata},
+            EndpointAddress,
+        },
         ClusterMap,
     },
     pool::PoolBuffer,
@@ -39,6 +44,17 @@ pub struct ReadContext {
     pub contents: PoolBuffer,
     /// Arbitrary values that can be passed from one filter to another.
     pub metadata: DynamicMetadata,
+    /// Values that persist across every packet from [`Self::source`], and
+    /// are visible to the filters processing packets sent back to it.
+    pub session: SessionMetadata,
+    /// How long the packet is held before it's forwarded, without holding
+    /// up the packets received after it.
+    pub delay: Duration,
+    /// Packets sent back to [`Self::source`] by the filters, see [`Self::reply`].
+    pub replies: Vec<Bytes>,
+    /// Further packets forwarded after the packet, see [`Self::forward`].
+    pub forwarded: Vec<PoolBuffer>,
+    handled: bool,
 }
 
 impl ReadContext {
@@ -51,6 +67,45 @@ impl ReadContext {
             source,
             contents,
             metadata: DynamicMetadata::new(),
+            session: SessionMetadata::default(),
+            delay: Duration::ZERO,
+            replies: Vec::new(),
+            forwarded: Vec::new(),
+            handled: false,
         }
     }
+
+    /// Answers the packet by sending `contents` back to [`Self::source`]
+    /// instead of forwarding it. The filters after the one replying are
+    /// skipped, and the packet isn't sent to any endpoint.
+    pub fn reply(&mut self, contents: impl Into<Bytes>) {
+        self.emit(contents);
+        self.handled = true;
+    }
+
+    /// Sends `contents` back to [`Self::source`], while the packet continues
+    /// through the filter chain and is forwarded as usual.
+    pub fn emit(&mut self, contents: impl Into<Bytes>) {
+        self.replies.push(contents.into());
+    }
+
+    /// Forwards `contents` as a further packet after this one, to
+    /// the same destinations, e.g. a fragment of it. The further
+    /// packets don't go through the filters after the one forwarding them.
+    pub fn forward(&mut self, contents: PoolBuffer) {
+        self.forwarded.push(contents);
+    }
+
+    /// Stops the packet without forwarding it or counting it as dropped,
+    /// e.g. because a filter keeps it until it can be reassembled. The
+    /// filters after the one consuming it are skipped.
+    pub fn consume(&mut self) {
+        self.handled = true;
+    }
+
+    /// Whether a filter has answered the packet with [`Self::reply`], or
+    /// consumed it with [`Self::consume`].
+    pub fn is_handled(&self) -> bool {
+        self.handled
+    }
 }
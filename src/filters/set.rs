@@ -34,7 +34,19 @@ pub type FilterMap = std::collections::HashMap<&'static str, Arc<DynFilterFactor
 /// - [`capture`][filters::capture]
 /// - [`token_router`][filters::token_router]
 /// - [`hashed_token_router`][filters::token_router]
+/// - [`signed_token_router`][filters::signed_token_router]
 /// - [`compress`][filters::compress]
+/// - [`label_router`][filters::label_router]
+/// - [`traffic_split`][filters::traffic_split]
+/// - [`metadata`][filters::metadata]
+/// - [`parse_header`][filters::parse_header]
+/// - [`a2s`][filters::a2s]
+/// - [`fan_out`][filters::fan_out]
+/// - [`broadcast`][filters::broadcast]
+/// - [`mirror`][filters::mirror]
+/// - [`chaos`][filters::chaos]
+/// - [`fec`][filters::fec]
+/// - [`fragment`][filters::fragment]
 #[derive(Clone)]
 pub struct FilterSet(FilterMap);
 
@@ -45,19 +57,31 @@ impl FilterSet {
     pub fn default_with(filters: impl IntoIterator<Item = DynFilterFactory>) -> Self {
         Self::with(
             [
+                filters::A2s::factory(),
+                filters::Broadcast::factory(),
                 filters::Capture::factory(),
+                filters::Chaos::factory(),
                 filters::Compress::factory(),
                 filters::Concatenate::factory(),
                 filters::Debug::factory(),
                 filters::Drop::factory(),
+                filters::FanOut::factory(),
+                filters::Fec::factory(),
                 filters::Firewall::factory(),
+                filters::Fragment::factory(),
                 filters::HashedTokenRouter::factory(),
+                filters::LabelRouter::factory(),
                 filters::LoadBalancer::factory(),
                 filters::LocalRateLimit::factory(),
                 filters::Match::factory(),
+                filters::Metadata::factory(),
+                filters::Mirror::factory(),
+                filters::ParseHeader::factory(),
                 filters::Pass::factory(),
+                filters::SignedTokenRouter::factory(),
                 filters::Timestamp::factory(),
                 filters::TokenRouter::factory(),
+                filters::TrafficSplit::factory(),
             ]
             .into_iter()
             .chain(filters),
@@ -0,0 +1,535 @@
+/*
+ * Copyright 2024 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::Base64Standard,
+    filters::{capture::CAPTURED_BYTES, prelude::*},
+    net::{cluster::Token, endpoint::metadata},
+    time::UtcTimestamp,
+};
+
+use crate::generated::quilkin::filters::signed_token_router::v1alpha1 as proto;
+
+/// The default key under which the player ID of a verified token is stored.
+pub const PLAYER_ID: &str = "quilkin.dev/player_id";
+
+/// Filter that routes packets carrying a token signed by a trusted issuer,
+/// such as a matchmaker, to the endpoint named in the token, as long as the
+/// token hasn't expired.
+///
+/// A signed token has the following layout, with all integers in big endian.
+///
+/// | Field              | Size                                        |
+/// |--------------------|---------------------------------------------|
+/// | Endpoint ID length | 1 byte                                      |
+/// | Endpoint ID        | Endpoint ID length                          |
+/// | Player ID length   | 1 byte                                      |
+/// | Player ID          | Player ID length                            |
+/// | Expiry             | 8 bytes, UNIX timestamp in seconds          |
+/// | Signature          | 32 bytes for HMAC-SHA256, 64 for Ed25519    |
+///
+/// The signature covers every field preceding it. The endpoint ID is matched
+/// against the tokens of each endpoint.
+pub struct SignedTokenRouter {
+    metadata_key: metadata::Key,
+    player_id_key: metadata::Key,
+    verifier: Verifier,
+}
+
+impl SignedTokenRouter {
+    fn new(config: Config) -> Result<Self, CreationError> {
+        Ok(Self {
+            metadata_key: config.metadata_key,
+            player_id_key: config.player_id_key,
+            verifier: Verifier::new(config.key)?,
+        })
+    }
+
+    fn verify<'token>(&self, token: &'token [u8]) -> Result<SignedToken<'token>, Error> {
+        let token =
+            SignedToken::parse(token, self.verifier.signature_len()).ok_or(Error::Malformed)?;
+
+        if !self.verifier.verify(token.signed, token.signature) {
+            return Err(Error::InvalidSignature);
+        }
+
+        let now = UtcTimestamp::now().unix();
+        if i64::try_from(token.expires_at).is_ok_and(|expires_at| expires_at < now) {
+            return Err(Error::Expired(token.expires_at));
+        }
+
+        Ok(token)
+    }
+}
+
+impl StaticFilter for SignedTokenRouter {
+    const NAME: &'static str = "quilkin.filters.signed_token_router.v1alpha1.SignedTokenRouter";
+    type Configuration = Config;
+    type BinaryConfiguration = proto::SignedTokenRouter;
+
+    fn try_from_config(config: Option<Self::Configuration>) -> Result<Self, CreationError> {
+        Self::new(Self::ensure_config_exists(config)?)
+    }
+}
+
+#[async_trait::async_trait]
+impl Filter for SignedTokenRouter {
+    async fn read(&self, ctx: &mut ReadContext) -> Result<(), FilterError> {
+        let token = match ctx.metadata.get(&self.metadata_key) {
+            Some(metadata::Value::Bytes(token)) => token.clone(),
+            Some(value) => {
+                return Err(FilterError::new(Error::InvalidType(
+                    self.metadata_key,
+                    value.clone(),
+                )))
+            }
+            None => return Err(FilterError::new(Error::NoTokenFound(self.metadata_key))),
+        };
+
+        let token = self.verify(&token).map_err(FilterError::new)?;
+
+        let mut destinations = Vec::new();
+        ctx.endpoints
+            .addresses_for_token(Token::new(token.endpoint_id), &mut destinations);
+
+        if destinations.is_empty() {
+            return Err(FilterError::new(Error::NoEndpointMatch(
+                crate::codec::base64::encode(token.endpoint_id),
+            )));
+        }
+
+        let player_id = metadata::Value::Bytes(bytes::Bytes::copy_from_slice(token.player_id));
+        ctx.session.insert(self.player_id_key, player_id.clone());
+        ctx.metadata.insert(self.player_id_key, player_id);
+        ctx.destinations = destinations;
+
+        Ok(())
+    }
+}
+
+/// A token whose fields have been parsed, but not yet verified.
+struct SignedToken<'token> {
+    endpoint_id: &'token [u8],
+    player_id: &'token [u8],
+    expires_at: u64,
+    /// The part of the token covered by the signature.
+    signed: &'token [u8],
+    signature: &'token [u8],
+}
+
+impl<'token> SignedToken<'token> {
+    fn parse(token: &'token [u8], signature_len: usize) -> Option<Self> {
+        fn split_field(bytes: &[u8]) -> Option<(&[u8], &[u8])> {
+            let (&len, rest) = bytes.split_first()?;
+            let len = usize::from(len);
+            (rest.len() >= len).then(|| rest.split_at(len))
+        }
+
+        let signed_len = token.len().checked_sub(signature_len)?;
+        let (signed, signature) = token.split_at(signed_len);
+        let (endpoint_id, rest) = split_field(signed)?;
+        let (player_id, rest) = split_field(rest)?;
+        let expires_at = u64::from_be_bytes(rest.try_into().ok()?);
+
+        Some(Self {
+            endpoint_id,
+            player_id,
+            expires_at,
+            signed,
+            signature,
+        })
+    }
+}
+
+enum Verifier {
+    HmacSha256(ring::hmac::Key),
+    Ed25519(ring::signature::UnparsedPublicKey<Vec<u8>>),
+}
+
+impl Verifier {
+    const ED25519_PUBLIC_KEY_LEN: usize = 32;
+
+    fn new(key: VerificationKey) -> Result<Self, CreationError> {
+        match key {
+            VerificationKey::HmacSha256(secret) if secret.is_empty() => {
+                Err(CreationError::FieldInvalid {
+                    field: "key.hmacSha256".into(),
+                    reason: "secret must not be empty".into(),
+                })
+            }
+            VerificationKey::HmacSha256(secret) => Ok(Self::HmacSha256(ring::hmac::Key::new(
+                ring::hmac::HMAC_SHA256,
+                &secret,
+            ))),
+            VerificationKey::Ed25519(public_key)
+                if public_key.len() != Self::ED25519_PUBLIC_KEY_LEN =>
+            {
+                Err(CreationError::FieldInvalid {
+                    field: "key.ed25519".into(),
+                    reason: format!(
+                        "public key must be {} bytes, found {}",
+                        Self::ED25519_PUBLIC_KEY_LEN,
+                        public_key.len()
+                    ),
+                })
+            }
+            VerificationKey::Ed25519(public_key) => Ok(Self::Ed25519(
+                ring::signature::UnparsedPublicKey::new(&ring::signature::ED25519, public_key),
+            )),
+        }
+    }
+
+    fn signature_len(&self) -> usize {
+        match self {
+            Self::HmacSha256(_) => 32,
+            Self::Ed25519(_) => 64,
+        }
+    }
+
+    fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+        match self {
+            Self::HmacSha256(key) => ring::hmac::verify(key, message, signature).is_ok(),
+            Self::Ed25519(key) => key.verify(message, signature).is_ok(),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("no routing token found for `{0}`")]
+    NoTokenFound(metadata::Key),
+    #[error("key `{0}` was found but wasn't bytes, found {1:?}")]
+    InvalidType(metadata::Key, metadata::Value),
+    #[error("routing token is malformed")]
+    Malformed,
+    #[error("routing token signature is invalid")]
+    InvalidSignature,
+    #[error("routing token expired at {0}")]
+    Expired(u64),
+    #[error("no endpoint matched endpoint ID `{0}`")]
+    NoEndpointMatch(String),
+}
+
+/// The key used to verify the signature of tokens.
+#[derive(Clone, Debug, Deserialize, Serialize, Eq, PartialEq, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum VerificationKey {
+    /// A base64 encoded secret shared with the token issuer, for tokens
+    /// signed with HMAC-SHA256.
+    HmacSha256(
+        #[serde(with = "Base64Standard")]
+        #[schemars(with = "String")]
+        Vec<u8>,
+    ),
+    /// The base64 encoded public key of the token issuer, for tokens signed
+    /// with Ed25519.
+    Ed25519(
+        #[serde(with = "Base64Standard")]
+        #[schemars(with = "String")]
+        Vec<u8>,
+    ),
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, schemars::JsonSchema)]
+pub struct Config {
+    /// the key to use when retrieving the token from the Filter's dynamic metadata
+    #[serde(rename = "metadataKey", default = "default_metadata_key")]
+    pub metadata_key: metadata::Key,
+    /// the key under which the player ID of a verified token is stored in
+    /// both the Filter's dynamic metadata and the session's metadata
+    #[serde(rename = "playerIdKey", default = "default_player_id_key")]
+    pub player_id_key: metadata::Key,
+    /// the key used to verify the signature of tokens
+    #[serde(with = "serde_yaml::with::singleton_map")]
+    #[schemars(with = "VerificationKey")]
+    pub key: VerificationKey,
+}
+
+/// Default value for [`Config::metadata_key`]
+fn default_metadata_key() -> metadata::Key {
+    metadata::Key::from_static(CAPTURED_BYTES)
+}
+
+/// Default value for [`Config::player_id_key`]
+fn default_player_id_key() -> metadata::Key {
+    metadata::Key::from_static(PLAYER_ID)
+}
+
+impl From<Config> for proto::SignedTokenRouter {
+    fn from(config: Config) -> Self {
+        Self {
+            metadata_key: Some(config.metadata_key.to_string()),
+            player_id_key: Some(config.player_id_key.to_string()),
+            key: Some(match config.key {
+                VerificationKey::HmacSha256(secret) => {
+                    proto::signed_token_router::Key::HmacSha256(secret)
+                }
+                VerificationKey::Ed25519(public_key) => {
+                    proto::signed_token_router::Key::Ed25519(public_key)
+                }
+            }),
+        }
+    }
+}
+
+impl TryFrom<proto::SignedTokenRouter> for Config {
+    type Error = ConvertProtoConfigError;
+
+    fn try_from(p: proto::SignedTokenRouter) -> Result<Self, Self::Error> {
+        let key = match p.key {
+            Some(proto::signed_token_router::Key::HmacSha256(secret)) => {
+                VerificationKey::HmacSha256(secret)
+            }
+            Some(proto::signed_token_router::Key::Ed25519(public_key)) => {
+                VerificationKey::Ed25519(public_key)
+            }
+            None => return Err(ConvertProtoConfigError::missing_field("key")),
+        };
+
+        Ok(Self {
+            metadata_key: p
+                .metadata_key
+                .map(metadata::Key::new)
+                .unwrap_or_else(default_metadata_key),
+            player_id_key: p
+                .player_id_key
+                .map(metadata::Key::new)
+                .unwrap_or_else(default_player_id_key),
+            key,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ring::signature::KeyPair;
+
+    use crate::{
+        net::endpoint::{metadata::Value, Endpoint, EndpointAddress, Metadata},
+        test::assert_write_no_change,
+    };
+
+    use super::*;
+
+    const SECRET: &[u8] = b"secret";
+
+    fn token(endpoint_id: &[u8], player_id: &[u8], expires_at: u64) -> Vec<u8> {
+        let mut token = vec![endpoint_id.len() as u8];
+        token.extend_from_slice(endpoint_id);
+        token.push(player_id.len() as u8);
+        token.extend_from_slice(player_id);
+        token.extend_from_slice(&expires_at.to_be_bytes());
+        token
+    }
+
+    fn hmac_token(endpoint_id: &[u8], player_id: &[u8], expires_at: u64) -> Vec<u8> {
+        let mut token = token(endpoint_id, player_id, expires_at);
+        let key = ring::hmac::Key::new(ring::hmac::HMAC_SHA256, SECRET);
+        let signature = ring::hmac::sign(&key, &token);
+        token.extend_from_slice(signature.as_ref());
+        token
+    }
+
+    fn in_an_hour() -> u64 {
+        (UtcTimestamp::now().unix() + 3600) as u64
+    }
+
+    fn hmac_filter() -> SignedTokenRouter {
+        SignedTokenRouter::from_config(
+            Config {
+                metadata_key: default_metadata_key(),
+                player_id_key: default_player_id_key(),
+                key: VerificationKey::HmacSha256(SECRET.into()),
+            }
+            .into(),
+        )
+    }
+
+    fn new_ctx(token: Vec<u8>) -> ReadContext {
+        let endpoint1 = Endpoint::with_metadata(
+            "127.0.0.1:80".parse().unwrap(),
+            Metadata {
+                tokens: vec!["server-1".into()].into_iter().collect(),
+                labels: <_>::default(),
+            },
+        );
+        let endpoint2 = Endpoint::with_metadata(
+            "127.0.0.1:90".parse().unwrap(),
+            Metadata {
+                tokens: vec!["server-2".into()].into_iter().collect(),
+                labels: <_>::default(),
+            },
+        );
+
+        let pool = std::sync::Arc::new(crate::pool::BufferPool::new(1, 5));
+
+        let endpoints = crate::net::cluster::ClusterMap::default();
+        endpoints.insert_default([endpoint1, endpoint2].into());
+        let mut ctx = ReadContext::new(
+            endpoints.into(),
+            "127.0.0.1:100".parse().unwrap(),
+            pool.alloc_slice(b"hello"),
+        );
+        ctx.metadata
+            .insert(CAPTURED_BYTES.into(), Value::Bytes(token.into()));
+        ctx
+    }
+
+    #[test]
+    fn convert_proto_config() {
+        let config = Config {
+            metadata_key: "foo".into(),
+            player_id_key: "bar".into(),
+            key: VerificationKey::Ed25519(vec![1; 32]),
+        };
+        let binary = proto::SignedTokenRouter::from(Config {
+            metadata_key: "foo".into(),
+            player_id_key: "bar".into(),
+            key: VerificationKey::Ed25519(vec![1; 32]),
+        });
+        assert_eq!(config, Config::try_from(binary).unwrap());
+
+        assert!(Config::try_from(proto::SignedTokenRouter {
+            metadata_key: None,
+            player_id_key: None,
+            key: None,
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn invalid_keys() {
+        assert!(SignedTokenRouter::try_from_config(Some(Config {
+            metadata_key: default_metadata_key(),
+            player_id_key: default_player_id_key(),
+            key: VerificationKey::Ed25519(vec![1; 16]),
+        }))
+        .is_err());
+        assert!(SignedTokenRouter::try_from_config(Some(Config {
+            metadata_key: default_metadata_key(),
+            player_id_key: default_player_id_key(),
+            key: VerificationKey::HmacSha256(Vec::new()),
+        }))
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn hmac() {
+        let filter = hmac_filter();
+
+        let mut ctx = new_ctx(hmac_token(b"server-2", b"player", in_an_hour()));
+        filter.read(&mut ctx).await.unwrap();
+        assert_eq!(
+            vec![EndpointAddress::from(
+                "127.0.0.1:90".parse::<std::net::SocketAddr>().unwrap()
+            )],
+            ctx.destinations
+        );
+        let player_id = Value::Bytes(bytes::Bytes::from_static(b"player"));
+        assert_eq!(Some(&player_id), ctx.metadata.get(&default_player_id_key()));
+        assert_eq!(Some(player_id), ctx.session.get(&default_player_id_key()));
+    }
+
+    #[tokio::test]
+    async fn ed25519() {
+        let rng = ring::rand::SystemRandom::new();
+        let pkcs8 = ring::signature::Ed25519KeyPair::generate_pkcs8(&rng).unwrap();
+        let pair = ring::signature::Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+
+        let filter = SignedTokenRouter::from_config(
+            Config {
+                metadata_key: default_metadata_key(),
+                player_id_key: default_player_id_key(),
+                key: VerificationKey::Ed25519(pair.public_key().as_ref().to_vec()),
+            }
+            .into(),
+        );
+
+        let mut token = token(b"server-1", b"player", in_an_hour());
+        let signature = pair.sign(&token);
+        token.extend_from_slice(signature.as_ref());
+
+        let mut ctx = new_ctx(token);
+        filter.read(&mut ctx).await.unwrap();
+        assert_eq!(
+            vec![EndpointAddress::from(
+                "127.0.0.1:80".parse::<std::net::SocketAddr>().unwrap()
+            )],
+            ctx.destinations
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_tokens() {
+        let filter = hmac_filter();
+
+        // expired
+        let mut ctx = new_ctx(hmac_token(b"server-1", b"player", 1));
+        assert!(filter.read(&mut ctx).await.is_err());
+
+        // tampered
+        let mut token = hmac_token(b"server-1", b"player", in_an_hour());
+        token[1] = b'x';
+        let mut ctx = new_ctx(token);
+        assert!(filter.read(&mut ctx).await.is_err());
+
+        // unknown endpoint
+        let mut ctx = new_ctx(hmac_token(b"server-3", b"player", in_an_hour()));
+        assert!(filter.read(&mut ctx).await.is_err());
+
+        // truncated
+        let mut ctx = new_ctx(b"\x08server".to_vec());
+        assert!(filter.read(&mut ctx).await.is_err());
+
+        // no token
+        let mut ctx = new_ctx(Vec::new());
+        ctx.metadata.clear();
+        assert!(filter.read(&mut ctx).await.is_err());
+    }
+
+    #[test]
+    fn parse() {
+        let token = hmac_token(b"server-1", b"player", 42);
+        let parsed = SignedToken::parse(&token, 32).unwrap();
+
+        assert_eq!(b"server-1", parsed.endpoint_id);
+        assert_eq!(b"player", parsed.player_id);
+        assert_eq!(42, parsed.expires_at);
+        assert_eq!(&token[..token.len() - 32], parsed.signed);
+
+        assert!(SignedToken::parse(&token[..token.len() - 33], 32).is_none());
+    }
+
+    #[test]
+    fn parse_config() {
+        let config: Config = serde_yaml::from_str(
+            "
+key:
+  hmacSha256: c2VjcmV0
+",
+        )
+        .unwrap();
+
+        assert_eq!(VerificationKey::HmacSha256(SECRET.into()), config.key);
+    }
+
+    #[tokio::test]
+    async fn write() {
+        assert_write_no_change(&hmac_filter()).await;
+    }
+}
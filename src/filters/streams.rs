@@ -0,0 +1,207 @@
+/*
+ * Copyright 2024 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The state of each client of the filters that keep packets between
+//! packets, such as [`Fec`](super::Fec) and [`Fragment`](super::Fragment).
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering::Relaxed},
+        Arc, OnceLock,
+    },
+    time::Duration,
+};
+
+use crate::{
+    collections::ttl::{Entry, TtlMap},
+    net::endpoint::EndpointAddress,
+    pool::BufferPool,
+};
+
+/// How long a client's state is kept after its last packet.
+const STREAM_TIMEOUT: Duration = Duration::from_secs(60);
+const STREAM_EXPIRY_POLL_INTERVAL: Duration = Duration::from_secs(60);
+/// The most clients with state at once, in each direction of a filter.
+pub(crate) const MAX_STREAMS: usize = 16 * 1024;
+/// The most bytes of packets kept at once, in each direction of a filter.
+pub(crate) const MAX_BUFFERED: usize = 64 * 1024 * 1024;
+
+/// Returns the pool of the packets created by a filter.
+pub(crate) fn buffer_pool() -> Arc<BufferPool> {
+    Arc::new(BufferPool::new(num_cpus::get(), 64 * 1024))
+}
+
+/// The state of a client.
+pub(crate) trait Stream: Default + Send + Sync + 'static {
+    /// Returns the number of bytes of packets the state keeps.
+    fn buffered(&self) -> usize;
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Full {
+    #[error("the state of too many clients is already kept")]
+    Streams,
+    #[error("too many bytes of packets are already kept")]
+    Buffered,
+}
+
+/// The state of each client, bounded by a number of clients and of bytes of
+/// packets kept across all of them.
+///
+/// The map of states, which spawns a task to expire them, is only created
+/// with the first state, so filters can be created outside of a runtime.
+pub(crate) struct Streams<S> {
+    streams: OnceLock<TtlMap<EndpointAddress, Tracked<S>>>,
+    buffered: Arc<AtomicUsize>,
+    max_streams: usize,
+    max_buffered: usize,
+}
+
+impl<S: Stream> Streams<S> {
+    pub fn new(max_streams: usize, max_buffered: usize) -> Self {
+        Self {
+            streams: OnceLock::new(),
+            buffered: Arc::default(),
+            max_streams,
+            max_buffered,
+        }
+    }
+
+    /// Calls `f` with the state of `key`, which keeps up to `incoming` more
+    /// bytes of packets. Fails without calling `f` when there's no room for
+    /// them, or for the state of another client.
+    pub fn with<T>(
+        &self,
+        key: &EndpointAddress,
+        incoming: usize,
+        f: impl FnOnce(&mut S) -> T,
+    ) -> Result<T, Full> {
+        if incoming > 0 && self.buffered() + incoming > self.max_buffered {
+            return Err(Full::Buffered);
+        }
+
+        let streams = self
+            .streams
+            .get_or_init(|| TtlMap::new(STREAM_TIMEOUT, STREAM_EXPIRY_POLL_INTERVAL));
+
+        // Checked before taking the entry, which locks its shard.
+        if streams.len() >= self.max_streams && !streams.contains_key(key) {
+            return Err(Full::Streams);
+        }
+
+        Ok(match streams.entry(key.clone()) {
+            Entry::Occupied(mut entry) => entry.get_mut().value.with(f),
+            Entry::Vacant(entry) => entry
+                .insert(Tracked {
+                    stream: S::default(),
+                    buffered: 0,
+                    total: self.buffered.clone(),
+                })
+                .value
+                .with(f),
+        })
+    }
+
+    /// Calls `f` with the state of every client, without resetting how long
+    /// it's kept.
+    pub fn for_each(&self, mut f: impl FnMut(&mut S)) {
+        if let Some(streams) = self.streams.get() {
+            streams.for_each_mut(|tracked| tracked.with(&mut f));
+        }
+    }
+
+    /// Returns the number of bytes of packets kept across every client.
+    pub fn buffered(&self) -> usize {
+        self.buffered.load(Relaxed)
+    }
+}
+
+/// The state of a client, counted in the bytes kept by every client until
+/// it's dropped.
+struct Tracked<S> {
+    stream: S,
+    buffered: usize,
+    total: Arc<AtomicUsize>,
+}
+
+impl<S: Stream> Tracked<S> {
+    fn with<T>(&mut self, f: impl FnOnce(&mut S) -> T) -> T {
+        let result = f(&mut self.stream);
+        let buffered = self.stream.buffered();
+        if buffered > self.buffered {
+            self.total.fetch_add(buffered - self.buffered, Relaxed);
+        } else {
+            self.total.fetch_sub(self.buffered - buffered, Relaxed);
+        }
+
+        self.buffered = buffered;
+        result
+    }
+}
+
+impl<S> Drop for Tracked<S> {
+    fn drop(&mut self) {
+        self.total.fetch_sub(self.buffered, Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Packets(Vec<usize>);
+
+    impl Stream for Packets {
+        fn buffered(&self) -> usize {
+            self.0.iter().sum()
+        }
+    }
+
+    #[tokio::test]
+    async fn bounded() {
+        let streams = Streams::<Packets>::new(2, 100);
+        let client = |port| EndpointAddress::from(([127, 0, 0, 1], port));
+
+        streams
+            .with(&client(1), 60, |packets| packets.0.push(60))
+            .unwrap();
+        streams
+            .with(&client(2), 40, |packets| packets.0.push(40))
+            .unwrap();
+        assert_eq!(100, streams.buffered());
+
+        // No room for more bytes, nor for a third client.
+        assert!(matches!(
+            streams.with(&client(1), 1, |_| ()),
+            Err(Full::Buffered)
+        ));
+        assert!(matches!(
+            streams.with(&client(3), 0, |_| ()),
+            Err(Full::Streams)
+        ));
+
+        // Until the kept packets are released.
+        streams
+            .with(&client(1), 0, |packets| packets.0.clear())
+            .unwrap();
+        assert_eq!(40, streams.buffered());
+        streams
+            .with(&client(2), 50, |packets| packets.0.push(50))
+            .unwrap();
+        assert_eq!(90, streams.buffered());
+    }
+}
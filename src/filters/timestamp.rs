@@ -184,6 +184,7 @@ mod tests {
                     size: 8,
                 }
                 .into(),
+                captures: vec![],
             }
             .into(),
         );
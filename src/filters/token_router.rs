@@ -17,8 +17,14 @@
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    filters::{capture::CAPTURED_BYTES, prelude::*},
-    net::endpoint::metadata,
+    filters::{
+        capture::{self, CAPTURED_BYTES},
+        prelude::*,
+    },
+    net::{
+        endpoint::{metadata, EndpointAddress},
+        ClusterMap,
+    },
 };
 
 use crate::generated::quilkin::filters::token_router::v1alpha1 as proto;
@@ -27,11 +33,15 @@ use crate::generated::quilkin::filters::token_router::v1alpha1 as proto;
 /// connection_id to the token stored in the Filter's dynamic metadata.
 pub struct TokenRouter {
     config: Config,
+    range_key: metadata::Key,
 }
 
 impl TokenRouter {
     fn new(config: Config) -> Self {
-        Self { config }
+        Self {
+            range_key: capture::range_key(config.metadata_key),
+            config,
+        }
     }
 }
 
@@ -54,36 +64,21 @@ impl Filter for TokenRouter {
 
 impl Router for TokenRouter {
     fn sync_read(&self, ctx: &mut ReadContext) -> Result<(), FilterError> {
-        match ctx.metadata.get(&self.config.metadata_key) {
-            Some(metadata::Value::Bytes(token)) => {
-                let destinations = ctx.endpoints.filter_endpoints(|endpoint| {
-                    if endpoint.metadata.known.tokens.contains(&**token) {
+        self.config.route(ctx, self.range_key, |endpoints, token| {
+            let now = crate::time::UtcTimestamp::now().unix();
+            endpoints
+                .filter_endpoints(|endpoint| {
+                    if endpoint.metadata.known.tokens.contains_unexpired(token, now) {
                         tracing::trace!(%endpoint.address, token = &*crate::codec::base64::encode(token), "Endpoint matched");
                         true
                     } else {
                         false
                     }
-                });
-
-                ctx.destinations = destinations.into_iter().map(|ep| ep.address).collect();
-
-                if ctx.destinations.is_empty() {
-                    Err(FilterError::new(Error::NoEndpointMatch(
-                        self.config.metadata_key,
-                        crate::codec::base64::encode(token),
-                    )))
-                } else {
-                    Ok(())
-                }
-            }
-            Some(value) => Err(FilterError::new(Error::InvalidType(
-                self.config.metadata_key,
-                value.clone(),
-            ))),
-            None => Err(FilterError::new(Error::NoTokenFound(
-                self.config.metadata_key,
-            ))),
-        }
+                })
+                .into_iter()
+                .map(|ep| ep.address)
+                .collect()
+        })
     }
 
     fn new() -> Self {
@@ -93,11 +88,15 @@ impl Router for TokenRouter {
 
 pub struct HashedTokenRouter {
     config: Config,
+    range_key: metadata::Key,
 }
 
 impl HashedTokenRouter {
     fn new(config: Config) -> Self {
-        Self { config }
+        Self {
+            range_key: capture::range_key(config.metadata_key),
+            config,
+        }
     }
 }
 
@@ -120,39 +119,12 @@ impl Filter for HashedTokenRouter {
 
 impl Router for HashedTokenRouter {
     fn sync_read(&self, ctx: &mut ReadContext) -> Result<(), FilterError> {
-        match ctx.metadata.get(&self.config.metadata_key) {
-            Some(metadata::Value::Bytes(token)) => {
-                let mut destinations = Vec::new();
-
-                let tok = crate::net::cluster::Token::new(token);
-
-                for ep in ctx.endpoints.iter() {
-                    ep.value().addresses_for_token(tok, &mut destinations);
-
-                    if !destinations.is_empty() {
-                        break;
-                    }
-                }
-
-                ctx.destinations = destinations;
-
-                if ctx.destinations.is_empty() {
-                    Err(FilterError::new(Error::NoEndpointMatch(
-                        self.config.metadata_key,
-                        crate::codec::base64::encode(token),
-                    )))
-                } else {
-                    Ok(())
-                }
-            }
-            Some(value) => Err(FilterError::new(Error::InvalidType(
-                self.config.metadata_key,
-                value.clone(),
-            ))),
-            None => Err(FilterError::new(Error::NoTokenFound(
-                self.config.metadata_key,
-            ))),
-        }
+        self.config.route(ctx, self.range_key, |endpoints, token| {
+            let mut destinations = Vec::new();
+            endpoints
+                .addresses_for_token(crate::net::cluster::Token::new(token), &mut destinations);
+            destinations
+        })
     }
 
     fn new() -> Self {
@@ -181,6 +153,85 @@ pub struct Config {
     /// the key to use when retrieving the token from the Filter's dynamic metadata
     #[serde(rename = "metadataKey", default = "default_metadata_key")]
     pub metadata_key: metadata::Key,
+    /// Whether a token is only required until the client's session is
+    /// established, after which packets without a valid token are routed to
+    /// the endpoints of the last valid token.
+    #[serde(rename = "sessionRouting", default)]
+    pub session_routing: bool,
+}
+
+impl Config {
+    /// Sets the destinations of `ctx` to the endpoints that `find` returns
+    /// for the token in the packet's metadata, or when session routing is
+    /// enabled and the packet has no valid token, for the token the session
+    /// was last bound to.
+    fn route(
+        &self,
+        ctx: &mut ReadContext,
+        range_key: metadata::Key,
+        find: impl Fn(&ClusterMap, &bytes::Bytes) -> Vec<EndpointAddress>,
+    ) -> Result<(), FilterError> {
+        let token = match ctx.metadata.get(&self.metadata_key) {
+            Some(metadata::Value::Bytes(token)) => Some(token.clone()),
+            Some(value) => {
+                return Err(FilterError::new(Error::InvalidType(
+                    self.metadata_key,
+                    value.clone(),
+                )))
+            }
+            None => None,
+        };
+
+        if let Some(token) = &token {
+            ctx.destinations = find(&ctx.endpoints, token);
+
+            if !ctx.destinations.is_empty() {
+                if self.session_routing {
+                    strip_token(ctx, range_key, token);
+                    ctx.session
+                        .insert(self.metadata_key, metadata::Value::Bytes(token.clone()));
+                }
+
+                return Ok(());
+            }
+        }
+
+        if self.session_routing {
+            if let Some(metadata::Value::Bytes(bound)) = ctx.session.get(&self.metadata_key) {
+                ctx.destinations = find(&ctx.endpoints, &bound);
+
+                if !ctx.destinations.is_empty() {
+                    return Ok(());
+                }
+            }
+        }
+
+        Err(FilterError::new(match token {
+            Some(token) => {
+                Error::NoEndpointMatch(self.metadata_key, crate::codec::base64::encode(token))
+            }
+            None => Error::NoTokenFound(self.metadata_key),
+        }))
+    }
+}
+
+/// Removes `token` from where the capture filter found it in the packet, as
+/// recorded under `range_key`. As tokens are optional with session routing,
+/// they're only captured and not removed by the capture filter, as the
+/// captured bytes are part of the payload if they aren't a valid token.
+fn strip_token(ctx: &mut ReadContext, range_key: metadata::Key, token: &[u8]) {
+    let Some(metadata::Value::List(range)) = ctx.metadata.get(&range_key) else {
+        return;
+    };
+
+    let &[metadata::Value::Number(start), metadata::Value::Number(end)] = &range[..] else {
+        return;
+    };
+
+    let range = start as usize..end as usize;
+    if ctx.contents.get(range.clone()) == Some(token) {
+        ctx.contents.remove(range);
+    }
 }
 
 /// Default value for [`Config::metadata_key`]
@@ -192,6 +243,7 @@ impl Default for Config {
     fn default() -> Self {
         Self {
             metadata_key: default_metadata_key(),
+            session_routing: false,
         }
     }
 }
@@ -200,6 +252,7 @@ impl From<Config> for proto::TokenRouter {
     fn from(config: Config) -> Self {
         Self {
             metadata_key: Some(config.metadata_key.to_string()),
+            session_routing: Some(config.session_routing),
         }
     }
 }
@@ -213,6 +266,7 @@ impl TryFrom<proto::TokenRouter> for Config {
                 .metadata_key
                 .map(metadata::Key::new)
                 .unwrap_or_else(default_metadata_key),
+            session_routing: p.session_routing.unwrap_or_default(),
         })
     }
 }
@@ -235,16 +289,22 @@ mod tests {
                 "should succeed when all valid values are provided",
                 proto::TokenRouter {
                     metadata_key: Some("foobar".into()),
+                    session_routing: Some(true),
                 },
                 Some(Config {
                     metadata_key: "foobar".into(),
+                    session_routing: true,
                 }),
             ),
             (
                 "should use correct default values",
-                proto::TokenRouter { metadata_key: None },
+                proto::TokenRouter {
+                    metadata_key: None,
+                    session_routing: None,
+                },
                 Some(Config {
                     metadata_key: default_metadata_key(),
+                    session_routing: false,
                 }),
             ),
         ];
@@ -267,6 +327,7 @@ mod tests {
         let filter = TokenRouter::from_config(
             Config {
                 metadata_key: TOKEN_KEY.into(),
+                ..<_>::default()
             }
             .into(),
         );
@@ -290,6 +351,7 @@ mod tests {
         // valid key
         let config = Config {
             metadata_key: CAPTURED_BYTES.into(),
+            ..<_>::default()
         };
         let filter = TokenRouter::from_config(config.into());
 
@@ -316,40 +378,135 @@ mod tests {
         assert!(filter.read(&mut ctx).await.is_err());
     }
 
+    #[tokio::test]
+    async fn session_routing() {
+        let config = Config {
+            session_routing: true,
+            ..<_>::default()
+        };
+        let filter = TokenRouter::from_config(config.into());
+        let session = crate::net::endpoint::SessionMetadata::default();
+
+        // No token and no session yet.
+        let mut ctx = new_ctx();
+        ctx.session = session.clone();
+        assert!(filter.read(&mut ctx).await.is_err());
+
+        // A valid token binds the session, and is stripped from the packet.
+        let mut ctx = new_ctx_with_contents(b"hello123");
+        ctx.session = session.clone();
+        insert_capture(&mut ctx, b"123", 5..8);
+        assert_read(&filter, ctx).await;
+
+        // Packets with no or an invalid token use the bound token, and keep
+        // their contents intact.
+        let mut ctx = new_ctx();
+        ctx.session = session.clone();
+        insert_capture(&mut ctx, b"llo", 2..5);
+        filter.read(&mut ctx).await.unwrap();
+        assert_eq!(b"hello", ctx.contents.as_ref());
+        assert_eq!(
+            vec![EndpointAddress::from(
+                "127.0.0.1:80".parse::<std::net::SocketAddr>().unwrap()
+            )],
+            ctx.destinations
+        );
+
+        // A new valid token rebinds the session.
+        let mut ctx = new_ctx_with_contents(b"hello456");
+        ctx.session = session.clone();
+        insert_capture(&mut ctx, b"456", 5..8);
+        assert_read(&filter, ctx).await;
+
+        let mut ctx = new_ctx();
+        ctx.session = session.clone();
+        filter.read(&mut ctx).await.unwrap();
+        assert_eq!(
+            vec![EndpointAddress::from(
+                "127.0.0.1:90".parse::<std::net::SocketAddr>().unwrap()
+            )],
+            ctx.destinations
+        );
+    }
+
+    #[tokio::test]
+    async fn session_routing_strips_captured_range() {
+        let config = Config {
+            session_routing: true,
+            ..<_>::default()
+        };
+        let filter = TokenRouter::from_config(config.into());
+
+        // Only the captured token is stripped, even when the payload also ends
+        // with the same bytes.
+        let mut ctx = new_ctx_with_contents(b"123hello123");
+        insert_capture(&mut ctx, b"123", 0..3);
+        filter.read(&mut ctx).await.unwrap();
+        assert_eq!(b"hello123", ctx.contents.as_ref());
+
+        // Tokens captured from the middle of the packet are stripped too.
+        let mut ctx = new_ctx_with_contents(b"he456llo");
+        insert_capture(&mut ctx, b"456", 2..5);
+        assert_read(&filter, ctx).await;
+
+        // Without a range, the token can't be located and is left in place.
+        let mut ctx = new_ctx_with_contents(b"hello123");
+        ctx.metadata
+            .insert(CAPTURED_BYTES.into(), Value::Bytes(b"123".to_vec().into()));
+        filter.read(&mut ctx).await.unwrap();
+        assert_eq!(b"hello123", ctx.contents.as_ref());
+    }
+
     #[tokio::test]
     async fn write() {
         let config = Config {
             metadata_key: CAPTURED_BYTES.into(),
+            ..<_>::default()
         };
         let filter = TokenRouter::from_config(config.into());
         assert_write_no_change(&filter).await;
     }
 
     fn new_ctx() -> ReadContext {
+        new_ctx_with_contents(b"hello")
+    }
+
+    fn new_ctx_with_contents(contents: &[u8]) -> ReadContext {
         let endpoint1 = Endpoint::with_metadata(
             "127.0.0.1:80".parse().unwrap(),
             Metadata {
                 tokens: vec!["123".into()].into_iter().collect(),
+                labels: <_>::default(),
             },
         );
         let endpoint2 = Endpoint::with_metadata(
             "127.0.0.1:90".parse().unwrap(),
             Metadata {
                 tokens: vec!["456".into()].into_iter().collect(),
+                labels: <_>::default(),
             },
         );
 
-        let pool = std::sync::Arc::new(crate::pool::BufferPool::new(1, 5));
+        let pool = std::sync::Arc::new(crate::pool::BufferPool::new(1, contents.len()));
 
         let endpoints = crate::net::cluster::ClusterMap::default();
         endpoints.insert_default([endpoint1, endpoint2].into());
         ReadContext::new(
             endpoints.into(),
             "127.0.0.1:100".parse().unwrap(),
-            pool.alloc_slice(b"hello"),
+            pool.alloc_slice(contents),
         )
     }
 
+    fn insert_capture(ctx: &mut ReadContext, token: &[u8], range: std::ops::Range<u64>) {
+        ctx.metadata
+            .insert(CAPTURED_BYTES.into(), Value::Bytes(token.to_vec().into()));
+        ctx.metadata.insert(
+            capture::range_key(CAPTURED_BYTES.into()),
+            Value::List(vec![Value::Number(range.start), Value::Number(range.end)]),
+        );
+    }
+
     async fn assert_read<F>(filter: &F, mut ctx: ReadContext)
     where
         F: Filter + ?Sized,
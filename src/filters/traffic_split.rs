@@ -0,0 +1,705 @@
+/*
+ * Copyright 2024 Google LLC
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::hash::{Hash, Hasher};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    filters::{capture::CAPTURED_BYTES, prelude::*},
+    net::{
+        endpoint::{metadata, EndpointAddress, Locality},
+        ClusterMap,
+    },
+};
+
+use crate::generated::quilkin::filters::traffic_split::v1alpha1 as proto;
+
+/// The default session metadata key the target a session is pinned to is
+/// stored under.
+pub const SESSION_TARGET: &str = "quilkin.dev/traffic_split";
+
+/// Filter that splits traffic between sets of endpoints, selected by
+/// locality or label, in proportion to their weights.
+///
+/// The first packet of a session is assigned a target using a hash of its
+/// source or token, and the session is pinned to that target, so changing the
+/// weights only affects new sessions. A session only moves when its target
+/// has no endpoints left.
+pub struct TrafficSplit {
+    targets: Vec<Target>,
+    /// Identifies each target by its selector, so sessions stay pinned to the
+    /// same endpoints when the targets are reordered.
+    target_ids: Vec<u64>,
+    total_weight: u64,
+    stickiness: Stickiness,
+    metadata_key: metadata::Key,
+    session_key: metadata::Key,
+}
+
+impl TrafficSplit {
+    fn new(config: Config) -> Result<Self, CreationError> {
+        let total_weight = config
+            .targets
+            .iter()
+            .map(|target| u64::from(target.weight))
+            .sum();
+
+        if total_weight == 0 {
+            return Err(CreationError::FieldInvalid {
+                field: "targets".into(),
+                reason: "at least one target must have a weight greater than zero".into(),
+            });
+        }
+
+        let target_ids = config
+            .targets
+            .iter()
+            .map(|target| {
+                let mut hasher = seahash::SeaHasher::new();
+                target.selector.hash(&mut hasher);
+                hasher.finish()
+            })
+            .collect();
+
+        Ok(Self {
+            targets: config.targets,
+            target_ids,
+            total_weight,
+            stickiness: config.stickiness,
+            metadata_key: config.metadata_key,
+            session_key: config.session_key,
+        })
+    }
+
+    fn hash(&self, ctx: &ReadContext) -> u64 {
+        if self.stickiness == Stickiness::Token {
+            if let Some(metadata::Value::Bytes(token)) = ctx.metadata.get(&self.metadata_key) {
+                return seahash::hash(token);
+            }
+        }
+
+        let mut hasher = seahash::SeaHasher::new();
+        ctx.source.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Returns the index of the target that `hash` falls into.
+    fn target_index(&self, hash: u64) -> usize {
+        let mut point = hash % self.total_weight;
+
+        for (index, target) in self.targets.iter().enumerate() {
+            let weight = u64::from(target.weight);
+            if point < weight {
+                return index;
+            }
+            point -= weight;
+        }
+
+        unreachable!("the point is always less than the total weight")
+    }
+}
+
+impl StaticFilter for TrafficSplit {
+    const NAME: &'static str = "quilkin.filters.traffic_split.v1alpha1.TrafficSplit";
+    type Configuration = Config;
+    type BinaryConfiguration = proto::TrafficSplit;
+
+    fn try_from_config(config: Option<Self::Configuration>) -> Result<Self, CreationError> {
+        Self::new(Self::ensure_config_exists(config)?)
+    }
+}
+
+#[async_trait::async_trait]
+impl Filter for TrafficSplit {
+    async fn read(&self, ctx: &mut ReadContext) -> Result<(), FilterError> {
+        let key = self.session_key;
+
+        // Sessions stay on their target, even if its weight has since changed,
+        // for as long as it has any endpoints.
+        if let Some(metadata::Value::Number(id)) = ctx.session.get(&key) {
+            if let Some(index) = self.target_ids.iter().position(|target| *target == id) {
+                let destinations = self.targets[index].selector.destinations(&ctx.endpoints);
+                if !destinations.is_empty() {
+                    ctx.destinations = destinations;
+                    return Ok(());
+                }
+            }
+        }
+
+        let start = self.target_index(self.hash(ctx));
+
+        // If the assigned target has no endpoints, fall back to the next
+        // target with any, so sessions aren't dropped while a target is empty.
+        for offset in 0..self.targets.len() {
+            let index = (start + offset) % self.targets.len();
+            let target = &self.targets[index];
+            if target.weight == 0 {
+                continue;
+            }
+
+            let destinations = target.selector.destinations(&ctx.endpoints);
+            if !destinations.is_empty() {
+                ctx.session
+                    .insert(key, metadata::Value::Number(self.target_ids[index]));
+                ctx.destinations = destinations;
+                return Ok(());
+            }
+        }
+
+        Err(FilterError::new(Error::NoEndpoints))
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("no target has any endpoints")]
+    NoEndpoints,
+}
+
+/// How packets are assigned to a target.
+#[derive(
+    Clone, Copy, Debug, Default, Deserialize, Serialize, Eq, PartialEq, schemars::JsonSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum Stickiness {
+    /// Use a hash of the packet's source address.
+    #[default]
+    Source,
+    /// Use a hash of the token found under the filter's `metadataKey`,
+    /// falling back to the source address when there's no token, so a player
+    /// keeps the same target when their address changes.
+    Token,
+}
+
+/// Selects the endpoints of a target.
+#[derive(Clone, Debug, Deserialize, Serialize, Eq, Hash, PartialEq, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum Selector {
+    /// The endpoints in a locality.
+    Locality(Locality),
+    /// The endpoints with a label equal to a value.
+    Label { key: String, value: String },
+}
+
+impl Selector {
+    fn destinations(&self, endpoints: &ClusterMap) -> Vec<EndpointAddress> {
+        match self {
+            Self::Locality(locality) => endpoints
+                .get(&Some(locality.clone()))
+                .map(|set| {
+                    set.endpoints
+                        .iter()
+                        .map(|endpoint| endpoint.address.clone())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            Self::Label { key, value } => endpoints
+                .filter_endpoints(|endpoint| endpoint.metadata.known.labels.get(key) == Some(value))
+                .into_iter()
+                .map(|endpoint| endpoint.address)
+                .collect(),
+        }
+    }
+}
+
+/// A set of endpoints, and the proportion of sessions it receives.
+#[derive(Clone, Debug, Deserialize, Serialize, Eq, PartialEq, schemars::JsonSchema)]
+pub struct Target {
+    /// the weight of this target relative to the other targets
+    pub weight: u32,
+    /// the endpoints of this target
+    #[serde(flatten)]
+    pub selector: Selector,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, schemars::JsonSchema)]
+pub struct Config {
+    /// the targets that traffic is split between
+    pub targets: Vec<Target>,
+    /// how packets are assigned to a target
+    #[serde(default)]
+    pub stickiness: Stickiness,
+    /// the key to use when retrieving the token from the Filter's dynamic
+    /// metadata, when `stickiness` is `token`
+    #[serde(rename = "metadataKey", default = "default_metadata_key")]
+    pub metadata_key: metadata::Key,
+    /// the session metadata key to pin sessions to their target under, which
+    /// must be unique to each `TrafficSplit` filter sessions go through
+    #[serde(rename = "sessionKey", default = "default_session_key")]
+    pub session_key: metadata::Key,
+}
+
+/// Default value for [`Config::metadata_key`]
+fn default_metadata_key() -> metadata::Key {
+    metadata::Key::from_static(CAPTURED_BYTES)
+}
+
+/// Default value for [`Config::session_key`]
+fn default_session_key() -> metadata::Key {
+    metadata::Key::from_static(SESSION_TARGET)
+}
+
+impl From<Stickiness> for proto::traffic_split::Stickiness {
+    fn from(stickiness: Stickiness) -> Self {
+        match stickiness {
+            Stickiness::Source => Self::Source,
+            Stickiness::Token => Self::Token,
+        }
+    }
+}
+
+impl From<proto::traffic_split::Stickiness> for Stickiness {
+    fn from(stickiness: proto::traffic_split::Stickiness) -> Self {
+        match stickiness {
+            proto::traffic_split::Stickiness::Source => Self::Source,
+            proto::traffic_split::Stickiness::Token => Self::Token,
+        }
+    }
+}
+
+impl From<Target> for proto::traffic_split::Target {
+    fn from(target: Target) -> Self {
+        use proto::traffic_split::target::Selector as ProtoSelector;
+
+        Self {
+            weight: target.weight,
+            selector: Some(match target.selector {
+                Selector::Locality(locality) => ProtoSelector::Locality(locality.to_string()),
+                Selector::Label { key, value } => {
+                    ProtoSelector::Label(proto::traffic_split::Label { key, value })
+                }
+            }),
+        }
+    }
+}
+
+impl TryFrom<proto::traffic_split::Target> for Target {
+    type Error = ConvertProtoConfigError;
+
+    fn try_from(target: proto::traffic_split::Target) -> Result<Self, Self::Error> {
+        use proto::traffic_split::target::Selector as ProtoSelector;
+
+        let selector = match target.selector {
+            Some(ProtoSelector::Locality(locality)) => {
+                Selector::Locality(locality.parse().map_err(|error| {
+                    ConvertProtoConfigError::new(
+                        format!("invalid locality `{locality}`: {error}"),
+                        Some("targets.locality".into()),
+                    )
+                })?)
+            }
+            Some(ProtoSelector::Label(proto::traffic_split::Label { key, value })) => {
+                Selector::Label { key, value }
+            }
+            None => return Err(ConvertProtoConfigError::missing_field("targets.selector")),
+        };
+
+        Ok(Self {
+            weight: target.weight,
+            selector,
+        })
+    }
+}
+
+impl From<Config> for proto::TrafficSplit {
+    fn from(config: Config) -> Self {
+        Self {
+            targets: config.targets.into_iter().map(From::from).collect(),
+            stickiness: Some(proto::traffic_split::StickinessValue {
+                value: proto::traffic_split::Stickiness::from(config.stickiness) as i32,
+            }),
+            metadata_key: Some(config.metadata_key.to_string()),
+            session_key: Some(config.session_key.to_string()),
+        }
+    }
+}
+
+impl TryFrom<proto::TrafficSplit> for Config {
+    type Error = ConvertProtoConfigError;
+
+    fn try_from(p: proto::TrafficSplit) -> Result<Self, Self::Error> {
+        let stickiness = p
+            .stickiness
+            .map(|value| {
+                proto::traffic_split::Stickiness::try_from(value.value)
+                    .map(Stickiness::from)
+                    .map_err(|_| {
+                        ConvertProtoConfigError::new(
+                            format!("invalid stickiness `{}`", value.value),
+                            Some("stickiness".into()),
+                        )
+                    })
+            })
+            .transpose()?
+            .unwrap_or_default();
+
+        Ok(Self {
+            targets: p
+                .targets
+                .into_iter()
+                .map(TryFrom::try_from)
+                .collect::<Result<_, _>>()?,
+            stickiness,
+            metadata_key: p
+                .metadata_key
+                .map(metadata::Key::new)
+                .unwrap_or_else(default_metadata_key),
+            session_key: p
+                .session_key
+                .map(metadata::Key::new)
+                .unwrap_or_else(default_session_key),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        net::endpoint::{metadata::Value, Endpoint, Metadata},
+        test::assert_write_no_change,
+    };
+
+    use super::*;
+
+    fn endpoint(port: u16, build: &str) -> Endpoint {
+        Endpoint::with_metadata(
+            (std::net::Ipv4Addr::LOCALHOST, port).into(),
+            Metadata {
+                tokens: <_>::default(),
+                labels: [("build".to_owned(), build.to_owned())].into(),
+            },
+        )
+    }
+
+    fn endpoints() -> std::sync::Arc<ClusterMap> {
+        let endpoints = ClusterMap::default();
+        endpoints.insert(
+            Some("eu".parse().unwrap()),
+            [endpoint(80, "stable"), endpoint(81, "stable")].into(),
+        );
+        endpoints.insert(Some("us".parse().unwrap()), [endpoint(90, "canary")].into());
+        endpoints.into()
+    }
+
+    fn label(weight: u32, build: &str) -> Target {
+        Target {
+            weight,
+            selector: Selector::Label {
+                key: "build".into(),
+                value: build.into(),
+            },
+        }
+    }
+
+    fn filter(targets: Vec<Target>, stickiness: Stickiness) -> TrafficSplit {
+        TrafficSplit::from_config(
+            Config {
+                targets,
+                stickiness,
+                metadata_key: default_metadata_key(),
+                session_key: default_session_key(),
+            }
+            .into(),
+        )
+    }
+
+    fn ctx(endpoints: &std::sync::Arc<ClusterMap>, source: EndpointAddress) -> ReadContext {
+        let pool = std::sync::Arc::new(crate::pool::BufferPool::new(1, 5));
+        ReadContext::new(endpoints.clone(), source, pool.alloc_slice(b"hello"))
+    }
+
+    fn source(port: u16) -> EndpointAddress {
+        (std::net::Ipv4Addr::new(10, 0, 0, 1), port).into()
+    }
+
+    #[tokio::test]
+    async fn split_by_weight() {
+        let endpoints = endpoints();
+        let filter = filter(
+            vec![label(90, "stable"), label(10, "canary")],
+            Stickiness::Source,
+        );
+
+        let mut canary = 0;
+        for port in 0..10_000 {
+            let mut ctx = ctx(&endpoints, source(port));
+            filter.read(&mut ctx).await.unwrap();
+
+            if ctx.destinations == [EndpointAddress::from((std::net::Ipv4Addr::LOCALHOST, 90))] {
+                canary += 1;
+            } else {
+                assert_eq!(ctx.destinations.len(), 2);
+            }
+        }
+
+        assert!((800..1200).contains(&canary), "{canary}");
+    }
+
+    #[tokio::test]
+    async fn sticky() {
+        let endpoints = endpoints();
+        let filter = filter(
+            vec![label(50, "stable"), label(50, "canary")],
+            Stickiness::Source,
+        );
+
+        for port in 0..100 {
+            let mut first = ctx(&endpoints, source(port));
+            filter.read(&mut first).await.unwrap();
+
+            for _ in 0..5 {
+                let mut ctx = ctx(&endpoints, source(port));
+                filter.read(&mut ctx).await.unwrap();
+                assert_eq!(first.destinations, ctx.destinations);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn sticky_by_token() {
+        let endpoints = endpoints();
+        let filter = filter(
+            vec![label(50, "stable"), label(50, "canary")],
+            Stickiness::Token,
+        );
+
+        for token in 0..100u64 {
+            let mut destinations = None;
+
+            for port in 0..5 {
+                let mut ctx = ctx(&endpoints, source(port));
+                ctx.metadata.insert(
+                    CAPTURED_BYTES.into(),
+                    Value::Bytes(token.to_be_bytes().to_vec().into()),
+                );
+                filter.read(&mut ctx).await.unwrap();
+
+                let destinations = destinations.get_or_insert_with(|| ctx.destinations.clone());
+                assert_eq!(*destinations, ctx.destinations);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn pinned() {
+        let endpoints = endpoints();
+        let stable = filter(
+            vec![label(100, "stable"), label(0, "canary")],
+            Stickiness::Source,
+        );
+        let canary = filter(
+            vec![label(0, "stable"), label(100, "canary")],
+            Stickiness::Source,
+        );
+        let session = crate::net::endpoint::SessionMetadata::default();
+
+        let mut first = ctx(&endpoints, source(1));
+        first.session = session.clone();
+        stable.read(&mut first).await.unwrap();
+        assert_eq!(first.destinations.len(), 2);
+
+        // The session stays on its target when the weights change.
+        let mut reweighted = ctx(&endpoints, source(1));
+        reweighted.session = session.clone();
+        canary.read(&mut reweighted).await.unwrap();
+        assert_eq!(reweighted.destinations.len(), 2);
+
+        let mut new_session = ctx(&endpoints, source(1));
+        canary.read(&mut new_session).await.unwrap();
+        assert_eq!(
+            new_session.destinations,
+            [EndpointAddress::from((std::net::Ipv4Addr::LOCALHOST, 90))]
+        );
+
+        // And moves once its target has no endpoints.
+        let endpoints =
+            std::sync::Arc::new(ClusterMap::new_default([endpoint(90, "canary")].into()));
+        let mut moved = ctx(&endpoints, source(1));
+        moved.session = session.clone();
+        canary.read(&mut moved).await.unwrap();
+        assert_eq!(
+            moved.destinations,
+            [EndpointAddress::from((std::net::Ipv4Addr::LOCALHOST, 90))]
+        );
+        assert_eq!(
+            session.get(&SESSION_TARGET.into()),
+            Some(Value::Number(canary.target_ids[1]))
+        );
+    }
+
+    #[tokio::test]
+    async fn session_key() {
+        let endpoints = endpoints();
+        let with_key = |targets, session_key: &str| {
+            TrafficSplit::from_config(
+                Config {
+                    targets,
+                    stickiness: Stickiness::Source,
+                    metadata_key: default_metadata_key(),
+                    session_key: session_key.into(),
+                }
+                .into(),
+            )
+        };
+        let stable = with_key(vec![label(1, "stable")], "stable");
+        let canary = with_key(vec![label(1, "canary")], "canary");
+        let session = crate::net::endpoint::SessionMetadata::default();
+
+        // Each filter pins the session under its own key, so one filter's
+        // target doesn't affect the other's.
+        let mut first = ctx(&endpoints, source(1));
+        first.session = session.clone();
+        stable.read(&mut first).await.unwrap();
+
+        let mut second = ctx(&endpoints, source(1));
+        second.session = session.clone();
+        canary.read(&mut second).await.unwrap();
+        assert_eq!(
+            second.destinations,
+            [EndpointAddress::from((std::net::Ipv4Addr::LOCALHOST, 90))]
+        );
+
+        assert_eq!(
+            session.get(&"stable".into()),
+            Some(Value::Number(stable.target_ids[0]))
+        );
+        assert_eq!(
+            session.get(&"canary".into()),
+            Some(Value::Number(canary.target_ids[0]))
+        );
+    }
+
+    #[tokio::test]
+    async fn locality() {
+        let endpoints = endpoints();
+        let filter = filter(
+            vec![Target {
+                weight: 1,
+                selector: Selector::Locality("us".parse().unwrap()),
+            }],
+            Stickiness::Source,
+        );
+
+        let mut ctx = ctx(&endpoints, source(1));
+        filter.read(&mut ctx).await.unwrap();
+        assert_eq!(
+            ctx.destinations,
+            [EndpointAddress::from((std::net::Ipv4Addr::LOCALHOST, 90))]
+        );
+    }
+
+    #[tokio::test]
+    async fn falls_back_when_empty() {
+        let endpoints = endpoints();
+        let split = filter(
+            vec![
+                label(50, "missing"),
+                label(0, "canary"),
+                label(50, "stable"),
+            ],
+            Stickiness::Source,
+        );
+
+        for port in 0..100 {
+            let mut ctx = ctx(&endpoints, source(port));
+            split.read(&mut ctx).await.unwrap();
+            assert_eq!(ctx.destinations.len(), 2);
+        }
+
+        let split = filter(vec![label(1, "missing")], Stickiness::Source);
+        let mut ctx = ctx(&endpoints, source(1));
+        assert!(split.read(&mut ctx).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn write() {
+        assert_write_no_change(&filter(vec![label(1, "stable")], Stickiness::Source)).await;
+    }
+
+    #[test]
+    fn zero_weight() {
+        assert!(TrafficSplit::try_from_config(Some(Config {
+            targets: vec![label(0, "stable")],
+            stickiness: Stickiness::Source,
+            metadata_key: default_metadata_key(),
+            session_key: default_session_key(),
+        }))
+        .is_err());
+    }
+
+    #[test]
+    fn parse_config() {
+        let config: Config = serde_yaml::from_str(
+            "
+targets:
+  - weight: 90
+    locality: eu
+  - weight: 10
+    label:
+      key: build
+      value: canary
+stickiness: token
+",
+        )
+        .unwrap();
+
+        assert_eq!(
+            config,
+            Config {
+                targets: vec![
+                    Target {
+                        weight: 90,
+                        selector: Selector::Locality("eu".parse().unwrap()),
+                    },
+                    label(10, "canary"),
+                ],
+                stickiness: Stickiness::Token,
+                metadata_key: default_metadata_key(),
+                session_key: default_session_key(),
+            }
+        );
+    }
+
+    #[test]
+    fn convert_proto_config() {
+        let config = || Config {
+            targets: vec![
+                Target {
+                    weight: 90,
+                    selector: Selector::Locality("eu:west:1".parse().unwrap()),
+                },
+                label(10, "canary"),
+            ],
+            stickiness: Stickiness::Token,
+            metadata_key: "foo".into(),
+            session_key: "bar".into(),
+        };
+        let binary = proto::TrafficSplit::from(config());
+        assert_eq!(config(), Config::try_from(binary).unwrap());
+
+        assert!(Config::try_from(proto::TrafficSplit {
+            targets: vec![proto::traffic_split::Target {
+                weight: 1,
+                selector: None,
+            }],
+            stickiness: None,
+            metadata_key: None,
+            session_key: None,
+        })
+        .is_err());
+    }
+}
@@ -14,10 +14,12 @@
  * limitations under the License.
  */
 
-use std::collections::HashMap;
+use std::{collections::HashMap, time::Duration};
+
+use bytes::Bytes;
 
 use crate::{
-    net::endpoint::{DynamicMetadata, EndpointAddress},
+    net::endpoint::{DynamicMetadata, EndpointAddress, SessionMetadata},
     pool::PoolBuffer,
 };
 
@@ -31,10 +33,24 @@ pub struct WriteContext {
     pub source: EndpointAddress,
     /// The destination of the received packet.
     pub dest: EndpointAddress,
+    /// Downstream addresses that a copy of the packet is also sent to,
+    /// besides [`Self::dest`].
+    pub destinations: Vec<EndpointAddress>,
     /// Contents of the received packet.
     pub contents: PoolBuffer,
     /// Arbitrary values that can be passed from one filter to another
     pub metadata: DynamicMetadata,
+    /// Values that persist across every packet of the session with
+    /// [`Self::dest`], shared with the filters processing its packets.
+    pub session: SessionMetadata,
+    /// How long the packet is held before it's forwarded, without holding
+    /// up the packets received after it.
+    pub delay: Duration,
+    /// Packets sent back to [`Self::source`] by the filters, see [`Self::reply`].
+    pub replies: Vec<Bytes>,
+    /// Further packets forwarded after the packet, see [`Self::forward`].
+    pub forwarded: Vec<PoolBuffer>,
+    handled: bool,
 }
 
 impl WriteContext {
@@ -44,8 +60,49 @@ impl WriteContext {
         Self {
             source,
             dest,
+            destinations: Vec::new(),
             contents,
             metadata: HashMap::new(),
+            session: SessionMetadata::default(),
+            delay: Duration::ZERO,
+            replies: Vec::new(),
+            forwarded: Vec::new(),
+            handled: false,
         }
     }
+
+    /// Answers the packet by sending `contents` back to the endpoint it came
+    /// from, [`Self::source`], instead of forwarding it to [`Self::dest`].
+    /// The filters after the one replying are skipped.
+    pub fn reply(&mut self, contents: impl Into<Bytes>) {
+        self.emit(contents);
+        self.handled = true;
+    }
+
+    /// Sends `contents` back to [`Self::source`], while the packet continues
+    /// through the filter chain and is forwarded as usual.
+    pub fn emit(&mut self, contents: impl Into<Bytes>) {
+        self.replies.push(contents.into());
+    }
+
+    /// Forwards `contents` as a further packet after this one, to
+    /// [`Self::dest`] and [`Self::destinations`], e.g. a fragment of it. The
+    /// further packets don't go through the filters after the one forwarding
+    /// them.
+    pub fn forward(&mut self, contents: PoolBuffer) {
+        self.forwarded.push(contents);
+    }
+
+    /// Stops the packet without forwarding it or counting it as dropped,
+    /// e.g. because a filter keeps it until it can be reassembled. The
+    /// filters after the one consuming it are skipped.
+    pub fn consume(&mut self) {
+        self.handled = true;
+    }
+
+    /// Whether a filter has answered the packet with [`Self::reply`], or
+    /// consumed it with [`Self::consume`].
+    pub fn is_handled(&self) -> bool {
+        self.handled
+    }
 }
@@ -77,16 +77,28 @@ pub(crate) trait Loggable {
 #[cfg(doctest)]
 mod external_doc_tests {
     #![doc = include_str!("../docs/src/services/proxy/filters.md")]
+    #![doc = include_str!("../docs/src/services/proxy/filters/a2s.md")]
+    #![doc = include_str!("../docs/src/services/proxy/filters/broadcast.md")]
     #![doc = include_str!("../docs/src/services/proxy/filters/capture.md")]
+    #![doc = include_str!("../docs/src/services/proxy/filters/chaos.md")]
     #![doc = include_str!("../docs/src/services/proxy/filters/compress.md")]
     #![doc = include_str!("../docs/src/services/proxy/filters/concatenate.md")]
     #![doc = include_str!("../docs/src/services/proxy/filters/debug.md")]
+    #![doc = include_str!("../docs/src/services/proxy/filters/fan_out.md")]
+    #![doc = include_str!("../docs/src/services/proxy/filters/fec.md")]
     #![doc = include_str!("../docs/src/services/proxy/filters/firewall.md")]
+    #![doc = include_str!("../docs/src/services/proxy/filters/fragment.md")]
+    #![doc = include_str!("../docs/src/services/proxy/filters/label_router.md")]
     #![doc = include_str!("../docs/src/services/proxy/filters/load_balancer.md")]
     #![doc = include_str!("../docs/src/services/proxy/filters/local_rate_limit.md")]
     #![doc = include_str!("../docs/src/services/proxy/filters/match.md")]
+    #![doc = include_str!("../docs/src/services/proxy/filters/metadata.md")]
+    #![doc = include_str!("../docs/src/services/proxy/filters/mirror.md")]
+    #![doc = include_str!("../docs/src/services/proxy/filters/parse_header.md")]
+    #![doc = include_str!("../docs/src/services/proxy/filters/signed_token_router.md")]
     #![doc = include_str!("../docs/src/services/proxy/filters/timestamp.md")]
     #![doc = include_str!("../docs/src/services/proxy/filters/token_router.md")]
+    #![doc = include_str!("../docs/src/services/proxy/filters/traffic_split.md")]
     #![doc = include_str!("../docs/src/services/proxy/filters/writing_custom_filters.md")]
     #![doc = include_str!("../docs/src/services/xds/providers/filesystem.md")]
 }
@@ -203,6 +203,26 @@ pub(crate) fn packets_dropped_total(
     ])
 }
 
+pub(crate) fn packets_replied_total(direction: Direction, asn: Option<&IpNetEntry>) -> IntCounter {
+    static PACKETS_REPLIED: Lazy<IntCounterVec> = Lazy::new(|| {
+        prometheus::register_int_counter_vec_with_registry! {
+            prometheus::opts! {
+                "packets_replied_total",
+                "Total number of packets sent back to their sender by filters",
+            },
+            &[Direction::LABEL, ASN_LABEL, PREFIX_LABEL],
+            registry(),
+        }
+        .unwrap()
+    });
+
+    PACKETS_REPLIED.with_label_values(&[
+        direction.label(),
+        &asn.map(|asn| asn.r#as.to_string()).unwrap_or_default(),
+        asn.map(|asn| &*asn.prefix).unwrap_or_default(),
+    ])
+}
+
 /// Create a generic metrics options.
 /// Use [filter_opts] instead if the intended target is a filter.
 pub fn opts(name: &str, subsystem: &str, description: &str) -> Opts {
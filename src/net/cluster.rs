@@ -18,7 +18,7 @@ use std::{
     borrow::Borrow,
     collections::{hash_map::RandomState, BTreeSet},
     fmt,
-    sync::atomic::{AtomicU64, AtomicUsize, Ordering::Relaxed},
+    sync::atomic::{AtomicI64, AtomicU64, AtomicUsize, Ordering::Relaxed},
 };
 
 use dashmap::DashMap;
@@ -62,8 +62,6 @@ pub(crate) fn active_endpoints() -> &'static prometheus::IntGauge {
     &ACTIVE_ENDPOINTS
 }
 
-pub type TokenAddressMap = std::collections::BTreeMap<u64, Vec<EndpointAddress>>;
-
 #[derive(Copy, Clone)]
 pub struct Token(u64);
 
@@ -74,10 +72,126 @@ impl Token {
     }
 }
 
+/// An index of the addresses of the endpoints with each token, across every
+/// locality.
+///
+/// Rather than being rebuilt whenever a cluster changes, the index is updated
+/// with only the endpoints that were added or removed. Every token is indexed,
+/// expired tokens are removed when the endpoint's expired tokens are pruned.
+struct TokenIndex<S = RandomState> {
+    addresses: DashMap<u64, Vec<EndpointAddress>, S>,
+    /// The earliest expiry of the indexed tokens, or [`i64::MAX`] when none
+    /// of them expire, so pruning can skip scanning every endpoint until then.
+    next_expiry: AtomicI64,
+}
+
+impl<S> TokenIndex<S>
+where
+    S: Default + std::hash::BuildHasher + Clone,
+{
+    fn with_hasher(hasher: S) -> Self {
+        Self {
+            addresses: DashMap::with_hasher(hasher),
+            next_expiry: AtomicI64::new(i64::MAX),
+        }
+    }
+
+    #[inline]
+    fn addresses_for_token(&self, token: Token, addresses: &mut Vec<EndpointAddress>) {
+        if let Some(addrs) = self.addresses.get(&token.0) {
+            addresses.extend_from_slice(&addrs);
+        }
+    }
+
+    fn insert(&self, endpoint: &Endpoint) {
+        for (_, expires_at) in endpoint.metadata.known.tokens.expiries() {
+            self.expires_at(expires_at);
+        }
+
+        for token in &endpoint.metadata.known.tokens {
+            self.addresses
+                .entry(Token::new(token).0)
+                .or_default()
+                .push(endpoint.address.clone());
+        }
+    }
+
+    fn remove(&self, endpoint: &Endpoint) {
+        for token in &endpoint.metadata.known.tokens {
+            self.remove_token(token, &endpoint.address);
+        }
+    }
+
+    /// Removes a single instance of `address` for `token`, if present
+    fn remove_token(&self, token: &[u8], address: &EndpointAddress) {
+        let token = Token::new(token).0;
+        let dashmap::mapref::entry::Entry::Occupied(mut entry) = self.addresses.entry(token) else {
+            return;
+        };
+
+        let addresses = entry.get_mut();
+        if let Some(index) = addresses.iter().position(|addr| addr == address) {
+            addresses.swap_remove(index);
+        }
+
+        if addresses.is_empty() {
+            entry.remove();
+        }
+    }
+
+    /// Updates the index with the endpoints that differ between `old` and
+    /// `new`, either by being added, removed, or by having different metadata
+    fn update(&self, old: &BTreeSet<Endpoint>, new: &BTreeSet<Endpoint>) {
+        for ep in old {
+            if new.get(ep) != Some(ep) {
+                self.remove(ep);
+            }
+        }
+
+        for ep in new {
+            if old.get(ep) != Some(ep) {
+                self.insert(ep);
+            }
+        }
+    }
+
+    /// Records that an indexed token expires at `expires_at`.
+    fn expires_at(&self, expires_at: i64) {
+        self.next_expiry.fetch_min(expires_at, Relaxed);
+    }
+
+    /// Returns whether any indexed token has expired at `now`, resetting the
+    /// earliest expiry if so, which is then recorded again for the tokens
+    /// that remain.
+    fn take_expired(&self, now: i64) -> bool {
+        // Reset in a single update, so an earlier expiry recorded in between
+        // isn't overwritten.
+        self.next_expiry
+            .fetch_update(Relaxed, Relaxed, |next| (next <= now).then_some(i64::MAX))
+            .is_ok()
+    }
+
+    fn clear(&self) {
+        self.addresses.clear();
+        self.next_expiry.store(i64::MAX, Relaxed);
+    }
+}
+
+impl<S> Default for TokenIndex<S>
+where
+    S: Default + std::hash::BuildHasher + Clone,
+{
+    fn default() -> Self {
+        Self {
+            addresses: DashMap::default(),
+            next_expiry: AtomicI64::new(i64::MAX),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct EndpointSet {
     pub endpoints: BTreeSet<Endpoint>,
-    pub token_map: TokenAddressMap,
     /// The hash of all of the endpoints in this set
     hash: u64,
     /// Version of this set of endpoints. Any mutatation of the endpoints
@@ -91,7 +205,6 @@ impl EndpointSet {
     pub fn new(endpoints: BTreeSet<Endpoint>) -> Self {
         let mut this = Self {
             endpoints,
-            token_map: TokenAddressMap::new(),
             hash: 0,
             version: 0,
         };
@@ -107,15 +220,11 @@ impl EndpointSet {
     /// across machines
     #[inline]
     pub fn with_version(endpoints: BTreeSet<Endpoint>, hash: EndpointSetVersion) -> Self {
-        let mut this = Self {
+        Self {
             endpoints,
-            token_map: TokenAddressMap::new(),
             hash: hash.number(),
             version: 1,
-        };
-
-        this.build_token_map();
-        this
+        }
     }
 
     #[inline]
@@ -133,13 +242,6 @@ impl EndpointSet {
         self.endpoints.contains(ep)
     }
 
-    #[inline]
-    pub fn addresses_for_token(&self, token: Token, addresses: &mut Vec<EndpointAddress>) {
-        if let Some(addrs) = self.token_map.get(&token.0) {
-            addresses.extend_from_slice(addrs);
-        }
-    }
-
     /// Unique version for this endpoint set
     #[inline]
     pub fn version(&self) -> EndpointSetVersion {
@@ -153,36 +255,37 @@ impl EndpointSet {
     pub fn update(&mut self) {
         use std::hash::{Hash, Hasher};
         let mut hasher = seahash::SeaHasher::with_seeds(0, 1, 2, 3);
-        let mut token_map = TokenAddressMap::new();
 
         for ep in &self.endpoints {
             ep.hash(&mut hasher);
-
-            for tok in &ep.metadata.known.tokens {
-                let hash = seahash::hash(tok);
-                token_map.entry(hash).or_default().push(ep.address.clone());
-            }
         }
 
         self.hash = hasher.finish();
         self.version += 1;
-        self.token_map = token_map;
     }
 
-    /// Creates a map of tokens -> address for the current set
-    #[inline]
-    pub fn build_token_map(&mut self) {
-        let mut token_map = TokenAddressMap::new();
-
-        // This is only called on proxies, so calculate a token map
-        for ep in &self.endpoints {
-            for tok in &ep.metadata.known.tokens {
-                let hash = seahash::hash(tok);
-                token_map.entry(hash).or_default().push(ep.address.clone());
-            }
+    /// Removes any tokens that have expired at `now` from the endpoints,
+    /// updating the set if any were removed, returning how many were removed
+    pub fn remove_expired_tokens(&mut self, now: i64) -> usize {
+        if !self
+            .endpoints
+            .iter()
+            .any(|ep| ep.metadata.known.tokens.has_expired(now))
+        {
+            return 0;
         }
 
-        self.token_map = token_map;
+        let mut removed = 0;
+        self.endpoints = std::mem::take(&mut self.endpoints)
+            .into_iter()
+            .map(|mut ep| {
+                removed += ep.metadata.known.tokens.remove_expired(now);
+                ep
+            })
+            .collect();
+
+        self.update();
+        removed
     }
 
     #[inline]
@@ -194,7 +297,6 @@ impl EndpointSet {
         } else {
             self.hash = replacement.hash;
             self.version += 1;
-            self.build_token_map();
         }
 
         old
@@ -204,13 +306,12 @@ impl EndpointSet {
 /// Represents a full snapshot of all clusters.
 pub struct ClusterMap<S = RandomState> {
     map: DashMap<Option<Locality>, EndpointSet, S>,
+    token_index: TokenIndex<S>,
     num_endpoints: AtomicUsize,
     version: AtomicU64,
 }
 
 type DashMapRef<'inner, S> = dashmap::mapref::one::Ref<'inner, Option<Locality>, EndpointSet, S>;
-type DashMapRefMut<'inner, S> =
-    dashmap::mapref::one::RefMut<'inner, Option<Locality>, EndpointSet, S>;
 
 impl ClusterMap<RandomState> {
     pub fn new() -> Self {
@@ -237,7 +338,8 @@ where
 {
     pub fn benchmarking(capacity: usize, hasher: S) -> Self {
         Self {
-            map: DashMap::with_capacity_and_hasher(capacity, hasher),
+            map: DashMap::with_capacity_and_hasher(capacity, hasher.clone()),
+            token_index: TokenIndex::with_hasher(hasher),
             ..Self::default()
         }
     }
@@ -262,6 +364,7 @@ where
 
             let old = current.replace(cluster);
             let old_len = old.len();
+            self.token_index.update(&old, &current.endpoints);
 
             if new_len >= old_len {
                 self.num_endpoints.fetch_add(new_len - old_len, Relaxed);
@@ -272,6 +375,8 @@ where
             self.version.fetch_add(1, Relaxed);
             Some(old)
         } else {
+            self.token_index
+                .update(&BTreeSet::new(), &cluster.endpoints);
             self.map.insert(locality, cluster);
             self.num_endpoints.fetch_add(new_len, Relaxed);
             self.version.fetch_add(1, Relaxed);
@@ -293,18 +398,10 @@ where
         self.map.get(key)
     }
 
-    pub fn get_mut(&self, key: &Option<Locality>) -> Option<DashMapRefMut<S>> {
-        self.map.get_mut(key)
-    }
-
     pub fn get_default(&self) -> Option<DashMapRef<S>> {
         self.get(&None)
     }
 
-    pub fn get_default_mut(&self) -> Option<DashMapRefMut<S>> {
-        self.get_mut(&None)
-    }
-
     #[inline]
     pub fn insert_default(&self, endpoints: BTreeSet<Endpoint>) {
         self.insert(None, endpoints);
@@ -315,7 +412,8 @@ where
         for mut entry in self.map.iter_mut() {
             let set = entry.value_mut();
 
-            if set.endpoints.remove(needle) {
+            if let Some(removed) = set.endpoints.take(needle) {
+                self.token_index.remove(&removed);
                 set.update();
                 self.num_endpoints.fetch_sub(1, Relaxed);
                 self.version.fetch_add(1, Relaxed);
@@ -339,6 +437,7 @@ where
                 // This will always be true, but....
                 let removed = set.endpoints.remove(&endpoint);
                 if removed {
+                    self.token_index.remove(&endpoint);
                     set.update();
                     self.num_endpoints.fetch_sub(1, Relaxed);
                     self.version.fetch_add(1, Relaxed);
@@ -350,22 +449,48 @@ where
         false
     }
 
+    /// Removes any tokens that have expired at `now` from every endpoint,
+    /// returning how many were removed
+    pub fn remove_expired_tokens(&self, now: i64) -> usize {
+        if !self.token_index.take_expired(now) {
+            return 0;
+        }
+
+        let mut removed = 0;
+        for mut entry in self.map.iter_mut() {
+            for ep in &entry.value().endpoints {
+                for (token, expires_at) in ep.metadata.known.tokens.expiries() {
+                    if expires_at <= now {
+                        self.token_index.remove_token(token, &ep.address);
+                    } else {
+                        self.token_index.expires_at(expires_at);
+                    }
+                }
+            }
+
+            removed += entry.value_mut().remove_expired_tokens(now);
+        }
+
+        if removed > 0 {
+            self.version.fetch_add(1, Relaxed);
+        }
+
+        removed
+    }
+
     #[inline]
     pub fn iter(&self) -> dashmap::iter::Iter<Option<Locality>, EndpointSet, S> {
         self.map.iter()
     }
 
-    pub fn entry(
-        &self,
-        key: Option<Locality>,
-    ) -> dashmap::mapref::entry::Entry<Option<Locality>, EndpointSet, S> {
-        self.map.entry(key)
-    }
-
     #[inline]
     pub fn replace(&self, locality: Option<Locality>, endpoint: Endpoint) -> Option<Endpoint> {
         if let Some(mut set) = self.map.get_mut(&locality) {
+            self.token_index.insert(&endpoint);
             let replaced = set.endpoints.replace(endpoint);
+            if let Some(replaced) = &replaced {
+                self.token_index.remove(replaced);
+            }
             set.update();
             self.version.fetch_add(1, Relaxed);
 
@@ -416,6 +541,13 @@ where
         endpoints
     }
 
+    /// Appends the addresses of the endpoints with `token`, in any locality,
+    /// to `addresses`.
+    #[inline]
+    pub fn addresses_for_token(&self, token: Token, addresses: &mut Vec<EndpointAddress>) {
+        self.token_index.addresses_for_token(token, addresses);
+    }
+
     #[inline]
     pub fn num_of_endpoints(&self) -> usize {
         self.num_endpoints.load(Relaxed)
@@ -431,6 +563,9 @@ where
         if let Some((_, set)) = self.map.remove(&None) {
             self.version.fetch_add(1, Relaxed);
             if let Some(replaced) = self.map.insert(Some(locality), set) {
+                for ep in &replaced.endpoints {
+                    self.token_index.remove(ep);
+                }
                 self.num_endpoints.fetch_sub(replaced.len(), Relaxed);
             }
         }
@@ -440,6 +575,9 @@ where
     pub fn remove_locality(&self, locality: &Option<Locality>) -> Option<EndpointSet> {
         let ret = self.map.remove(locality).map(|(_k, v)| v);
         if let Some(ret) = &ret {
+            for ep in &ret.endpoints {
+                self.token_index.remove(ep);
+            }
             self.version.fetch_add(1, Relaxed);
             self.num_endpoints.fetch_sub(ret.len(), Relaxed);
         }
@@ -447,11 +585,15 @@ where
         ret
     }
 
-    /// Builds token maps for every locality. Only used by testing/benching
-    #[doc(hidden)]
-    pub fn build_token_maps(&self) {
-        for mut eps in self.map.iter_mut() {
-            eps.build_token_map();
+    /// Rebuilds the token index from scratch, e.g. after endpoints have been
+    /// changed through [`Self::get_mut`] or [`Self::entry`]
+    pub fn rebuild_token_index(&self) {
+        self.token_index.clear();
+
+        for set in self.map.iter() {
+            for ep in &set.value().endpoints {
+                self.token_index.insert(ep);
+            }
         }
     }
 }
@@ -491,6 +633,7 @@ where
     fn default() -> Self {
         Self {
             map: <DashMap<Option<Locality>, EndpointSet, S>>::default(),
+            token_index: <_>::default(),
             version: <_>::default(),
             num_endpoints: <_>::default(),
         }
@@ -624,11 +767,15 @@ where
 {
     fn from(map: DashMap<Option<Locality>, EndpointSet, S>) -> Self {
         let num_endpoints = AtomicUsize::new(map.iter().map(|kv| kv.value().len()).sum());
-        Self {
+        let this = Self {
+            token_index: TokenIndex::with_hasher(map.hasher().clone()),
             map,
             num_endpoints,
             version: AtomicU64::new(1),
-        }
+        };
+
+        this.rebuild_token_index();
+        this
     }
 }
 
@@ -637,12 +784,40 @@ impl From<&'_ Endpoint> for proto::Endpoint {
         Self {
             host: endpoint.address.host.to_string(),
             port: endpoint.address.port.into(),
+            token_expiry: endpoint.metadata.known.token_expiry(),
             metadata: Some((&endpoint.metadata).into()),
             host2: None,
         }
     }
 }
 
+/// How often [`prune_expired_tokens`] checks for expired tokens.
+pub const TOKEN_PRUNE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Periodically removes any expired tokens from `clusters` until shutdown. Any
+/// endpoint sets that had tokens removed have their version bumped, so that
+/// the change is pushed to any xDS clients.
+pub async fn prune_expired_tokens(
+    clusters: crate::config::Watch<ClusterMap>,
+    mut shutdown_rx: crate::ShutdownRx,
+) {
+    let mut interval = tokio::time::interval(TOKEN_PRUNE_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = shutdown_rx.changed() => return,
+        }
+
+        let now = crate::time::UtcTimestamp::now().unix();
+        let removed = clusters.modify(|clusters| clusters.remove_expired_tokens(now));
+
+        if removed > 0 {
+            tracing::debug!(removed, "removed expired tokens");
+        }
+    }
+}
+
 pub(crate) fn locality_and_set_to_proto(
     locality: impl Borrow<Option<Locality>>,
     endpoints: impl Borrow<BTreeSet<Endpoint>>,
@@ -697,4 +872,102 @@ mod tests {
         assert_eq!(cluster1.get(&Some(nl1.clone())).unwrap().len(), 1);
         assert!(cluster1.get(&Some(de1.clone())).unwrap().is_empty());
     }
+
+    #[test]
+    fn remove_expired_tokens() {
+        let mut tokens = crate::net::endpoint::Set::new();
+        tokens.insert(b"forever".to_vec());
+        tokens.insert_expiring(b"expiring".to_vec(), 10);
+        tokens.insert_expiring(b"later".to_vec(), 20);
+        let endpoint = Endpoint::with_metadata(
+            (Ipv4Addr::LOCALHOST, 7777).into(),
+            crate::net::endpoint::Metadata {
+                tokens,
+                labels: <_>::default(),
+            },
+        );
+
+        let cluster = ClusterMap::new_default([endpoint.clone()].into());
+        let mut addresses = Vec::new();
+        cluster.addresses_for_token(Token::new(b"expiring"), &mut addresses);
+        assert_eq!(addresses, [endpoint.address.clone()]);
+
+        let version = cluster.version();
+        let set_version = cluster.get_default().unwrap().version();
+        assert_eq!(cluster.remove_expired_tokens(5), 0);
+        assert_eq!(cluster.version(), version);
+
+        assert_eq!(cluster.remove_expired_tokens(10), 1);
+        assert_ne!(cluster.version(), version);
+        assert!(cluster.get_default().unwrap().version() != set_version);
+
+        let endpoints = cluster.endpoints();
+        let tokens = &endpoints[0].metadata.known.tokens;
+        assert!(tokens.contains(b"forever"));
+        assert!(!tokens.contains(b"expiring"));
+
+        addresses.clear();
+        cluster.addresses_for_token(Token::new(b"expiring"), &mut addresses);
+        assert!(addresses.is_empty());
+
+        // Pruning is skipped until the next token expires.
+        assert_eq!(cluster.token_index.next_expiry.load(Relaxed), 20);
+        assert_eq!(cluster.remove_expired_tokens(15), 0);
+        assert_eq!(cluster.remove_expired_tokens(20), 1);
+        assert_eq!(cluster.token_index.next_expiry.load(Relaxed), i64::MAX);
+    }
+
+    #[test]
+    fn token_index() {
+        let nl1 = Some(Locality::with_region("nl-1"));
+        let de1 = Some(Locality::with_region("de-1"));
+        let ep = |port: u16, tokens: &[&[u8]]| {
+            Endpoint::with_metadata(
+                (Ipv4Addr::LOCALHOST, port).into(),
+                crate::net::endpoint::Metadata {
+                    tokens: tokens.iter().map(|t| t.to_vec()).collect(),
+                    labels: <_>::default(),
+                },
+            )
+        };
+        let addresses = |cluster: &ClusterMap, token: &[u8]| {
+            let mut addresses = Vec::new();
+            cluster.addresses_for_token(Token::new(token), &mut addresses);
+            addresses.sort();
+            addresses
+        };
+
+        let cluster = ClusterMap::new();
+        let a = ep(1, &[b"abc", b"def"]);
+        let b = ep(2, &[b"abc"]);
+        cluster.insert(nl1.clone(), [a.clone()].into());
+        cluster.insert(de1.clone(), [b.clone()].into());
+
+        assert_eq!(
+            addresses(&cluster, b"abc"),
+            [a.address.clone(), b.address.clone()]
+        );
+        assert_eq!(addresses(&cluster, b"def"), [a.address.clone()]);
+
+        // Changing an endpoint's tokens only updates that endpoint
+        let a2 = ep(1, &[b"ghi"]);
+        cluster.insert(nl1.clone(), [a2.clone()].into());
+        assert_eq!(addresses(&cluster, b"abc"), [b.address.clone()]);
+        assert!(addresses(&cluster, b"def").is_empty());
+        assert_eq!(addresses(&cluster, b"ghi"), [a2.address.clone()]);
+
+        let b2 = ep(2, &[b"ghi"]);
+        cluster.replace(de1.clone(), b2.clone());
+        assert!(addresses(&cluster, b"abc").is_empty());
+        assert_eq!(
+            addresses(&cluster, b"ghi"),
+            [a2.address.clone(), b2.address.clone()]
+        );
+
+        assert!(cluster.remove_endpoint(&a2));
+        assert_eq!(addresses(&cluster, b"ghi"), [b2.address.clone()]);
+
+        cluster.remove_locality(&de1);
+        assert!(addresses(&cluster, b"ghi").is_empty());
+    }
 }
@@ -25,7 +25,7 @@ use serde::{Deserialize, Serialize};
 
 pub use self::{
     address::{AddressKind, EndpointAddress},
-    metadata::DynamicMetadata,
+    metadata::{DynamicMetadata, SessionMetadata},
 };
 
 pub use xds::locality::Locality;
@@ -78,13 +78,16 @@ impl Endpoint {
             proto.host.parse()?
         };
 
+        let mut metadata: EndpointMetadata = proto
+            .metadata
+            .map(TryFrom::try_from)
+            .transpose()?
+            .unwrap_or_default();
+        metadata.known.apply_token_expiry(proto.token_expiry);
+
         Ok(Self {
             address: (host, proto.port as u16).into(),
-            metadata: proto
-                .metadata
-                .map(TryFrom::try_from)
-                .transpose()?
-                .unwrap_or_default(),
+            metadata,
         })
     }
 
@@ -110,6 +113,7 @@ impl Endpoint {
         proto::Endpoint {
             host: String::new(),
             port: self.address.port.into(),
+            token_expiry: self.metadata.known.token_expiry(),
             metadata: Some(self.metadata.into()),
             host2: Some(proto::Host { inner: Some(host) }),
         }
@@ -141,6 +145,7 @@ impl From<Endpoint> for proto::Endpoint {
         Self {
             host: endpoint.address.host.to_string(),
             port: endpoint.address.port.into(),
+            token_expiry: endpoint.metadata.known.token_expiry(),
             metadata: Some(endpoint.metadata.into()),
             host2: None,
         }
@@ -156,13 +161,16 @@ impl TryFrom<proto::Endpoint> for Endpoint {
             return Err(eyre::eyre!("invalid endpoint port"));
         }
 
+        let mut metadata: EndpointMetadata = endpoint
+            .metadata
+            .map(TryFrom::try_from)
+            .transpose()?
+            .unwrap_or_default();
+        metadata.known.apply_token_expiry(endpoint.token_expiry);
+
         Ok(Self {
             address: (host, endpoint.port as u16).into(),
-            metadata: endpoint
-                .metadata
-                .map(TryFrom::try_from)
-                .transpose()?
-                .unwrap_or_default(),
+            metadata,
         })
     }
 }
@@ -195,6 +203,7 @@ impl std::hash::Hash for Endpoint {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.address.hash(state);
         self.metadata.known.tokens.hash(state);
+        self.metadata.known.labels.hash(state);
     }
 }
 
@@ -204,10 +213,37 @@ impl std::hash::Hash for Endpoint {
 )]
 pub struct Metadata {
     #[serde(
+        default,
         serialize_with = "base64_set::serialize",
         deserialize_with = "base64_set::deserialize"
     )]
     pub tokens: base64_set::Set,
+    /// Arbitrary key value labels, such as the game mode or build of the
+    /// server, which filters can route on.
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub labels: std::collections::BTreeMap<String, String>,
+}
+
+impl Metadata {
+    /// Returns the expiry of each token that has one, in the form sent
+    /// alongside the endpoint over xDS.
+    pub fn token_expiry(&self) -> Vec<proto::TokenExpiry> {
+        self.tokens
+            .expiries()
+            .map(|(token, expires_at)| proto::TokenExpiry {
+                token: token.clone(),
+                expires_at,
+            })
+            .collect()
+    }
+
+    /// Sets the expiry of each token in `expiry`, ignoring any tokens that
+    /// aren't present.
+    pub fn apply_token_expiry(&mut self, expiry: Vec<proto::TokenExpiry>) {
+        for te in expiry {
+            self.tokens.set_expiry(&te.token, Some(te.expires_at));
+        }
+    }
 }
 
 impl From<Metadata> for crate::net::endpoint::metadata::MetadataView<Metadata> {
@@ -235,9 +271,33 @@ impl From<Metadata> for prost_types::Struct {
             )),
         };
 
-        Self {
-            fields: <_>::from([("tokens".into(), tokens)]),
+        let mut fields = std::collections::BTreeMap::from([("tokens".into(), tokens)]);
+
+        if !metadata.labels.is_empty() {
+            let labels = prost_types::Struct {
+                fields: metadata
+                    .labels
+                    .into_iter()
+                    .map(|(key, value)| {
+                        (
+                            key,
+                            prost_types::Value {
+                                kind: Some(prost_types::value::Kind::StringValue(value)),
+                            },
+                        )
+                    })
+                    .collect(),
+            };
+
+            fields.insert(
+                "labels".into(),
+                prost_types::Value {
+                    kind: Some(prost_types::value::Kind::StructValue(labels)),
+                },
+            );
         }
+
+        Self { fields }
     }
 }
 
@@ -247,6 +307,7 @@ impl std::convert::TryFrom<prost_types::Struct> for Metadata {
     fn try_from(mut value: prost_types::Struct) -> Result<Self, Self::Error> {
         use prost_types::value::Kind;
         const TOKENS: &str = "tokens";
+        const LABELS: &str = "labels";
 
         let tokens =
             if let Some(kind) = value.fields.remove(TOKENS).and_then(|v| v.kind) {
@@ -275,7 +336,28 @@ impl std::convert::TryFrom<prost_types::Struct> for Metadata {
                 <_>::default()
             };
 
-        Ok(Self { tokens })
+        let labels = match value.fields.remove(LABELS).and_then(|v| v.kind) {
+            Some(Kind::StructValue(labels)) => labels
+                .fields
+                .into_iter()
+                .map(|(key, value)| match value.kind {
+                    Some(Kind::StringValue(value)) => Ok((key, value)),
+                    _ => Err(MetadataError::InvalidType {
+                        key: "quilkin.dev.labels",
+                        expected: "string",
+                    }),
+                })
+                .collect::<Result<_, _>>()?,
+            Some(_) => {
+                return Err(MetadataError::InvalidType {
+                    key: "quilkin.dev.labels",
+                    expected: "struct",
+                })
+            }
+            None => <_>::default(),
+        };
+
+        Ok(Self { tokens, labels })
     }
 }
 
@@ -292,57 +374,208 @@ pub enum MetadataError {
     },
 }
 
-/// A module for providing base64 encoding for a `BTreeSet` at the `serde`
-/// boundary. Accepts a list of strings representing Base64 encoded data,
+/// A module for providing base64 encoding for a set of tokens at the `serde`
+/// boundary. Accepts a list of strings representing Base64 encoded data, or
+/// objects with a base64 encoded `token` and an `expiresAt` UNIX timestamp,
 /// this list is then converted into its binary representation while in memory,
-/// and then encoded back as a list of base64 strings.
+/// and then encoded back in the same form.
 mod base64_set {
     use serde::de::Error;
 
-    pub type Set = std::collections::BTreeSet<Vec<u8>>;
+    /// A set of tokens, each with an optional expiry as a UNIX timestamp in
+    /// seconds.
+    #[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct Set(std::collections::BTreeMap<Vec<u8>, Option<i64>>);
+
+    impl Set {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Adds a token that never expires, returning whether it wasn't
+        /// already present.
+        pub fn insert(&mut self, token: Vec<u8>) -> bool {
+            self.0.insert(token, None).is_none()
+        }
+
+        /// Adds a token that expires at `expires_at`, returning whether it
+        /// wasn't already present.
+        pub fn insert_expiring(&mut self, token: Vec<u8>, expires_at: i64) -> bool {
+            self.0.insert(token, Some(expires_at)).is_none()
+        }
+
+        pub fn remove(&mut self, token: &[u8]) -> bool {
+            self.0.remove(token).is_some()
+        }
+
+        /// Whether `token` is present, regardless of whether it has expired.
+        pub fn contains(&self, token: &[u8]) -> bool {
+            self.0.contains_key(token)
+        }
+
+        /// Whether `token` is present and hasn't expired at `now`.
+        pub fn contains_unexpired(&self, token: &[u8], now: i64) -> bool {
+            self.0
+                .get(token)
+                .map_or(false, |expires_at| !is_expired(*expires_at, now))
+        }
+
+        /// Returns the expiry of `token`, if it's present and has one.
+        pub fn expiry(&self, token: &[u8]) -> Option<i64> {
+            self.0.get(token).copied().flatten()
+        }
+
+        /// Sets the expiry of `token`, returning whether it's present.
+        pub fn set_expiry(&mut self, token: &[u8], expires_at: Option<i64>) -> bool {
+            self.0
+                .get_mut(token)
+                .map(|expiry| *expiry = expires_at)
+                .is_some()
+        }
+
+        pub fn iter(&self) -> impl Iterator<Item = &Vec<u8>> {
+            self.0.keys()
+        }
+
+        /// Iterates over the tokens with an expiry.
+        pub fn expiries(&self) -> impl Iterator<Item = (&Vec<u8>, i64)> {
+            self.0
+                .iter()
+                .filter_map(|(token, expires_at)| expires_at.map(|expires_at| (token, expires_at)))
+        }
+
+        /// Iterates over the tokens that haven't expired at `now`.
+        pub fn unexpired(&self, now: i64) -> impl Iterator<Item = &Vec<u8>> {
+            self.0
+                .iter()
+                .filter(move |(_, expires_at)| !is_expired(**expires_at, now))
+                .map(|(token, _)| token)
+        }
+
+        /// Whether any token has expired at `now`.
+        pub fn has_expired(&self, now: i64) -> bool {
+            self.0
+                .values()
+                .any(|expires_at| is_expired(*expires_at, now))
+        }
+
+        /// Removes the tokens that have expired at `now`, returning how many
+        /// were removed.
+        pub fn remove_expired(&mut self, now: i64) -> usize {
+            let len = self.0.len();
+            self.0.retain(|_, expires_at| !is_expired(*expires_at, now));
+            len - self.0.len()
+        }
+
+        pub fn len(&self) -> usize {
+            self.0.len()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.0.is_empty()
+        }
+    }
+
+    fn is_expired(expires_at: Option<i64>, now: i64) -> bool {
+        expires_at.map_or(false, |expires_at| expires_at <= now)
+    }
+
+    impl FromIterator<Vec<u8>> for Set {
+        fn from_iter<I: IntoIterator<Item = Vec<u8>>>(iter: I) -> Self {
+            Self(iter.into_iter().map(|token| (token, None)).collect())
+        }
+    }
+
+    impl<const N: usize> From<[Vec<u8>; N]> for Set {
+        fn from(tokens: [Vec<u8>; N]) -> Self {
+            tokens.into_iter().collect()
+        }
+    }
+
+    impl From<std::collections::BTreeSet<Vec<u8>>> for Set {
+        fn from(tokens: std::collections::BTreeSet<Vec<u8>>) -> Self {
+            tokens.into_iter().collect()
+        }
+    }
+
+    impl IntoIterator for Set {
+        type Item = Vec<u8>;
+        type IntoIter = std::collections::btree_map::IntoKeys<Vec<u8>, Option<i64>>;
+
+        fn into_iter(self) -> Self::IntoIter {
+            self.0.into_keys()
+        }
+    }
+
+    impl<'set> IntoIterator for &'set Set {
+        type Item = &'set Vec<u8>;
+        type IntoIter = std::collections::btree_map::Keys<'set, Vec<u8>, Option<i64>>;
+
+        fn into_iter(self) -> Self::IntoIter {
+            self.0.keys()
+        }
+    }
+
+    /// The serialized form of a single token.
+    #[derive(serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+    #[serde(untagged)]
+    enum Entry {
+        Token(String),
+        Expiring {
+            token: String,
+            #[serde(rename = "expiresAt")]
+            expires_at: i64,
+        },
+    }
+
+    impl schemars::JsonSchema for Set {
+        fn schema_name() -> String {
+            "TokenSet".into()
+        }
+
+        fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+            <Vec<Entry>>::json_schema(gen)
+        }
+    }
 
     pub fn serialize<S>(set: &Set, ser: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        ser.collect_seq(set.iter().map(crate::codec::base64::encode))
+        ser.collect_seq(set.0.iter().map(|(token, expires_at)| {
+            let token = crate::codec::base64::encode(token);
+            match expires_at {
+                Some(expires_at) => Entry::Expiring {
+                    token,
+                    expires_at: *expires_at,
+                },
+                None => Entry::Token(token),
+            }
+        }))
     }
 
     pub fn deserialize<'de, D>(de: D) -> Result<Set, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        struct TokenVisitor;
+        let entries = <Vec<Entry> as serde::Deserialize>::deserialize(de)?;
+        let mut set = Set::new();
 
-        impl<'de> serde::de::Visitor<'de> for TokenVisitor {
-            type Value = Set;
-
-            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-                formatter.write_str("an array of base64 encoded tokens")
-            }
-
-            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
-            where
-                A: serde::de::SeqAccess<'de>,
-            {
-                let mut set = Set::new();
-
-                while let Some(token) = seq.next_element::<std::borrow::Cow<'_, str>>()? {
-                    let decoded =
-                        crate::codec::base64::decode(token.as_ref()).map_err(Error::custom)?;
-
-                    if !set.insert(decoded) {
-                        return Err(Error::custom(
-                            "Found duplicate tokens in endpoint metadata.",
-                        ));
-                    }
-                }
+        for entry in entries {
+            let (token, expires_at) = match entry {
+                Entry::Token(token) => (token, None),
+                Entry::Expiring { token, expires_at } => (token, Some(expires_at)),
+            };
+            let decoded = crate::codec::base64::decode(token).map_err(Error::custom)?;
 
-                Ok(set)
+            if set.0.insert(decoded, expires_at).is_some() {
+                return Err(Error::custom(
+                    "Found duplicate tokens in endpoint metadata.",
+                ));
             }
         }
 
-        de.deserialize_seq(TokenVisitor)
+        Ok(set)
     }
 }
 
@@ -354,6 +587,7 @@ mod tests {
     fn endpoint_metadata() {
         let metadata = Metadata {
             tokens: vec!["Man".into()].into_iter().collect(),
+            labels: <_>::default(),
         };
 
         assert_eq!(
@@ -366,6 +600,80 @@ mod tests {
         );
     }
 
+    #[test]
+    fn endpoint_metadata_token_expiry() {
+        let mut tokens = Set::new();
+        tokens.insert(b"Man".to_vec());
+        tokens.insert_expiring(b"abc".to_vec(), 1_700_000_000);
+        let metadata = EndpointMetadata::from(Metadata {
+            tokens,
+            labels: <_>::default(),
+        });
+
+        let json = serde_json::json!({
+            crate::net::endpoint::metadata::KEY: {
+                "tokens": [
+                    "TWFu",
+                    { "token": "YWJj", "expiresAt": 1_700_000_000 },
+                ],
+            }
+        });
+
+        assert_eq!(serde_json::to_value(&metadata).unwrap(), json);
+        assert_eq!(
+            serde_json::from_value::<EndpointMetadata>(json).unwrap(),
+            metadata
+        );
+    }
+
+    #[test]
+    fn endpoint_metadata_labels() {
+        let metadata: EndpointMetadata = serde_yaml::from_str(
+            "
+quilkin.dev:
+    labels:
+        game_mode: ranked
+        build: 1.2.3
+",
+        )
+        .unwrap();
+
+        assert!(metadata.known.tokens.is_empty());
+        assert_eq!(metadata.known.labels["game_mode"], "ranked");
+        assert_eq!(metadata.known.labels["build"], "1.2.3");
+
+        let endpoint = Endpoint::with_metadata("127.0.0.1:8080".parse().unwrap(), metadata);
+        assert_eq!(
+            Endpoint::from_proto(endpoint.clone().into_proto()).unwrap(),
+            endpoint
+        );
+    }
+
+    #[test]
+    fn token_expiry() {
+        let mut tokens = Set::new();
+        tokens.insert(b"forever".to_vec());
+        tokens.insert_expiring(b"old".to_vec(), 10);
+        tokens.insert_expiring(b"new".to_vec(), 20);
+
+        assert!(tokens.contains_unexpired(b"forever", 15));
+        assert!(!tokens.contains_unexpired(b"old", 15));
+        assert!(tokens.contains(b"old"));
+        assert!(tokens.contains_unexpired(b"new", 15));
+        assert_eq!(
+            tokens.unexpired(15).cloned().collect::<Vec<_>>(),
+            [b"forever".to_vec(), b"new".to_vec()]
+        );
+
+        assert!(!tokens.has_expired(5));
+        assert!(tokens.has_expired(15));
+        assert_eq!(tokens.remove_expired(15), 1);
+        assert!(!tokens.has_expired(15));
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens.expiry(b"new"), Some(20));
+        assert_eq!(tokens.expiry(b"forever"), None);
+    }
+
     #[test]
     fn parse_dns_endpoints() {
         let localhost = "address: localhost:80";
@@ -411,4 +719,25 @@ mod tests {
 
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn endpoint_proto_conversion_token_expiry() {
+        let mut tokens = Set::new();
+        tokens.insert(b"abc".to_vec());
+        tokens.insert_expiring(b"def".to_vec(), 1_700_000_000);
+        let endpoint = Endpoint::with_metadata(
+            "127.0.0.1:8080".parse().unwrap(),
+            Metadata {
+                tokens,
+                labels: <_>::default(),
+            },
+        );
+
+        let proto = endpoint.clone().into_proto();
+        assert_eq!(proto.token_expiry.len(), 1);
+        assert_eq!(Endpoint::from_proto(proto).unwrap(), endpoint);
+
+        let proto = proto::Endpoint::from(endpoint.clone());
+        assert_eq!(Endpoint::try_from(proto).unwrap(), endpoint);
+    }
 }
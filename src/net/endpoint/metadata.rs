@@ -21,13 +21,50 @@ pub mod build {
     pub const GIT_COMMIT_HASH: Option<&str> = option_env!("GIT_COMMIT_HASH");
 }
 
-use std::{collections::HashMap, convert::TryFrom};
+use std::{collections::HashMap, convert::TryFrom, sync::Arc};
 
 pub use symbol::{Key, Reference, Symbol};
 
 /// Shared state between [`Filter`][crate::filters::Filter]s during processing for a single packet.
 pub type DynamicMetadata = HashMap<Key, Value>;
 
+/// Shared state between [`Filter`][crate::filters::Filter]s for every packet
+/// of a session, in both the read and write direction. Cloning returns a
+/// handle to the same underlying metadata.
+#[derive(Clone, Debug, Default)]
+pub struct SessionMetadata(Arc<parking_lot::RwLock<DynamicMetadata>>);
+
+impl SessionMetadata {
+    /// Returns a copy of the value stored under `key`.
+    pub fn get(&self, key: &Key) -> Option<Value> {
+        self.0.read().get(key).cloned()
+    }
+
+    /// Stores `value` under `key`, returning the previous value if present.
+    pub fn insert(&self, key: Key, value: Value) -> Option<Value> {
+        self.0.write().insert(key, value)
+    }
+
+    /// Removes the value stored under `key`, returning it if present.
+    pub fn remove(&self, key: &Key) -> Option<Value> {
+        self.0.write().remove(key)
+    }
+
+    pub fn contains_key(&self, key: &Key) -> bool {
+        self.0.read().contains_key(key)
+    }
+
+    /// Locks the metadata for reading.
+    pub fn read(&self) -> parking_lot::RwLockReadGuard<'_, DynamicMetadata> {
+        self.0.read()
+    }
+
+    /// Locks the metadata for writing.
+    pub fn write(&self) -> parking_lot::RwLockWriteGuard<'_, DynamicMetadata> {
+        self.0.write()
+    }
+}
+
 pub const KEY: &str = "quilkin.dev";
 
 #[derive(
@@ -181,6 +181,17 @@ impl PoolBuffer {
         }
     }
 
+    /// Removes the bytes in `range` from the buffer, moving the bytes after
+    /// it forward.
+    ///
+    /// The buffer will now be [0, range.start) followed by [range.end, len)
+    #[inline]
+    pub fn remove(&mut self, range: std::ops::Range<usize>) {
+        let len = self.inner.len();
+        self.inner.copy_within(range.end..len, range.start);
+        self.inner.truncate(len - range.len());
+    }
+
     #[inline]
     pub fn freeze(self) -> FrozenPoolBuffer {
         FrozenPoolBuffer {
@@ -336,4 +347,17 @@ mod test {
         assert_eq!(&[9], buf.split_prefix(1));
         assert_eq!(&[1, 1, 1, 1], buf.as_ref());
     }
+
+    #[test]
+    fn remove() {
+        let pool = Arc::new(BufferPool::new(1, 10));
+
+        let mut buf = pool.alloc_slice(&[9, 1, 2, 3, 4, 8]);
+
+        buf.remove(2..4);
+        assert_eq!(&[9, 1, 4, 8], buf.as_ref());
+        assert_eq!(&[9], buf.split_prefix(1));
+        buf.remove(2..3);
+        assert_eq!(&[1, 4], buf.as_ref());
+    }
 }
@@ -308,6 +308,7 @@ impl TestHelper {
                 socket: crate::net::raw_socket_with_reuse(0).unwrap(),
                 qcmp,
                 phoenix,
+                canary: None,
             }
         });
 
@@ -449,6 +450,7 @@ impl TestConfig {
                     name: "TestFilter".into(),
                     label: None,
                     config: None,
+                    when: None,
                 },
             ))
             .unwrap(),
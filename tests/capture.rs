@@ -45,11 +45,13 @@ async fn token_router() {
                     }
                 }))
                 .unwrap(),
+                when: None,
             },
             Filter {
                 name: TokenRouter::factory().name().into(),
                 label: None,
                 config: None,
+                when: None,
             },
         ])
         .map(std::sync::Arc::new)
@@ -42,6 +42,7 @@ on_write: COMPRESS
             name: Compress::factory().name().into(),
             label: None,
             config: serde_yaml::from_str(yaml).unwrap(),
+            when: None,
         }])
         .map(std::sync::Arc::new)
         .unwrap(),
@@ -63,6 +64,7 @@ on_write: DECOMPRESS
             name: Compress::factory().name().into(),
             label: None,
             config: serde_yaml::from_str(yaml).unwrap(),
+            when: None,
         }])
         .map(std::sync::Arc::new)
         .unwrap(),
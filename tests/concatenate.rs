@@ -43,6 +43,7 @@ bytes: YWJj #abc
             name: Concatenate::factory().name().into(),
             label: None,
             config: serde_yaml::from_str(yaml).unwrap(),
+            when: None,
         }])
         .map(std::sync::Arc::new)
         .unwrap(),
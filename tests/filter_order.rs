@@ -64,16 +64,19 @@ on_write: DECOMPRESS
                 name: Concatenate::factory().name().into(),
                 label: None,
                 config: serde_yaml::from_str(yaml_concat_read).unwrap(),
+                when: None,
             },
             Filter {
                 name: Concatenate::factory().name().into(),
                 label: None,
                 config: serde_yaml::from_str(yaml_concat_write).unwrap(),
+                when: None,
             },
             Filter {
                 name: Compress::factory().name().into(),
                 label: None,
                 config: serde_yaml::from_str(yaml_compress).unwrap(),
+                when: None,
             },
         ])
         .map(std::sync::Arc::new)
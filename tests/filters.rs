@@ -42,6 +42,7 @@ async fn test_filter() {
             name: "TestFilter".to_string(),
             label: None,
             config: None,
+            when: None,
         }])
         .map(std::sync::Arc::new)
         .unwrap(),
@@ -68,6 +69,7 @@ async fn test_filter() {
             name: "TestFilter".to_string(),
             label: None,
             config: None,
+            when: None,
         }])
         .map(std::sync::Arc::new)
         .unwrap(),
@@ -126,6 +128,7 @@ async fn debug_filter() {
             name: factory.name().into(),
             label: None,
             config: Some(serde_json::json!({ "id":  "server", })),
+            when: None,
         }])
         .map(std::sync::Arc::new)
         .unwrap(),
@@ -148,6 +151,7 @@ async fn debug_filter() {
             name: factory.name().into(),
             label: None,
             config: Some(serde_json::json!({ "id":  "client" })),
+            when: None,
         }])
         .map(std::sync::Arc::new)
         .unwrap(),
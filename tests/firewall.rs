@@ -201,6 +201,7 @@ async fn test(t: &mut TestHelper, yaml: &str, address_type: AddressType) -> mpsc
             name: Firewall::factory().name().into(),
             label: None,
             config: serde_yaml::from_str(yaml.as_str()).unwrap(),
+            when: None,
         }])
         .map(std::sync::Arc::new)
         .unwrap(),
@@ -53,8 +53,6 @@ quilkin.dev:
             )]
             .into(),
         );
-
-        clusters.build_token_maps();
     });
 
     server_config.filters.store(
@@ -63,11 +61,13 @@ quilkin.dev:
                 name: Capture::factory().name().into(),
                 label: None,
                 config: serde_yaml::from_str(capture_yaml).unwrap(),
+                when: None,
             },
             Filter {
                 name: HashedTokenRouter::factory().name().into(),
                 label: None,
                 config: None,
+                when: None,
             },
         ])
         .map(std::sync::Arc::new)
@@ -55,6 +55,7 @@ policy: ROUND_ROBIN
             name: LoadBalancer::factory().name().into(),
             label: None,
             config: serde_yaml::from_str(yaml).unwrap(),
+            when: None,
         }])
         .map(std::sync::Arc::new)
         .unwrap(),
@@ -44,6 +44,7 @@ period: 1
             name: LocalRateLimit::factory().name().into(),
             label: None,
             config: serde_yaml::from_str(yaml).unwrap(),
+            when: None,
         }])
         .map(std::sync::Arc::new)
         .unwrap(),
@@ -67,11 +67,13 @@ on_read:
                 name: Capture::NAME.into(),
                 label: None,
                 config: serde_yaml::from_str(capture_yaml).unwrap(),
+                when: None,
             },
             Filter {
                 name: Match::NAME.into(),
                 label: None,
                 config: serde_yaml::from_str(matches_yaml).unwrap(),
+                when: None,
             },
         ])
         .map(std::sync::Arc::new)
@@ -61,11 +61,13 @@ quilkin.dev:
                 name: Capture::factory().name().into(),
                 label: None,
                 config: serde_yaml::from_str(capture_yaml).unwrap(),
+                when: None,
             },
             Filter {
                 name: TokenRouter::factory().name().into(),
                 label: None,
                 config: None,
+                when: None,
             },
         ])
         .map(std::sync::Arc::new)